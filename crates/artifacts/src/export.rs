@@ -0,0 +1,148 @@
+//! Signed tarball exports of a request's full output.
+//!
+//! The tarball holds `<request_id>/manifest.json` followed by every file
+//! in manifest order, with fixed ownership, modes and timestamps so the
+//! same output always produces the same bytes. The signature is an
+//! HMAC-SHA256 over the whole tarball, `sha256=<hex>`, checked in
+//! constant time by [`Signer::verify`].
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{ArtifactError, ArtifactStore, Digest};
+
+/// Response header carrying an export's signature.
+pub const SIGNATURE_HEADER: &str = "X-Artifact-Signature";
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VerifyError {
+    #[error("signature is not sha256=<hex>")]
+    Malformed,
+    #[error("signature mismatch")]
+    Mismatch,
+}
+
+/// Signs exports with `refinory.secrets.artifact_signing_key_ref`.
+#[derive(Clone)]
+pub struct Signer {
+    key: Vec<u8>,
+}
+
+impl std::fmt::Debug for Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signer")
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Signer {
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: key.as_ref().to_vec(),
+        }
+    }
+
+    fn mac(&self, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(body);
+        mac
+    }
+
+    pub fn sign(&self, body: &[u8]) -> String {
+        format!(
+            "sha256={}",
+            hex::encode(self.mac(body).finalize().into_bytes())
+        )
+    }
+
+    pub fn verify(&self, body: &[u8], signature: &str) -> Result<(), VerifyError> {
+        let tag = signature
+            .strip_prefix("sha256=")
+            .and_then(|h| hex::decode(h).ok())
+            .ok_or(VerifyError::Malformed)?;
+        self.mac(body)
+            .verify_slice(&tag)
+            .map_err(|_| VerifyError::Mismatch)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub tarball: Vec<u8>,
+    pub sha256: Digest,
+    pub signature: String,
+}
+
+impl ArtifactStore {
+    /// Packs the request's manifest and files into a signed tarball, or
+    /// `None` if nothing was stored for the request. Each file is checked
+    /// against its digest on the way in.
+    pub async fn export(
+        &self,
+        request_id: &str,
+        signer: &Signer,
+    ) -> Result<Option<Export>, ArtifactError> {
+        let Some(manifest) = self.manifest(request_id).await? else {
+            return Ok(None);
+        };
+        let mut tar = tar::Builder::new(Vec::new());
+        let json = serde_json::to_vec_pretty(&manifest).expect("manifest serializes");
+        let updated = manifest.entries.iter().map(|e| e.created_at).max();
+        append(
+            &mut tar,
+            &format!("{request_id}/manifest.json"),
+            updated.unwrap_or(0),
+            &json,
+        );
+        for entry in &manifest.entries {
+            let file = self.read(request_id, &entry.filename, None).await?;
+            append(
+                &mut tar,
+                &format!("{request_id}/{}", entry.filename),
+                entry.created_at,
+                &file.body,
+            );
+        }
+        let tarball = tar.into_inner().expect("writing to a Vec cannot fail");
+        Ok(Some(Export {
+            sha256: Digest::of(&tarball),
+            signature: signer.sign(&tarball),
+            tarball,
+        }))
+    }
+}
+
+fn append(tar: &mut tar::Builder<Vec<u8>>, path: &str, mtime: u64, body: &[u8]) {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(mtime);
+    header.set_size(body.len() as u64);
+    tar.append_data(&mut header, path, body)
+        .expect("writing to a Vec cannot fail");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_round_trip() {
+        let signer = Signer::new("k");
+        let sig = signer.sign(b"tarball");
+        assert_eq!(signer.verify(b"tarball", &sig), Ok(()));
+        assert_eq!(signer.verify(b"tarbalL", &sig), Err(VerifyError::Mismatch));
+        assert_eq!(
+            Signer::new("other").verify(b"tarball", &sig),
+            Err(VerifyError::Mismatch)
+        );
+        assert_eq!(
+            signer.verify(b"tarball", "md5=00"),
+            Err(VerifyError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Artifact routes for the refinory API.
+//!
+//! | Route                                  | Response                        |
+//! |----------------------------------------|---------------------------------|
+//! | `GET /requests/{id}/artifacts`         | `{"request_id", "artifacts"}`   |
+//! | `GET /requests/{id}/artifacts/{*path}` | the file; honours `Range`       |
+//! | `GET /requests/{id}/export`            | signed `application/x-tar`      |
+//!
+//! Listings keep the `name`, `url`, `type`, `size` and `created_at` fields
+//! the orchestrator returned, with real values. Errors are JSON
+//! `{"error": ...}`. Authentication is left to the router this is merged
+//! into.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::json;
+
+use crate::export::{Signer, SIGNATURE_HEADER};
+use crate::{rfc3339, ArtifactError, ArtifactStore, ByteRange};
+
+#[derive(Clone)]
+struct App {
+    store: Arc<ArtifactStore>,
+    signer: Option<Arc<Signer>>,
+}
+
+/// Without a `signer`, `/export` answers 503.
+pub fn router(store: Arc<ArtifactStore>, signer: Option<Signer>) -> Router {
+    let app = App {
+        store,
+        signer: signer.map(Arc::new),
+    };
+    Router::new()
+        .route("/requests/{id}/artifacts", get(list))
+        .route("/requests/{id}/artifacts/{*path}", get(download))
+        .route("/requests/{id}/export", get(export))
+        .with_state(app)
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+impl IntoResponse for ArtifactError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ArtifactError::InvalidName { .. } => StatusCode::BAD_REQUEST,
+            ArtifactError::NotFound { .. } => StatusCode::NOT_FOUND,
+            ArtifactError::Unsatisfiable { len } => {
+                let mut res = error(StatusCode::RANGE_NOT_SATISFIABLE, &self.to_string());
+                let range = HeaderValue::from_str(&format!("bytes */{len}"))
+                    .expect("content range is ASCII");
+                res.headers_mut().insert(header::CONTENT_RANGE, range);
+                return res;
+            }
+            ArtifactError::Corrupt { .. } | ArtifactError::Malformed { .. } => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            ArtifactError::Store(_) => StatusCode::BAD_GATEWAY,
+        };
+        if status.is_server_error() {
+            tracing::error!(error = %self, "artifact store failed");
+        }
+        error(status, &self.to_string())
+    }
+}
+
+async fn list(State(app): State<App>, Path(id): Path<String>) -> Result<Response, ArtifactError> {
+    let Some(manifest) = app.store.manifest(&id).await? else {
+        return Ok(error(
+            StatusCode::NOT_FOUND,
+            "no artifacts for this request",
+        ));
+    };
+    let artifacts: Vec<_> = manifest
+        .entries
+        .iter()
+        .map(|e| {
+            json!({
+                "name": e.filename,
+                "url": format!("/requests/{id}/artifacts/{}", e.filename),
+                "type": e.artifact_type,
+                "content_type": e.content_type,
+                "size": e.size,
+                "sha256": e.sha256,
+                "artifact_id": e.artifact_id,
+                "created_at": rfc3339(e.created_at),
+            })
+        })
+        .collect();
+    Ok(Json(json!({ "request_id": id, "artifacts": artifacts })).into_response())
+}
+
+async fn download(
+    State(app): State<App>,
+    Path((id, path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, ArtifactError> {
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(ByteRange::parse);
+    let file = app.store.read(&id, &path, range).await?;
+    let etag = format!("\"{}\"", file.entry.sha256);
+    let mut res = Response::builder()
+        .header(header::CONTENT_TYPE, &file.entry.content_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, etag);
+    if let Some(r) = &file.range {
+        res = res.status(StatusCode::PARTIAL_CONTENT).header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", r.start, r.end - 1, file.entry.size),
+        );
+    }
+    Ok(res
+        .body(file.body.into())
+        .expect("artifact headers are valid"))
+}
+
+async fn export(State(app): State<App>, Path(id): Path<String>) -> Result<Response, ArtifactError> {
+    let Some(signer) = &app.signer else {
+        return Ok(error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "exports need refinory.secrets.artifact_signing_key_ref",
+        ));
+    };
+    let Some(export) = app.store.export(&id, signer).await? else {
+        return Ok(error(
+            StatusCode::NOT_FOUND,
+            "no artifacts for this request",
+        ));
+    };
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/x-tar")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{id}.tar\""),
+        )
+        .header(header::ETAG, format!("\"{}\"", export.sha256))
+        .header(SIGNATURE_HEADER, export.signature)
+        .body(export.tarball.into())
+        .expect("export headers are valid"))
+}
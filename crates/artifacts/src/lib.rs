@@ -0,0 +1,502 @@
+//! Content-addressed storage for refinory outputs.
+//!
+//! Each file is stored once, under `blobs/sha256/<hex>`, however many
+//! requests produce it. A request's files are listed in its manifest,
+//! `manifests/<request_id>.json`, which records their digests and, when
+//! they came from the database, the `architecture_artifacts` row. Objects
+//! live in an [`ObjectStore`]: [`local::LocalDisk`] or [`s3::S3`].
+//! [`http::router`] serves manifests, ranged downloads and signed exports
+//! in place of the made-up URLs `src/refinory/orchestrator.js` returned.
+
+pub mod export;
+pub mod http;
+pub mod local;
+pub mod s3;
+
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+/// A SHA-256 content digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest(pub [u8; 32]);
+
+impl Digest {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self(Sha256::digest(bytes).into())
+    }
+
+    pub fn hex(&self) -> String {
+        hex::encode(self.0)
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hex())
+    }
+}
+
+impl FromStr for Digest {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut out = [0; 32];
+        hex::decode_to_slice(s, &mut out)?;
+        Ok(Self(out))
+    }
+}
+
+impl Serialize for Digest {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&self.hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Digest {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(d)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// One range from an HTTP `Range: bytes=...` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=a-b`, both inclusive.
+    Bounded(u64, u64),
+    /// `bytes=a-`.
+    From(u64),
+    /// `bytes=-n`: the last `n` bytes.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parses a single-range header. Multiple ranges are not supported and,
+    /// like anything unparseable, yield `None` so the whole file is served.
+    pub fn parse(header: &str) -> Option<Self> {
+        let spec = header.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+        let (start, end) = spec.trim().split_once('-')?;
+        match (start, end) {
+            ("", n) => n.parse().ok().map(ByteRange::Suffix),
+            (a, "") => a.parse().ok().map(ByteRange::From),
+            (a, b) => {
+                let (a, b) = (a.parse().ok()?, b.parse().ok()?);
+                (a <= b).then_some(ByteRange::Bounded(a, b))
+            }
+        }
+    }
+
+    /// The half-open byte range within a `len`-byte file, or `None` if the
+    /// range cannot be satisfied.
+    pub fn resolve(self, len: u64) -> Option<Range<u64>> {
+        let range = match self {
+            ByteRange::Bounded(a, b) => a..b.saturating_add(1).min(len),
+            ByteRange::From(a) => a..len,
+            ByteRange::Suffix(0) => return None,
+            ByteRange::Suffix(n) => len.saturating_sub(n)..len,
+        };
+        (range.start < range.end).then_some(range)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("{key}: {source}")]
+    Io { key: String, source: std::io::Error },
+    #[error("object store request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{key}: object store returned {status}: {body}")]
+    Status {
+        key: String,
+        status: u16,
+        body: String,
+    },
+    #[error("{0}")]
+    Config(String),
+}
+
+/// Flat key/value storage for blobs and manifests. Keys are relative,
+/// `/`-separated paths.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), StoreError>;
+
+    /// The object's bytes within `range` (all of them without one), or
+    /// `None` if there is no such object.
+    async fn get(
+        &self,
+        key: &str,
+        range: Option<Range<u64>>,
+    ) -> Result<Option<Vec<u8>>, StoreError>;
+
+    async fn exists(&self, key: &str) -> Result<bool, StoreError>;
+}
+
+/// The object store `refinory.storage` describes: its S3 bucket when one
+/// is configured, `artifacts_dir` otherwise.
+pub fn from_discovery(
+    storage: &discovery::RefinoryStorage,
+    s3_credentials: Option<s3::Credentials>,
+) -> Result<Arc<dyn ObjectStore>, StoreError> {
+    match (&storage.s3, s3_credentials) {
+        (Some(cfg), Some(creds)) => Ok(Arc::new(s3::S3::from_discovery(cfg, creds))),
+        (Some(_), None) => Err(StoreError::Config(
+            "refinory.storage.s3 is set but no credentials were resolved".to_string(),
+        )),
+        (None, _) => Ok(Arc::new(local::LocalDisk::new(&storage.artifacts_dir))),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArtifactError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("invalid {what} {value:?}")]
+    InvalidName { what: &'static str, value: String },
+    #[error("no artifact {filename:?} for request {request_id}")]
+    NotFound {
+        request_id: String,
+        filename: String,
+    },
+    #[error("range not satisfiable for a {len}-byte artifact")]
+    Unsatisfiable { len: u64 },
+    #[error("{filename}: expected sha256 {expected}, stored blob hashes to {actual}")]
+    Corrupt {
+        filename: String,
+        expected: Digest,
+        actual: Digest,
+    },
+    #[error("{key}: {source}")]
+    Malformed {
+        key: String,
+        source: serde_json::Error,
+    },
+}
+
+/// A file listed in a request's manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Relative path within the request's output, e.g. `docs/adr-001.md`.
+    pub filename: String,
+    pub artifact_type: String,
+    pub sha256: Digest,
+    pub size: u64,
+    pub content_type: String,
+    /// `architecture_artifacts.artifact_id` of the row this file came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_id: Option<String>,
+    /// Unix seconds.
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub request_id: String,
+    pub entries: Vec<Entry>,
+}
+
+impl Manifest {
+    pub fn get(&self, filename: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.filename == filename)
+    }
+}
+
+/// Describes a file being added with [`ArtifactStore::put`].
+#[derive(Debug, Clone)]
+pub struct NewArtifact {
+    pub filename: String,
+    pub artifact_type: String,
+    pub content_type: String,
+    pub artifact_id: Option<String>,
+}
+
+impl NewArtifact {
+    /// The content type is guessed from the extension.
+    pub fn new(filename: impl Into<String>, artifact_type: impl Into<String>) -> Self {
+        let filename = filename.into();
+        let content_type = content_type(&filename).to_string();
+        Self {
+            filename,
+            artifact_type: artifact_type.into(),
+            content_type,
+            artifact_id: None,
+        }
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    pub fn with_artifact_id(mut self, id: impl Into<String>) -> Self {
+        self.artifact_id = Some(id.into());
+        self
+    }
+}
+
+fn content_type(filename: &str) -> &'static str {
+    match filename.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("md") => "text/markdown; charset=utf-8",
+        Some("json") => "application/json",
+        Some("yml" | "yaml") => "application/yaml",
+        Some("txt" | "log") => "text/plain; charset=utf-8",
+        Some("html") => "text/html; charset=utf-8",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("tar") => "application/x-tar",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A file read with [`ArtifactStore::read`].
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub entry: Entry,
+    /// The bytes served when a range was asked for.
+    pub range: Option<Range<u64>>,
+    pub body: Vec<u8>,
+}
+
+pub struct ArtifactStore {
+    objects: Arc<dyn ObjectStore>,
+    /// Serializes manifest read-modify-writes.
+    manifests: tokio::sync::Mutex<()>,
+}
+
+impl ArtifactStore {
+    pub fn new(objects: Arc<dyn ObjectStore>) -> Self {
+        Self {
+            objects,
+            manifests: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Stores `body` and lists it in the request's manifest, replacing any
+    /// entry with the same filename.
+    pub async fn put(
+        &self,
+        request_id: &str,
+        artifact: NewArtifact,
+        body: Vec<u8>,
+    ) -> Result<Entry, ArtifactError> {
+        check_request_id(request_id)?;
+        check_filename(&artifact.filename)?;
+        let digest = Digest::of(&body);
+        let entry = Entry {
+            filename: artifact.filename,
+            artifact_type: artifact.artifact_type,
+            sha256: digest,
+            size: body.len() as u64,
+            content_type: artifact.content_type,
+            artifact_id: artifact.artifact_id,
+            created_at: now(),
+        };
+        let key = blob_key(&digest);
+        if !self.objects.exists(&key).await? {
+            self.objects.put(&key, body).await?;
+        }
+
+        let _guard = self.manifests.lock().await;
+        let mut manifest = self.manifest(request_id).await?.unwrap_or(Manifest {
+            request_id: request_id.to_string(),
+            entries: Vec::new(),
+        });
+        match manifest
+            .entries
+            .iter_mut()
+            .find(|e| e.filename == entry.filename)
+        {
+            Some(existing) => *existing = entry.clone(),
+            None => manifest.entries.push(entry.clone()),
+        }
+        let json = serde_json::to_vec_pretty(&manifest).expect("manifest serializes");
+        self.objects.put(&manifest_key(request_id), json).await?;
+        Ok(entry)
+    }
+
+    /// The request's manifest, or `None` if nothing was stored for it.
+    pub async fn manifest(&self, request_id: &str) -> Result<Option<Manifest>, ArtifactError> {
+        check_request_id(request_id)?;
+        let key = manifest_key(request_id);
+        let Some(bytes) = self.objects.get(&key, None).await? else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|source| ArtifactError::Malformed { key, source })
+    }
+
+    /// Reads a file, or the part of it `range` covers. Whole reads are
+    /// checked against the manifest's digest.
+    pub async fn read(
+        &self,
+        request_id: &str,
+        filename: &str,
+        range: Option<ByteRange>,
+    ) -> Result<Download, ArtifactError> {
+        let not_found = || ArtifactError::NotFound {
+            request_id: request_id.to_string(),
+            filename: filename.to_string(),
+        };
+        let entry = self
+            .manifest(request_id)
+            .await?
+            .and_then(|m| m.get(filename).cloned())
+            .ok_or_else(not_found)?;
+        let range = match range {
+            Some(r) => Some(
+                r.resolve(entry.size)
+                    .ok_or(ArtifactError::Unsatisfiable { len: entry.size })?,
+            ),
+            None => None,
+        };
+        let body = self
+            .objects
+            .get(&blob_key(&entry.sha256), range.clone())
+            .await?
+            .ok_or_else(not_found)?;
+        if range.is_none() {
+            let actual = Digest::of(&body);
+            if actual != entry.sha256 {
+                return Err(ArtifactError::Corrupt {
+                    filename: entry.filename,
+                    expected: entry.sha256,
+                    actual,
+                });
+            }
+        }
+        Ok(Download { entry, range, body })
+    }
+}
+
+fn blob_key(digest: &Digest) -> String {
+    format!("blobs/sha256/{digest}")
+}
+
+fn manifest_key(request_id: &str) -> String {
+    format!("manifests/{request_id}.json")
+}
+
+fn check_request_id(id: &str) -> Result<(), ArtifactError> {
+    let ok = !id.is_empty()
+        && id.len() <= 128
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if ok {
+        Ok(())
+    } else {
+        Err(ArtifactError::InvalidName {
+            what: "request id",
+            value: id.to_string(),
+        })
+    }
+}
+
+/// Relative paths only: no empty, `.` or `..` segments and no backslashes,
+/// so a filename can neither escape an export nor shadow another entry.
+fn check_filename(name: &str) -> Result<(), ArtifactError> {
+    let ok = !name.is_empty()
+        && name.len() <= 512
+        && !name.contains('\\')
+        && !name.chars().any(char::is_control)
+        && name
+            .split('/')
+            .all(|s| !s.is_empty() && s != "." && s != "..");
+    if ok {
+        Ok(())
+    } else {
+        Err(ArtifactError::InvalidName {
+            what: "filename",
+            value: name.to_string(),
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Unix seconds as UTC `[year, month, day, hour, minute, second]`.
+pub(crate) fn civil(secs: u64) -> [u64; 6] {
+    // Howard Hinnant's days-to-civil, restricted to dates after 1970.
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    [year, month, day, rem / 3600, rem % 3600 / 60, rem % 60]
+}
+
+/// Unix seconds as RFC 3339, e.g. `2024-02-29T13:05:09Z`.
+pub fn rfc3339(secs: u64) -> String {
+    let [y, mo, d, h, mi, s] = civil(secs);
+    format!("{y:04}-{mo:02}-{d:02}T{h:02}:{mi:02}:{s:02}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_parse_and_resolve_like_rfc_9110() {
+        let r = |h| ByteRange::parse(h).and_then(|r| r.resolve(10));
+        assert_eq!(r("bytes=0-3"), Some(0..4));
+        assert_eq!(r("bytes=5-100"), Some(5..10));
+        assert_eq!(r("bytes=7-"), Some(7..10));
+        assert_eq!(r("bytes=0-18446744073709551615"), Some(0..10));
+        assert_eq!(r("bytes=-3"), Some(7..10));
+        assert_eq!(r("bytes=-30"), Some(0..10));
+        assert_eq!(r("bytes=10-"), None);
+        assert_eq!(r("bytes=-0"), None);
+        assert_eq!(ByteRange::parse("bytes=4-2"), None);
+        assert_eq!(ByteRange::parse("bytes=0-1,4-5"), None);
+        assert_eq!(ByteRange::parse("items=0-1"), None);
+    }
+
+    #[test]
+    fn filenames_stay_inside_the_request() {
+        for ok in ["adr.md", "docs/adr-001.md", "src/.env.example"] {
+            assert!(check_filename(ok).is_ok(), "{ok}");
+        }
+        for bad in [
+            "",
+            "/etc/passwd",
+            "../x",
+            "a/../../x",
+            "a//b",
+            "a\\b",
+            "./a",
+        ] {
+            assert!(check_filename(bad).is_err(), "{bad}");
+        }
+        assert!(check_request_id("6f1c0b3e-8d6a-4c1e-9a57-1b2c3d4e5f60").is_ok());
+        assert!(check_request_id("../manifests").is_err());
+    }
+
+    #[test]
+    fn formats_utc_dates() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_709_211_909), "2024-02-29T13:05:09Z");
+    }
+}
@@ -0,0 +1,133 @@
+//! Objects as files under a directory, e.g. `refinory.storage.artifacts_dir`.
+//!
+//! Writes go to a uniquely named temporary file that is synced and renamed
+//! into place, so readers never see a partial blob and two writers of the
+//! same key do not interleave.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+
+use crate::{ObjectStore, StoreError};
+
+pub struct LocalDisk {
+    root: PathBuf,
+    tmp_seq: AtomicU64,
+}
+
+fn io(key: &str) -> impl FnOnce(std::io::Error) -> StoreError + '_ {
+    move |source| StoreError::Io {
+        key: key.to_string(),
+        source,
+    }
+}
+
+impl LocalDisk {
+    /// Directories are created on first write.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            tmp_seq: AtomicU64::new(0),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalDisk {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), StoreError> {
+        let path = self.path(key);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await.map_err(io(key))?;
+        }
+        let seq = self.tmp_seq.fetch_add(1, Ordering::Relaxed);
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}.{seq}.tmp", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        tokio::fs::write(&tmp, body).await.map_err(io(key))?;
+        let file = tokio::fs::File::open(&tmp).await.map_err(io(key))?;
+        file.sync_all().await.map_err(io(key))?;
+        tokio::fs::rename(&tmp, &path).await.map_err(io(key))
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+        range: Option<Range<u64>>,
+    ) -> Result<Option<Vec<u8>>, StoreError> {
+        let path = self.path(key);
+        let read = tokio::task::spawn_blocking(move || -> std::io::Result<Vec<u8>> {
+            let mut file = std::fs::File::open(path)?;
+            let Some(range) = range else {
+                let mut body = Vec::new();
+                file.read_to_end(&mut body)?;
+                return Ok(body);
+            };
+            file.seek(SeekFrom::Start(range.start))?;
+            let mut body = Vec::new();
+            file.take(range.end - range.start).read_to_end(&mut body)?;
+            Ok(body)
+        })
+        .await
+        .expect("blocking read panicked");
+        match read {
+            Ok(body) => Ok(Some(body)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io(key)(e)),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StoreError> {
+        tokio::fs::try_exists(self.path(key)).await.map_err(io(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{ArtifactError, ArtifactStore, ByteRange, Digest, NewArtifact};
+
+    #[tokio::test]
+    async fn identical_files_share_a_blob_and_corruption_is_caught() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(Arc::new(LocalDisk::new(dir.path())));
+        let adr = b"# ADR-001\n\nUse Postgres.\n".to_vec();
+
+        store
+            .put("r1", NewArtifact::new("adr.md", "adr"), adr.clone())
+            .await
+            .unwrap();
+        let entry = store
+            .put(
+                "r2",
+                NewArtifact::new("docs/adr.md", "adr").with_artifact_id("a-1"),
+                adr.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(entry.sha256, Digest::of(&adr));
+        assert_eq!(entry.content_type, "text/markdown; charset=utf-8");
+        let blobs = std::fs::read_dir(dir.path().join("blobs/sha256")).unwrap();
+        assert_eq!(blobs.count(), 1);
+
+        let part = store
+            .read("r2", "docs/adr.md", ByteRange::parse("bytes=2-8"))
+            .await
+            .unwrap();
+        assert_eq!(part.body, b"ADR-001");
+        assert_eq!(part.range, Some(2..9));
+
+        let blob = dir.path().join(format!("blobs/sha256/{}", entry.sha256));
+        std::fs::write(&blob, b"tampered").unwrap();
+        let err = store.read("r1", "adr.md", None).await.unwrap_err();
+        assert!(matches!(err, ArtifactError::Corrupt { .. }), "{err}");
+    }
+}
@@ -0,0 +1,193 @@
+//! The S3 backend against a local MinIO stand-in, with the HTTP routes on
+//! top.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use artifacts::export::{Signer, SIGNATURE_HEADER};
+use artifacts::s3::{Credentials, S3};
+use artifacts::{ArtifactStore, NewArtifact};
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use secrets::Secret;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+type Bucket = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+/// Serves `app` on a free port and returns its root URL.
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+/// Just enough of MinIO: unsigned requests and payload hashes that do not
+/// match the body are refused, and `Range` is honoured.
+fn minio(bucket: Bucket) -> Router {
+    fn signed(headers: &HeaderMap, body: &[u8]) -> bool {
+        let auth = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let hash = headers
+            .get("x-amz-content-sha256")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let signed = auth.starts_with("AWS4-HMAC-SHA256 Credential=minio/")
+            && auth.contains("/us-east-1/s3/aws4_request")
+            && headers.contains_key("x-amz-date");
+        signed && hash == hex::encode(Sha256::digest(body))
+    }
+    fn forbidden() -> Response {
+        (StatusCode::FORBIDDEN, "SignatureDoesNotMatch").into_response()
+    }
+    Router::new()
+        .route(
+            "/refinory/{*key}",
+            get(
+                |State(bucket): State<Bucket>, Path(key): Path<String>, headers: HeaderMap| async move {
+                    if !signed(&headers, b"") {
+                        return forbidden();
+                    }
+                    let Some(body) = bucket.lock().unwrap().get(&key).cloned() else {
+                        return (StatusCode::NOT_FOUND, "NoSuchKey").into_response();
+                    };
+                    let range = headers.get("range").and_then(|v| v.to_str().ok());
+                    match range.and_then(|r| r.strip_prefix("bytes=")?.split_once('-')) {
+                        Some((a, b)) => {
+                            let (a, b): (usize, usize) = (a.parse().unwrap(), b.parse().unwrap());
+                            (StatusCode::PARTIAL_CONTENT, body[a..=b].to_vec()).into_response()
+                        }
+                        None => body.into_response(),
+                    }
+                },
+            )
+            .put(
+                |State(bucket): State<Bucket>, Path(key): Path<String>, headers: HeaderMap, body: Bytes| async move {
+                    if !signed(&headers, &body) {
+                        return forbidden();
+                    }
+                    bucket.lock().unwrap().insert(key, body.to_vec());
+                    StatusCode::OK.into_response()
+                },
+            ),
+        )
+        .with_state(bucket)
+}
+
+#[tokio::test]
+async fn stores_in_a_bucket_and_serves_ranges_and_signed_exports() {
+    let bucket = Bucket::default();
+    let endpoint = serve(minio(bucket.clone())).await;
+    let s3 = S3::new(
+        endpoint,
+        "refinory",
+        Credentials::new("minio", Secret::new("minio-secret")),
+    );
+    let store = Arc::new(ArtifactStore::new(Arc::new(s3)));
+    let adr = "# ADR-001: Use Postgres\n\nStatus: accepted\n";
+    store
+        .put(
+            "req-1",
+            NewArtifact::new("docs/adr-001.md", "adr").with_artifact_id("7d9e"),
+            adr.as_bytes().to_vec(),
+        )
+        .await
+        .unwrap();
+    store
+        .put(
+            "req-1",
+            NewArtifact::new("plan.json", "implementation_plan"),
+            br#"{"services":["api"]}"#.to_vec(),
+        )
+        .await
+        .unwrap();
+    assert!(bucket.lock().unwrap().contains_key("manifests/req-1.json"));
+
+    let signer = Signer::new("export-key");
+    let api = serve(artifacts::http::router(store, Some(signer.clone()))).await;
+    let http = reqwest::Client::new();
+
+    let listing: Value = http
+        .get(format!("{api}/requests/req-1/artifacts"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let first = &listing["artifacts"][0];
+    assert_eq!(first["name"], "docs/adr-001.md");
+    assert_eq!(first["url"], "/requests/req-1/artifacts/docs/adr-001.md");
+    assert_eq!(first["size"], adr.len());
+    assert_eq!(first["artifact_id"], "7d9e");
+
+    let part = http
+        .get(format!("{api}/requests/req-1/artifacts/docs/adr-001.md"))
+        .header("range", "bytes=2-8")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(part.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        part.headers()["content-range"],
+        format!("bytes 2-8/{}", adr.len())
+    );
+    assert_eq!(part.text().await.unwrap(), "ADR-001");
+
+    let beyond = http
+        .get(format!("{api}/requests/req-1/artifacts/plan.json"))
+        .header("range", "bytes=500-")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(beyond.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(beyond.headers()["content-range"], "bytes */20");
+
+    let export = http
+        .get(format!("{api}/requests/req-1/export"))
+        .send()
+        .await
+        .unwrap();
+    let signature = export.headers()[SIGNATURE_HEADER]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let tarball = export.bytes().await.unwrap();
+    assert_eq!(signer.verify(&tarball, &signature), Ok(()));
+    let mut archive = tar::Archive::new(&tarball[..]);
+    let files: Vec<(String, String)> = archive
+        .entries()
+        .unwrap()
+        .map(|e| {
+            let mut e = e.unwrap();
+            let mut body = String::new();
+            e.read_to_string(&mut body).unwrap();
+            (e.path().unwrap().display().to_string(), body)
+        })
+        .collect();
+    let names: Vec<&str> = files.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "req-1/manifest.json",
+            "req-1/docs/adr-001.md",
+            "req-1/plan.json"
+        ]
+    );
+    assert_eq!(files[1].1, adr);
+
+    let missing = http
+        .get(format!("{api}/requests/req-2/artifacts"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,321 @@
+//! Source chunks on definition boundaries, via tree-sitter.
+
+use tree_sitter::{Language, Node, Parser};
+
+use crate::{words, Kind, Span};
+
+fn grammar(kind: Kind) -> Option<Language> {
+    Some(match kind {
+        Kind::Rust => tree_sitter_rust::LANGUAGE.into(),
+        Kind::Python => tree_sitter_python::LANGUAGE.into(),
+        Kind::TypeScript => tree_sitter_typescript::LANGUAGE_TSX.into(),
+        Kind::Shell => tree_sitter_bash::LANGUAGE.into(),
+        _ => return None,
+    })
+}
+
+/// Joins a member to its container's symbol.
+fn separator(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Rust => "::",
+        _ => ".",
+    }
+}
+
+/// Comments and attributes that lead into the next definition.
+fn is_preamble(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "comment" | "line_comment" | "block_comment" | "attribute_item" | "decorator"
+    )
+}
+
+/// A definition's name and, for containers, the node holding its members.
+struct Definition<'t> {
+    name: String,
+    body: Option<Node<'t>>,
+}
+
+fn definition<'t>(kind: Kind, node: Node<'t>, src: &[u8]) -> Option<Definition<'t>> {
+    let text = |n: Node| n.utf8_text(src).ok().map(str::to_string);
+    let field = |f: &str| node.child_by_field_name(f);
+    let named = |body: Option<Node<'t>>| {
+        Some(Definition {
+            name: text(field("name")?)?,
+            body,
+        })
+    };
+    match (kind, node.kind()) {
+        (
+            Kind::Rust,
+            "function_item"
+            | "function_signature_item"
+            | "struct_item"
+            | "enum_item"
+            | "union_item"
+            | "macro_definition"
+            | "const_item"
+            | "static_item"
+            | "type_item",
+        ) => named(None),
+        (Kind::Rust, "trait_item" | "mod_item") => named(field("body")),
+        (Kind::Rust, "impl_item") => Some(Definition {
+            name: text(field("type")?)?,
+            body: field("body"),
+        }),
+
+        (Kind::Python, "function_definition") => named(None),
+        (Kind::Python, "class_definition") => named(field("body")),
+        (Kind::Python, "decorated_definition") => definition(kind, field("definition")?, src),
+
+        (
+            Kind::TypeScript,
+            "function_declaration"
+            | "generator_function_declaration"
+            | "function_signature"
+            | "interface_declaration"
+            | "type_alias_declaration"
+            | "enum_declaration"
+            | "method_definition"
+            | "abstract_method_signature",
+        ) => named(None),
+        (Kind::TypeScript, "class_declaration" | "abstract_class_declaration") => {
+            named(field("body"))
+        }
+        (Kind::TypeScript, "internal_module" | "module") => named(field("body")),
+        (Kind::TypeScript, "export_statement") => definition(kind, field("declaration")?, src),
+        // `const handler = async () => {…}`
+        (Kind::TypeScript, "lexical_declaration" | "variable_declaration") => {
+            let declarator = node.named_child(0)?;
+            let value = declarator.child_by_field_name("value")?;
+            matches!(
+                value.kind(),
+                "arrow_function" | "function_expression" | "function"
+            )
+            .then_some(())?;
+            Some(Definition {
+                name: text(declarator.child_by_field_name("name")?)?,
+                body: None,
+            })
+        }
+
+        (Kind::Shell, "function_definition") => named(None),
+        _ => None,
+    }
+}
+
+/// Last line a node covers; a node ending at column 0 ends on the line
+/// before.
+fn last_line(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+struct Walk<'a> {
+    kind: Kind,
+    src: &'a [u8],
+    lines: &'a [&'a str],
+    max_words: usize,
+    spans: Vec<Span>,
+    /// First line not yet in a span.
+    next_line: usize,
+}
+
+impl Walk<'_> {
+    fn push(&mut self, start: usize, end: usize, symbol: Option<String>) {
+        let start = start.max(self.next_line);
+        if start <= end {
+            self.spans.push(Span::new(start, end).with_symbol(symbol));
+            self.next_line = end + 1;
+        }
+    }
+
+    /// Chunks the children of `parent`, naming their symbols under `scope`.
+    fn children(&mut self, parent: Node, scope: Option<&str>) {
+        // Lines of statements between definitions, kept together.
+        let mut glue: Option<(usize, usize)> = None;
+        let mut preamble: Option<usize> = None;
+        let mut cursor = parent.walk();
+        for child in parent.named_children(&mut cursor) {
+            let (start, end) = (child.start_position().row, last_line(child));
+            if is_preamble(child) {
+                preamble.get_or_insert(start);
+                continue;
+            }
+            let from = preamble.take().unwrap_or(start);
+            match definition(self.kind, child, self.src) {
+                Some(def) => {
+                    if let Some((a, b)) = glue.take() {
+                        self.push(a, b, scope.map(str::to_string));
+                    }
+                    let symbol = match scope {
+                        Some(s) => format!("{s}{}{}", separator(self.kind), def.name),
+                        None => def.name,
+                    };
+                    self.definition(from, end, symbol, def.body);
+                }
+                None => match glue {
+                    Some((a, _)) if words(&self.lines[a..=end]) <= self.max_words => {
+                        glue = Some((a, end));
+                    }
+                    _ => {
+                        if let Some((a, b)) = glue.take() {
+                            self.push(a, b, scope.map(str::to_string));
+                        }
+                        glue = Some((from, end));
+                    }
+                },
+            }
+        }
+        let tail = match (glue, preamble) {
+            (Some((a, _)), _) | (None, Some(a)) => Some(a),
+            (None, None) => None,
+        };
+        if let Some(a) = tail {
+            let b = last_line(parent).min(self.lines.len() - 1);
+            self.push(a, b, scope.map(str::to_string));
+        }
+    }
+
+    fn definition(&mut self, start: usize, end: usize, symbol: String, body: Option<Node>) {
+        let members = body.filter(|b| {
+            words(&self.lines[start..=end]) > self.max_words && b.named_child_count() > 0
+        });
+        let Some(body) = members else {
+            self.push(start, end, Some(symbol));
+            return;
+        };
+        // The signature up to the first member, then each member.
+        let first = body.named_child(0).map_or(end, |n| n.start_position().row);
+        if first > start {
+            self.push(start, first - 1, Some(symbol.clone()));
+        }
+        self.children(body, Some(&symbol));
+        // The closing line, e.g. `}`.
+        self.push(self.next_line, end, Some(symbol));
+    }
+}
+
+/// `None` when `kind` has no grammar here or parsing fails outright.
+pub(crate) fn spans(kind: Kind, text: &str, lines: &[&str], max_words: usize) -> Option<Vec<Span>> {
+    let mut parser = Parser::new();
+    parser.set_language(&grammar(kind)?).ok()?;
+    let tree = parser.parse(text, None)?;
+    let mut walk = Walk {
+        kind,
+        src: text.as_bytes(),
+        lines,
+        max_words,
+        spans: Vec::new(),
+        next_line: 0,
+    };
+    walk.children(tree.root_node(), None);
+    Some(walk.spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Chunk, Chunker};
+
+    fn outline(chunks: &[Chunk]) -> Vec<(Option<&str>, usize, usize)> {
+        chunks
+            .iter()
+            .map(|c| (c.symbol.as_deref(), c.start_line, c.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn splits_source_on_definitions_in_each_language() {
+        let rust = "\
+use std::fmt;
+
+/// Doc.
+#[derive(Debug)]
+pub struct Report {
+    files: usize,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self { files: 0 }
+    }
+
+    fn rate(&self) -> f64 {
+        0.0
+    }
+}
+";
+        let chunks = Chunker::default().chunk("src/lib.rs", rust);
+        assert_eq!(
+            outline(&chunks),
+            [
+                (None, 1, 1),
+                (Some("Report"), 3, 7),
+                (Some("Report"), 9, 17)
+            ]
+        );
+        // Too long for one chunk: the impl splits into its methods.
+        let chunks = Chunker::new(12, 0).chunk("src/lib.rs", rust);
+        assert_eq!(
+            outline(&chunks)[2..],
+            [
+                (Some("Report"), 9, 9),
+                (Some("Report::new"), 10, 12),
+                (Some("Report::rate"), 14, 16),
+                (Some("Report"), 17, 17),
+            ]
+        );
+
+        let python = "\
+import os
+
+@dataclass
+class Ingestor:
+    def chunk_text(self, text):
+        return text.split()
+
+def main():
+    pass
+";
+        let chunks = Chunker::new(6, 0).chunk("ingest.py", python);
+        assert_eq!(
+            outline(&chunks),
+            [
+                (None, 1, 1),
+                (Some("Ingestor"), 3, 4),
+                (Some("Ingestor.chunk_text"), 5, 6),
+                (Some("main"), 8, 9),
+            ]
+        );
+
+        let ts = "\
+import { x } from './x';
+export const handler = async (event: Event) => {
+  return x(event);
+};
+export interface Options { verbose: boolean }
+function helper() {}
+";
+        let chunks = Chunker::default().chunk("bot.ts", ts);
+        assert_eq!(
+            outline(&chunks),
+            [
+                (None, 1, 1),
+                (Some("handler"), 2, 4),
+                (Some("Options"), 5, 5),
+                (Some("helper"), 6, 6),
+            ]
+        );
+
+        let sh = "#!/bin/sh\nset -e\n\n# Fetch one source.\nfetch() {\n  curl \"$1\"\n}\nfetch https://example.com\n";
+        let chunks = Chunker::default().chunk("collect.sh", sh);
+        assert_eq!(
+            outline(&chunks),
+            [(None, 1, 2), (Some("fetch"), 4, 7), (None, 8, 8)]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Structure-aware chunking for recon ingest.
+//!
+//! [`Chunker::chunk`] picks a strategy from the file extension:
+//!
+//! - Rust, Python, TypeScript/JavaScript and shell source is parsed with
+//!   tree-sitter and split on function, class, impl and similar
+//!   boundaries; each chunk names its [`Chunk::symbol`], e.g.
+//!   `Ingestor::run` or `RepositoryIngestor.chunk_text`. Imports and other
+//!   top-level statements between definitions are grouped together.
+//! - YAML is split per item of each top-level list, with the item's
+//!   [`Chunk::key_path`] (`automation_patterns[3]`) and its `id` or `name`
+//!   as the symbol. Other top-level keys become one chunk each.
+//! - Markdown is split per heading, the symbol being the heading trail
+//!   (`Setup > Vault`).
+//! - Anything else, and anything that fails to parse, is plain text.
+//!
+//! Chunks keep whole source lines. A piece still longer than
+//! `max_words` is cut into overlapping line windows that keep its symbol
+//! and key path; a class or impl that is too long is split into its
+//! members first.
+
+mod code;
+mod markdown;
+mod yaml;
+
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub text: String,
+    /// 1-based, inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Function, class or heading the chunk belongs to.
+    pub symbol: Option<String>,
+    /// Dotted YAML key path.
+    pub key_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Rust,
+    Python,
+    /// TypeScript and JavaScript, parsed with the TSX grammar.
+    TypeScript,
+    Shell,
+    Yaml,
+    Markdown,
+    Text,
+}
+
+impl Kind {
+    pub fn of(path: impl AsRef<Path>) -> Self {
+        let ext = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match ext.as_str() {
+            "rs" => Kind::Rust,
+            "py" => Kind::Python,
+            "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => Kind::TypeScript,
+            "sh" | "bash" => Kind::Shell,
+            "yaml" | "yml" => Kind::Yaml,
+            "md" | "markdown" => Kind::Markdown,
+            _ => Kind::Text,
+        }
+    }
+}
+
+/// Lines `start..=end` (0-based) of a file and what they belong to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub symbol: Option<String>,
+    pub key_path: Option<String>,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            symbol: None,
+            key_path: None,
+        }
+    }
+
+    pub fn with_symbol(mut self, symbol: Option<String>) -> Self {
+        self.symbol = symbol;
+        self
+    }
+}
+
+pub(crate) fn words(lines: &[&str]) -> usize {
+    lines.iter().map(|l| l.split_whitespace().count()).sum()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Chunker {
+    max_words: usize,
+    overlap_words: usize,
+}
+
+impl Default for Chunker {
+    /// The word budget of `recon/ingest/ingest.py`.
+    fn default() -> Self {
+        Self::new(400, 60)
+    }
+}
+
+impl Chunker {
+    pub fn new(max_words: usize, overlap_words: usize) -> Self {
+        Self {
+            max_words: max_words.max(1),
+            overlap_words,
+        }
+    }
+
+    /// Chunks `text`, the content of the file at `path`.
+    pub fn chunk(&self, path: impl AsRef<Path>, text: &str) -> Vec<Chunk> {
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.is_empty() {
+            return Vec::new();
+        }
+        let whole = || vec![Span::new(0, lines.len() - 1)];
+        let spans = match Kind::of(path) {
+            Kind::Yaml => yaml::spans(text, &lines),
+            Kind::Markdown => Some(markdown::spans(&lines)),
+            Kind::Text => None,
+            lang => code::spans(lang, text, &lines, self.max_words),
+        };
+        let mut out = Vec::new();
+        for span in spans.unwrap_or_else(whole) {
+            self.emit(&lines, span, &mut out);
+        }
+        out
+    }
+
+    /// Trims blank lines off `span` and pushes it, in windows if too long.
+    fn emit(&self, lines: &[&str], span: Span, out: &mut Vec<Chunk>) {
+        let blank = |i: usize| lines[i].trim().is_empty();
+        let (mut start, mut end) = (span.start, span.end.min(lines.len() - 1));
+        while start <= end && blank(start) {
+            start += 1;
+        }
+        while end > start && blank(end) {
+            end -= 1;
+        }
+        if start > end || blank(start) {
+            return;
+        }
+        let push = |out: &mut Vec<Chunk>, text: String, first: usize, last: usize| {
+            out.push(Chunk {
+                text,
+                start_line: first + 1,
+                end_line: last + 1,
+                symbol: span.symbol.clone(),
+                key_path: span.key_path.clone(),
+            })
+        };
+        let count = |i: usize| lines[i].split_whitespace().count();
+        let max = self.max_words;
+        loop {
+            let mut last = start;
+            let mut total = count(start);
+            while last < end && total + count(last + 1) <= max {
+                last += 1;
+                total += count(last);
+            }
+            if total > max {
+                // One line longer than the budget: window its words.
+                let words: Vec<&str> = lines[start].split_whitespace().collect();
+                let step = max.saturating_sub(self.overlap_words).max(1);
+                let mut from = 0;
+                loop {
+                    let to = (from + max).min(words.len());
+                    push(out, words[from..to].join(" "), start, start);
+                    if to == words.len() {
+                        break;
+                    }
+                    from += step;
+                }
+            } else {
+                push(out, lines[start..=last].join("\n"), start, last);
+            }
+            if last >= end {
+                return;
+            }
+            // Start the next window early enough to repeat up to
+            // `overlap_words` words, but always move forward.
+            let mut next = last + 1;
+            let mut repeated = 0;
+            while next > start + 1 && repeated + count(next - 1) <= self.overlap_words {
+                next -= 1;
+                repeated += count(next);
+            }
+            start = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_text_is_windowed_on_lines_with_overlap() {
+        let text = (1..=6)
+            .map(|i| format!("l{i}a l{i}b"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let chunks = Chunker::new(6, 2).chunk("notes.txt", &text);
+        let ranges: Vec<(usize, usize)> =
+            chunks.iter().map(|c| (c.start_line, c.end_line)).collect();
+        assert_eq!(ranges, [(1, 3), (3, 5), (5, 6)]);
+        assert_eq!(chunks[1].text, "l3a l3b\nl4a l4b\nl5a l5b");
+
+        let wide = Chunker::new(3, 1).chunk("one.txt", "a b c d e");
+        let texts: Vec<&str> = wide.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["a b c", "c d e"]);
+        assert!(Chunker::default().chunk("empty.txt", "\n  \n").is_empty());
+    }
+}
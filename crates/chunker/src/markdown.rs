@@ -0,0 +1,105 @@
+//! One chunk per ATX heading section.
+
+use crate::Span;
+
+/// Level and title if `line` is an ATX heading (`## Title`).
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.bytes().take_while(|&b| b == b'#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+/// Sections from each heading to the next. A heading with nothing under it
+/// but a subheading is left out; the subsection's trail names it.
+pub(crate) fn spans(lines: &[&str]) -> Vec<Span> {
+    let mut trail: Vec<(usize, String)> = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut spans = Vec::new();
+    let mut current = Span::new(0, 0);
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            continue;
+        }
+        let Some((level, title)) = heading(line) else {
+            continue;
+        };
+        if i > current.start {
+            current.end = i - 1;
+            spans.push(current);
+        }
+        trail.retain(|(l, _)| *l < level);
+        trail.push((level, title.to_string()));
+        let symbol = trail
+            .iter()
+            .map(|(_, t)| t.as_str())
+            .collect::<Vec<_>>()
+            .join(" > ");
+        current = Span::new(i, i).with_symbol(Some(symbol));
+    }
+    current.end = lines.len() - 1;
+    spans.push(current);
+    let has_body = |s: &Span| {
+        let body = if s.symbol.is_some() {
+            s.start + 1
+        } else {
+            s.start
+        };
+        lines[body.min(s.end + 1)..=s.end]
+            .iter()
+            .any(|l| !l.trim().is_empty())
+    };
+    let nested = |outer: &Span, inner: Option<&Span>| {
+        let (Some(o), Some(i)) = (&outer.symbol, inner.and_then(|s| s.symbol.as_ref())) else {
+            return false;
+        };
+        i.starts_with(&format!("{o} > "))
+    };
+    let mut kept = Vec::with_capacity(spans.len());
+    for (i, span) in spans.iter().enumerate() {
+        if has_body(span) || (span.symbol.is_some() && !nested(span, spans.get(i + 1))) {
+            kept.push(span.clone());
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Chunker;
+
+    #[test]
+    fn splits_per_heading_with_the_heading_trail() {
+        let text = "intro text\n\n# Setup\n\n## Vault\nset VAULT_ADDR\n```sh\n# not a heading\n```\n\n## Empty\n# Usage ##\nrun it\n## Done\n";
+        let chunks = Chunker::default().chunk("README.md", text);
+        let got: Vec<(Option<&str>, usize, usize)> = chunks
+            .iter()
+            .map(|c| (c.symbol.as_deref(), c.start_line, c.end_line))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (None, 1, 1),
+                (Some("Setup > Vault"), 5, 9),
+                (Some("Setup > Empty"), 11, 11),
+                (Some("Usage"), 12, 13),
+                (Some("Usage > Done"), 14, 14),
+            ]
+        );
+        assert!(chunks[1].text.contains("# not a heading"));
+    }
+}
@@ -0,0 +1,269 @@
+//! YAML chunks: one per item of each top-level list, one per other
+//! top-level key.
+//!
+//! Positions come from yaml-rust2's marked event stream, so chunks keep
+//! the source text, comments included, rather than re-serialized values.
+
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+use crate::Span;
+
+/// A mapping key or sequence item.
+struct Entry {
+    path: String,
+    /// 1 for keys and items of the document root.
+    depth: usize,
+    /// 0-based.
+    line: usize,
+    /// Whether this key's value is a sequence.
+    list: bool,
+    /// `id` (or failing that `name`) of a mapping item.
+    id: Option<String>,
+}
+
+enum Frame {
+    Map {
+        path: String,
+        key: Option<String>,
+        /// Entry of the sequence item this mapping is.
+        item: Option<usize>,
+    },
+    Seq {
+        path: String,
+        next: usize,
+    },
+}
+
+#[derive(Default)]
+struct Outline {
+    stack: Vec<Frame>,
+    entries: Vec<Entry>,
+}
+
+fn join(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+impl Outline {
+    /// Path of the node that is about to start and, for sequence items,
+    /// its entry. Returns `None` for mapping keys.
+    fn enter(&mut self, scalar: Option<&str>, mark: Marker) -> Option<(String, Option<usize>)> {
+        let depth = self.stack.len();
+        let line = mark.line().saturating_sub(1);
+        match self.stack.last_mut() {
+            None => Some((String::new(), None)),
+            Some(Frame::Map { path, key, item }) => match key.take() {
+                None => {
+                    let k = scalar.unwrap_or_default().to_string();
+                    self.entries.push(Entry {
+                        path: join(path, &k),
+                        depth,
+                        line,
+                        list: false,
+                        id: None,
+                    });
+                    *key = Some(k);
+                    None
+                }
+                Some(k) => {
+                    if let (Some(i), Some(value)) = (*item, scalar) {
+                        let id = &mut self.entries[i].id;
+                        if k == "id" || (k == "name" && id.is_none()) {
+                            *id = Some(value.to_string());
+                        }
+                    }
+                    Some((join(path, &k), None))
+                }
+            },
+            Some(Frame::Seq { path, next }) => {
+                let full = format!("{path}[{next}]");
+                *next += 1;
+                self.entries.push(Entry {
+                    path: full.clone(),
+                    depth,
+                    line,
+                    list: false,
+                    id: None,
+                });
+                Some((full, Some(self.entries.len() - 1)))
+            }
+        }
+    }
+}
+
+impl MarkedEventReceiver for Outline {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                self.enter(Some(&value), mark);
+            }
+            Event::Alias(_) => {
+                self.enter(None, mark);
+            }
+            Event::MappingStart(..) => {
+                let (path, item) = self.enter(None, mark).unwrap_or_default();
+                self.stack.push(Frame::Map {
+                    path,
+                    key: None,
+                    item,
+                });
+            }
+            Event::SequenceStart(..) => {
+                if let Some(Frame::Map { key: Some(_), .. }) = self.stack.last() {
+                    if let Some(e) = self.entries.last_mut() {
+                        e.list = true;
+                    }
+                }
+                let (path, _) = self.enter(None, mark).unwrap_or_default();
+                self.stack.push(Frame::Seq { path, next: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_comment_or_blank(line: &str) -> bool {
+    let t = line.trim();
+    t.is_empty() || t.starts_with('#')
+}
+
+/// Adds `span` after the last one, or into it when both start on the same
+/// line, as the keys and items of a flow collection can.
+fn push(spans: &mut Vec<Span>, span: Span) {
+    match spans.last_mut() {
+        Some(last) if span.start <= last.start => {
+            last.end = last.end.max(span.end);
+            last.symbol = None;
+            last.key_path = None;
+        }
+        _ => spans.push(span),
+    }
+}
+
+/// `None` if `text` is not valid YAML or has no top-level keys or items.
+pub(crate) fn spans(text: &str, lines: &[&str]) -> Option<Vec<Span>> {
+    let mut outline = Outline::default();
+    Parser::new_from_str(text).load(&mut outline, true).ok()?;
+    let entries = outline.entries;
+    let top: Vec<usize> = (0..entries.len())
+        .filter(|&i| entries[i].depth == 1)
+        .collect();
+    if top.is_empty() {
+        return None;
+    }
+
+    let mut spans = Vec::new();
+    for (n, &t) in top.iter().enumerate() {
+        let end = top
+            .get(n + 1)
+            .map_or(lines.len(), |&next| entries[next].line)
+            .saturating_sub(1);
+        let entry = &entries[t];
+        let items: Vec<&Entry> = entries[t + 1..top.get(n + 1).copied().unwrap_or(entries.len())]
+            .iter()
+            .filter(|e| e.depth == 2 && e.path.starts_with(&format!("{}[", entry.path)))
+            .collect();
+        // Only block lists have an item per line to split on.
+        let block = entry.list
+            && !items.is_empty()
+            && items[0].line > entry.line
+            && items.windows(2).all(|w| w[0].line < w[1].line);
+        if !block {
+            let mut span = Span::new(entry.line, end);
+            span.key_path = (!entry.path.is_empty()).then(|| entry.path.clone());
+            push(&mut spans, span);
+            continue;
+        }
+        for (i, item) in items.iter().enumerate() {
+            let start = if i == 0 { entry.line } else { item.line };
+            let stop = items.get(i + 1).map_or(end, |next| next.line - 1);
+            let mut span = Span::new(start, stop).with_symbol(item.id.clone());
+            span.key_path = Some(item.path.clone());
+            push(&mut spans, span);
+        }
+    }
+
+    // Comments above a key or item belong to it, not to the one before.
+    spans[0].start = 0;
+    for i in 1..spans.len() {
+        let mut cut = spans[i].start;
+        while cut > spans[i - 1].start + 1 && is_comment_or_blank(lines[cut - 1]) {
+            cut -= 1;
+        }
+        spans[i - 1].end = cut - 1;
+        spans[i].start = cut;
+    }
+    Some(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Chunker;
+
+    #[test]
+    fn splits_top_level_lists_per_item_with_key_paths() {
+        let text = "\
+version: 1
+tags: [a, b]
+automation_patterns:
+  # === STRATEGY ===
+  - id: \"policy_as_code\"
+    config: |
+      - not an item
+  - name: golden
+    tool: Packer
+
+  # === DELIVERY ===
+  - id: canary
+settings:
+  retries: 3
+";
+        let chunks = Chunker::default().chunk("bigtech.yaml", text);
+        let got: Vec<(Option<&str>, Option<&str>, usize, usize)> = chunks
+            .iter()
+            .map(|c| {
+                (
+                    c.key_path.as_deref(),
+                    c.symbol.as_deref(),
+                    c.start_line,
+                    c.end_line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (Some("version"), None, 1, 1),
+                (Some("tags"), None, 2, 2),
+                (Some("automation_patterns[0]"), Some("policy_as_code"), 3, 7),
+                (Some("automation_patterns[1]"), Some("golden"), 8, 9),
+                (Some("automation_patterns[2]"), Some("canary"), 11, 12),
+                (Some("settings"), None, 13, 14),
+            ]
+        );
+        assert!(chunks[4].text.starts_with("  # === DELIVERY ==="));
+
+        // Unparseable YAML is still indexed, as text.
+        let broken = Chunker::default().chunk("bad.yml", "a: [unclosed\n");
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].key_path, None);
+    }
+
+    #[test]
+    fn flow_documents_are_one_chunk() {
+        for text in ["{a: 1, b: 2}\n", "[a, b]\n"] {
+            let chunks = Chunker::default().chunk("flow.yaml", text);
+            assert_eq!(chunks.len(), 1, "{text:?}");
+            assert_eq!(chunks[0].key_path, None);
+            assert_eq!(chunks[0].start_line, 1);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! The parts of a recon config (`cyber_recon_v2.yaml`, `llm_recon_v1.yaml`)
+//! that say what to fetch and how politely.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::Duration;
+
+use extract::Source;
+use serde::Deserialize;
+
+use crate::CollectError;
+
+/// What `collect_cyber_sources.sh` sent.
+pub const DEFAULT_USER_AGENT: &str = "Strategickhaos-Recon/1.0";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Globals {
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    /// Per request, including reading the body.
+    #[serde(default = "default_timeout")]
+    pub timeout_sec: u64,
+    /// Attempts after the first when a fetch times out, cannot connect or
+    /// is answered 429 or 5xx.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Least time between the starts of two requests, retries included.
+    #[serde(default = "default_rate_limit")]
+    pub rate_limit_sec: f64,
+}
+
+fn default_user_agent() -> String {
+    DEFAULT_USER_AGENT.to_string()
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_rate_limit() -> f64 {
+    1.0
+}
+
+impl Default for Globals {
+    fn default() -> Self {
+        Self {
+            user_agent: default_user_agent(),
+            timeout_sec: default_timeout(),
+            retries: default_retries(),
+            rate_limit_sec: default_rate_limit(),
+        }
+    }
+}
+
+impl Globals {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_sec)
+    }
+
+    pub fn rate_limit(&self) -> Duration {
+        Duration::from_secs_f64(self.rate_limit_sec.max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Where sources are saved, relative to the repository root.
+    pub output_dir: String,
+    #[serde(default)]
+    pub globals: Globals,
+    #[serde(default)]
+    pub sources: Vec<Source>,
+}
+
+impl Config {
+    /// Reads the config at `path`, rejecting sources that share an id or a
+    /// file or whose file would land outside `output_dir`.
+    pub fn load(path: &Path) -> Result<Self, CollectError> {
+        let display = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|source| CollectError::Io {
+            path: display.clone(),
+            source,
+        })?;
+        let config: Self = serde_yaml::from_str(&text).map_err(|e| CollectError::Config {
+            path: display.clone(),
+            message: e.to_string(),
+        })?;
+        let invalid = |message: String| CollectError::Config {
+            path: display.clone(),
+            message,
+        };
+        let (mut ids, mut files) = (BTreeSet::new(), BTreeSet::new());
+        for source in &config.sources {
+            if !ids.insert(&source.id) {
+                return Err(invalid(format!("duplicate source id {}", source.id)));
+            }
+            let file = Path::new(&source.file);
+            if source.file.is_empty()
+                || file.is_absolute()
+                || file.components().any(|c| c.as_os_str() == "..")
+            {
+                return Err(invalid(format!(
+                    "{}: bad file {:?}",
+                    source.id, source.file
+                )));
+            }
+            if !files.insert(source.file.trim_start_matches("./")) {
+                return Err(invalid(format!(
+                    "{}: file {} is taken",
+                    source.id, source.file
+                )));
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_checked_in_configs() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+        let cyber = Config::load(&root.join("cyber_recon_v2.yaml")).unwrap();
+        assert_eq!(cyber.output_dir, "recon/cyber_v2");
+        assert_eq!(cyber.sources.len(), 30);
+        assert_eq!(
+            cyber.globals,
+            Globals {
+                user_agent: "Strategickhaos-Recon/1.0".into(),
+                timeout_sec: 60,
+                retries: 2,
+                rate_limit_sec: 1.0,
+            }
+        );
+        let llm = Config::load(&root.join("llm_recon_v1.yaml")).unwrap();
+        assert_eq!(llm.output_dir, "recon/llm_v1");
+        assert_eq!(llm.sources[0].id, "attention_is_all_you_need");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.yaml");
+        for sources in [
+            "[{id: a, url: u, file: a.html}, {id: a, url: v, file: b.html}]",
+            "[{id: a, url: u, file: a.html}, {id: b, url: v, file: ./a.html}]",
+            "[{id: a, url: u, file: ../escape.html}]",
+        ] {
+            std::fs::write(&path, format!("output_dir: out\nsources: {sources}\n")).unwrap();
+            let err = Config::load(&path).unwrap_err();
+            assert!(matches!(err, CollectError::Config { .. }), "{err}");
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! Fetching the sources of a recon config with a record of what came back.
+//!
+//! [`Collector::run`] fetches every source of a [`Config`] in order, one
+//! request at a time and no faster than `globals.rate_limit_sec`, sending
+//! `globals.user_agent` and retrying timeouts, 429s and 5xx answers
+//! `globals.retries` times. Each body is checked with [`problem`] before it
+//! is saved, so an error page never replaces a good copy. The output
+//! directory's [`Manifest`] records, per source id, the SHA-256, HTTP
+//! status, content type, ETag and fetch time of the saved file and why the
+//! latest fetch was refused, if it was. A body that differs from the saved
+//! one is reported as [`Outcome::Changed`]; a saved file still matching
+//! the manifest is revalidated with `If-None-Match` / `If-Modified-Since`.
+
+mod config;
+pub mod manifest;
+mod validate;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use artifacts::rfc3339;
+use extract::Source;
+use reqwest::header::{
+    HeaderMap, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+
+pub use config::{Config, Globals, DEFAULT_USER_AGENT};
+pub use manifest::{Entry, Fetch, Manifest, Rejection};
+pub use validate::{problem, MIN_WORDS};
+
+/// Wait before the first retry, doubled before each next one; the
+/// `--retry-delay` of `collect_cyber_sources.sh`.
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum CollectError {
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("{path}: {message}")]
+    Config { path: String, message: String },
+    #[error("{path}: {message}")]
+    Manifest { path: String, message: String },
+    #[error("http client: {0}")]
+    Client(#[from] reqwest::Error),
+}
+
+/// What became of one source in a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Saved for the first time.
+    New,
+    /// Fetched again with the same bytes.
+    Unchanged,
+    /// The server answered 304 to the saved file's validators.
+    NotModified,
+    /// Saved over a different body: the source drifted.
+    Changed { previous: String },
+    /// A response came but is not a document; nothing was saved.
+    Rejected(String),
+    /// No usable response, retries included; nothing was saved.
+    Failed(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::New => f.write_str("new"),
+            Outcome::Unchanged => f.write_str("unchanged"),
+            Outcome::NotModified => f.write_str("not modified"),
+            Outcome::Changed { previous } => write!(
+                f,
+                "changed (was {})",
+                previous.get(..12).unwrap_or(previous)
+            ),
+            Outcome::Rejected(reason) => write!(f, "rejected: {reason}"),
+            Outcome::Failed(reason) => write!(f, "failed: {reason}"),
+        }
+    }
+}
+
+/// Outcomes by source id, in config order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub outcomes: Vec<(String, Outcome)>,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    fn count(&self, f: impl Fn(&Outcome) -> bool) -> usize {
+        self.outcomes.iter().filter(|(_, o)| f(o)).count()
+    }
+
+    /// Sources whose content changed since the last run.
+    pub fn drifted(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Changed { .. }))
+    }
+
+    /// Sources rejected or failed.
+    pub fn problems(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Rejected(_) | Outcome::Failed(_)))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} sources: {} new, {} changed, {} unchanged, {} rejected, {} failed in {:.1}s",
+            self.outcomes.len(),
+            self.count(|o| *o == Outcome::New),
+            self.drifted(),
+            self.count(|o| matches!(o, Outcome::Unchanged | Outcome::NotModified)),
+            self.count(|o| matches!(o, Outcome::Rejected(_))),
+            self.count(|o| matches!(o, Outcome::Failed(_))),
+            self.elapsed.as_secs_f64(),
+        )
+    }
+}
+
+fn now() -> String {
+    rfc3339(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    )
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Replaces `path` through a temporary file beside it.
+pub(crate) fn write_atomic(path: &Path, body: &[u8]) -> Result<(), CollectError> {
+    let io = |path: &Path| {
+        let path = path.display().to_string();
+        move |source| CollectError::Io { path, source }
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(io(parent))?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, body).map_err(io(&tmp))?;
+    std::fs::rename(&tmp, path).map_err(io(path))
+}
+
+/// Spaces request starts at least `interval` apart.
+struct Pace {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Pace {
+    async fn wait(&mut self) {
+        if let Some(last) = self.last {
+            tokio::time::sleep_until((last + self.interval).into()).await;
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+/// A response worth validating.
+struct Body {
+    status: u16,
+    headers: HeaderMap,
+    bytes: Vec<u8>,
+}
+
+enum Response {
+    Body(Body),
+    NotModified,
+}
+
+/// Why a fetch produced nothing to validate.
+struct Failure {
+    status: Option<u16>,
+    reason: String,
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+pub struct Collector {
+    http: reqwest::Client,
+    config: Config,
+    dir: PathBuf,
+    backoff: Duration,
+    dry_run: bool,
+}
+
+impl Collector {
+    /// Saves `config`'s sources and the manifest in `dir`, normally the
+    /// config's `output_dir` under the repository root.
+    pub fn new(config: Config, dir: impl Into<PathBuf>) -> Result<Self, CollectError> {
+        let http = reqwest::Client::builder()
+            .user_agent(&config.globals.user_agent)
+            .timeout(config.globals.timeout())
+            .build()?;
+        Ok(Self {
+            http,
+            config,
+            dir: dir.into(),
+            backoff: DEFAULT_BACKOFF,
+            dry_run: false,
+        })
+    }
+
+    /// [`DEFAULT_BACKOFF`] by default.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Fetches and compares without writing sources or the manifest.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Fetches every source once. Entries of sources no longer in the
+    /// config are dropped from the manifest; their files are left alone.
+    pub async fn run(&self) -> Result<Report, CollectError> {
+        let started = Instant::now();
+        let mut manifest = Manifest::load(&self.dir)?;
+        let ids: BTreeSet<_> = self.config.sources.iter().map(|s| &s.id).collect();
+        manifest.sources.retain(|id, _| ids.contains(id));
+
+        let mut pace = Pace {
+            interval: self.config.globals.rate_limit(),
+            last: None,
+        };
+        let mut report = Report::default();
+        for source in &self.config.sources {
+            let previous = manifest.sources.remove(&source.id);
+            let (entry, outcome) = self.collect(&mut pace, source, previous).await?;
+            match &outcome {
+                Outcome::New | Outcome::Unchanged | Outcome::NotModified => {
+                    tracing::info!(source = %source.id, %outcome)
+                }
+                _ => tracing::warn!(source = %source.id, url = %source.url, %outcome),
+            }
+            manifest.sources.insert(source.id.clone(), entry);
+            if !self.dry_run {
+                // After every source, so an interrupted run keeps its work.
+                manifest.save(&self.dir)?;
+            }
+            report.outcomes.push((source.id.clone(), outcome));
+        }
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+
+    async fn collect(
+        &self,
+        pace: &mut Pace,
+        source: &Source,
+        previous: Option<Entry>,
+    ) -> Result<(Entry, Outcome), CollectError> {
+        let file = source.file.trim_start_matches("./");
+        let path = self.dir.join(file);
+        let saved = previous.as_ref().and_then(|e| e.saved.clone());
+        // Revalidate only a copy that is still the one the manifest
+        // describes and came from the same URL.
+        let current = match (&saved, &previous) {
+            (Some(fetch), Some(entry)) if entry.url == source.url => {
+                match tokio::fs::read(&path).await {
+                    Ok(bytes) if sha256(&bytes) == fetch.sha256 => Some(fetch.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let mut entry = Entry {
+            url: source.url.clone(),
+            file: file.to_string(),
+            saved: saved.clone(),
+            changed_at: previous.and_then(|e| e.changed_at),
+            rejected: None,
+            checked_at: now(),
+        };
+
+        let body = match self.fetch(pace, &source.url, current.as_ref()).await {
+            Ok(Response::NotModified) => return Ok((entry, Outcome::NotModified)),
+            Ok(Response::Body(body)) => body,
+            Err(failure) => {
+                entry.rejected = Some(Rejection {
+                    status: failure.status,
+                    reason: failure.reason.clone(),
+                    at: entry.checked_at.clone(),
+                });
+                return Ok((entry, Outcome::Failed(failure.reason)));
+            }
+        };
+        let content_type = header(&body.headers, CONTENT_TYPE);
+        let (name, kind) = (file.to_string(), content_type.clone());
+        let (bytes, found) = tokio::task::spawn_blocking(move || {
+            let found = problem(&name, kind.as_deref(), &body.bytes);
+            (body.bytes, found)
+        })
+        .await
+        .expect("validation panicked");
+        if let Some(reason) = found {
+            entry.rejected = Some(Rejection {
+                status: Some(body.status),
+                reason: reason.clone(),
+                at: entry.checked_at.clone(),
+            });
+            return Ok((entry, Outcome::Rejected(reason)));
+        }
+
+        let sha256 = sha256(&bytes);
+        let outcome = match saved {
+            None => Outcome::New,
+            Some(fetch) if fetch.sha256 == sha256 => Outcome::Unchanged,
+            Some(fetch) => Outcome::Changed {
+                previous: fetch.sha256,
+            },
+        };
+        // An unchanged body is written again when the copy on disk is
+        // missing or was edited.
+        if !self.dry_run && (outcome != Outcome::Unchanged || current.is_none()) {
+            write_atomic(&path, &bytes)?;
+        }
+        if outcome != Outcome::Unchanged {
+            entry.changed_at = Some(entry.checked_at.clone());
+        }
+        entry.saved = Some(Fetch {
+            sha256,
+            bytes: bytes.len() as u64,
+            status: body.status,
+            content_type,
+            etag: header(&body.headers, ETAG),
+            last_modified: header(&body.headers, LAST_MODIFIED),
+            fetched_at: entry.checked_at.clone(),
+        });
+        Ok((entry, outcome))
+    }
+
+    /// GETs `url`, conditionally on `current`'s validators, retrying what
+    /// may pass.
+    async fn fetch(
+        &self,
+        pace: &mut Pace,
+        url: &str,
+        current: Option<&Fetch>,
+    ) -> Result<Response, Failure> {
+        let mut wait = self.backoff;
+        let mut retries = self.config.globals.retries;
+        loop {
+            pace.wait().await;
+            let failure = match self.attempt(url, current).await {
+                Ok(response) => return Ok(response),
+                Err((failure, false)) => return Err(failure),
+                Err((failure, true)) => failure,
+            };
+            if retries == 0 {
+                return Err(failure);
+            }
+            tracing::warn!(%url, reason = %failure.reason, retry_in = ?wait, "fetch failed");
+            tokio::time::sleep(wait).await;
+            wait *= 2;
+            retries -= 1;
+        }
+    }
+
+    /// One request; a failure says whether it is worth retrying.
+    async fn attempt(
+        &self,
+        url: &str,
+        current: Option<&Fetch>,
+    ) -> Result<Response, (Failure, bool)> {
+        let mut request = self.http.get(url);
+        if let Some(fetch) = current {
+            if let Some(etag) = &fetch.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(modified) = &fetch.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, modified);
+            }
+        }
+        let transport = |e: reqwest::Error| {
+            let retry = e.is_connect() || e.is_timeout() || e.is_body();
+            let failure = Failure {
+                status: e.status().map(|s| s.as_u16()),
+                reason: e.to_string(),
+            };
+            (failure, retry)
+        };
+        let res = request.send().await.map_err(transport)?;
+        let status = res.status();
+        if status == StatusCode::NOT_MODIFIED && current.is_some() {
+            return Ok(Response::NotModified);
+        }
+        if !status.is_success() {
+            let failure = Failure {
+                status: Some(status.as_u16()),
+                reason: format!("HTTP {status}"),
+            };
+            let retry = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            return Err((failure, retry));
+        }
+        let headers = res.headers().clone();
+        let bytes = res.bytes().await.map_err(transport)?.to_vec();
+        Ok(Response::Body(Body {
+            status: status.as_u16(),
+            headers,
+            bytes,
+        }))
+    }
+}
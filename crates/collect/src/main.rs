@@ -0,0 +1,64 @@
+//! `recon-collect`: fetches the sources of recon configs into their
+//! `output_dir`, keeping a manifest of what each fetch returned.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use collect::{Collector, Config, Outcome};
+
+/// Recon configs read from the repository root when none are named.
+const CONFIGS: &[&str] = &["cyber_recon_v2.yaml", "llm_recon_v1.yaml"];
+
+#[derive(Parser)]
+#[command(
+    name = "recon-collect",
+    about = "Fetch recon sources with checksums and drift detection"
+)]
+struct Args {
+    /// Recon configs; `cyber_recon_v2.yaml` and `llm_recon_v1.yaml` under
+    /// the root by default.
+    configs: Vec<PathBuf>,
+    /// Repository root the configs' `output_dir`s are relative to.
+    #[arg(long, default_value = ".")]
+    root: PathBuf,
+    /// Fetch and compare without saving; exits non-zero on drift as well.
+    #[arg(long)]
+    check: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+    let configs = if args.configs.is_empty() {
+        CONFIGS.iter().map(|name| args.root.join(name)).collect()
+    } else {
+        args.configs.clone()
+    };
+
+    let (mut problems, mut drifted) = (0, 0);
+    for path in &configs {
+        let config = Config::load(path)?;
+        let dir = args.root.join(&config.output_dir);
+        let report = Collector::new(config, &dir)?
+            .with_dry_run(args.check)
+            .run()
+            .await?;
+        for (id, outcome) in &report.outcomes {
+            if !matches!(
+                outcome,
+                Outcome::New | Outcome::Unchanged | Outcome::NotModified
+            ) {
+                println!("  {id}: {outcome}");
+            }
+        }
+        println!("{}: {report}", path.display());
+        problems += report.problems();
+        drifted += report.drifted();
+    }
+    anyhow::ensure!(problems == 0, "{problems} source(s) not collected");
+    anyhow::ensure!(!args.check || drifted == 0, "{drifted} source(s) changed");
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! What each source fetched to, kept as `manifest.json` in the output
+//! directory.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CollectError;
+
+pub const FILE: &str = "manifest.json";
+
+/// The response a saved file came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fetch {
+    /// Hex SHA-256 of the body.
+    pub sha256: String,
+    pub bytes: u64,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// RFC 3339.
+    pub fetched_at: String,
+}
+
+/// A fetch that was not saved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// `None` when no response arrived.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub reason: String,
+    /// RFC 3339.
+    pub at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    /// Relative to the output directory.
+    pub file: String,
+    /// What is saved at `file`; `None` until a fetch passes validation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved: Option<Fetch>,
+    /// When the saved body last differed from the one before it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_at: Option<String>,
+    /// Set while the latest fetch failed; the saved file is then older.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected: Option<Rejection>,
+    /// RFC 3339 time of the latest attempt.
+    pub checked_at: String,
+}
+
+/// Entries by source id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub sources: BTreeMap<String, Entry>,
+}
+
+impl Manifest {
+    /// An empty manifest if `dir` has none yet.
+    pub fn load(dir: &Path) -> Result<Self, CollectError> {
+        let path = dir.join(FILE);
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| CollectError::Manifest {
+                path: path.display().to_string(),
+                message: e.to_string(),
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(CollectError::Io {
+                path: path.display().to_string(),
+                source,
+            }),
+        }
+    }
+
+    /// Replaces `dir`'s manifest atomically.
+    pub fn save(&self, dir: &Path) -> Result<(), CollectError> {
+        let body = serde_json::to_vec_pretty(self).expect("manifest serializes");
+        crate::write_atomic(&dir.join(FILE), &body)
+    }
+}
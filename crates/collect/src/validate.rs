@@ -0,0 +1,138 @@
+//! Telling a document from the error page, bot wall or empty app shell a
+//! site sends instead, with a 200 as often as not: `cisa_kev.html` is an
+//! Akamai "Access Denied" page, `msrc_update_guide.html` a page asking for
+//! JavaScript.
+
+use extract::Format;
+
+/// Fewer words of extracted text than this and a page is not a document.
+/// The shortest real page in `recon/cyber_v2`, the SP 800-61 landing page,
+/// has 100; script-rendered shells have a handful.
+pub const MIN_WORDS: usize = 80;
+
+/// Title phrases of error and challenge pages, lower-case, matched as
+/// whole words.
+const ERROR_TITLES: &[&str] = &[
+    "access denied",
+    "forbidden",
+    "not found",
+    "403",
+    "404",
+    "502",
+    "503",
+    "internal server error",
+    "just a moment",
+    "attention required",
+    "are you a robot",
+    "captcha",
+    "service unavailable",
+    "too many requests",
+];
+
+/// Why `body`, fetched for `file` and served as `content_type`, is not
+/// worth saving, or `None` if it is.
+///
+/// The body must be the kind of document `file`'s extension names, must
+/// extract, must not carry an error page's title and must hold
+/// [`MIN_WORDS`] words of text. CPU-bound like [`extract::extract`].
+pub fn problem(file: &str, content_type: Option<&str>, body: &[u8]) -> Option<String> {
+    if body.is_empty() {
+        return Some("empty body".into());
+    }
+    let expected = Format::from_path(file);
+    let format = Format::of(file, body).or(expected);
+    if expected == Some(Format::Pdf) && format != Some(Format::Pdf) {
+        return Some(format!(
+            "expected a PDF, got {}",
+            content_type.unwrap_or("an unknown content type")
+        ));
+    }
+    let Some(format) = format else {
+        // No extension to go by; a non-empty body is all there is to check.
+        return None;
+    };
+    let doc = match extract::extract(format, body) {
+        Ok(doc) => doc,
+        Err(e) => return Some(e.to_string()),
+    };
+    if let Some(title) = &doc.title {
+        let words: Vec<_> = title
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect();
+        let words = format!(" {} ", words.join(" "));
+        if let Some(phrase) = ERROR_TITLES
+            .iter()
+            .find(|p| words.contains(&format!(" {p} ")))
+        {
+            return Some(format!("error page ({phrase:?} in title {title:?})"));
+        }
+    }
+    let words: usize = doc
+        .blocks
+        .iter()
+        .map(|b| b.text.split_whitespace().count())
+        .sum();
+    if words < MIN_WORDS {
+        return Some(format!("only {words} words of text"));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn saved(file: &str) -> Option<String> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../recon/cyber_v2");
+        problem(
+            file,
+            Some("text/html"),
+            &std::fs::read(path.join(file)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn sorts_the_saved_cyber_sources() {
+        for file in [
+            "nist_sp800-61.html",
+            "owasp_top10.html",
+            "first_cvss_v31.html",
+            "sleuthkit_home.html",
+        ] {
+            assert_eq!(saved(file), None, "{file}");
+        }
+        for (file, reason) in [
+            ("cisa_kev.html", "access denied"),
+            ("nist_cftt.html", "not found"),
+            ("sigma_rules_docs.html", "not found"),
+            ("mitre_attack_evals_fixed.html", "only 0 words"),
+            ("msrc_update_guide.html", "only 0 words"),
+            ("atomic_red_team.html", "only 3 words"),
+        ] {
+            let found = saved(file).unwrap_or_default();
+            assert!(found.contains(reason), "{file}: {found}");
+        }
+    }
+
+    #[test]
+    fn checks_pdfs_are_pdfs() {
+        let denied = std::fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../recon/cyber_v2/cisa_kev.html"),
+        )
+        .unwrap();
+        assert_eq!(
+            problem("paper.pdf", Some("text/html"), &denied).as_deref(),
+            Some("expected a PDF, got text/html")
+        );
+        assert_eq!(
+            problem("paper.pdf", None, b"").as_deref(),
+            Some("empty body")
+        );
+        assert_eq!(problem("notes", None, b"plain text"), None);
+    }
+}
@@ -0,0 +1,281 @@
+//! Collecting from a local server: politeness, the manifest, refused
+//! error pages and drift between runs.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use collect::{Collector, Config, Globals, Manifest, Outcome};
+use extract::Source;
+use sha2::{Digest, Sha256};
+
+const DENIED: &str = "<html><head><title>Access Denied</title></head>\
+    <body><h1>Access Denied</h1>You don't have permission to access this server.</body></html>";
+
+fn page(version: u32) -> String {
+    let words: String = (0..120).map(|i| format!("control{i} ")).collect();
+    format!(
+        "<html><head><title>Guide v{version}</title></head>\
+         <body><main><h1>Guide</h1><p>Version {version}. {words}</p></main></body></html>"
+    )
+}
+
+fn html(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], body).into_response()
+}
+
+#[derive(Default)]
+struct Site {
+    /// Path, `User-Agent`, `If-None-Match` and arrival of every request.
+    requests: Vec<(String, String, Option<String>, Instant)>,
+    /// Version `/changing` serves; 0 serves the access-denied page.
+    version: u32,
+    flaky_failures: u32,
+}
+
+type Shared = Arc<Mutex<Site>>;
+
+fn log(site: &Shared, path: &str, headers: &HeaderMap) {
+    let value = |name| {
+        headers
+            .get(name)
+            .map(|v: &header::HeaderValue| v.to_str().unwrap().to_string())
+    };
+    site.lock().unwrap().requests.push((
+        path.to_string(),
+        value(header::USER_AGENT).unwrap_or_default(),
+        value(header::IF_NONE_MATCH),
+        Instant::now(),
+    ));
+}
+
+async fn serve(site: Shared) -> String {
+    let app = Router::new()
+        .route(
+            "/doc",
+            get(
+                |State(site): State<Shared>, headers: HeaderMap| async move {
+                    log(&site, "/doc", &headers);
+                    if headers
+                        .get(header::IF_NONE_MATCH)
+                        .is_some_and(|v| v == "\"v1\"")
+                    {
+                        return StatusCode::NOT_MODIFIED.into_response();
+                    }
+                    let mut res = html(page(1));
+                    res.headers_mut()
+                        .insert(header::ETAG, "\"v1\"".parse().unwrap());
+                    res
+                },
+            ),
+        )
+        .route(
+            "/changing",
+            get(
+                |State(site): State<Shared>, headers: HeaderMap| async move {
+                    log(&site, "/changing", &headers);
+                    match site.lock().unwrap().version {
+                        0 => html(DENIED.to_string()),
+                        v => html(page(v)),
+                    }
+                },
+            ),
+        )
+        .route(
+            "/flaky",
+            get(
+                |State(site): State<Shared>, headers: HeaderMap| async move {
+                    log(&site, "/flaky", &headers);
+                    let mut site = site.lock().unwrap();
+                    if site.flaky_failures > 0 {
+                        site.flaky_failures -= 1;
+                        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+                    }
+                    html(page(7))
+                },
+            ),
+        )
+        .route(
+            "/denied",
+            get(
+                |State(site): State<Shared>, headers: HeaderMap| async move {
+                    log(&site, "/denied", &headers);
+                    html(DENIED.to_string())
+                },
+            ),
+        )
+        .route(
+            "/missing",
+            get(
+                |State(site): State<Shared>, headers: HeaderMap| async move {
+                    log(&site, "/missing", &headers);
+                    StatusCode::NOT_FOUND
+                },
+            ),
+        )
+        .with_state(site);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+fn config(base: &str, paths: &[&str]) -> Config {
+    Config {
+        output_dir: "out".into(),
+        globals: Globals {
+            user_agent: "recon-test/1.0".into(),
+            timeout_sec: 5,
+            retries: 1,
+            rate_limit_sec: 0.05,
+        },
+        sources: paths
+            .iter()
+            .map(|p| Source {
+                id: p.to_string(),
+                url: format!("{base}/{p}"),
+                file: format!("{p}.html"),
+                category: None,
+            })
+            .collect(),
+    }
+}
+
+fn collector(base: &str, paths: &[&str], dir: &Path) -> Collector {
+    Collector::new(config(base, paths), dir)
+        .unwrap()
+        .with_backoff(Duration::from_millis(10))
+}
+
+fn sha256(bytes: impl AsRef<[u8]>) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+#[tokio::test]
+async fn paces_retries_and_records_what_each_fetch_returned() {
+    let site = Shared::default();
+    site.lock().unwrap().version = 1;
+    site.lock().unwrap().flaky_failures = 1;
+    let base = serve(site.clone()).await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let report = collector(&base, &["doc", "flaky", "denied", "missing"], dir.path())
+        .run()
+        .await
+        .unwrap();
+    let outcomes: Vec<_> = report.outcomes.iter().map(|(_, o)| o.clone()).collect();
+    assert_eq!(outcomes[..2], [Outcome::New, Outcome::New]);
+    assert!(
+        matches!(&outcomes[2], Outcome::Rejected(r) if r.contains("access denied")),
+        "{outcomes:?}"
+    );
+    assert_eq!(outcomes[3], Outcome::Failed("HTTP 404 Not Found".into()));
+    assert_eq!(report.problems(), 2);
+
+    // The 503 was retried and the 404 was not; every request identified
+    // itself and none started sooner than the rate limit allows.
+    let requests = std::mem::take(&mut site.lock().unwrap().requests);
+    let paths: Vec<_> = requests.iter().map(|r| r.0.as_str()).collect();
+    assert_eq!(paths, ["/doc", "/flaky", "/flaky", "/denied", "/missing"]);
+    assert!(requests.iter().all(|r| r.1 == "recon-test/1.0"));
+    for pair in requests.windows(2) {
+        let gap = pair[1].3 - pair[0].3;
+        assert!(gap >= Duration::from_millis(45), "{gap:?}");
+    }
+
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("doc.html")).unwrap(),
+        page(1)
+    );
+    assert!(!dir.path().join("denied.html").exists());
+    let manifest = Manifest::load(dir.path()).unwrap();
+    let doc = manifest.sources["doc"].saved.as_ref().unwrap();
+    assert_eq!(doc.sha256, sha256(page(1)));
+    assert_eq!(doc.bytes, page(1).len() as u64);
+    assert_eq!(doc.status, 200);
+    assert_eq!(
+        doc.content_type.as_deref(),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(doc.etag.as_deref(), Some("\"v1\""));
+    assert!(doc.fetched_at.ends_with('Z'));
+    let denied = &manifest.sources["denied"];
+    assert_eq!(denied.saved, None);
+    assert_eq!(denied.rejected.as_ref().unwrap().status, Some(200));
+    let missing = manifest.sources["missing"].rejected.as_ref().unwrap();
+    assert_eq!(missing.status, Some(404));
+}
+
+#[tokio::test]
+async fn detects_drift_and_keeps_good_copies_over_error_pages() {
+    let site = Shared::default();
+    site.lock().unwrap().version = 1;
+    let base = serve(site.clone()).await;
+    let dir = tempfile::tempdir().unwrap();
+    let run = || async {
+        collector(&base, &["doc", "changing"], dir.path())
+            .run()
+            .await
+            .unwrap()
+            .outcomes
+    };
+    let changing = dir.path().join("changing.html");
+    run().await;
+
+    // The saved ETag is sent back; the changed page is drift.
+    site.lock().unwrap().version = 2;
+    let outcomes = run().await;
+    assert_eq!(outcomes[0].1, Outcome::NotModified);
+    assert_eq!(
+        outcomes[1].1,
+        Outcome::Changed {
+            previous: sha256(page(1))
+        }
+    );
+    assert_eq!(std::fs::read_to_string(&changing).unwrap(), page(2));
+    let manifest = Manifest::load(dir.path()).unwrap();
+    let entry = &manifest.sources["changing"];
+    assert_eq!(entry.saved.as_ref().unwrap().sha256, sha256(page(2)));
+    assert_eq!(entry.changed_at.as_ref(), Some(&entry.checked_at));
+    let etags: Vec<_> = site
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .map(|r| r.2.clone())
+        .collect();
+    assert_eq!(etags[2], Some("\"v1\"".into()));
+
+    // An error page leaves the good copy, and a missing copy is
+    // fetched whole rather than revalidated.
+    site.lock().unwrap().version = 0;
+    std::fs::remove_file(dir.path().join("doc.html")).unwrap();
+    let outcomes = run().await;
+    assert_eq!(outcomes[0].1, Outcome::Unchanged);
+    assert!(matches!(outcomes[1].1, Outcome::Rejected(_)));
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("doc.html")).unwrap(),
+        page(1)
+    );
+    assert_eq!(std::fs::read_to_string(&changing).unwrap(), page(2));
+    let manifest = Manifest::load(dir.path()).unwrap();
+    let entry = &manifest.sources["changing"];
+    assert_eq!(entry.saved.as_ref().unwrap().sha256, sha256(page(2)));
+    assert!(entry.rejected.is_some());
+
+    // A dry run reports drift and writes nothing.
+    site.lock().unwrap().version = 3;
+    let report = collector(&base, &["doc", "changing"], dir.path())
+        .with_dry_run(true)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.drifted(), 1);
+    assert_eq!(std::fs::read_to_string(&changing).unwrap(), page(2));
+    assert_eq!(Manifest::load(dir.path()).unwrap(), manifest);
+}
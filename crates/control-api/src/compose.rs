@@ -0,0 +1,442 @@
+//! Docker Compose backend over the repo's `docker-compose*.yml` files.
+//!
+//! One Compose project is one environment. Only services whose `image:`
+//! references `${IMAGE_TAG}` can be deployed; a deploy naming no services
+//! deploys all of them, and one naming any other service is refused before
+//! anything runs. Deploys export `IMAGE_TAG` and run `docker compose up -d
+//! --build` for those services, then check that every running container
+//! of them has the requested tag and fail if not.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::runner::{Process, Runner};
+use crate::{
+    image_tag, Backend, BackendError, DeployRequest, DeployResult, Replicas, ResultStatus,
+    ScaleRequest, ScaleResult, ServiceStatus,
+};
+
+/// Environment variable deploys set to the requested tag.
+pub const TAG_VAR: &str = "IMAGE_TAG";
+
+pub struct Compose {
+    runner: Arc<dyn Runner>,
+    files: Vec<PathBuf>,
+    project: Option<String>,
+    env: String,
+    services: BTreeSet<String>,
+    /// Services whose image references [`TAG_VAR`].
+    tagged: BTreeSet<String>,
+}
+
+/// One entry of `docker compose ps --format json`.
+#[derive(Deserialize)]
+struct Container {
+    #[serde(rename = "State", default)]
+    state: String,
+    #[serde(rename = "Health", default)]
+    health: String,
+    #[serde(rename = "Image", default)]
+    image: String,
+    #[serde(rename = "Service", default)]
+    service: String,
+}
+
+impl Compose {
+    /// Serves `env` from the services declared across `files`.
+    pub fn new(files: Vec<PathBuf>, env: impl Into<String>) -> Result<Self, BackendError> {
+        let (mut services, mut tagged) = (BTreeSet::new(), BTreeSet::new());
+        for file in &files {
+            let text = std::fs::read_to_string(file)?;
+            let doc: serde_yaml::Value = serde_yaml::from_str(&text)
+                .map_err(|e| BackendError::Invalid(format!("{}: {e}", file.display())))?;
+            let Some(map) = doc.get("services").and_then(|s| s.as_mapping()) else {
+                continue;
+            };
+            for (name, service) in map {
+                let Some(name) = name.as_str() else { continue };
+                services.insert(name.to_string());
+                let image = service.get("image").and_then(|i| i.as_str());
+                if image.is_some_and(uses_tag) {
+                    tagged.insert(name.to_string());
+                }
+            }
+        }
+        Ok(Self {
+            runner: Arc::new(Process),
+            files,
+            project: None,
+            env: env.into(),
+            services,
+            tagged,
+        })
+    }
+
+    /// `docker compose -p`.
+    pub fn with_project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    pub fn with_runner(mut self, runner: Arc<dyn Runner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn services(&self) -> &BTreeSet<String> {
+        &self.services
+    }
+
+    /// The services a deploy can roll to a tag.
+    pub fn deployable(&self) -> &BTreeSet<String> {
+        &self.tagged
+    }
+
+    fn known(&self, service: &str) -> Result<(), BackendError> {
+        if self.services.contains(service) {
+            Ok(())
+        } else {
+            Err(BackendError::UnknownService(service.to_string()))
+        }
+    }
+
+    async fn compose(
+        &self,
+        args: &[&str],
+        env: &[(String, String)],
+    ) -> Result<String, BackendError> {
+        let mut full = vec!["compose".to_string()];
+        for file in &self.files {
+            full.push("-f".to_string());
+            full.push(file.display().to_string());
+        }
+        if let Some(project) = &self.project {
+            full.push("-p".to_string());
+            full.push(project.clone());
+        }
+        full.extend(args.iter().map(|a| a.to_string()));
+        self.runner.run("docker", &full, env).await
+    }
+}
+
+/// Whether a Compose `image:` value interpolates [`TAG_VAR`], as
+/// `$IMAGE_TAG`, `${IMAGE_TAG}` or `${IMAGE_TAG:-latest}`.
+fn uses_tag(image: &str) -> bool {
+    image.match_indices('$').any(|(i, _)| {
+        let rest = image[i + 1..].trim_start_matches('{');
+        rest.strip_prefix(TAG_VAR).is_some_and(|after| {
+            !after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        })
+    })
+}
+
+/// Older Compose prints a JSON array, newer one object per line.
+fn parse_ps(out: &str) -> Result<Vec<Container>, BackendError> {
+    let invalid = |e: serde_json::Error| BackendError::Output {
+        program: "docker compose ps".to_string(),
+        message: e.to_string(),
+    };
+    let out = out.trim();
+    if out.starts_with('[') {
+        return serde_json::from_str(out).map_err(invalid);
+    }
+    out.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(invalid))
+        .collect()
+}
+
+#[async_trait]
+impl Backend for Compose {
+    fn name(&self) -> &'static str {
+        "compose"
+    }
+
+    async fn status(&self, service: &str) -> Result<ServiceStatus, BackendError> {
+        self.known(service)?;
+        let containers = parse_ps(
+            &self
+                .compose(&["ps", "--all", "--format", "json", service], &[])
+                .await?,
+        )?;
+        let ready = containers
+            .iter()
+            .filter(|c| c.state == "running" && c.health != "unhealthy")
+            .count();
+        let replicas = Replicas {
+            ready: ready as u32,
+            desired: containers.len() as u32,
+        };
+        Ok(ServiceStatus {
+            service: service.to_string(),
+            state: replicas.state(),
+            version: containers
+                .first()
+                .map_or("unknown", |c| image_tag(&c.image))
+                .to_string(),
+            replicas,
+        })
+    }
+
+    async fn logs(&self, service: &str, tail: u32) -> Result<String, BackendError> {
+        self.known(service)?;
+        let tail = tail.to_string();
+        self.compose(
+            &[
+                "logs",
+                "--no-color",
+                "--no-log-prefix",
+                "--tail",
+                &tail,
+                service,
+            ],
+            &[],
+        )
+        .await
+    }
+
+    async fn deploy(&self, req: &DeployRequest) -> Result<DeployResult, BackendError> {
+        if req.env != self.env {
+            return Err(BackendError::UnknownEnv(req.env.clone()));
+        }
+        let services: Vec<String> = if req.services.is_empty() {
+            self.tagged.iter().cloned().collect()
+        } else {
+            for s in &req.services {
+                self.known(s)?;
+                if !self.tagged.contains(s) {
+                    return Err(BackendError::Invalid(format!(
+                        "{s} cannot be deployed: its image does not use ${{{TAG_VAR}}}"
+                    )));
+                }
+            }
+            req.services.clone()
+        };
+        if services.is_empty() {
+            return Err(BackendError::Invalid(format!(
+                "no service image uses ${{{TAG_VAR}}}"
+            )));
+        }
+        let mut args = vec!["up", "-d", "--build"];
+        args.extend(services.iter().map(String::as_str));
+        self.compose(&args, &[(TAG_VAR.to_string(), req.tag.clone())])
+            .await?;
+
+        let mut ps = vec!["ps", "--format", "json"];
+        ps.extend(services.iter().map(String::as_str));
+        let containers = parse_ps(&self.compose(&ps, &[]).await?)?;
+        let off: Vec<String> = services
+            .iter()
+            .filter_map(|service| {
+                let tags: BTreeSet<&str> = containers
+                    .iter()
+                    .filter(|c| &c.service == service)
+                    .map(|c| image_tag(&c.image))
+                    .collect();
+                if tags.is_empty() {
+                    Some(format!("{service} has no running containers"))
+                } else if tags.len() == 1 && tags.contains(req.tag.as_str()) {
+                    None
+                } else {
+                    let tags: Vec<_> = tags.into_iter().collect();
+                    Some(format!("{service} runs {}", tags.join(", ")))
+                }
+            })
+            .collect();
+        if !off.is_empty() {
+            return Err(BackendError::Rollout(format!(
+                "not on {}: {}",
+                req.tag,
+                off.join("; ")
+            )));
+        }
+        Ok(DeployResult {
+            status: ResultStatus::Ok,
+            env: req.env.clone(),
+            tag: req.tag.clone(),
+            services,
+        })
+    }
+
+    async fn scale(&self, req: &ScaleRequest) -> Result<ScaleResult, BackendError> {
+        self.known(&req.service)?;
+        let scale = format!("{}={}", req.service, req.replicas);
+        self.compose(
+            &["up", "-d", "--no-recreate", "--scale", &scale, &req.service],
+            &[],
+        )
+        .await?;
+        Ok(ScaleResult {
+            status: ResultStatus::Ok,
+            service: req.service.clone(),
+            replicas: req.replicas,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Scripted;
+    use crate::ServiceState;
+
+    fn compose(runner: Arc<Scripted>) -> Compose {
+        let file = PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../docker-compose.yml"
+        ));
+        Compose::new(vec![file], "dev")
+            .unwrap()
+            .with_project("ops")
+            .with_runner(runner)
+    }
+
+    #[tokio::test]
+    async fn reads_services_and_drives_docker_compose() {
+        let ps = concat!(
+            r#"{"Name":"ops-event-gateway-1","State":"running","Health":"","Image":"ops-event-gateway:1.4.2"}"#,
+            "\n",
+            r#"{"Name":"ops-event-gateway-2","State":"exited","Health":"","Image":"ops-event-gateway:1.4.2"}"#,
+        );
+        let deployed =
+            r#"{"Service":"event-gateway","State":"running","Image":"ops-event-gateway:v2"}"#;
+        let runner = Arc::new(Scripted::default().reply(ps).reply("").reply(deployed));
+        let backend = compose(runner.clone());
+        assert!(backend.services().contains("discord-bot"));
+
+        let status = backend.status("event-gateway").await.unwrap();
+        assert_eq!(status.state, ServiceState::Degraded);
+        assert_eq!(status.version, "1.4.2");
+        assert_eq!(
+            status.replicas,
+            Replicas {
+                ready: 1,
+                desired: 2
+            }
+        );
+
+        assert!(matches!(
+            backend.status("nope").await,
+            Err(BackendError::UnknownService(_))
+        ));
+        assert!(matches!(
+            backend
+                .deploy(&DeployRequest {
+                    env: "prod".into(),
+                    tag: "v2".into(),
+                    services: vec![],
+                })
+                .await,
+            Err(BackendError::UnknownEnv(_))
+        ));
+        backend
+            .deploy(&DeployRequest {
+                env: "dev".into(),
+                tag: "v2".into(),
+                services: vec!["event-gateway".into()],
+            })
+            .await
+            .unwrap();
+        backend
+            .scale(&ScaleRequest {
+                service: "discord-bot".into(),
+                replicas: 3,
+            })
+            .await
+            .unwrap();
+
+        let lines = runner.lines();
+        let prefix = format!(
+            "docker compose -f {}/../../docker-compose.yml -p ops",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        assert_eq!(
+            lines,
+            [
+                format!("{prefix} ps --all --format json event-gateway"),
+                format!("{prefix} up -d --build event-gateway"),
+                format!("{prefix} ps --format json event-gateway"),
+                format!("{prefix} up -d --no-recreate --scale discord-bot=3 discord-bot"),
+            ]
+        );
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(calls[1].2, [(TAG_VAR.to_string(), "v2".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn deploy_fails_when_containers_keep_another_tag() {
+        let ps = concat!(
+            r#"{"Service":"event-gateway","State":"running","Image":"ops-event-gateway:latest"}"#,
+            "\n",
+            r#"{"Service":"discord-bot","State":"running","Image":"ops-discord-bot:v2"}"#,
+        );
+        let runner = Arc::new(Scripted::default().reply("").reply(ps));
+        let backend = compose(runner.clone());
+        assert_eq!(
+            backend.deployable().iter().collect::<Vec<_>>(),
+            [
+                "discord-bot",
+                "event-gateway",
+                "jdk-workspace",
+                "refinory-api"
+            ]
+        );
+
+        // A pinned image is refused before the stack is touched.
+        let err = backend
+            .deploy(&DeployRequest {
+                env: "dev".into(),
+                tag: "v2".into(),
+                services: vec!["event-gateway".into(), "redis".into()],
+            })
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, BackendError::Invalid(m) if m.contains("redis")),
+            "{err}"
+        );
+        assert!(runner.lines().is_empty());
+
+        // No services means every deployable one.
+        let err = backend
+            .deploy(&DeployRequest {
+                env: "dev".into(),
+                tag: "v2".into(),
+                services: vec![],
+            })
+            .await
+            .unwrap_err();
+        let BackendError::Rollout(message) = err else {
+            panic!("{err}");
+        };
+        assert!(message.contains("event-gateway runs latest"), "{message}");
+        assert!(
+            message.contains("refinory-api has no running containers"),
+            "{message}"
+        );
+        assert!(!message.contains("discord-bot"), "{message}");
+        assert!(runner.lines()[0]
+            .ends_with("up -d --build discord-bot event-gateway jdk-workspace refinory-api"));
+    }
+
+    #[test]
+    fn recognizes_images_that_use_the_tag() {
+        for image in ["a:$IMAGE_TAG", "a:${IMAGE_TAG}", "a:${IMAGE_TAG:-latest}"] {
+            assert!(uses_tag(image), "{image}");
+        }
+        for image in ["redis:7-alpine", "a:${IMAGE_TAGS}", "a:IMAGE_TAG"] {
+            assert!(!uses_tag(image), "{image}");
+        }
+    }
+
+    #[test]
+    fn parses_array_output_of_older_compose() {
+        let ps = r#"[{"State":"running","Health":"healthy","Image":"redis:7"}]"#;
+        assert_eq!(parse_ps(ps).unwrap().len(), 1);
+        assert!(parse_ps("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+//! Kubernetes backend for the workloads in `bootstrap/k8s/*.yaml`.
+//!
+//! Talks to the cluster through `kubectl`, so it uses whatever credentials
+//! and contexts the host (or the pod's service account) already has.
+//! Deploys set the first container's image to `<repo>:<tag>` and wait for
+//! the rollout.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::runner::{Process, Runner};
+use crate::{
+    image_repo, image_tag, Backend, BackendError, DeployRequest, DeployResult, Replicas,
+    ResultStatus, ScaleRequest, ScaleResult, ServiceStatus,
+};
+
+/// How long a deploy waits for each rollout.
+pub const ROLLOUT_TIMEOUT: &str = "180s";
+
+/// A Deployment or StatefulSet found in the manifests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workload {
+    /// `deployment` or `statefulset`, as `kubectl` spells it.
+    pub kind: &'static str,
+    pub namespace: String,
+    pub container: String,
+    /// Image without its tag.
+    pub repo: String,
+}
+
+impl Workload {
+    fn target(&self, name: &str) -> String {
+        format!("{}/{name}", self.kind)
+    }
+}
+
+pub struct Kubernetes {
+    runner: Arc<dyn Runner>,
+    workloads: BTreeMap<String, Workload>,
+    /// Environment → kubectl context; empty uses the current context for all.
+    contexts: BTreeMap<String, String>,
+    /// Context for `/status`, `/logs` and `/scale`, which name no environment.
+    default_context: Option<String>,
+}
+
+impl Kubernetes {
+    pub fn new(workloads: BTreeMap<String, Workload>) -> Self {
+        Self {
+            runner: Arc::new(Process),
+            workloads,
+            contexts: BTreeMap::new(),
+            default_context: None,
+        }
+    }
+
+    /// Workloads from every `*.yaml`/`*.yml` in `dir`.
+    pub fn from_manifests(dir: impl AsRef<Path>) -> Result<Self, BackendError> {
+        let mut workloads = BTreeMap::new();
+        let mut paths: Vec<_> = std::fs::read_dir(dir.as_ref())?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "yaml" || e == "yml"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let text = std::fs::read_to_string(&path)?;
+            for doc in serde_yaml::Deserializer::from_str(&text) {
+                let doc = serde_yaml::Value::deserialize(doc)
+                    .map_err(|e| BackendError::Invalid(format!("{}: {e}", path.display())))?;
+                if let Some((name, w)) = workload(&doc) {
+                    workloads.insert(name, w);
+                }
+            }
+        }
+        Ok(Self::new(workloads))
+    }
+
+    /// Deploys to `env` use kubectl context `context`.
+    pub fn with_context(mut self, env: impl Into<String>, context: impl Into<String>) -> Self {
+        self.contexts.insert(env.into(), context.into());
+        self
+    }
+
+    pub fn with_default_context(mut self, context: impl Into<String>) -> Self {
+        self.default_context = Some(context.into());
+        self
+    }
+
+    pub fn with_runner(mut self, runner: Arc<dyn Runner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn workloads(&self) -> &BTreeMap<String, Workload> {
+        &self.workloads
+    }
+
+    fn workload(&self, name: &str) -> Result<&Workload, BackendError> {
+        self.workloads
+            .get(name)
+            .ok_or_else(|| BackendError::UnknownService(name.to_string()))
+    }
+
+    async fn kubectl(
+        &self,
+        context: Option<&str>,
+        namespace: &str,
+        args: &[&str],
+    ) -> Result<String, BackendError> {
+        let mut full = Vec::new();
+        if let Some(ctx) = context {
+            full.extend(["--context".to_string(), ctx.to_string()]);
+        }
+        full.extend(["-n".to_string(), namespace.to_string()]);
+        full.extend(args.iter().map(|a| a.to_string()));
+        self.runner.run("kubectl", &full, &[]).await
+    }
+}
+
+fn workload(doc: &serde_yaml::Value) -> Option<(String, Workload)> {
+    let kind = match doc.get("kind")?.as_str()? {
+        "Deployment" => "deployment",
+        "StatefulSet" => "statefulset",
+        _ => return None,
+    };
+    let meta = doc.get("metadata")?;
+    let container = doc
+        .get("spec")?
+        .get("template")?
+        .get("spec")?
+        .get("containers")?
+        .get(0)?;
+    Some((
+        meta.get("name")?.as_str()?.to_string(),
+        Workload {
+            kind,
+            namespace: meta
+                .get("namespace")
+                .and_then(|n| n.as_str())
+                .unwrap_or("default")
+                .to_string(),
+            container: container.get("name")?.as_str()?.to_string(),
+            repo: image_repo(container.get("image")?.as_str()?).to_string(),
+        },
+    ))
+}
+
+fn output_err(message: impl Into<String>) -> BackendError {
+    BackendError::Output {
+        program: "kubectl".to_string(),
+        message: message.into(),
+    }
+}
+
+#[async_trait]
+impl Backend for Kubernetes {
+    fn name(&self) -> &'static str {
+        "kubernetes"
+    }
+
+    async fn status(&self, service: &str) -> Result<ServiceStatus, BackendError> {
+        let w = self.workload(service)?;
+        let out = self
+            .kubectl(
+                self.default_context.as_deref(),
+                &w.namespace,
+                &["get", &w.target(service), "-o", "json"],
+            )
+            .await?;
+        let obj: Value = serde_json::from_str(&out).map_err(|e| output_err(e.to_string()))?;
+        let count = |v: &Value| v.as_u64().unwrap_or(0) as u32;
+        let replicas = Replicas {
+            ready: count(&obj["status"]["readyReplicas"]),
+            desired: count(&obj["spec"]["replicas"]),
+        };
+        let version = obj["spec"]["template"]["spec"]["containers"]
+            .as_array()
+            .and_then(|cs| cs.iter().find(|c| c["name"] == w.container.as_str()))
+            .and_then(|c| c["image"].as_str())
+            .map_or("unknown", image_tag);
+        Ok(ServiceStatus {
+            service: service.to_string(),
+            state: replicas.state(),
+            version: version.to_string(),
+            replicas,
+        })
+    }
+
+    async fn logs(&self, service: &str, tail: u32) -> Result<String, BackendError> {
+        let w = self.workload(service)?;
+        let tail = format!("--tail={tail}");
+        self.kubectl(
+            self.default_context.as_deref(),
+            &w.namespace,
+            &["logs", &w.target(service), "-c", &w.container, &tail],
+        )
+        .await
+    }
+
+    async fn deploy(&self, req: &DeployRequest) -> Result<DeployResult, BackendError> {
+        let context = match self.contexts.get(&req.env) {
+            Some(ctx) => Some(ctx.as_str()),
+            None if self.contexts.is_empty() => self.default_context.as_deref(),
+            None => return Err(BackendError::UnknownEnv(req.env.clone())),
+        };
+        let services: Vec<String> = if req.services.is_empty() {
+            self.workloads.keys().cloned().collect()
+        } else {
+            req.services.clone()
+        };
+        let targets = services
+            .iter()
+            .map(|name| Ok((name, self.workload(name)?)))
+            .collect::<Result<Vec<_>, BackendError>>()?;
+        for (name, w) in targets {
+            let target = w.target(name);
+            let image = format!("{}={}:{}", w.container, w.repo, req.tag);
+            self.kubectl(context, &w.namespace, &["set", "image", &target, &image])
+                .await?;
+            let timeout = format!("--timeout={ROLLOUT_TIMEOUT}");
+            self.kubectl(
+                context,
+                &w.namespace,
+                &["rollout", "status", &target, &timeout],
+            )
+            .await?;
+        }
+        Ok(DeployResult {
+            status: ResultStatus::Ok,
+            env: req.env.clone(),
+            tag: req.tag.clone(),
+            services,
+        })
+    }
+
+    async fn scale(&self, req: &ScaleRequest) -> Result<ScaleResult, BackendError> {
+        let w = self.workload(&req.service)?;
+        let replicas = format!("--replicas={}", req.replicas);
+        self.kubectl(
+            self.default_context.as_deref(),
+            &w.namespace,
+            &["scale", &w.target(&req.service), &replicas],
+        )
+        .await?;
+        Ok(ScaleResult {
+            status: ResultStatus::Ok,
+            service: req.service.clone(),
+            replicas: req.replicas,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::Scripted;
+    use crate::ServiceState;
+
+    #[tokio::test]
+    async fn drives_bootstrap_workloads_through_kubectl() {
+        let get = serde_json::json!({
+            "spec": { "replicas": 2, "template": { "spec": { "containers": [
+                { "name": "gateway", "image": "ghcr.io/strategickhaos-swarm-intelligence/event-gateway:1.4.2" }
+            ] } } },
+            "status": { "readyReplicas": 2 }
+        });
+        let runner = Arc::new(Scripted::default().reply(&get.to_string()));
+        let k8s =
+            Kubernetes::from_manifests(concat!(env!("CARGO_MANIFEST_DIR"), "/../../bootstrap/k8s"))
+                .unwrap()
+                .with_context("prod", "prod-us")
+                .with_runner(runner.clone());
+        assert_eq!(
+            k8s.workloads().keys().collect::<Vec<_>>(),
+            ["discord-ops-bot", "event-gateway"]
+        );
+
+        let status = k8s.status("event-gateway").await.unwrap();
+        assert_eq!(status.state, ServiceState::Running);
+        assert_eq!(status.version, "1.4.2");
+
+        k8s.deploy(&DeployRequest {
+            env: "prod".into(),
+            tag: "1.5.0".into(),
+            services: vec!["event-gateway".into()],
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            k8s.deploy(&DeployRequest {
+                env: "staging".into(),
+                tag: "1.5.0".into(),
+                services: vec![],
+            })
+            .await,
+            Err(BackendError::UnknownEnv(_))
+        ));
+        k8s.scale(&ScaleRequest {
+            service: "discord-ops-bot".into(),
+            replicas: 0,
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            runner.lines(),
+            [
+                "kubectl -n ops get deployment/event-gateway -o json",
+                "kubectl --context prod-us -n ops set image deployment/event-gateway gateway=ghcr.io/strategickhaos-swarm-intelligence/event-gateway:1.5.0",
+                "kubectl --context prod-us -n ops rollout status deployment/event-gateway --timeout=180s",
+                "kubectl -n ops scale deployment/discord-ops-bot --replicas=0",
+            ]
+        );
+    }
+}
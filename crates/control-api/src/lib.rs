@@ -0,0 +1,314 @@
+//! The control-plane API the Discord bot calls (`infra.control_api`).
+//!
+//! | Route                  | Body / query            | Response          |
+//! |------------------------|-------------------------|-------------------|
+//! | `GET /status/{svc}`    |                         | [`ServiceStatus`] |
+//! | `GET /logs/{svc}`      | `?tail=200`             | plain text        |
+//! | `POST /deploy`         | [`DeployRequest`]       | [`DeployResult`]  |
+//! | `POST /scale`          | [`ScaleRequest`]        | [`ScaleResult`]   |
+//!
+//! Every route except `/health` needs `Authorization: Bearer <token>`.
+//! Failures are JSON `{"status": "failed", "error": ...}` so the bot's
+//! `result: ${r.status}` still reads sensibly. The work itself is done by a
+//! [`Backend`]: [`compose::Compose`], [`kube::Kubernetes`] or
+//! [`memory::InMemory`].
+
+pub mod compose;
+pub mod kube;
+pub mod memory;
+pub mod runner;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use secrets::Secret;
+use serde::{Deserialize, Serialize};
+
+/// `tail` used when `/logs` is called without one, as in `src/bot.ts`.
+pub const DEFAULT_TAIL: u32 = 200;
+/// Largest `tail` served.
+pub const MAX_TAIL: u32 = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceState {
+    Running,
+    /// Some but not all replicas are ready.
+    Degraded,
+    Stopped,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replicas {
+    pub ready: u32,
+    pub desired: u32,
+}
+
+impl Replicas {
+    pub fn state(&self) -> ServiceState {
+        match (self.ready, self.desired) {
+            (_, 0) | (0, _) => ServiceState::Stopped,
+            (r, d) if r >= d => ServiceState::Running,
+            _ => ServiceState::Degraded,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub service: String,
+    pub state: ServiceState,
+    /// Image tag, or `unknown`.
+    pub version: String,
+    pub replicas: Replicas,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployRequest {
+    pub env: String,
+    pub tag: String,
+    /// Services to roll; empty means every service the backend manages.
+    #[serde(default)]
+    pub services: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultStatus {
+    Ok,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployResult {
+    pub status: ResultStatus,
+    pub env: String,
+    pub tag: String,
+    pub services: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScaleRequest {
+    pub service: String,
+    pub replicas: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScaleResult {
+    pub status: ResultStatus,
+    pub service: String,
+    pub replicas: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackendError {
+    #[error("unknown service {0:?}")]
+    UnknownService(String),
+    #[error("unknown environment {0:?}")]
+    UnknownEnv(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{program} exited with {code:?}: {stderr}")]
+    Command {
+        program: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    #[error("unexpected output from {program}: {message}")]
+    Output { program: String, message: String },
+    /// The backend ran the deploy but the services are not on the tag.
+    #[error("rollout: {0}")]
+    Rollout(String),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl BackendError {
+    fn status(&self) -> StatusCode {
+        match self {
+            BackendError::UnknownService(_) => StatusCode::NOT_FOUND,
+            BackendError::UnknownEnv(_) | BackendError::Invalid(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+impl IntoResponse for BackendError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status == StatusCode::BAD_GATEWAY {
+            tracing::error!(error = %self, "backend failed");
+        }
+        failed(status, &self.to_string())
+    }
+}
+
+fn failed(status: StatusCode, error: &str) -> Response {
+    let body = serde_json::json!({ "status": ResultStatus::Failed, "error": error });
+    (status, Json(body)).into_response()
+}
+
+/// Where `/status`, `/logs`, `/deploy` and `/scale` are carried out.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn status(&self, service: &str) -> Result<ServiceStatus, BackendError>;
+
+    /// The last `tail` log lines of `service`.
+    async fn logs(&self, service: &str, tail: u32) -> Result<String, BackendError>;
+
+    async fn deploy(&self, req: &DeployRequest) -> Result<DeployResult, BackendError>;
+
+    async fn scale(&self, req: &ScaleRequest) -> Result<ScaleResult, BackendError>;
+}
+
+#[derive(Clone)]
+struct App {
+    backend: Arc<dyn Backend>,
+    token: Arc<Secret>,
+    environments: Arc<Vec<String>>,
+}
+
+/// Builds the HTTP API over `backend`, accepting `token` as bearer.
+pub struct ControlApi {
+    backend: Arc<dyn Backend>,
+    token: Secret,
+    environments: Vec<String>,
+}
+
+impl ControlApi {
+    pub fn new(backend: Arc<dyn Backend>, token: Secret) -> Self {
+        Self {
+            backend,
+            token,
+            environments: Vec::new(),
+        }
+    }
+
+    /// Rejects deploys to anything else (`infra.environments`); empty allows
+    /// any environment the backend accepts.
+    pub fn with_environments(mut self, envs: impl IntoIterator<Item = String>) -> Self {
+        self.environments = envs.into_iter().collect();
+        self
+    }
+
+    pub fn router(self) -> Router {
+        let app = App {
+            backend: self.backend,
+            token: Arc::new(self.token),
+            environments: Arc::new(self.environments),
+        };
+        let protected = Router::new()
+            .route("/status/{svc}", get(status))
+            .route("/logs/{svc}", get(logs))
+            .route("/deploy", post(deploy))
+            .route("/scale", post(scale))
+            .route_layer(axum::middleware::from_fn_with_state(app.clone(), auth));
+        Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .merge(protected)
+            .with_state(app)
+    }
+}
+
+async fn auth(
+    State(app): State<App>,
+    headers: HeaderMap,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let presented = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match presented {
+        Some(t) if app.token.matches(t) => next.run(req).await,
+        _ => failed(StatusCode::UNAUTHORIZED, "missing or invalid bearer token"),
+    }
+}
+
+async fn status(
+    State(app): State<App>,
+    Path(svc): Path<String>,
+) -> Result<Json<ServiceStatus>, BackendError> {
+    Ok(Json(app.backend.status(&svc).await?))
+}
+
+#[derive(Deserialize)]
+struct LogsQuery {
+    tail: Option<u32>,
+}
+
+async fn logs(
+    State(app): State<App>,
+    Path(svc): Path<String>,
+    Query(q): Query<LogsQuery>,
+) -> Result<String, BackendError> {
+    let tail = q.tail.unwrap_or(DEFAULT_TAIL).clamp(1, MAX_TAIL);
+    app.backend.logs(&svc, tail).await
+}
+
+async fn deploy(
+    State(app): State<App>,
+    Json(req): Json<DeployRequest>,
+) -> Result<Json<DeployResult>, BackendError> {
+    if !app.environments.is_empty() && !app.environments.contains(&req.env) {
+        return Err(BackendError::UnknownEnv(req.env));
+    }
+    if req.tag.is_empty() || req.tag.contains(char::is_whitespace) {
+        return Err(BackendError::Invalid(format!("invalid tag {:?}", req.tag)));
+    }
+    tracing::info!(env = %req.env, tag = %req.tag, services = ?req.services, backend = app.backend.name(), "deploy");
+    Ok(Json(app.backend.deploy(&req).await?))
+}
+
+async fn scale(
+    State(app): State<App>,
+    Json(req): Json<ScaleRequest>,
+) -> Result<Json<ScaleResult>, BackendError> {
+    tracing::info!(service = %req.service, replicas = req.replicas, backend = app.backend.name(), "scale");
+    Ok(Json(app.backend.scale(&req).await?))
+}
+
+/// The part of an image reference after the last `:` that follows the last
+/// `/`, i.e. its tag; untagged images are `latest`.
+pub fn image_tag(image: &str) -> &str {
+    let name = image.rsplit('/').next().unwrap_or(image);
+    let name = name.split('@').next().unwrap_or(name);
+    name.split_once(':').map_or("latest", |(_, tag)| tag)
+}
+
+/// `image` with its tag (and digest) removed.
+pub fn image_repo(image: &str) -> &str {
+    let image = image.split('@').next().unwrap_or(image);
+    match image.rfind(':') {
+        Some(i) if !image[i..].contains('/') => &image[..i],
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_references() {
+        let img = "ghcr.io/strategickhaos-swarm-intelligence/event-gateway:latest";
+        assert_eq!(image_tag(img), "latest");
+        assert_eq!(
+            image_repo(img),
+            "ghcr.io/strategickhaos-swarm-intelligence/event-gateway"
+        );
+        assert_eq!(image_tag("localhost:5000/bot"), "latest");
+        assert_eq!(image_repo("localhost:5000/bot"), "localhost:5000/bot");
+        assert_eq!(image_tag("redis:7-alpine"), "7-alpine");
+        assert_eq!(image_repo("redis:7-alpine@sha256:abc"), "redis");
+    }
+}
@@ -0,0 +1,130 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use control_api::compose::Compose;
+use control_api::kube::Kubernetes;
+use control_api::memory::InMemory;
+use control_api::{Backend, ControlApi};
+use discovery::Discovery;
+use secrets::redact::Redactor;
+use secrets::{Resolver, Secret};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BackendKind {
+    Compose,
+    Kubernetes,
+    Memory,
+}
+
+#[derive(Parser)]
+#[command(about = "Control API behind the bot's /status, /logs, /deploy and /scale")]
+struct Args {
+    #[arg(long, env = "DISCOVERY_CONFIG_PATH", default_value = discovery::DEFAULT_PATH)]
+    config: PathBuf,
+    #[arg(long, env = "CONTROL_API_LISTEN", default_value = "0.0.0.0:8088")]
+    listen: SocketAddr,
+    /// Overrides `infra.control_api.auth.token_secret_ref`.
+    #[arg(long, env = "CONTROL_API_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    #[arg(
+        long,
+        env = "CONTROL_API_BACKEND",
+        value_enum,
+        default_value = "compose"
+    )]
+    backend: BackendKind,
+    /// Compose files, in `docker compose -f` order.
+    #[arg(long = "compose-file", default_value = "docker-compose.yml")]
+    compose_files: Vec<PathBuf>,
+    /// Environment the Compose project serves.
+    #[arg(long, default_value = "dev")]
+    compose_env: String,
+    #[arg(long)]
+    compose_project: Option<String>,
+    /// Directory of Kubernetes manifests naming the managed workloads.
+    #[arg(long, default_value = "bootstrap/k8s")]
+    manifests: PathBuf,
+    /// `ENV=CONTEXT` kubectl context for deploys to ENV; repeatable.
+    #[arg(long = "kube-context", value_parser = parse_mapping)]
+    kube_contexts: Vec<(String, String)>,
+    /// kubectl context for status, logs and scale.
+    #[arg(long)]
+    default_context: Option<String>,
+    /// Services the in-memory backend pretends to run.
+    #[arg(
+        long = "service",
+        default_values = ["discord-bot", "event-gateway", "refinory-api"]
+    )]
+    services: Vec<String>,
+}
+
+fn parse_mapping(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("expected ENV=CONTEXT, got {s:?}"))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let cfg = Discovery::load(&args.config)?;
+    let redactor =
+        Redactor::with_patterns(cfg.security.redaction.patterns.iter().map(String::as_str))?;
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(redactor.stderr_writer())
+        .init();
+
+    let token = match args.token.clone() {
+        Some(token) => Secret::new(token),
+        None => {
+            let reference = &cfg.infra.control_api.auth.token_secret_ref;
+            Resolver::new(secrets::backend_from_env()?)
+                .with_redactor(redactor)
+                .resolve_str(reference)
+                .await
+                .with_context(|| format!("resolving {reference}"))?
+        }
+    };
+    let backend = backend(&args)?;
+    tracing::info!(backend = backend.name(), "control API backend ready");
+    let app = ControlApi::new(backend, token)
+        .with_environments(cfg.infra.environments.iter().cloned())
+        .router();
+
+    let listener = tokio::net::TcpListener::bind(args.listen).await?;
+    tracing::info!(listen = %args.listen, "control API listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+fn backend(args: &Args) -> anyhow::Result<Arc<dyn Backend>> {
+    Ok(match args.backend {
+        BackendKind::Compose => {
+            let mut compose = Compose::new(args.compose_files.clone(), &args.compose_env)?;
+            if let Some(project) = &args.compose_project {
+                compose = compose.with_project(project);
+            }
+            Arc::new(compose)
+        }
+        BackendKind::Kubernetes => {
+            let mut k8s = Kubernetes::from_manifests(&args.manifests)
+                .with_context(|| format!("reading {}", args.manifests.display()))?;
+            for (env, ctx) in &args.kube_contexts {
+                k8s = k8s.with_context(env, ctx);
+            }
+            if let Some(ctx) = &args.default_context {
+                k8s = k8s.with_default_context(ctx);
+            }
+            Arc::new(k8s)
+        }
+        BackendKind::Memory => Arc::new(InMemory::new(args.services.iter().cloned())),
+    })
+}
@@ -0,0 +1,114 @@
+//! In-memory backend for tests and local bot development.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::{
+    Backend, BackendError, DeployRequest, DeployResult, Replicas, ResultStatus, ScaleRequest,
+    ScaleResult, ServiceStatus,
+};
+
+struct Service {
+    version: String,
+    replicas: u32,
+    logs: Vec<String>,
+}
+
+/// Every service starts at version `0.0.0` with one ready replica. Deploys
+/// and scales take effect immediately and are written to the service log.
+pub struct InMemory {
+    services: Mutex<BTreeMap<String, Service>>,
+}
+
+impl InMemory {
+    pub fn new<S: Into<String>>(services: impl IntoIterator<Item = S>) -> Self {
+        let services = services
+            .into_iter()
+            .map(|name| {
+                (
+                    name.into(),
+                    Service {
+                        version: "0.0.0".to_string(),
+                        replicas: 1,
+                        logs: vec!["started".to_string()],
+                    },
+                )
+            })
+            .collect();
+        Self {
+            services: Mutex::new(services),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for InMemory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn status(&self, service: &str) -> Result<ServiceStatus, BackendError> {
+        let services = self.services.lock().expect("services poisoned");
+        let s = services
+            .get(service)
+            .ok_or_else(|| BackendError::UnknownService(service.to_string()))?;
+        let replicas = Replicas {
+            ready: s.replicas,
+            desired: s.replicas,
+        };
+        Ok(ServiceStatus {
+            service: service.to_string(),
+            state: replicas.state(),
+            version: s.version.clone(),
+            replicas,
+        })
+    }
+
+    async fn logs(&self, service: &str, tail: u32) -> Result<String, BackendError> {
+        let services = self.services.lock().expect("services poisoned");
+        let s = services
+            .get(service)
+            .ok_or_else(|| BackendError::UnknownService(service.to_string()))?;
+        let skip = s.logs.len().saturating_sub(tail as usize);
+        Ok(s.logs[skip..].join("\n"))
+    }
+
+    async fn deploy(&self, req: &DeployRequest) -> Result<DeployResult, BackendError> {
+        let mut services = self.services.lock().expect("services poisoned");
+        let names: Vec<String> = if req.services.is_empty() {
+            services.keys().cloned().collect()
+        } else {
+            req.services.clone()
+        };
+        if let Some(unknown) = names.iter().find(|n| !services.contains_key(*n)) {
+            return Err(BackendError::UnknownService(unknown.clone()));
+        }
+        for name in &names {
+            let s = services.get_mut(name).expect("checked above");
+            s.version = req.tag.clone();
+            s.logs.push(format!("deployed {} to {}", req.tag, req.env));
+        }
+        Ok(DeployResult {
+            status: ResultStatus::Ok,
+            env: req.env.clone(),
+            tag: req.tag.clone(),
+            services: names,
+        })
+    }
+
+    async fn scale(&self, req: &ScaleRequest) -> Result<ScaleResult, BackendError> {
+        let mut services = self.services.lock().expect("services poisoned");
+        let s = services
+            .get_mut(&req.service)
+            .ok_or_else(|| BackendError::UnknownService(req.service.clone()))?;
+        s.replicas = req.replicas;
+        s.logs.push(format!("scaled to {}", req.replicas));
+        Ok(ScaleResult {
+            status: ResultStatus::Ok,
+            service: req.service.clone(),
+            replicas: req.replicas,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+//! Running `docker` and `kubectl`.
+
+use async_trait::async_trait;
+
+use crate::BackendError;
+
+#[async_trait]
+pub trait Runner: Send + Sync {
+    /// Runs `program` with `args` and extra environment `env`; returns stdout,
+    /// or [`BackendError::Command`] with stderr on a non-zero exit.
+    async fn run(
+        &self,
+        program: &str,
+        args: &[String],
+        env: &[(String, String)],
+    ) -> Result<String, BackendError>;
+}
+
+/// Spawns real processes.
+pub struct Process;
+
+#[async_trait]
+impl Runner for Process {
+    async fn run(
+        &self,
+        program: &str,
+        args: &[String],
+        env: &[(String, String)],
+    ) -> Result<String, BackendError> {
+        tracing::debug!(program, ?args, "running");
+        let out = tokio::process::Command::new(program)
+            .args(args)
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        if !out.status.success() {
+            return Err(BackendError::Command {
+                program: program.to_string(),
+                code: out.status.code(),
+                stderr: String::from_utf8_lossy(&out.stderr).trim().to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+}
+
+/// Records invocations and answers from a queue, for backend tests.
+#[cfg(test)]
+pub(crate) mod fake {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Program, arguments and extra environment of one call.
+    pub(crate) type Call = (String, Vec<String>, Vec<(String, String)>);
+
+    #[derive(Default)]
+    pub(crate) struct Scripted {
+        pub(crate) calls: Mutex<Vec<Call>>,
+        pub(crate) replies: Mutex<VecDeque<String>>,
+    }
+
+    impl Scripted {
+        pub(crate) fn reply(self, stdout: &str) -> Self {
+            self.replies.lock().unwrap().push_back(stdout.to_string());
+            self
+        }
+
+        /// Each call as one space-separated command line.
+        pub(crate) fn lines(&self) -> Vec<String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(p, args, _)| format!("{p} {}", args.join(" ")))
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl Runner for Scripted {
+        async fn run(
+            &self,
+            program: &str,
+            args: &[String],
+            env: &[(String, String)],
+        ) -> Result<String, BackendError> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push((program.to_string(), args.to_vec(), env.to_vec()));
+            Ok(self.replies.lock().unwrap().pop_front().unwrap_or_default())
+        }
+    }
+}
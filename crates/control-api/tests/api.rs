@@ -0,0 +1,113 @@
+//! The HTTP contract `src/bot.ts` relies on, over the in-memory backend.
+
+use std::sync::Arc;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use control_api::memory::InMemory;
+use control_api::ControlApi;
+use secrets::Secret;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn app() -> Router {
+    let backend = Arc::new(InMemory::new(["event-gateway", "discord-bot"]));
+    ControlApi::new(backend, Secret::new("ctrl-token"))
+        .with_environments(["dev", "staging", "prod"].map(String::from))
+        .router()
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri)
+        .header("Authorization", "Bearer ctrl-token")
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn post(uri: &str, body: Value) -> Request<Body> {
+    Request::post(uri)
+        .header("Authorization", "Bearer ctrl-token")
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+async fn call(app: &Router, req: Request<Body>) -> (StatusCode, String) {
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn call_json(app: &Router, req: Request<Body>) -> (StatusCode, Value) {
+    let (status, body) = call(app, req).await;
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+#[tokio::test]
+async fn requires_bearer_token() {
+    let app = app();
+    let (status, body) = call_json(
+        &app,
+        Request::get("/status/event-gateway")
+            .header("Authorization", "Bearer wrong")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["status"], "failed");
+    let (status, _) = call(&app, Request::get("/health").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn deploy_scale_status_and_logs() {
+    let app = app();
+    let (status, body) = call_json(&app, get("/status/event-gateway")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["state"], "running");
+    assert_eq!(body["version"], "0.0.0");
+
+    let (status, body) = call_json(
+        &app,
+        post("/deploy", json!({ "env": "prod", "tag": "v1.2.0" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["services"], json!(["discord-bot", "event-gateway"]));
+
+    let (status, body) = call_json(
+        &app,
+        post(
+            "/scale",
+            json!({ "service": "event-gateway", "replicas": 0 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["replicas"], 0);
+
+    let (_, body) = call_json(&app, get("/status/event-gateway")).await;
+    assert_eq!(body["state"], "stopped");
+    assert_eq!(body["version"], "v1.2.0");
+
+    let (status, logs) = call(&app, get("/logs/event-gateway?tail=2")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(logs, "deployed v1.2.0 to prod\nscaled to 0");
+}
+
+#[tokio::test]
+async fn rejects_unknown_services_and_environments() {
+    let app = app();
+    let (status, body) = call_json(&app, get("/status/nope")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["status"], "failed");
+
+    let (status, body) =
+        call_json(&app, post("/deploy", json!({ "env": "qa", "tag": "v1" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "unknown environment \"qa\"");
+}
@@ -0,0 +1,307 @@
+//! At-least-once delivery of outbound Discord messages.
+//!
+//! Producers [`Queue::enqueue`] an [`Envelope`] and return immediately; a
+//! [`Worker`] claims envelopes, hands them to a [`Deliver`] implementation
+//! and acknowledges them only once Discord accepted the post. Failures are
+//! retried with exponential backoff ([`RetryPolicy`]) until they succeed or
+//! run out of attempts, at which point they land on a dead-letter stream
+//! that can be listed, replayed or discarded.
+//!
+//! Envelopes carry an idempotency key (e.g. GitHub's `X-GitHub-Delivery`);
+//! a key seen within [`DEDUPE_WINDOW`] is not enqueued twice, so webhook
+//! redeliveries do not double-post.
+//!
+//! [`redis::RedisStreams`] is the production queue on `infra.message_bus`;
+//! [`memory::InMemory`] serves tests and single-process setups.
+
+pub mod memory;
+pub mod redis;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How long an idempotency key suppresses duplicates.
+pub const DEDUPE_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a claimed envelope may stay unacknowledged before another
+/// worker takes it over (the claimant is presumed dead).
+pub const VISIBILITY_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub(crate) fn unix_ms(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// One message bound for a Discord channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Idempotency key.
+    pub key: String,
+    /// Channel name (`#prs`), resolved to an id by the deliverer.
+    pub channel: String,
+    /// Message body for the deliverer, e.g. an embed.
+    pub payload: Value,
+    /// Posts sharing a thread key are replies to the first one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread: Option<String>,
+    /// Delivery attempts made so far.
+    #[serde(default)]
+    pub attempt: u32,
+    /// Unix milliseconds of the first enqueue.
+    pub enqueued_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl Envelope {
+    pub fn new(key: impl Into<String>, channel: impl Into<String>, payload: Value) -> Self {
+        Self {
+            key: key.into(),
+            channel: channel.into(),
+            payload,
+            thread: None,
+            attempt: 0,
+            enqueued_at: unix_ms(SystemTime::now()),
+            last_error: None,
+        }
+    }
+
+    pub fn with_thread(mut self, thread: impl Into<String>) -> Self {
+        self.thread = Some(thread.into());
+        self
+    }
+
+    /// An envelope with a random key, for producers without a natural one.
+    pub fn unkeyed(channel: impl Into<String>, payload: Value) -> Self {
+        Self::new(uuid::Uuid::new_v4().to_string(), channel, payload)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enqueued {
+    Queued,
+    /// The key was already enqueued within [`DEDUPE_WINDOW`].
+    Duplicate,
+}
+
+/// An envelope handed to one worker until it is acked, retried or
+/// dead-lettered.
+#[derive(Debug, Clone)]
+pub struct Claimed {
+    pub receipt: String,
+    pub envelope: Envelope,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: String,
+    pub envelope: Envelope,
+    pub error: String,
+    /// Unix milliseconds.
+    pub failed_at: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueueError {
+    #[error("redis: {0}")]
+    Redis(#[from] ::redis::RedisError),
+    #[error("malformed envelope {id}: {source}")]
+    Malformed {
+        id: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+#[async_trait]
+pub trait Queue: Send + Sync {
+    /// Adds `envelope` unless its key was enqueued within [`DEDUPE_WINDOW`].
+    async fn enqueue(&self, envelope: Envelope) -> Result<Enqueued, QueueError>;
+
+    /// Up to `max` ready envelopes, waiting at most `wait` for the first.
+    /// Envelopes left unacknowledged past [`VISIBILITY_TIMEOUT`] come back.
+    async fn claim(&self, max: usize, wait: Duration) -> Result<Vec<Claimed>, QueueError>;
+
+    async fn ack(&self, receipt: &str) -> Result<(), QueueError>;
+
+    /// Acks `receipt` and makes `envelope` ready again at `due`.
+    async fn retry(
+        &self,
+        receipt: &str,
+        envelope: Envelope,
+        due: SystemTime,
+    ) -> Result<(), QueueError>;
+
+    /// Acks `receipt` and moves `envelope` to the dead-letter stream.
+    async fn dead_letter(
+        &self,
+        receipt: &str,
+        envelope: Envelope,
+        error: &str,
+    ) -> Result<(), QueueError>;
+
+    /// Makes retries whose time has come ready; returns how many.
+    async fn promote_due(&self) -> Result<usize, QueueError>;
+
+    /// Oldest first.
+    async fn dead_letters(&self, max: usize) -> Result<Vec<DeadLetter>, QueueError>;
+
+    /// Re-enqueues dead letter `id` with its attempts reset; `false` if there
+    /// is no such dead letter.
+    async fn replay(&self, id: &str) -> Result<bool, QueueError>;
+
+    async fn discard(&self, id: &str) -> Result<bool, QueueError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DeliverError {
+    /// Worth retrying: timeouts, 5xx, rate limits.
+    #[error("{0}")]
+    Retryable(String),
+    /// Will never succeed as is: unknown channel, rejected payload.
+    #[error("{0}")]
+    Permanent(String),
+}
+
+/// Performs the actual post.
+#[async_trait]
+pub trait Deliver: Send + Sync {
+    async fn deliver(&self, envelope: &Envelope) -> Result<(), DeliverError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Delay after the first failure; doubled after each further one.
+    pub base: Duration,
+    pub max_delay: Duration,
+    /// Attempts before an envelope is dead-lettered.
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(2),
+            max_delay: Duration::from_secs(300),
+            max_attempts: 8,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before attempt `attempt + 1`, after `attempt` failures.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Outcome counts of one [`Worker::tick`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    pub delivered: usize,
+    pub retried: usize,
+    pub dead: usize,
+}
+
+pub struct Worker {
+    queue: Arc<dyn Queue>,
+    sink: Arc<dyn Deliver>,
+    policy: RetryPolicy,
+    batch: usize,
+    wait: Duration,
+}
+
+impl Worker {
+    pub fn new(queue: Arc<dyn Queue>, sink: Arc<dyn Deliver>) -> Self {
+        Self {
+            queue,
+            sink,
+            policy: RetryPolicy::default(),
+            batch: 16,
+            wait: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// How long [`Worker::tick`] blocks waiting for work.
+    pub fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Promotes due retries, then claims and delivers one batch.
+    pub async fn tick(&self) -> Result<Tick, QueueError> {
+        self.queue.promote_due().await?;
+        let mut tick = Tick::default();
+        for Claimed {
+            receipt,
+            mut envelope,
+        } in self.queue.claim(self.batch, self.wait).await?
+        {
+            envelope.attempt += 1;
+            let error = match self.sink.deliver(&envelope).await {
+                Ok(()) => {
+                    self.queue.ack(&receipt).await?;
+                    tick.delivered += 1;
+                    continue;
+                }
+                Err(e) => e,
+            };
+            tracing::warn!(key = %envelope.key, channel = %envelope.channel, attempt = envelope.attempt, error = %error, "delivery failed");
+            let exhausted = envelope.attempt >= self.policy.max_attempts;
+            if matches!(error, DeliverError::Permanent(_)) || exhausted {
+                self.queue
+                    .dead_letter(&receipt, envelope, &error.to_string())
+                    .await?;
+                tick.dead += 1;
+            } else {
+                let due = SystemTime::now() + self.policy.delay(envelope.attempt);
+                envelope.last_error = Some(error.to_string());
+                self.queue.retry(&receipt, envelope, due).await?;
+                tick.retried += 1;
+            }
+        }
+        Ok(tick)
+    }
+
+    /// Ticks until `shutdown` resolves. Queue errors are logged and retried
+    /// after a pause.
+    pub async fn run(self, shutdown: impl std::future::Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => return,
+                result = self.tick() => if let Err(e) = result {
+                    tracing::error!(error = %e, "delivery queue error");
+                    tokio::time::sleep(self.policy.base).await;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let p = RetryPolicy {
+            base: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+            max_attempts: 10,
+        };
+        let delays: Vec<u64> = (1..=7).map(|a| p.delay(a).as_secs()).collect();
+        assert_eq!(delays, [2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(p.delay(u32::MAX), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,286 @@
+//! Process-local queue with the same semantics as the Redis one.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+
+use crate::{unix_ms, Claimed, DeadLetter, Enqueued, Envelope, Queue, QueueError, DEDUPE_WINDOW};
+
+#[derive(Default)]
+struct State {
+    ready: VecDeque<Envelope>,
+    in_flight: HashMap<String, (Instant, Envelope)>,
+    delayed: Vec<(SystemTime, Envelope)>,
+    dead: Vec<DeadLetter>,
+    seen: HashMap<String, Instant>,
+}
+
+pub struct InMemory {
+    state: Mutex<State>,
+    next: AtomicU64,
+    visibility: Duration,
+    wake: tokio::sync::Notify,
+}
+
+impl Default for InMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemory {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            next: AtomicU64::new(1),
+            visibility: crate::VISIBILITY_TIMEOUT,
+            wake: tokio::sync::Notify::new(),
+        }
+    }
+
+    /// Overrides [`crate::VISIBILITY_TIMEOUT`].
+    pub fn with_visibility(mut self, timeout: Duration) -> Self {
+        self.visibility = timeout;
+        self
+    }
+
+    fn id(&self) -> String {
+        format!("{}-0", self.next.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("queue poisoned")
+    }
+
+    fn take(&self, max: usize) -> Vec<Claimed> {
+        let mut st = self.state();
+        let now = Instant::now();
+        let expired: Vec<String> = st
+            .in_flight
+            .iter()
+            .filter(|(_, (at, _))| now.duration_since(*at) >= self.visibility)
+            .map(|(r, _)| r.clone())
+            .collect();
+        let mut out = Vec::new();
+        for receipt in expired.into_iter().take(max) {
+            let (_, envelope) = st.in_flight.remove(&receipt).expect("listed above");
+            st.in_flight
+                .insert(receipt.clone(), (now, envelope.clone()));
+            out.push(Claimed { receipt, envelope });
+        }
+        while out.len() < max {
+            let Some(envelope) = st.ready.pop_front() else {
+                break;
+            };
+            let receipt = self.id();
+            st.in_flight
+                .insert(receipt.clone(), (now, envelope.clone()));
+            out.push(Claimed { receipt, envelope });
+        }
+        out
+    }
+}
+
+#[async_trait]
+impl Queue for InMemory {
+    async fn enqueue(&self, envelope: Envelope) -> Result<Enqueued, QueueError> {
+        {
+            let mut st = self.state();
+            let now = Instant::now();
+            st.seen
+                .retain(|_, at| now.duration_since(*at) < DEDUPE_WINDOW);
+            if st.seen.contains_key(&envelope.key) {
+                return Ok(Enqueued::Duplicate);
+            }
+            st.seen.insert(envelope.key.clone(), now);
+            st.ready.push_back(envelope);
+        }
+        self.wake.notify_one();
+        Ok(Enqueued::Queued)
+    }
+
+    async fn claim(&self, max: usize, wait: Duration) -> Result<Vec<Claimed>, QueueError> {
+        let claimed = self.take(max);
+        if !claimed.is_empty() {
+            return Ok(claimed);
+        }
+        let _ = tokio::time::timeout(wait, self.wake.notified()).await;
+        Ok(self.take(max))
+    }
+
+    async fn ack(&self, receipt: &str) -> Result<(), QueueError> {
+        self.state().in_flight.remove(receipt);
+        Ok(())
+    }
+
+    async fn retry(
+        &self,
+        receipt: &str,
+        envelope: Envelope,
+        due: SystemTime,
+    ) -> Result<(), QueueError> {
+        let mut st = self.state();
+        st.in_flight.remove(receipt);
+        st.delayed.push((due, envelope));
+        Ok(())
+    }
+
+    async fn dead_letter(
+        &self,
+        receipt: &str,
+        envelope: Envelope,
+        error: &str,
+    ) -> Result<(), QueueError> {
+        let id = self.id();
+        let mut st = self.state();
+        st.in_flight.remove(receipt);
+        st.dead.push(DeadLetter {
+            id,
+            envelope,
+            error: error.to_string(),
+            failed_at: unix_ms(SystemTime::now()),
+        });
+        Ok(())
+    }
+
+    async fn promote_due(&self) -> Result<usize, QueueError> {
+        let now = SystemTime::now();
+        let mut st = self.state();
+        let (due, later): (Vec<_>, Vec<_>) = st.delayed.drain(..).partition(|(at, _)| *at <= now);
+        st.delayed = later;
+        let n = due.len();
+        st.ready.extend(due.into_iter().map(|(_, e)| e));
+        Ok(n)
+    }
+
+    async fn dead_letters(&self, max: usize) -> Result<Vec<DeadLetter>, QueueError> {
+        Ok(self.state().dead.iter().take(max).cloned().collect())
+    }
+
+    async fn replay(&self, id: &str) -> Result<bool, QueueError> {
+        let mut st = self.state();
+        let Some(pos) = st.dead.iter().position(|d| d.id == id) else {
+            return Ok(false);
+        };
+        let mut envelope = st.dead.remove(pos).envelope;
+        envelope.attempt = 0;
+        envelope.last_error = None;
+        st.ready.push_back(envelope);
+        drop(st);
+        self.wake.notify_one();
+        Ok(true)
+    }
+
+    async fn discard(&self, id: &str) -> Result<bool, QueueError> {
+        let mut st = self.state();
+        let before = st.dead.len();
+        st.dead.retain(|d| d.id != id);
+        Ok(st.dead.len() < before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Deliver, DeliverError, RetryPolicy, Worker};
+
+    /// Fails the first `failures` deliveries with `error`, then succeeds.
+    struct Flaky {
+        failures: Mutex<u32>,
+        error: DeliverError,
+    }
+
+    #[async_trait]
+    impl Deliver for Flaky {
+        async fn deliver(&self, _: &Envelope) -> Result<(), DeliverError> {
+            let mut left = self.failures.lock().unwrap();
+            if *left == 0 {
+                return Ok(());
+            }
+            *left -= 1;
+            Err(self.error.clone())
+        }
+    }
+
+    fn worker(queue: &Arc<InMemory>, failures: u32, error: DeliverError) -> Worker {
+        let sink = Arc::new(Flaky {
+            failures: Mutex::new(failures),
+            error,
+        });
+        Worker::new(queue.clone(), sink)
+            .with_wait(Duration::ZERO)
+            .with_policy(RetryPolicy {
+                base: Duration::ZERO,
+                max_delay: Duration::ZERO,
+                max_attempts: 3,
+            })
+    }
+
+    #[tokio::test]
+    async fn duplicate_keys_are_enqueued_once() {
+        let q = InMemory::new();
+        let e = Envelope::new("github:abc", "#prs", json!({}));
+        assert_eq!(q.enqueue(e.clone()).await.unwrap(), Enqueued::Queued);
+        assert_eq!(q.enqueue(e).await.unwrap(), Enqueued::Duplicate);
+        assert_eq!(q.claim(10, Duration::ZERO).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_until_delivered_then_dead_letters_and_replays() {
+        let q = Arc::new(InMemory::new());
+        q.enqueue(Envelope::new("a", "#prs", json!({})))
+            .await
+            .unwrap();
+        let w = worker(&q, 2, DeliverError::Retryable("502".into()));
+        assert_eq!(w.tick().await.unwrap().retried, 1);
+        assert_eq!(w.tick().await.unwrap().retried, 1);
+        assert_eq!(w.tick().await.unwrap().delivered, 1);
+
+        q.enqueue(Envelope::new("b", "#prs", json!({})))
+            .await
+            .unwrap();
+        let w = worker(&q, 5, DeliverError::Retryable("502".into()));
+        for _ in 0..2 {
+            w.tick().await.unwrap();
+        }
+        assert_eq!(w.tick().await.unwrap().dead, 1);
+        let dead = q.dead_letters(10).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].envelope.attempt, 3);
+        assert_eq!(dead[0].envelope.last_error.as_deref(), Some("502"));
+
+        assert!(q.replay(&dead[0].id).await.unwrap());
+        assert!(q.dead_letters(10).await.unwrap().is_empty());
+        let claimed = q.claim(10, Duration::ZERO).await.unwrap();
+        assert_eq!(claimed[0].envelope.key, "b");
+        assert_eq!(claimed[0].envelope.attempt, 0);
+    }
+
+    #[tokio::test]
+    async fn permanent_errors_skip_retries_and_stale_claims_return() {
+        let q = Arc::new(InMemory::new().with_visibility(Duration::ZERO));
+        q.enqueue(Envelope::new("a", "#nope", json!({})))
+            .await
+            .unwrap();
+        let first = q.claim(1, Duration::ZERO).await.unwrap();
+        let again = q.claim(1, Duration::ZERO).await.unwrap();
+        assert_eq!(first[0].receipt, again[0].receipt);
+        q.ack(&again[0].receipt).await.unwrap();
+
+        q.enqueue(Envelope::new("b", "#nope", json!({})))
+            .await
+            .unwrap();
+        let w = worker(&q, 1, DeliverError::Permanent("unknown channel".into()));
+        assert_eq!(w.tick().await.unwrap().dead, 1);
+        let dead = q.dead_letters(10).await.unwrap();
+        assert!(q.discard(&dead[0].id).await.unwrap());
+        assert!(!q.discard(&dead[0].id).await.unwrap());
+    }
+}
@@ -0,0 +1,316 @@
+//! The queue on Redis Streams (`infra.message_bus`).
+//!
+//! With `topic_prefix: ops.` the keys are:
+//!
+//! | Key                                | Type   | Holds                              |
+//! |------------------------------------|--------|------------------------------------|
+//! | `ops.discord.outbound`             | stream | ready envelopes, group `delivery`  |
+//! | `ops.discord.outbound.retry`       | zset   | envelopes scored by due time (ms)  |
+//! | `ops.discord.outbound.dead`        | stream | dead letters                       |
+//! | `ops.discord.outbound.seen:{key}`  | string | idempotency marker, 24h TTL        |
+//!
+//! Stream entries carry the [`Envelope`] as JSON in the `envelope` field;
+//! dead letters add `error` and `failed_at`. A claim is an entry pending in
+//! the consumer group, so a worker that dies mid-delivery leaves it pending
+//! and another worker takes it over with `XAUTOCLAIM` once it has been idle
+//! for the visibility timeout.
+
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::streams::{
+    StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamRangeReply, StreamReadOptions,
+    StreamReadReply,
+};
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+
+use crate::{unix_ms, Claimed, DeadLetter, Enqueued, Envelope, Queue, QueueError, DEDUPE_WINDOW};
+
+/// Consumer group shared by every worker.
+pub const GROUP: &str = "delivery";
+
+const FIELD: &str = "envelope";
+
+/// Moves due retries back onto the stream in one step so a crash cannot
+/// lose or duplicate them.
+const PROMOTE: &str = r"
+local due = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, 100)
+for _, envelope in ipairs(due) do
+  redis.call('ZREM', KEYS[1], envelope)
+  redis.call('XADD', KEYS[2], '*', 'envelope', envelope)
+end
+return #due
+";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Keys {
+    stream: String,
+    retry: String,
+    dead: String,
+    seen: String,
+}
+
+impl Keys {
+    fn new(prefix: &str) -> Self {
+        let stream = format!("{prefix}discord.outbound");
+        Self {
+            retry: format!("{stream}.retry"),
+            dead: format!("{stream}.dead"),
+            seen: format!("{stream}.seen:"),
+            stream,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RedisStreams {
+    conn: ConnectionManager,
+    keys: Keys,
+    consumer: String,
+    visibility: Duration,
+}
+
+impl RedisStreams {
+    /// Connects to `url` and creates the consumer group if needed.
+    /// `prefix` is `infra.message_bus.topic_prefix`.
+    pub async fn connect(url: &str, prefix: &str) -> Result<Self, QueueError> {
+        let conn = ConnectionManager::new(redis::Client::open(url)?).await?;
+        let queue = Self {
+            conn,
+            keys: Keys::new(prefix),
+            consumer: format!("worker-{}", uuid::Uuid::new_v4()),
+            visibility: crate::VISIBILITY_TIMEOUT,
+        };
+        let created: redis::RedisResult<()> = queue
+            .conn()
+            .xgroup_create_mkstream(&queue.keys.stream, GROUP, "0")
+            .await;
+        match created {
+            Err(e) if e.code() != Some("BUSYGROUP") => Err(e.into()),
+            _ => Ok(queue),
+        }
+    }
+
+    /// Overrides [`crate::VISIBILITY_TIMEOUT`].
+    pub fn with_visibility(mut self, timeout: Duration) -> Self {
+        self.visibility = timeout;
+        self
+    }
+
+    fn conn(&self) -> ConnectionManager {
+        self.conn.clone()
+    }
+
+    /// Parses claimed entries; ones that are not envelopes are dropped with
+    /// an error log since no worker could ever deliver them.
+    async fn parse(&self, entries: Vec<StreamId>) -> Result<Vec<Claimed>, QueueError> {
+        let mut out = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match envelope(&entry) {
+                Ok(envelope) => out.push(Claimed {
+                    receipt: entry.id,
+                    envelope,
+                }),
+                Err(e) => {
+                    tracing::error!(error = %e, "dropping malformed delivery entry");
+                    self.ack(&entry.id).await?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn envelope(entry: &StreamId) -> Result<Envelope, QueueError> {
+    let raw: String = entry.get(FIELD).unwrap_or_default();
+    serde_json::from_str(&raw).map_err(|source| QueueError::Malformed {
+        id: entry.id.clone(),
+        source,
+    })
+}
+
+fn json(envelope: &Envelope) -> String {
+    serde_json::to_string(envelope).expect("envelopes serialize")
+}
+
+#[async_trait]
+impl Queue for RedisStreams {
+    async fn enqueue(&self, envelope: Envelope) -> Result<Enqueued, QueueError> {
+        let mut conn = self.conn();
+        let seen = format!("{}{}", self.keys.seen, envelope.key);
+        let marked: Option<String> = conn
+            .set_options(
+                &seen,
+                1,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(DEDUPE_WINDOW.as_secs())),
+            )
+            .await?;
+        if marked.is_none() {
+            return Ok(Enqueued::Duplicate);
+        }
+        let added: redis::RedisResult<String> = conn
+            .xadd(&self.keys.stream, "*", &[(FIELD, json(&envelope))])
+            .await;
+        if let Err(e) = added {
+            // Let a later redelivery of the same key through.
+            let _: redis::RedisResult<()> = conn.del(&seen).await;
+            return Err(e.into());
+        }
+        Ok(Enqueued::Queued)
+    }
+
+    async fn claim(&self, max: usize, wait: Duration) -> Result<Vec<Claimed>, QueueError> {
+        let mut conn = self.conn();
+        let stale: StreamAutoClaimReply = conn
+            .xautoclaim_options(
+                &self.keys.stream,
+                GROUP,
+                &self.consumer,
+                self.visibility.as_millis() as u64,
+                "0-0",
+                StreamAutoClaimOptions::default().count(max),
+            )
+            .await?;
+        if !stale.claimed.is_empty() {
+            return self.parse(stale.claimed).await;
+        }
+        let mut opts = StreamReadOptions::default()
+            .group(GROUP, &self.consumer)
+            .count(max);
+        if !wait.is_zero() {
+            opts = opts.block(wait.as_millis() as usize);
+        }
+        let reply: Option<StreamReadReply> = conn
+            .xread_options(&[&self.keys.stream], &[">"], &opts)
+            .await?;
+        let entries = reply
+            .into_iter()
+            .flat_map(|r| r.keys)
+            .flat_map(|k| k.ids)
+            .collect();
+        self.parse(entries).await
+    }
+
+    async fn ack(&self, receipt: &str) -> Result<(), QueueError> {
+        redis::pipe()
+            .atomic()
+            .xack(&self.keys.stream, GROUP, &[receipt])
+            .xdel(&self.keys.stream, &[receipt])
+            .exec_async(&mut self.conn())
+            .await?;
+        Ok(())
+    }
+
+    async fn retry(
+        &self,
+        receipt: &str,
+        envelope: Envelope,
+        due: SystemTime,
+    ) -> Result<(), QueueError> {
+        redis::pipe()
+            .atomic()
+            .zadd(&self.keys.retry, json(&envelope), unix_ms(due))
+            .xack(&self.keys.stream, GROUP, &[receipt])
+            .xdel(&self.keys.stream, &[receipt])
+            .exec_async(&mut self.conn())
+            .await?;
+        Ok(())
+    }
+
+    async fn dead_letter(
+        &self,
+        receipt: &str,
+        envelope: Envelope,
+        error: &str,
+    ) -> Result<(), QueueError> {
+        let failed_at = unix_ms(SystemTime::now()).to_string();
+        redis::pipe()
+            .atomic()
+            .xadd(
+                &self.keys.dead,
+                "*",
+                &[
+                    (FIELD, json(&envelope).as_str()),
+                    ("error", error),
+                    ("failed_at", failed_at.as_str()),
+                ],
+            )
+            .xack(&self.keys.stream, GROUP, &[receipt])
+            .xdel(&self.keys.stream, &[receipt])
+            .exec_async(&mut self.conn())
+            .await?;
+        Ok(())
+    }
+
+    async fn promote_due(&self) -> Result<usize, QueueError> {
+        let n: usize = redis::Script::new(PROMOTE)
+            .key(&self.keys.retry)
+            .key(&self.keys.stream)
+            .arg(unix_ms(SystemTime::now()))
+            .invoke_async(&mut self.conn())
+            .await?;
+        Ok(n)
+    }
+
+    async fn dead_letters(&self, max: usize) -> Result<Vec<DeadLetter>, QueueError> {
+        let reply: StreamRangeReply = self
+            .conn()
+            .xrange_count(&self.keys.dead, "-", "+", max)
+            .await?;
+        reply
+            .ids
+            .iter()
+            .map(|entry| {
+                Ok(DeadLetter {
+                    id: entry.id.clone(),
+                    envelope: envelope(entry)?,
+                    error: entry.get("error").unwrap_or_default(),
+                    failed_at: entry.get("failed_at").unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    async fn replay(&self, id: &str) -> Result<bool, QueueError> {
+        let mut conn = self.conn();
+        let reply: StreamRangeReply = conn.xrange_count(&self.keys.dead, id, id, 1).await?;
+        let Some(entry) = reply.ids.first() else {
+            return Ok(false);
+        };
+        let mut envelope = envelope(entry)?;
+        envelope.attempt = 0;
+        envelope.last_error = None;
+        // Whoever deletes the dead letter re-enqueues it, so concurrent
+        // replays of one id post once.
+        let deleted: usize = conn.xdel(&self.keys.dead, &[id]).await?;
+        if deleted == 0 {
+            return Ok(false);
+        }
+        let _: String = conn
+            .xadd(&self.keys.stream, "*", &[(FIELD, json(&envelope))])
+            .await?;
+        Ok(true)
+    }
+
+    async fn discard(&self, id: &str) -> Result<bool, QueueError> {
+        let deleted: usize = self.conn().xdel(&self.keys.dead, &[id]).await?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_follow_topic_prefix() {
+        let keys = Keys::new("ops.");
+        assert_eq!(keys.stream, "ops.discord.outbound");
+        assert_eq!(keys.retry, "ops.discord.outbound.retry");
+        assert_eq!(keys.dead, "ops.discord.outbound.dead");
+        assert_eq!(keys.seen, "ops.discord.outbound.seen:");
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// `ai_agents`: model provider and retrieval store.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AiAgents {
+    pub enabled: bool,
+    pub model_provider: ModelProvider,
+    pub model_name: String,
+    #[serde(default)]
+    pub routing: AgentRouting,
+    pub vector_store: VectorStore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelProvider {
+    Openai,
+    Azure,
+    Anthropic,
+    Local,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AgentRouting {
+    /// Channel → model name; `none` disables agents in that channel.
+    #[serde(default)]
+    pub per_channel: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VectorStore {
+    /// `null` disables retrieval.
+    #[serde(rename = "type")]
+    pub kind: Option<VectorStoreType>,
+    pub conn_string_secret_ref: Option<String>,
+    /// Index directory for the `embedded` type.
+    pub path: Option<String>,
+    #[serde(default)]
+    pub namespaces: Vec<VectorNamespace>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorStoreType {
+    Weaviate,
+    Qdrant,
+    Pgvector,
+    /// The in-process `vectors` index, persisted under `path`.
+    Embedded,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VectorNamespace {
+    pub name: String,
+    #[serde(default)]
+    pub sources: Vec<Source>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Source {
+    Wiki {
+        url: String,
+    },
+    Repo {
+        repo: String,
+    },
+    S3 {
+        bucket: String,
+        prefix: Option<String>,
+    },
+}
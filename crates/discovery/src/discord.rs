@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// `discord`: guild, channels and bot settings.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Discord {
+    /// Guild (server) id, quoted; `null` until provisioned.
+    pub guild_id: Option<String>,
+    /// Logical name (`prs`, `alerts`, ...) to channel name (`#prs`).
+    #[serde(default)]
+    pub channels: BTreeMap<String, String>,
+    pub bot: Bot,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Bot {
+    /// Discord application id, quoted; `null` until provisioned.
+    pub app_id: Option<String>,
+    pub token_secret_ref: String,
+    pub intents: Intents,
+    pub rbac: Rbac,
+    pub rate_limits: RateLimits,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Intents {
+    #[serde(default)]
+    pub message_content: bool,
+    #[serde(default)]
+    pub guild_members: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Rbac {
+    /// Role required to run `prod_protected_commands` against prod.
+    pub prod_role: String,
+    pub allow_commands: Vec<String>,
+    #[serde(default)]
+    pub prod_protected_commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimits {
+    pub max_msgs_per_min: u32,
+    pub burst: u32,
+}
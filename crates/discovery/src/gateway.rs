@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// `event_gateway`: public webhook ingress.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EventGateway {
+    pub public_url: String,
+    pub auth: GatewayAuth,
+    /// Versioned JSON Schemas and templates for service events, relative to
+    /// `discovery.yml`; without one, events are posted unvalidated.
+    pub schema_registry: Option<String>,
+    #[serde(default)]
+    pub endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GatewayAuth {
+    pub hmac: Hmac,
+    /// Bearer token Alertmanager sends to the endpoint at
+    /// `infra.metrics.alertmanager_webhook`, since it cannot sign bodies.
+    /// Without one that endpoint takes HMAC-signed requests only.
+    #[serde(default)]
+    pub alertmanager_token_secret_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Hmac {
+    pub key_secret_ref: String,
+    /// Request header carrying the signature, e.g. `X-Sig`.
+    pub header: String,
+    pub algo: HmacAlgo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HmacAlgo {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl HmacAlgo {
+    /// Prefix used in `<algo>=<hex>` signature headers.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HmacAlgo::Sha1 => "sha1",
+            HmacAlgo::Sha256 => "sha256",
+            HmacAlgo::Sha512 => "sha512",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Endpoint {
+    pub path: String,
+    /// Service-name globs (`agent-*`) allowed to post to this endpoint.
+    #[serde(default)]
+    pub allowed_services: Vec<String>,
+    pub discord_channel: Option<String>,
+    /// Alert `severity` label → channel on the Alertmanager endpoint;
+    /// other severities go to `discord_channel`.
+    #[serde(default)]
+    pub severity_channels: BTreeMap<String, String>,
+    /// Alternative verification scheme; when unset the shared HMAC applies.
+    pub verify: Option<Verify>,
+    #[serde(default)]
+    pub routes: Vec<GitRoute>,
+}
+
+/// One `routes` entry on a `github_app` endpoint.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GitRoute {
+    pub event: String,
+    /// Allowed `action` values; empty means any.
+    #[serde(default)]
+    pub actions: Vec<String>,
+    /// Branch globs (`release/*`); empty means any.
+    #[serde(default)]
+    pub branches: Vec<String>,
+    pub discord_channel: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Verify {
+    /// `X-Hub-Signature-256` checked against `git.app.webhook_secret_ref`.
+    GithubApp,
+}
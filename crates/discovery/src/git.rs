@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// `git`: source hosting, watched repos and CI.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Git {
+    pub provider: GitProvider,
+    pub org: String,
+    pub app: GitApp,
+    #[serde(default)]
+    pub repos: Vec<Repo>,
+    pub gitlens: Option<Gitlens>,
+    pub ci: Option<Ci>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GitProvider {
+    Github,
+    Gitlab,
+    Bitbucket,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GitApp {
+    pub id: Option<u64>,
+    pub webhook_secret_ref: String,
+    pub private_key_secret_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Repo {
+    pub name: String,
+    /// Channel for `post_events` that no gateway route claims.
+    pub channel: String,
+    pub env: String,
+    /// Events this repo posts at all; anything else is dropped.
+    #[serde(default)]
+    pub post_events: Vec<String>,
+    /// Event → channel, taking precedence over the gateway routes.
+    #[serde(default)]
+    pub channel_overrides: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Gitlens {
+    pub edition: GitlensEdition,
+    pub vscode_users_group: Option<String>,
+    #[serde(default)]
+    pub features_used: Vec<String>,
+    pub notify_to_discord: Option<GitlensNotify>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GitlensEdition {
+    Pro,
+    Community,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GitlensNotify {
+    pub enabled: bool,
+    pub channel: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub cli_token_secret_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Ci {
+    pub system: CiSystem,
+    #[serde(default)]
+    pub post_status_to_discord: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CiSystem {
+    GithubActions,
+    GitlabCi,
+    Jenkins,
+    Circleci,
+}
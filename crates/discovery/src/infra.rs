@@ -0,0 +1,131 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// `infra`: environments, control plane and observability backends.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Infra {
+    pub environments: Vec<String>,
+    pub control_api: ControlApi,
+    pub message_bus: MessageBus,
+    pub nodes: Nodes,
+    pub logging: Logging,
+    pub metrics: Metrics,
+    pub tracing: Tracing,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ControlApi {
+    pub base_url: String,
+    pub auth: ControlAuth,
+    pub network: Network,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ControlAuth {
+    #[serde(rename = "type")]
+    pub kind: ControlAuthType,
+    pub token_secret_ref: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlAuthType {
+    Bearer,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Network {
+    pub access: NetworkAccess,
+    #[serde(default)]
+    pub cidrs_allowed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkAccess {
+    Vpn,
+    Zerotrust,
+    Public,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MessageBus {
+    /// `null` disables the bus.
+    #[serde(rename = "type")]
+    pub kind: Option<MessageBusType>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub topic_prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageBusType {
+    Redis,
+    Nats,
+    Kafka,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Nodes {
+    pub orchestrator: Orchestrator,
+    #[serde(default)]
+    pub clusters: Vec<Cluster>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Orchestrator {
+    Kubernetes,
+    Ecs,
+    Nomad,
+    Baremetal,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Cluster {
+    pub name: String,
+    pub api_server: String,
+    pub auth: String,
+    /// Namespaces the bot may act on; `*` means all.
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Logging {
+    pub provider: LogProvider,
+    pub endpoint: Option<String>,
+    pub auth_secret_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogProvider {
+    Loki,
+    Elk,
+    Datadog,
+    Splunk,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Metrics {
+    pub provider: String,
+    pub alertmanager_webhook: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Tracing {
+    pub provider: String,
+    pub collector_endpoint: Option<String>,
+}
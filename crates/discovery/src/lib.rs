@@ -0,0 +1,269 @@
+//! Typed, validated model of `discovery.yml`.
+//!
+//! Loading happens in two passes: serde deserialization, which rejects
+//! unknown keys and out-of-range enum values, then [`Discovery::validate`],
+//! which checks cross-references (channels, environments, secret refs, ...).
+//! Both report the `line:column` in the source file.
+//!
+//! [`json_schema`] exports the same model for the TypeScript and Python
+//! loaders; the committed copy lives next to the config as
+//! `discovery.schema.json`.
+
+mod agents;
+mod discord;
+mod gateway;
+mod git;
+mod infra;
+mod locate;
+mod ops;
+mod refinory;
+mod validate;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+pub use agents::*;
+pub use discord::*;
+pub use gateway::*;
+pub use git::*;
+pub use infra::*;
+pub use ops::*;
+pub use refinory::*;
+
+/// Default location, matching `loadConfig()` in `src/config.ts`.
+pub const DEFAULT_PATH: &str = "discovery.yml";
+
+/// A problem found in the document, with its position when known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// Dotted key path, e.g. `event_gateway.endpoints[1].path`.
+    pub path: String,
+    /// 1-based line and column.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, col)) = self.location {
+            write!(f, "{line}:{col}: ")?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("reading {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{}:{issue}", display_path(.file))]
+    Parse { file: Option<PathBuf>, issue: Issue },
+    #[error("{}: {} problem(s)\n{}", display_path(.file), .issues.len(), join_issues(.file, .issues))]
+    Invalid {
+        file: Option<PathBuf>,
+        issues: Vec<Issue>,
+    },
+}
+
+fn display_path(file: &Option<PathBuf>) -> String {
+    file.as_deref()
+        .map_or_else(|| DEFAULT_PATH.to_string(), |p| p.display().to_string())
+}
+
+fn join_issues(file: &Option<PathBuf>, issues: &[Issue]) -> String {
+    let file = display_path(file);
+    issues
+        .iter()
+        .map(|i| format!("  {file}:{i}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Error {
+    fn with_file(self, path: &Path) -> Self {
+        let file = Some(path.to_path_buf());
+        match self {
+            Error::Parse { issue, .. } => Error::Parse { file, issue },
+            Error::Invalid { issues, .. } => Error::Invalid { file, issues },
+            e => e,
+        }
+    }
+}
+
+/// The whole `discovery.yml` document.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Discovery {
+    pub org: Org,
+    pub discord: Discord,
+    pub infra: Infra,
+    pub ai_agents: AiAgents,
+    pub git: Git,
+    pub event_gateway: EventGateway,
+    pub commands: Commands,
+    pub security: Security,
+    pub governance: Governance,
+    pub testing: Testing,
+    pub limits: Limits,
+    /// Refinory orchestration platform; absent in minimal deployments.
+    #[serde(default)]
+    pub refinory: Option<Refinory>,
+}
+
+impl Discovery {
+    /// Reads, parses and validates the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_yaml(&text).map_err(|e| e.with_file(path))
+    }
+
+    /// Parses and validates a document held in memory.
+    pub fn from_yaml(text: &str) -> Result<Self, Error> {
+        let cfg: Self = serde_yaml::from_str(text).map_err(|e| Error::Parse {
+            file: None,
+            issue: parse_issue(&e),
+        })?;
+        let mut issues = cfg.validate();
+        if issues.is_empty() {
+            return Ok(cfg);
+        }
+        let index = locate::index(text);
+        for issue in &mut issues {
+            issue.location = index.get(&issue.path).copied();
+        }
+        Err(Error::Invalid { file: None, issues })
+    }
+
+    /// Semantic checks serde cannot express; locations are left unset.
+    pub fn validate(&self) -> Vec<Issue> {
+        validate::run(self)
+    }
+
+    /// Every Discord channel name the config references.
+    pub fn channel_names(&self) -> BTreeSet<&str> {
+        let endpoints = self.event_gateway.endpoints.iter();
+        let repos = self.git.repos.iter();
+        self.discord
+            .channels
+            .values()
+            .chain(endpoints.clone().filter_map(|e| e.discord_channel.as_ref()))
+            .chain(endpoints.flat_map(|e| e.routes.iter().map(|r| &r.discord_channel)))
+            .chain(
+                repos.flat_map(|r| std::iter::once(&r.channel).chain(r.channel_overrides.values())),
+            )
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+fn parse_issue(e: &serde_yaml::Error) -> Issue {
+    let mut message = e.to_string();
+    let location = e.location().map(|l| (l.line(), l.column()));
+    if let Some((line, col)) = location {
+        let suffix = format!(" at line {line} column {col}");
+        if let Some(stripped) = message.strip_suffix(&suffix) {
+            message = stripped.to_string();
+        }
+    }
+    Issue {
+        path: String::new(),
+        location,
+        message,
+    }
+}
+
+/// JSON Schema (draft 2020-12) for the document.
+pub fn json_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Discovery)).expect("schema serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_file() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml")
+    }
+
+    #[test]
+    fn loads_repo_discovery_file() {
+        let cfg = Discovery::load(repo_file()).unwrap();
+        assert_eq!(cfg.event_gateway.auth.hmac.algo, HmacAlgo::Sha256);
+        assert_eq!(cfg.infra.nodes.orchestrator, Orchestrator::Kubernetes);
+        assert_eq!(cfg.infra.message_bus.kind, Some(MessageBusType::Redis));
+        assert_eq!(
+            cfg.ai_agents.vector_store.kind,
+            Some(VectorStoreType::Pgvector)
+        );
+        let git = cfg
+            .event_gateway
+            .endpoints
+            .iter()
+            .find(|e| e.path == "/git")
+            .unwrap();
+        assert_eq!(git.verify, Some(Verify::GithubApp));
+    }
+
+    #[test]
+    fn bad_enum_reports_line_and_column() {
+        let text = std::fs::read_to_string(repo_file())
+            .unwrap()
+            .replace(r#"orchestrator: "kubernetes""#, r#"orchestrator: "k8s""#);
+        let line = text.lines().position(|l| l.contains("\"k8s\"")).unwrap() + 1;
+        let Err(Error::Parse { issue, .. }) = Discovery::from_yaml(&text) else {
+            panic!("expected parse error");
+        };
+        assert_eq!(issue.location.map(|l| l.0), Some(line));
+        assert!(
+            issue.message.contains("unknown variant `k8s`"),
+            "{}",
+            issue.message
+        );
+    }
+
+    #[test]
+    fn semantic_issue_points_at_key() {
+        let text = std::fs::read_to_string(repo_file()).unwrap().replacen(
+            r##"discord_channel: "#prs""##,
+            r#"discord_channel: "prs""#,
+            1,
+        );
+        let line = text.lines().position(|l| l.contains(r#""prs""#)).unwrap() + 1;
+        let Err(Error::Invalid { issues, .. }) = Discovery::from_yaml(&text) else {
+            panic!("expected validation error");
+        };
+        assert_eq!(issues.len(), 1, "{issues:?}");
+        assert_eq!(
+            issues[0].path,
+            "event_gateway.endpoints[2].routes[0].discord_channel"
+        );
+        assert_eq!(issues[0].location.map(|l| l.0), Some(line));
+    }
+
+    #[test]
+    fn committed_schema_is_current() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.schema.json");
+        let committed: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(
+            committed,
+            json_schema(),
+            "regenerate with `cargo run -p discovery -- schema > discovery.schema.json`"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Maps dotted key paths back to source positions.
+//!
+//! serde discards spans, so validation issues are located by replaying the
+//! document through a marked YAML event stream.
+
+use std::collections::HashMap;
+
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+enum Frame {
+    Map { path: String, key: Option<String> },
+    Seq { path: String, next: usize },
+}
+
+#[derive(Default)]
+struct Index {
+    stack: Vec<Frame>,
+    out: HashMap<String, (usize, usize)>,
+}
+
+impl Index {
+    /// Path of the node that is about to start, recording its position.
+    /// Returns `None` for mapping keys.
+    fn enter(&mut self, scalar: Option<&str>, mark: Marker) -> Option<String> {
+        let pos = (mark.line(), mark.col() + 1);
+        match self.stack.last_mut() {
+            None => Some(String::new()),
+            Some(Frame::Map { path, key }) => match key.take() {
+                None => {
+                    let k = scalar.unwrap_or_default().to_string();
+                    let full = join(path, &k);
+                    self.out.entry(full).or_insert(pos);
+                    *key = Some(k);
+                    None
+                }
+                Some(k) => Some(join(path, &k)),
+            },
+            Some(Frame::Seq { path, next }) => {
+                let full = format!("{path}[{next}]");
+                *next += 1;
+                self.out.entry(full.clone()).or_insert(pos);
+                Some(full)
+            }
+        }
+    }
+}
+
+fn join(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+impl MarkedEventReceiver for Index {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                self.enter(Some(&value), mark);
+            }
+            Event::Alias(_) => {
+                self.enter(None, mark);
+            }
+            Event::MappingStart(..) => {
+                let path = self.enter(None, mark).unwrap_or_default();
+                self.stack.push(Frame::Map { path, key: None });
+            }
+            Event::SequenceStart(..) => {
+                let path = self.enter(None, mark).unwrap_or_default();
+                self.stack.push(Frame::Seq { path, next: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Key path → 1-based (line, column) of the key (or sequence item).
+pub fn index(text: &str) -> HashMap<String, (usize, usize)> {
+    let mut idx = Index::default();
+    // Only called on text serde already accepted, so scan errors cannot occur.
+    let _ = Parser::new_from_str(text).load(&mut idx, false);
+    idx.out
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn indexes_nested_keys_and_items() {
+        let idx = super::index("a:\n  b: 1\n  c:\n    - x: 2\n    - y: 3\n");
+        assert_eq!(idx["a"], (1, 1));
+        assert_eq!(idx["a.b"], (2, 3));
+        assert_eq!(idx["a.c[1].y"], (5, 7));
+    }
+}
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use discovery::Discovery;
+
+#[derive(Parser)]
+#[command(about = "Validate discovery.yml or export its JSON Schema")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Parse and validate a config file.
+    Check {
+        #[arg(default_value = discovery::DEFAULT_PATH)]
+        path: PathBuf,
+    },
+    /// Print the JSON Schema for discovery.yml.
+    Schema,
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    match Args::parse().command {
+        Command::Check { path } => match Discovery::load(&path) {
+            Ok(_) => {
+                println!("{}: ok", path.display());
+                Ok(ExitCode::SUCCESS)
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                Ok(ExitCode::FAILURE)
+            }
+        },
+        Command::Schema => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&discovery::json_schema())?
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
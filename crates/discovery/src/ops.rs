@@ -0,0 +1,173 @@
+//! Organisation and operational policy sections.
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// `org`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Org {
+    pub name: String,
+    pub contact: Option<Contact>,
+    /// Compliance regimes (`soc2`, `hipaa`, ...); `none` if not applicable.
+    #[serde(default)]
+    pub compliance: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Contact {
+    pub owner: Option<String>,
+    pub slack_or_discord_handle: Option<String>,
+}
+
+/// `commands`: slash commands the bot registers.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Commands {
+    pub enabled: bool,
+    #[serde(default)]
+    pub list: Vec<Command>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Command {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub protected: bool,
+    #[serde(default)]
+    pub params: Vec<CommandParam>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CommandParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ParamType,
+    #[serde(default)]
+    pub required: bool,
+    pub default: Option<serde_json::Value>,
+    #[serde(rename = "enum", default)]
+    pub choices: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    String,
+    Int,
+    Bool,
+}
+
+/// `security`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Security {
+    pub secrets_manager: SecretsManager,
+    /// Secret name → rotation period in days.
+    #[serde(default)]
+    pub rotate_days: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub redaction: Redaction,
+    pub audit_log_sink: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretsManager {
+    Vault,
+    AwsSsm,
+    GcpSm,
+    AzureKv,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Redaction {
+    /// Regexes whose matches are masked before anything is logged or posted.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+/// `governance`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Governance {
+    pub approvals: Approvals,
+    pub change_management: Option<ChangeManagement>,
+    pub data_handling: DataHandling,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Approvals {
+    /// Roles that must approve prod commands.
+    #[serde(default)]
+    pub prod_commands_require: Vec<String>,
+    /// Prod-protected commands also need a second member to confirm them.
+    #[serde(default)]
+    pub two_person: bool,
+    /// How long a pending two-person confirmation stays open.
+    #[serde(default = "default_approval_ttl_secs")]
+    pub approval_ttl_secs: u64,
+}
+
+fn default_approval_ttl_secs() -> u64 {
+    900
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ChangeManagement {
+    pub link: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DataHandling {
+    #[serde(default)]
+    pub post_pii_to_discord: bool,
+}
+
+/// `testing`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Testing {
+    pub sandbox_guild_id: Option<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    pub notify_channel: Option<String>,
+}
+
+/// `limits`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    pub discord_api: DiscordApiLimits,
+    pub attachments: Attachments,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DiscordApiLimits {
+    pub max_messages_per_minute: u32,
+    /// Messages the whole bot may send at once, shared by every user and
+    /// channel. Defaults to `max_messages_per_minute`.
+    #[serde(default)]
+    pub burst: Option<u32>,
+    pub max_embed_size_kb: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Attachments {
+    #[serde(default)]
+    pub allow_images: bool,
+    #[serde(default)]
+    pub allow_logs_upload: bool,
+}
@@ -0,0 +1,152 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// `refinory`: AI agent orchestration platform.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Refinory {
+    pub enabled: bool,
+    pub repo_root: String,
+    pub services_domain: String,
+    pub network: String,
+    pub ports: RefinoryPorts,
+    pub runtime: RefinoryRuntime,
+    pub storage: RefinoryStorage,
+    pub secrets: RefinorySecrets,
+    pub integrations: RefinoryIntegrations,
+    pub git: RefinoryGit,
+    pub experts: Experts,
+    pub policies: RefinoryPolicies,
+    pub discord: RefinoryDiscord,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinoryPorts {
+    pub api: u16,
+    pub orchestrator: u16,
+    pub dashboard: u16,
+    pub temporal: u16,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinoryRuntime {
+    #[serde(default)]
+    pub gpu: bool,
+    pub workers: u32,
+    pub max_concurrency: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinoryStorage {
+    pub artifacts_dir: String,
+    pub outputs_dir: String,
+    pub models_dir: String,
+    /// Keeps artifacts in an S3-compatible bucket instead of
+    /// `artifacts_dir`.
+    pub s3: Option<S3Storage>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct S3Storage {
+    /// e.g. `http://minio:9000`. Requests use path-style URLs.
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub access_key_ref: String,
+    pub secret_key_ref: String,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinorySecrets {
+    pub openai_api_key_ref: Option<String>,
+    pub github_token_ref: Option<String>,
+    /// HMAC key that signs artifact exports.
+    pub artifact_signing_key_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinoryIntegrations {
+    pub postgres_dsn_ref: Option<String>,
+    pub redis_url_ref: Option<String>,
+    pub qdrant_url: Option<String>,
+    pub grafana_url: Option<String>,
+    pub prometheus_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinoryGit {
+    pub default_org: String,
+    pub default_branch: String,
+    #[serde(default)]
+    pub repo_prefix: String,
+    #[serde(default)]
+    pub auto_create_repo: bool,
+    #[serde(default)]
+    pub create_pr: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Experts {
+    pub team: Vec<ExpertEntry>,
+    pub orchestration: ExpertOrchestration,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ExpertEntry {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ExpertOrchestration {
+    pub strategy: OrchestrationStrategy,
+    pub max_rounds: u32,
+    /// Team member that merges the other experts' output.
+    pub reducer: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrchestrationStrategy {
+    ParallelThenReduce,
+    Cascade,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinoryPolicies {
+    #[serde(default)]
+    pub allow_external_calls: bool,
+    #[serde(default)]
+    pub require_security_review: bool,
+    #[serde(default)]
+    pub require_tests: bool,
+    #[serde(default)]
+    pub require_containerization: bool,
+    #[serde(default)]
+    pub deployment_envs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RefinoryDiscord {
+    pub announce_channel: String,
+    pub alerts_channel: String,
+    pub prs_channel: String,
+    #[serde(default)]
+    pub notify_events: Vec<String>,
+}
@@ -0,0 +1,291 @@
+//! Cross-field checks run after deserialization.
+
+use std::collections::HashSet;
+
+use crate::{Discovery, Issue, VectorStoreType, Verify};
+
+struct Checker {
+    issues: Vec<Issue>,
+}
+
+impl Checker {
+    fn push(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(Issue {
+            path: path.into(),
+            location: None,
+            message: message.into(),
+        });
+    }
+
+    fn channel(&mut self, path: impl Into<String>, name: &str) {
+        if !name.starts_with('#') || name.len() < 2 {
+            self.push(path, format!("channel {name:?} must be a #channel name"));
+        }
+    }
+
+    fn secret_ref(&mut self, path: impl Into<String>, value: &str) {
+        match value.split_once("://") {
+            Some((scheme, rest)) if !scheme.is_empty() && !rest.is_empty() => {}
+            _ => self.push(
+                path,
+                format!("secret ref {value:?} must be a <scheme>://<path> URI"),
+            ),
+        }
+    }
+
+    fn env(&mut self, path: impl Into<String>, env: &str, known: &[String]) {
+        if !known.iter().any(|e| e == env) {
+            self.push(
+                path,
+                format!("environment {env:?} is not in infra.environments"),
+            );
+        }
+    }
+}
+
+pub(crate) fn run(cfg: &Discovery) -> Vec<Issue> {
+    let mut c = Checker { issues: Vec::new() };
+    let envs = &cfg.infra.environments;
+
+    for (name, channel) in &cfg.discord.channels {
+        c.channel(format!("discord.channels.{name}"), channel);
+    }
+    c.secret_ref(
+        "discord.bot.token_secret_ref",
+        &cfg.discord.bot.token_secret_ref,
+    );
+    let rbac = &cfg.discord.bot.rbac;
+    for (i, cmd) in rbac.prod_protected_commands.iter().enumerate() {
+        if !rbac.allow_commands.contains(cmd) {
+            c.push(
+                format!("discord.bot.rbac.prod_protected_commands[{i}]"),
+                format!("{cmd} is not in allow_commands"),
+            );
+        }
+    }
+    if cfg.discord.bot.rate_limits.burst == 0 {
+        c.push("discord.bot.rate_limits.burst", "must be at least 1");
+    }
+    if cfg.limits.discord_api.burst == Some(0) {
+        c.push("limits.discord_api.burst", "must be at least 1");
+    }
+
+    c.secret_ref(
+        "infra.control_api.auth.token_secret_ref",
+        &cfg.infra.control_api.auth.token_secret_ref,
+    );
+    let bus = &cfg.infra.message_bus;
+    if bus.kind.is_some() && bus.url.is_none() {
+        c.push(
+            "infra.message_bus.url",
+            "required when a message bus type is set",
+        );
+    }
+    if let Some(r) = &cfg.infra.logging.auth_secret_ref {
+        c.secret_ref("infra.logging.auth_secret_ref", r);
+    }
+
+    let vs = &cfg.ai_agents.vector_store;
+    match vs.kind {
+        Some(VectorStoreType::Embedded) if vs.path.as_deref().is_none_or(str::is_empty) => c.push(
+            "ai_agents.vector_store.path",
+            "required for the embedded type",
+        ),
+        Some(VectorStoreType::Embedded) | None => {}
+        Some(_) if vs.conn_string_secret_ref.is_none() => c.push(
+            "ai_agents.vector_store.conn_string_secret_ref",
+            "required when a vector store type is set",
+        ),
+        Some(_) => {}
+    }
+    if let Some(r) = &vs.conn_string_secret_ref {
+        c.secret_ref("ai_agents.vector_store.conn_string_secret_ref", r);
+    }
+    for channel in cfg.ai_agents.routing.per_channel.keys() {
+        c.channel(format!("ai_agents.routing.per_channel.{channel}"), channel);
+    }
+
+    c.secret_ref(
+        "git.app.webhook_secret_ref",
+        &cfg.git.app.webhook_secret_ref,
+    );
+    if let Some(r) = &cfg.git.app.private_key_secret_ref {
+        c.secret_ref("git.app.private_key_secret_ref", r);
+    }
+    let mut repos = HashSet::new();
+    for (i, repo) in cfg.git.repos.iter().enumerate() {
+        let p = format!("git.repos[{i}]");
+        if !repos.insert(repo.name.as_str()) {
+            c.push(
+                format!("{p}.name"),
+                format!("duplicate repo {:?}", repo.name),
+            );
+        }
+        c.channel(format!("{p}.channel"), &repo.channel);
+        c.env(format!("{p}.env"), &repo.env, envs);
+        for (event, channel) in &repo.channel_overrides {
+            c.channel(format!("{p}.channel_overrides.{event}"), channel);
+            if !repo.post_events.contains(event) {
+                c.push(
+                    format!("{p}.channel_overrides.{event}"),
+                    format!("{event} is not in post_events"),
+                );
+            }
+        }
+    }
+    if let Some(notify) = cfg
+        .git
+        .gitlens
+        .as_ref()
+        .and_then(|g| g.notify_to_discord.as_ref())
+    {
+        c.channel("git.gitlens.notify_to_discord.channel", &notify.channel);
+    }
+
+    let gw = &cfg.event_gateway;
+    c.secret_ref(
+        "event_gateway.auth.hmac.key_secret_ref",
+        &gw.auth.hmac.key_secret_ref,
+    );
+    if let Some(r) = &gw.auth.alertmanager_token_secret_ref {
+        c.secret_ref("event_gateway.auth.alertmanager_token_secret_ref", r);
+    }
+    let mut paths = HashSet::new();
+    for (i, ep) in gw.endpoints.iter().enumerate() {
+        let p = format!("event_gateway.endpoints[{i}]");
+        if !ep.path.starts_with('/') {
+            c.push(
+                format!("{p}.path"),
+                format!("{:?} must start with /", ep.path),
+            );
+        }
+        if !paths.insert(ep.path.as_str()) {
+            c.push(
+                format!("{p}.path"),
+                format!("duplicate endpoint {:?}", ep.path),
+            );
+        }
+        if let Some(channel) = &ep.discord_channel {
+            c.channel(format!("{p}.discord_channel"), channel);
+        }
+        for (severity, channel) in &ep.severity_channels {
+            c.channel(format!("{p}.severity_channels.{severity}"), channel);
+        }
+        match ep.verify {
+            Some(Verify::GithubApp) => {}
+            None => {
+                if ep.allowed_services.is_empty() {
+                    c.push(
+                        format!("{p}.allowed_services"),
+                        "required unless `verify` is set",
+                    );
+                }
+                if ep.discord_channel.is_none() {
+                    c.push(
+                        format!("{p}.discord_channel"),
+                        "required unless `verify` is set",
+                    );
+                }
+                if !ep.routes.is_empty() {
+                    c.push(
+                        format!("{p}.routes"),
+                        "only valid on `verify: github_app` endpoints",
+                    );
+                }
+            }
+        }
+        for (j, route) in ep.routes.iter().enumerate() {
+            c.channel(
+                format!("{p}.routes[{j}].discord_channel"),
+                &route.discord_channel,
+            );
+        }
+    }
+
+    let mut names = HashSet::new();
+    for (i, cmd) in cfg.commands.list.iter().enumerate() {
+        if !names.insert(cmd.name.as_str()) {
+            c.push(
+                format!("commands.list[{i}].name"),
+                format!("duplicate command {:?}", cmd.name),
+            );
+        }
+        if cmd.protected
+            && !rbac
+                .prod_protected_commands
+                .contains(&format!("/{}", cmd.name))
+        {
+            c.push(
+                format!("commands.list[{i}].protected"),
+                format!(
+                    "/{} is not in discord.bot.rbac.prod_protected_commands",
+                    cmd.name
+                ),
+            );
+        }
+    }
+
+    for (i, pattern) in cfg.security.redaction.patterns.iter().enumerate() {
+        if let Err(e) = regex::Regex::new(pattern) {
+            c.push(format!("security.redaction.patterns[{i}]"), e.to_string());
+        }
+    }
+    if let Some(ch) = &cfg.testing.notify_channel {
+        c.channel("testing.notify_channel", ch);
+    }
+
+    if let Some(r) = &cfg.refinory {
+        let team: Vec<&str> = r.experts.team.iter().map(|e| e.name.as_str()).collect();
+        if !team.contains(&r.experts.orchestration.reducer.as_str()) {
+            c.push(
+                "refinory.experts.orchestration.reducer",
+                format!(
+                    "{:?} is not in experts.team",
+                    r.experts.orchestration.reducer
+                ),
+            );
+        }
+        for (i, env) in r.policies.deployment_envs.iter().enumerate() {
+            c.env(format!("refinory.policies.deployment_envs[{i}]"), env, envs);
+        }
+        c.channel(
+            "refinory.discord.announce_channel",
+            &r.discord.announce_channel,
+        );
+        c.channel("refinory.discord.alerts_channel", &r.discord.alerts_channel);
+        c.channel("refinory.discord.prs_channel", &r.discord.prs_channel);
+        let refs = [
+            (
+                "refinory.secrets.openai_api_key_ref",
+                &r.secrets.openai_api_key_ref,
+            ),
+            (
+                "refinory.secrets.github_token_ref",
+                &r.secrets.github_token_ref,
+            ),
+            (
+                "refinory.secrets.artifact_signing_key_ref",
+                &r.secrets.artifact_signing_key_ref,
+            ),
+            (
+                "refinory.integrations.postgres_dsn_ref",
+                &r.integrations.postgres_dsn_ref,
+            ),
+            (
+                "refinory.integrations.redis_url_ref",
+                &r.integrations.redis_url_ref,
+            ),
+        ];
+        for (path, value) in refs {
+            if let Some(v) = value {
+                c.secret_ref(path, v);
+            }
+        }
+        if let Some(s3) = &r.storage.s3 {
+            c.secret_ref("refinory.storage.s3.access_key_ref", &s3.access_key_ref);
+            c.secret_ref("refinory.storage.s3.secret_key_ref", &s3.secret_key_ref);
+        }
+    }
+
+    c.issues
+}
@@ -0,0 +1,249 @@
+//! Coalescing of concurrent embedding calls into bounded batches.
+//!
+//! Every call is cut into jobs of at most the batch size and queued for
+//! one background task. Whenever fewer than the concurrency limit of
+//! requests are in flight, the task packs whole queued jobs into one
+//! request of at most the batch size distinct texts, so calls arriving
+//! while the server is busy share requests, and a text asked for twice
+//! in a batch is embedded once.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+
+use crate::{EmbedError, Embedder};
+
+/// Distinct texts per request.
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+/// Requests in flight.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+type Reply = Result<Vec<Vec<f32>>, EmbedError>;
+
+struct Job {
+    texts: Vec<String>,
+    reply: oneshot::Sender<Reply>,
+}
+
+pub struct Batching {
+    inner: Arc<dyn Embedder>,
+    batch_size: usize,
+    concurrency: usize,
+    /// Started on the first call, so that construction needs no runtime.
+    jobs: OnceLock<mpsc::UnboundedSender<Job>>,
+}
+
+impl Batching {
+    pub fn new(inner: Arc<dyn Embedder>) -> Self {
+        Self {
+            inner,
+            batch_size: DEFAULT_BATCH_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
+            jobs: OnceLock::new(),
+        }
+    }
+
+    /// [`DEFAULT_BATCH_SIZE`] by default; at least 1.
+    pub fn with_batch_size(mut self, texts: usize) -> Self {
+        self.batch_size = texts.max(1);
+        self
+    }
+
+    /// [`DEFAULT_CONCURRENCY`] by default; at least 1.
+    pub fn with_concurrency(mut self, requests: usize) -> Self {
+        self.concurrency = requests.max(1);
+        self
+    }
+}
+
+#[async_trait]
+impl Embedder for Batching {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let jobs = self.jobs.get_or_init(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tokio::spawn(run(
+                self.inner.clone(),
+                rx,
+                self.batch_size,
+                self.concurrency,
+            ));
+            tx
+        });
+        let mut replies = Vec::new();
+        for chunk in texts.chunks(self.batch_size) {
+            let (reply, rx) = oneshot::channel();
+            let job = Job {
+                texts: chunk.to_vec(),
+                reply,
+            };
+            if jobs.send(job).is_err() {
+                return Err(stopped());
+            }
+            replies.push(rx);
+        }
+        let mut vectors = Vec::with_capacity(texts.len());
+        for rx in replies {
+            vectors.extend(rx.await.map_err(|_| stopped())??);
+        }
+        Ok(vectors)
+    }
+}
+
+fn stopped() -> EmbedError {
+    EmbedError::Malformed("embedding batch ended without a reply".into())
+}
+
+async fn run(
+    inner: Arc<dyn Embedder>,
+    mut rx: mpsc::UnboundedReceiver<Job>,
+    batch_size: usize,
+    concurrency: usize,
+) {
+    let permits = Arc::new(Semaphore::new(concurrency));
+    let mut queue: VecDeque<Job> = VecDeque::new();
+    loop {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        if queue.is_empty() {
+            match rx.recv().await {
+                Some(job) => queue.push_back(job),
+                None => return,
+            }
+        }
+        while let Ok(job) = rx.try_recv() {
+            queue.push_back(job);
+        }
+
+        let mut texts: Vec<String> = Vec::new();
+        let mut slots: HashMap<String, usize> = HashMap::new();
+        let mut batch = Vec::new();
+        while let Some(job) = queue.front() {
+            let mut new: Vec<&String> = job
+                .texts
+                .iter()
+                .filter(|t| !slots.contains_key(*t))
+                .collect();
+            new.sort();
+            new.dedup();
+            if !batch.is_empty() && texts.len() + new.len() > batch_size {
+                break;
+            }
+            let job = queue.pop_front().expect("front exists");
+            for text in &job.texts {
+                if !slots.contains_key(text) {
+                    slots.insert(text.clone(), texts.len());
+                    texts.push(text.clone());
+                }
+            }
+            batch.push(job);
+        }
+
+        let inner = inner.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let result =
+                match inner.embed(&texts).await {
+                    Ok(vectors) if vectors.len() != texts.len() => Err(EmbedError::Malformed(
+                        format!("{} embeddings for {} texts", vectors.len(), texts.len()),
+                    )),
+                    result => result,
+                };
+            for job in batch {
+                let reply = match &result {
+                    Ok(vectors) => Ok(job
+                        .texts
+                        .iter()
+                        .map(|t| vectors[slots[t]].clone())
+                        .collect()),
+                    Err(e) => Err(e.clone()),
+                };
+                let _ = job.reply.send(reply);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Embeds a text as its length, slowly, recording each request.
+    #[derive(Default)]
+    struct Slow {
+        batches: Mutex<Vec<Vec<String>>>,
+        in_flight: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Embedder for Slow {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            self.batches.lock().unwrap().push(texts.to_vec());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(texts.iter().map(|t| vec![t.len() as f32]).collect())
+        }
+    }
+
+    fn strings(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn coalesces_concurrent_calls_within_size_and_concurrency_limits() {
+        let slow = Arc::new(Slow::default());
+        let batching = Batching::new(slow.clone())
+            .with_batch_size(4)
+            .with_concurrency(1);
+
+        let calls = [
+            strings(&["a", "bb", "ccc"]),
+            strings(&["ccc", "dddd", "e"]),
+            strings(&["e", "ff"]),
+        ];
+        let (a, b, c) = tokio::join!(
+            batching.embed(&calls[0]),
+            batching.embed(&calls[1]),
+            batching.embed(&calls[2]),
+        );
+        assert_eq!(a.unwrap(), [[1.0], [2.0], [3.0]]);
+        assert_eq!(b.unwrap(), [[3.0], [4.0], [1.0]]);
+        assert_eq!(c.unwrap(), [[1.0], [2.0]]);
+        // Eight texts asked for, six distinct, in two requests of at
+        // most four.
+        let batches = slow.batches.lock().unwrap().clone();
+        assert_eq!(
+            batches,
+            [
+                strings(&["a", "bb", "ccc"]),
+                strings(&["ccc", "dddd", "e", "ff"])
+            ]
+        );
+        assert_eq!(slow.peak.load(Ordering::SeqCst), 1);
+
+        let slow = Arc::new(Slow::default());
+        let batching = Batching::new(slow.clone())
+            .with_batch_size(3)
+            .with_concurrency(2);
+        let texts: Vec<String> = (1..=10).map(|n| "x".repeat(n)).collect();
+        let vectors = batching.embed(&texts).await.unwrap();
+        assert_eq!(vectors.len(), 10);
+        assert_eq!(vectors[9], [10.0]);
+        assert_eq!(slow.batches.lock().unwrap().len(), 4);
+        assert_eq!(slow.peak.load(Ordering::SeqCst), 2);
+    }
+}
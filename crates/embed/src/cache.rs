@@ -0,0 +1,229 @@
+//! On-disk embedding cache, shared by every process pointed at it.
+//!
+//! `recon/retriever/api.py` kept an in-process dict of at most 1000
+//! vectors, keyed by Python's per-process `hash()` and lost on restart.
+//! Here a vector is a file `{root}/{model}/{hash[..2]}/{hash}`, `hash`
+//! being the SHA-256 of the text, holding its little-endian `f32`s. A file
+//! is written under a temporary name and renamed into place, so readers
+//! and writers in any number of processes never see part of a vector.
+//! Nothing is evicted; the directory may be deleted at any time.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::{metrics, EmbedError, Embedder};
+
+/// Vectors of one model.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// `BAAI/bge-small-en-v1.5` becomes `BAAI_bge-small-en-v1.5`.
+fn slug(model: &str) -> String {
+    model
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl Cache {
+    /// The cache of `model` under `root`, created if missing.
+    pub fn open(root: impl AsRef<Path>, model: &str) -> io::Result<Self> {
+        let dir = root.as_ref().join(slug(model));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, text: &str) -> PathBuf {
+        let hash = hex::encode(Sha256::digest(text.as_bytes()));
+        self.dir.join(&hash[..2]).join(hash)
+    }
+
+    /// `None` when absent or unreadable.
+    pub fn get(&self, text: &str) -> Option<Vec<f32>> {
+        let bytes = std::fs::read(self.path(text)).ok()?;
+        if bytes.is_empty() || bytes.len() % 4 != 0 {
+            return None;
+        }
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().expect("chunks of 4")))
+                .collect(),
+        )
+    }
+
+    pub fn put(&self, text: &str, vector: &[f32]) -> io::Result<()> {
+        static TEMP: AtomicU64 = AtomicU64::new(0);
+        let path = self.path(text);
+        let shard = path.parent().expect("shard directory");
+        std::fs::create_dir_all(shard)?;
+        let temp = shard.join(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+        std::fs::write(&temp, bytes)?;
+        std::fs::rename(&temp, &path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+    }
+}
+
+/// Answers from a [`Cache`] where it can, embedding and storing the rest.
+pub struct Cached {
+    inner: Arc<dyn Embedder>,
+    cache: Arc<Cache>,
+}
+
+impl Cached {
+    pub fn new(inner: Arc<dyn Embedder>, cache: Cache) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(cache),
+        }
+    }
+}
+
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+#[async_trait]
+impl Embedder for Cached {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let cache = self.cache.clone();
+        let owned = texts.to_vec();
+        let mut vectors: Vec<Option<Vec<f32>>> =
+            blocking(move || owned.iter().map(|t| cache.get(t)).collect()).await;
+        let hits = vectors.iter().filter(|v| v.is_some()).count();
+        metrics::CACHE_HITS.inc_by(hits as u64);
+        metrics::CACHE_MISSES.inc_by((texts.len() - hits) as u64);
+
+        let mut missing: Vec<String> = texts
+            .iter()
+            .zip(&vectors)
+            .filter(|(_, v)| v.is_none())
+            .map(|(t, _)| t.clone())
+            .collect();
+        if missing.is_empty() {
+            return Ok(vectors.into_iter().flatten().collect());
+        }
+        missing.sort();
+        missing.dedup();
+        let embedded = self.inner.embed(&missing).await?;
+        if embedded.len() != missing.len() {
+            return Err(EmbedError::Malformed(format!(
+                "{} embeddings for {} texts",
+                embedded.len(),
+                missing.len()
+            )));
+        }
+        for (text, slot) in texts.iter().zip(&mut vectors) {
+            if slot.is_none() {
+                let i = missing.binary_search(text).expect("missing text");
+                *slot = Some(embedded[i].clone());
+            }
+        }
+
+        let cache = self.cache.clone();
+        blocking(move || {
+            for (text, vector) in missing.iter().zip(&embedded) {
+                if let Err(e) = cache.put(text, vector) {
+                    tracing::warn!(
+                        dir = %cache.dir().display(),
+                        error = %e,
+                        "embedding cache write failed"
+                    );
+                    break;
+                }
+            }
+        })
+        .await;
+        Ok(vectors.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Counting {
+        seen: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Embedder for Counting {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+            self.seen.lock().unwrap().extend_from_slice(texts);
+            Ok(texts.iter().map(|t| vec![t.len() as f32, -0.5]).collect())
+        }
+    }
+
+    fn strings(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn persists_vectors_per_model_across_instances_and_counts_hits() {
+        let root = tempfile::tempdir().unwrap();
+        let hits = metrics::CACHE_HITS.get();
+
+        let first = Arc::new(Counting::default());
+        let cached = Cached::new(
+            first.clone(),
+            Cache::open(root.path(), "BAAI/bge-small-en-v1.5").unwrap(),
+        );
+        let vectors = cached
+            .embed(&strings(&["dao", "llc", "dao"]))
+            .await
+            .unwrap();
+        assert_eq!(vectors, [[3.0, -0.5], [3.0, -0.5], [3.0, -0.5]]);
+        assert_eq!(*first.seen.lock().unwrap(), ["dao", "llc"]);
+        assert_eq!(metrics::CACHE_HITS.get(), hits);
+        assert!(root.path().join("BAAI_bge-small-en-v1.5").is_dir());
+
+        // Another process, say the retriever after ingest, shares the files.
+        let second = Arc::new(Counting::default());
+        let cached = Cached::new(
+            second.clone(),
+            Cache::open(root.path(), "BAAI/bge-small-en-v1.5").unwrap(),
+        );
+        let vectors = cached.embed(&strings(&["llc", "sf0068"])).await.unwrap();
+        assert_eq!(vectors[1], [6.0, -0.5]);
+        assert_eq!(*second.seen.lock().unwrap(), ["sf0068"]);
+        assert_eq!(metrics::CACHE_HITS.get(), hits + 1);
+
+        // Another model shares nothing.
+        let cache = Cache::open(root.path(), "other").unwrap();
+        assert!(cache.get("llc").is_none());
+        let torn = cache.path("torn");
+        std::fs::create_dir_all(torn.parent().unwrap()).unwrap();
+        std::fs::write(torn, [0u8; 3]).unwrap();
+        assert!(cache.get("torn").is_none());
+    }
+}
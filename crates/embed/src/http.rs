@@ -0,0 +1,209 @@
+//! Client for an HTTP embedding server (`EMBED_URL`).
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{EmbedError, Embedder};
+
+pub const DEFAULT_URL: &str = "http://localhost:8081/embed";
+
+/// The model `recon/ingest/embedder.py` serves.
+pub const DEFAULT_MODEL: &str = "bge-small-en-v1.5";
+
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(250);
+
+pub struct Http {
+    http: reqwest::Client,
+    url: String,
+    retries: u32,
+    backoff: Duration,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl Http {
+    /// `url` is the full endpoint, e.g. [`DEFAULT_URL`].
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.into(),
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+
+    /// Attempts after the first when the server answers 5xx or cannot be
+    /// reached; [`DEFAULT_RETRIES`] by default.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait before the first retry, doubled before each next one;
+    /// [`DEFAULT_BACKOFF`] by default.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    async fn attempt(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let res = self
+            .http
+            .post(&self.url)
+            .json(&json!({ "texts": texts }))
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            let status = res.status().as_u16();
+            let mut body = res.text().await.unwrap_or_default();
+            body.truncate(body.floor_char_boundary(512));
+            return Err(EmbedError::Status { status, body });
+        }
+        let body: Response = res
+            .json()
+            .await
+            .map_err(|e| EmbedError::Malformed(e.to_string()))?;
+        if body.embeddings.len() != texts.len() {
+            return Err(EmbedError::Malformed(format!(
+                "{} embeddings for {} texts",
+                body.embeddings.len(),
+                texts.len()
+            )));
+        }
+        Ok(body.embeddings)
+    }
+}
+
+fn transient(e: &EmbedError) -> bool {
+    match e {
+        EmbedError::Http(e) => e.is_connect() || e.is_timeout(),
+        EmbedError::Status { status, .. } => *status >= 500,
+        EmbedError::Malformed(_) | EmbedError::Model(_) => false,
+    }
+}
+
+#[async_trait]
+impl Embedder for Http {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut wait = self.backoff;
+        let mut retries = self.retries;
+        loop {
+            match self.attempt(texts).await {
+                Err(e) if retries > 0 && transient(&e) => {
+                    tracing::warn!(error = %e, retry_in = ?wait, "embedding request failed");
+                    tokio::time::sleep(wait).await;
+                    wait *= 2;
+                    retries -= 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+
+    use super::*;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn posts_texts_and_checks_the_embedding_count() {
+        let app = Router::new()
+            .route(
+                "/embed",
+                post(|Json(body): Json<Value>| async move {
+                    let n = body["texts"].as_array().unwrap().len();
+                    Json(json!({ "embeddings": (0..n).map(|i| [i as f32, 1.0]).collect::<Vec<_>>() }))
+                }),
+            )
+            .route(
+                "/short",
+                post(|| async { Json(json!({ "embeddings": [[1.0]] })) }),
+            );
+        let base = serve(app).await;
+        let texts = vec!["a".to_string(), "b".to_string()];
+
+        let vectors = Http::new(format!("{base}/embed"))
+            .embed(&texts)
+            .await
+            .unwrap();
+        assert_eq!(vectors, [[0.0, 1.0], [1.0, 1.0]]);
+
+        let err = Http::new(format!("{base}/short"))
+            .embed(&texts)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, EmbedError::Malformed(_)), "{err}");
+        let err = Http::new(format!("{base}/missing"))
+            .embed(&texts)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, EmbedError::Status { status: 404, .. }),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_with_backoff() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/embed",
+            post(move || {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if n < 2 {
+                        (StatusCode::SERVICE_UNAVAILABLE, "loading model").into_response()
+                    } else {
+                        Json(json!({ "embeddings": [[1.0]] })).into_response()
+                    }
+                }
+            }),
+        );
+        let url = format!("{}/embed", serve(app).await);
+        let texts = vec!["a".to_string()];
+
+        let http = Http::new(&url).with_backoff(Duration::from_millis(1));
+        assert_eq!(http.embed(&texts).await.unwrap(), [[1.0]]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        calls.store(0, Ordering::SeqCst);
+        let err = Http::new(&url)
+            .with_retries(1)
+            .with_backoff(Duration::from_millis(1))
+            .embed(&texts)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, EmbedError::Status { status: 503, .. }),
+            "{err}"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
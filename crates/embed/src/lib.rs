@@ -0,0 +1,112 @@
+//! Text embeddings for recon ingest and retrieval.
+//!
+//! An [`Embedder`] turns texts into vectors, one per text and in order.
+//! [`http::Http`] calls an embedding server speaking the protocol of
+//! `recon/ingest/embedder.py`: `POST /embed {"texts": [...]}` answered by
+//! `{"embeddings": [[...], ...]}`, retrying server errors. With the
+//! `local` feature, [`local::Local`] runs the model in process instead.
+//!
+//! Two wrappers stack on any embedder. [`batch::Batching`] merges the
+//! texts of concurrent calls into requests of a bounded size, with a
+//! bounded number in flight. [`cache::Cached`] keeps every vector on
+//! disk under the model name and a hash of the text, so ingest and the
+//! retriever pointed at the same directory never embed a text twice:
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # fn main() -> std::io::Result<()> {
+//! # tokio::runtime::Runtime::new()?.block_on(async {
+//! use embed::{batch::Batching, cache::{Cache, Cached}, http::Http};
+//!
+//! let http = Arc::new(Http::new(embed::http::DEFAULT_URL));
+//! let cache = Cache::open("recon-embed-cache", embed::http::DEFAULT_MODEL)?;
+//! let embedder = Cached::new(Arc::new(Batching::new(http)), cache);
+//! # let _ = embedder;
+//! # Ok(())
+//! # })
+//! # }
+//! ```
+
+pub mod batch;
+pub mod cache;
+pub mod http;
+#[cfg(feature = "local")]
+pub mod local;
+pub mod metrics;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+/// Cheap to clone, so that one failed request can fail every call
+/// batched into it.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum EmbedError {
+    #[error("http: {0}")]
+    Http(Arc<reqwest::Error>),
+    #[error("embedding server returned HTTP {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error("malformed embedding response: {0}")]
+    Malformed(String),
+    #[error("embedding model: {0}")]
+    Model(String),
+}
+
+impl From<reqwest::Error> for EmbedError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(Arc::new(e))
+    }
+}
+
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// One vector per text, in the order given.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError>;
+}
+
+/// The in-process model in `model_dir` when one is given, else the
+/// embedding server at `url` serving `model`, with the model name that
+/// keys the [`cache::Cache`]: a local model's directory name.
+pub fn backend(
+    url: &str,
+    model: &str,
+    model_dir: Option<&Path>,
+) -> Result<(Arc<dyn Embedder>, String), EmbedError> {
+    if let Some(dir) = model_dir {
+        #[cfg(feature = "local")]
+        {
+            let local = local::Local::load(dir)?;
+            tracing::info!(model = %dir.display(), dimension = local.dimension(), "embedding in process");
+            let name = dir.file_name().map_or_else(
+                || dir.display().to_string(),
+                |n| n.to_string_lossy().into_owned(),
+            );
+            return Ok((Arc::new(local), name));
+        }
+        #[cfg(not(feature = "local"))]
+        return Err(EmbedError::Model(format!(
+            "{}: in-process embedding needs the `local` feature",
+            dir.display()
+        )));
+    }
+    Ok((Arc::new(http::Http::new(url)), model.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_model_name_keys_the_cache_unless_a_model_dir_is_given() {
+        let (_, name) = backend(http::DEFAULT_URL, "bge-small", None).unwrap();
+        assert_eq!(name, "bge-small");
+        #[cfg(not(feature = "local"))]
+        assert!(backend(
+            http::DEFAULT_URL,
+            "bge-small",
+            Some(Path::new("models/bge"))
+        )
+        .is_err());
+    }
+}
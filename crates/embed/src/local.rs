@@ -0,0 +1,182 @@
+//! In-process embeddings on CPU, for air-gapped hosts with no embedding
+//! server.
+//!
+//! [`Local::load`] reads a sentence-transformers BERT checkpoint as it is
+//! laid out on the Hugging Face hub: `config.json`, `tokenizer.json`,
+//! `model.safetensors` and optionally `1_Pooling/config.json`. That covers
+//! `BAAI/bge-small-en-v1.5`, the model `recon/ingest/embedder.py` serves,
+//! and `all-MiniLM-L6-v2`. ONNX exports are not read; the hub publishes
+//! safetensors next to them.
+//!
+//! Like the HTTP path, vectors have the model's hidden size and unit
+//! length, so an index built by one is searchable with the other.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use candle_core::{DType, Device, IndexOp, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config};
+use serde::Deserialize;
+use tokenizers::{PaddingParams, PaddingStrategy, Tokenizer, TruncationParams};
+
+use crate::{EmbedError, Embedder};
+
+/// How token states become one vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pooling {
+    /// The `[CLS]` token's state, as bge models use.
+    Cls,
+    /// The mean over real tokens; the sentence-transformers default.
+    Mean,
+}
+
+/// The fields of `1_Pooling/config.json` that matter here.
+#[derive(Deserialize)]
+struct PoolingConfig {
+    #[serde(default)]
+    pooling_mode_cls_token: bool,
+}
+
+struct Model {
+    bert: BertModel,
+    tokenizer: Tokenizer,
+    pooling: Pooling,
+}
+
+pub struct Local {
+    model: Arc<Model>,
+    dimension: usize,
+}
+
+fn invalid(context: &str, e: impl std::fmt::Display) -> EmbedError {
+    EmbedError::Model(format!("{context}: {e}"))
+}
+
+impl Local {
+    /// Loads the checkpoint in `dir`; see the module docs for its layout.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, EmbedError> {
+        let dir = dir.as_ref();
+        let read = |name: &str| {
+            std::fs::read_to_string(dir.join(name))
+                .map_err(|e| invalid(&dir.join(name).display().to_string(), e))
+        };
+        let config: Config =
+            serde_json::from_str(&read("config.json")?).map_err(|e| invalid("config.json", e))?;
+        let pooling = if dir.join("1_Pooling/config.json").exists() {
+            let pooling: PoolingConfig = serde_json::from_str(&read("1_Pooling/config.json")?)
+                .map_err(|e| invalid("1_Pooling/config.json", e))?;
+            if pooling.pooling_mode_cls_token {
+                Pooling::Cls
+            } else {
+                Pooling::Mean
+            }
+        } else {
+            Pooling::Mean
+        };
+
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| invalid("tokenizer.json", e))?;
+        let pad_token = tokenizer
+            .id_to_token(config.pad_token_id as u32)
+            .unwrap_or_else(|| "[PAD]".into());
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            pad_id: config.pad_token_id as u32,
+            pad_token,
+            ..PaddingParams::default()
+        }));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..TruncationParams::default()
+            }))
+            .map_err(|e| invalid("tokenizer.json", e))?;
+
+        let weights = dir.join("model.safetensors");
+        // SAFETY: the file is mapped read-only for the model's lifetime;
+        // like any checkpoint it must not be rewritten while loaded.
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&[&weights], DType::F32, &Device::Cpu) }
+                .map_err(|e| invalid(&weights.display().to_string(), e))?;
+        let bert = BertModel::load(vb, &config).map_err(|e| invalid("model.safetensors", e))?;
+        Ok(Self {
+            model: Arc::new(Model {
+                bert,
+                tokenizer,
+                pooling,
+            }),
+            dimension: config.hidden_size,
+        })
+    }
+
+    /// Length of every vector.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn pooling(&self) -> Pooling {
+        self.model.pooling
+    }
+}
+
+impl Model {
+    fn encode(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let fail = |e: candle_core::Error| invalid("inference", e);
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts, true)
+            .map_err(|e| invalid("tokenizing", e))?;
+        let device = &self.bert.device;
+        let ids = encodings
+            .iter()
+            .map(|e| Tensor::new(e.get_ids(), device))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|ids| Tensor::stack(&ids, 0))
+            .map_err(fail)?;
+        let mask = encodings
+            .iter()
+            .map(|e| Tensor::new(e.get_attention_mask(), device))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|mask| Tensor::stack(&mask, 0))
+            .map_err(fail)?;
+        let pooled = (|| {
+            let types = ids.zeros_like()?;
+            let states = self.bert.forward(&ids, &types, Some(&mask))?;
+            let pooled = match self.pooling {
+                Pooling::Cls => states.i((.., 0))?,
+                Pooling::Mean => {
+                    let mask = mask.to_dtype(DType::F32)?.unsqueeze(2)?;
+                    states
+                        .broadcast_mul(&mask)?
+                        .sum(1)?
+                        .broadcast_div(&mask.sum(1)?)?
+                }
+            };
+            // `normalize_embeddings=True` in `embedder.py`.
+            let norm = pooled
+                .sqr()?
+                .sum_keepdim(1)?
+                .sqrt()?
+                .clamp(1e-12, f32::MAX)?;
+            pooled.broadcast_div(&norm)?.to_vec2::<f32>()
+        })()
+        .map_err(fail)?;
+        Ok(pooled)
+    }
+}
+
+#[async_trait]
+impl Embedder for Local {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let model = self.model.clone();
+        let texts = texts.to_vec();
+        tokio::task::spawn_blocking(move || model.encode(texts))
+            .await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+}
@@ -0,0 +1,19 @@
+//! Prometheus metrics, registered in the process-wide default registry
+//! under the names `recon/retriever/api.py` exported.
+
+use std::sync::LazyLock;
+
+use prometheus::{register_int_counter, IntCounter};
+
+/// `rag_embedding_cache_hits_total`: texts answered from the cache.
+pub static CACHE_HITS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("rag_embedding_cache_hits_total", "Embedding cache hits")
+        .expect("metric registered twice")
+});
+
+/// `rag_embedding_cache_misses_total`: texts the cache sent on to be
+/// embedded.
+pub static CACHE_MISSES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("rag_embedding_cache_misses_total", "Embedding cache misses")
+        .expect("metric registered twice")
+});
@@ -0,0 +1,118 @@
+//! A tiny randomly initialized BERT, laid out like a hub checkpoint,
+//! loaded and run by [`embed::local::Local`].
+
+#![cfg(feature = "local")]
+
+use std::path::Path;
+
+use candle_core::{DType, Device};
+use candle_nn::{VarBuilder, VarMap};
+use candle_transformers::models::bert::{BertModel, Config};
+use embed::local::{Local, Pooling};
+use embed::{EmbedError, Embedder};
+use serde_json::json;
+
+const VOCAB: &[&str] = &[
+    "[PAD]", "[UNK]", "[CLS]", "dao", "llc", "wyoming", "statute",
+];
+
+fn checkpoint(dir: &Path) {
+    let config = Config {
+        vocab_size: VOCAB.len(),
+        hidden_size: 16,
+        num_hidden_layers: 1,
+        num_attention_heads: 2,
+        intermediate_size: 32,
+        max_position_embeddings: 32,
+        ..Config::default()
+    };
+    std::fs::write(
+        dir.join("config.json"),
+        json!({
+            "vocab_size": config.vocab_size,
+            "hidden_size": config.hidden_size,
+            "num_hidden_layers": config.num_hidden_layers,
+            "num_attention_heads": config.num_attention_heads,
+            "intermediate_size": config.intermediate_size,
+            "hidden_act": "gelu",
+            "hidden_dropout_prob": 0.1,
+            "max_position_embeddings": config.max_position_embeddings,
+            "type_vocab_size": config.type_vocab_size,
+            "initializer_range": 0.02,
+            "layer_norm_eps": 1e-12,
+            "pad_token_id": 0,
+            "model_type": "bert"
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let vocab: serde_json::Map<String, serde_json::Value> = VOCAB
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.to_string(), json!(i)))
+        .collect();
+    std::fs::write(
+        dir.join("tokenizer.json"),
+        json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [],
+            "normalizer": {"type": "Lowercase"},
+            "pre_tokenizer": {"type": "Whitespace"},
+            "post_processor": null,
+            "decoder": null,
+            "model": {"type": "WordLevel", "vocab": vocab, "unk_token": "[UNK]"}
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let weights = VarMap::new();
+    BertModel::load(
+        VarBuilder::from_varmap(&weights, DType::F32, &Device::Cpu),
+        &config,
+    )
+    .unwrap();
+    weights.save(dir.join("model.safetensors")).unwrap();
+}
+
+fn strings(texts: &[&str]) -> Vec<String> {
+    texts.iter().map(|t| t.to_string()).collect()
+}
+
+#[tokio::test]
+async fn embeds_unit_vectors_of_the_hidden_size_regardless_of_padding() {
+    let dir = tempfile::tempdir().unwrap();
+    checkpoint(dir.path());
+    let local = Local::load(dir.path()).unwrap();
+    assert_eq!(local.dimension(), 16);
+    assert_eq!(local.pooling(), Pooling::Mean);
+
+    let texts = strings(&["DAO", "Wyoming DAO LLC statute", "unheard of words"]);
+    let vectors = local.embed(&texts).await.unwrap();
+    assert_eq!(vectors.len(), 3);
+    for v in &vectors {
+        assert_eq!(v.len(), 16);
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-4, "norm {norm}");
+    }
+    // Padding to the longest text in a batch must not move a vector.
+    let alone = local.embed(&strings(&["DAO"])).await.unwrap();
+    for (a, b) in alone[0].iter().zip(&vectors[0]) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    std::fs::create_dir(dir.path().join("1_Pooling")).unwrap();
+    std::fs::write(
+        dir.path().join("1_Pooling/config.json"),
+        r#"{"word_embedding_dimension": 16, "pooling_mode_cls_token": true}"#,
+    )
+    .unwrap();
+    assert_eq!(Local::load(dir.path()).unwrap().pooling(), Pooling::Cls);
+
+    std::fs::remove_file(dir.path().join("model.safetensors")).unwrap();
+    let err = Local::load(dir.path()).err().unwrap();
+    assert!(matches!(err, EmbedError::Model(_)), "{err}");
+}
@@ -0,0 +1,108 @@
+//! Diffing a [`Report`] against a stored baseline.
+//!
+//! The baseline is a report saved as JSON. A set metric more than the
+//! tolerance below its baseline value is a regression, as is a set the
+//! baseline has and the run lacks, or scores at another `k`, since its
+//! metrics are then not comparable. Metrics only one side measured, such
+//! as faithfulness in a run without an LLM, are not compared.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::{EvalError, Report};
+
+pub const DEFAULT_TOLERANCE: f64 = 0.02;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub set: String,
+    pub metric: &'static str,
+    /// `None` for a set the run lacks; for `k`, the set's `k`.
+    pub baseline: Option<f64>,
+    pub current: Option<f64>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.metric, self.baseline, self.current) {
+            ("k", Some(b), Some(c)) => {
+                write!(f, "{}: scored at k={c}, baseline at k={b}", self.set)
+            }
+            (_, Some(b), Some(c)) => write!(
+                f,
+                "{} {}: {b:.3} -> {c:.3} ({:+.3})",
+                self.set,
+                self.metric,
+                c - b
+            ),
+            _ => write!(f, "{}: not evaluated", self.set),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub regressions: Vec<Change>,
+    pub improvements: Vec<Change>,
+}
+
+pub fn compare(baseline: &Report, current: &Report, tolerance: f64) -> Diff {
+    let mut diff = Diff::default();
+    for (name, base) in &baseline.sets {
+        let Some(now) = current.sets.get(name) else {
+            diff.regressions.push(Change {
+                set: name.clone(),
+                metric: "set",
+                baseline: None,
+                current: None,
+            });
+            continue;
+        };
+        if now.k != base.k {
+            diff.regressions.push(Change {
+                set: name.clone(),
+                metric: "k",
+                baseline: Some(base.k as f64),
+                current: Some(now.k as f64),
+            });
+            continue;
+        }
+        for ((metric, b), (_, c)) in base.metrics.named().into_iter().zip(now.metrics.named()) {
+            let (Some(b), Some(c)) = (b, c) else {
+                continue;
+            };
+            let change = Change {
+                set: name.clone(),
+                metric,
+                baseline: Some(b),
+                current: Some(c),
+            };
+            if c < b - tolerance {
+                diff.regressions.push(change);
+            } else if c > b + tolerance {
+                diff.improvements.push(change);
+            }
+        }
+    }
+    diff
+}
+
+pub fn load(path: &Path) -> Result<Report, EvalError> {
+    let display = path.display().to_string();
+    let text = std::fs::read_to_string(path).map_err(|source| EvalError::Io {
+        path: display.clone(),
+        source,
+    })?;
+    serde_json::from_str(&text).map_err(|e| EvalError::Invalid {
+        path: display,
+        message: e.to_string(),
+    })
+}
+
+pub fn save(path: &Path, report: &Report) -> Result<(), EvalError> {
+    let json = serde_json::to_string_pretty(report).expect("reports serialize");
+    std::fs::write(path, json + "\n").map_err(|source| EvalError::Io {
+        path: path.display().to_string(),
+        source,
+    })
+}
@@ -0,0 +1,504 @@
+//! Retrieval quality of the recon RAG API, measured on golden sets.
+//!
+//! A [`GoldenSet`] is a YAML file of questions, each with a reference
+//! answer and the chunks a good retrieval returns. [`evaluate`] asks a
+//! [`Target`] every question and scores the response:
+//!
+//! - **recall@k**: share of the relevant chunks among the top `k`;
+//! - **MRR**: mean of `1 / rank` of the first relevant context;
+//! - **nDCG@k**: discounted gain of the relevant contexts by their grade,
+//!   over that of the ideal order;
+//! - **faithfulness**: the `support` of the answer's
+//!   [`retriever::answer::SupportReport`], the share of its sentences the
+//!   cited contexts back;
+//! - **answer recall**: share of the reference answer's key terms the
+//!   answer states.
+//!
+//! [`baseline::compare`] diffs a [`Report`] against a stored one and lists
+//! the metrics that got worse.
+//!
+//! ```yaml
+//! name: sf0068
+//! k: 5
+//! questions:
+//!   - id: dissolution-events
+//!     q: When is a Wyoming DAO dissolved?
+//!     answer: By member vote, secretary of state order or withdrawal of all members.
+//!     relevant:
+//!       - path: legal/wyoming_sf0068/SF0068_2022_Enrolled_alt.pdf
+//!         section: "17-31-114"
+//!         grade: 2
+//! ```
+
+pub mod baseline;
+pub mod target;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use retriever::bm25::tokens;
+use retriever::{ContextResult, QueryRequest, QueryResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub use target::Target;
+
+pub const DEFAULT_K: usize = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EvalError {
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("{path}: {source}")]
+    Parse {
+        path: String,
+        source: serde_yaml::Error,
+    },
+    #[error("{path}: {message}")]
+    Invalid { path: String, message: String },
+    #[error("question {id}: {message}")]
+    Query { id: String, message: String },
+}
+
+fn default_k() -> usize {
+    DEFAULT_K
+}
+
+fn default_grade() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoldenSet {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Contexts asked for and scored per question.
+    #[serde(default = "default_k")]
+    pub k: usize,
+    /// The retriever's default collection when absent.
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// The retriever's default floor when absent; `null` in the request
+    /// is not expressible here, use `0`.
+    #[serde(default)]
+    pub min_score: Option<f32>,
+    pub questions: Vec<Question>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Question {
+    pub id: String,
+    pub q: String,
+    /// Reference answer; its key terms are looked for in the answer.
+    #[serde(default)]
+    pub answer: Option<String>,
+    pub relevant: Vec<Relevant>,
+}
+
+/// A chunk that answers a question, by where it sits rather than by its
+/// chunk number alone, so a set survives re-chunking. Every field given
+/// must match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relevant {
+    /// Repository-relative path, as ingest records it.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<usize>,
+    /// A PDF statute number such as `17-31-114`, or one heading of an
+    /// HTML section trail such as `6. Vector String`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    /// Text the chunk contains, compared case-insensitively.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+    /// Gain in nDCG; 1 for useful, 2 for the best evidence.
+    #[serde(default = "default_grade")]
+    pub grade: u32,
+}
+
+impl Relevant {
+    pub fn matches(&self, ctx: &ContextResult) -> bool {
+        if ctx.path != self.path {
+            return false;
+        }
+        if self.chunk.is_some_and(|c| c != ctx.chunk) {
+            return false;
+        }
+        if let Some(want) = &self.section {
+            let Some(section) = ctx.metadata.get("section").and_then(Value::as_str) else {
+                return false;
+            };
+            if section != want && !section.split(" > ").any(|s| s == want) {
+                return false;
+            }
+        }
+        if let Some(want) = self.page {
+            let page = ctx.metadata.get("page").and_then(Value::as_u64);
+            if page != Some(u64::from(want)) {
+                return false;
+            }
+        }
+        if let Some(want) = &self.contains {
+            if !ctx.text.to_lowercase().contains(&want.to_lowercase()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn describe(&self) -> String {
+        let mut out = self.path.clone();
+        if let Some(c) = self.chunk {
+            out += &format!(" chunk {c}");
+        }
+        if let Some(s) = &self.section {
+            out += &format!(" section {s:?}");
+        }
+        if let Some(p) = self.page {
+            out += &format!(" page {p}");
+        }
+        if let Some(c) = &self.contains {
+            out += &format!(" containing {c:?}");
+        }
+        out
+    }
+}
+
+impl GoldenSet {
+    pub fn load(path: &Path) -> Result<Self, EvalError> {
+        let display = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|source| EvalError::Io {
+            path: display.clone(),
+            source,
+        })?;
+        let set: Self = serde_yaml::from_str(&text).map_err(|source| EvalError::Parse {
+            path: display.clone(),
+            source,
+        })?;
+        let invalid = |message: String| EvalError::Invalid {
+            path: display.clone(),
+            message,
+        };
+        if set.k == 0 || set.k > retriever::MAX_K {
+            return Err(invalid(format!("k must be 1 to {}", retriever::MAX_K)));
+        }
+        let mut ids = std::collections::HashSet::new();
+        for q in &set.questions {
+            if !ids.insert(q.id.as_str()) {
+                return Err(invalid(format!("question {} appears twice", q.id)));
+            }
+            if q.relevant.is_empty() {
+                return Err(invalid(format!("question {} has no relevant chunks", q.id)));
+            }
+        }
+        Ok(set)
+    }
+}
+
+/// Means over a set's questions. `faithfulness` and `answer_recall` are
+/// over the questions that got an answer, `None` when none did.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    pub recall_at_k: f64,
+    pub mrr: f64,
+    pub ndcg_at_k: f64,
+    pub faithfulness: Option<f64>,
+    pub answer_recall: Option<f64>,
+}
+
+impl Metrics {
+    /// Name and value of each metric, in report order.
+    pub fn named(&self) -> [(&'static str, Option<f64>); 5] {
+        [
+            ("recall@k", Some(self.recall_at_k)),
+            ("mrr", Some(self.mrr)),
+            ("ndcg@k", Some(self.ndcg_at_k)),
+            ("faithfulness", self.faithfulness),
+            ("answer_recall", self.answer_recall),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuestionResult {
+    pub id: String,
+    pub recall: f64,
+    pub reciprocal_rank: f64,
+    pub ndcg: f64,
+    #[serde(default)]
+    pub faithfulness: Option<f64>,
+    #[serde(default)]
+    pub answer_recall: Option<f64>,
+    #[serde(default)]
+    pub abstained: Option<bool>,
+    /// `path#chunk` of each context, best first.
+    pub retrieved: Vec<String>,
+    /// The relevant chunks no context matched.
+    pub missed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetResult {
+    pub k: usize,
+    pub metrics: Metrics,
+    pub questions: Vec<QuestionResult>,
+}
+
+/// Results by set name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub sets: BTreeMap<String, SetResult>,
+}
+
+/// Which relevant item, if any, each context is credited with: the
+/// first one it matches that no better-ranked context took.
+fn credit(relevant: &[Relevant], contexts: &[ContextResult]) -> Vec<Option<usize>> {
+    let mut taken = vec![false; relevant.len()];
+    contexts
+        .iter()
+        .map(|ctx| {
+            let i = (0..relevant.len()).find(|&i| !taken[i] && relevant[i].matches(ctx))?;
+            taken[i] = true;
+            Some(i)
+        })
+        .collect()
+}
+
+pub fn recall(relevant: &[Relevant], contexts: &[ContextResult]) -> f64 {
+    let found = credit(relevant, contexts).iter().flatten().count();
+    found as f64 / relevant.len().max(1) as f64
+}
+
+/// `1 / rank` of the first context matching any relevant item, or 0.
+pub fn reciprocal_rank(relevant: &[Relevant], contexts: &[ContextResult]) -> f64 {
+    contexts
+        .iter()
+        .position(|ctx| relevant.iter().any(|r| r.matches(ctx)))
+        .map_or(0.0, |i| 1.0 / (i + 1) as f64)
+}
+
+/// nDCG at `k` over the contexts given, gains being the grades of the
+/// relevant items credited. The ideal ranking fills all `k` places, so
+/// returning fewer contexts than `k` is no excuse for missing items.
+pub fn ndcg(relevant: &[Relevant], contexts: &[ContextResult], k: usize) -> f64 {
+    let discount = |i: usize| ((i + 2) as f64).log2();
+    let dcg: f64 = credit(relevant, contexts)
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| r.map(|r| f64::from(relevant[r].grade) / discount(i)))
+        .sum();
+    let mut grades: Vec<u32> = relevant.iter().map(|r| r.grade).collect();
+    grades.sort_unstable_by(|a, b| b.cmp(a));
+    let ideal: f64 = grades
+        .iter()
+        .take(k)
+        .enumerate()
+        .map(|(i, &g)| f64::from(g) / discount(i))
+        .sum();
+    if ideal == 0.0 {
+        0.0
+    } else {
+        dcg / ideal
+    }
+}
+
+/// Words of a reference answer worth finding in an answer: numbers,
+/// identifiers and words of four letters or more.
+const COMMON: &[&str] = &[
+    "also", "been", "from", "have", "into", "must", "only", "shall", "such", "than", "that",
+    "their", "then", "there", "these", "this", "when", "where", "which", "will", "with",
+];
+
+fn key_terms(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = tokens(text)
+        .into_iter()
+        .filter(|t| {
+            t.chars().any(|c| c.is_ascii_digit())
+                || (t.chars().count() >= 4 && !COMMON.contains(&t.as_str()))
+        })
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// Share of the reference's key terms in `answer`.
+pub fn answer_recall(reference: &str, answer: &str) -> f64 {
+    let want = key_terms(reference);
+    if want.is_empty() {
+        return 1.0;
+    }
+    let have = tokens(answer);
+    let found = want.iter().filter(|t| have.contains(t)).count();
+    found as f64 / want.len() as f64
+}
+
+/// Scores one response to `question`.
+pub fn score(question: &Question, k: usize, response: &QueryResponse) -> QuestionResult {
+    let contexts = &response.contexts[..response.contexts.len().min(k)];
+    let credited = credit(&question.relevant, contexts);
+    let missed = (0..question.relevant.len())
+        .filter(|i| !credited.contains(&Some(*i)))
+        .map(|i| question.relevant[i].describe())
+        .collect();
+    let abstained = response.support.as_ref().map(|s| s.abstained);
+    let answer_recall = match (&question.answer, &response.answer, abstained) {
+        (Some(_), Some(_), Some(true)) => Some(0.0),
+        (Some(reference), Some(answer), _) => Some(answer_recall(reference, answer)),
+        _ => None,
+    };
+    QuestionResult {
+        id: question.id.clone(),
+        recall: recall(&question.relevant, contexts),
+        reciprocal_rank: reciprocal_rank(&question.relevant, contexts),
+        ndcg: ndcg(&question.relevant, contexts, k),
+        faithfulness: response.support.as_ref().map(|s| f64::from(s.support)),
+        answer_recall,
+        abstained,
+        retrieved: contexts
+            .iter()
+            .map(|c| format!("{}#{}", c.path, c.chunk))
+            .collect(),
+        missed,
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, n) = values.fold((0.0, 0usize), |(s, n), v| (s + v, n + 1));
+    (n > 0).then(|| sum / n as f64)
+}
+
+pub fn summarize(questions: &[QuestionResult]) -> Metrics {
+    Metrics {
+        recall_at_k: mean(questions.iter().map(|q| q.recall)).unwrap_or(0.0),
+        mrr: mean(questions.iter().map(|q| q.reciprocal_rank)).unwrap_or(0.0),
+        ndcg_at_k: mean(questions.iter().map(|q| q.ndcg)).unwrap_or(0.0),
+        faithfulness: mean(questions.iter().filter_map(|q| q.faithfulness)),
+        answer_recall: mean(questions.iter().filter_map(|q| q.answer_recall)),
+    }
+}
+
+/// Asks `target` every question of `set`, with an LLM answer when
+/// `include_llm`.
+pub async fn evaluate(
+    set: &GoldenSet,
+    target: &Target,
+    include_llm: bool,
+) -> Result<SetResult, EvalError> {
+    let mut questions = Vec::with_capacity(set.questions.len());
+    for question in &set.questions {
+        let mut req = QueryRequest::new(&question.q);
+        req.k = set.k;
+        req.collection = set.collection.clone();
+        if set.min_score.is_some() {
+            req.min_score = set.min_score;
+        }
+        req.include_llm = include_llm;
+        let response = target
+            .query(&req)
+            .await
+            .map_err(|message| EvalError::Query {
+                id: question.id.clone(),
+                message,
+            })?;
+        let result = score(question, set.k, &response);
+        tracing::debug!(
+            set = %set.name,
+            question = %question.id,
+            recall = result.recall,
+            reciprocal_rank = result.reciprocal_rank,
+            "scored"
+        );
+        questions.push(result);
+    }
+    Ok(SetResult {
+        k: set.k,
+        metrics: summarize(&questions),
+        questions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map};
+
+    use super::*;
+
+    fn ctx(path: &str, chunk: usize, section: &str) -> ContextResult {
+        let mut metadata = Map::new();
+        metadata.insert("section".into(), json!(section));
+        metadata.insert("page".into(), json!(3));
+        ContextResult {
+            path: path.into(),
+            chunk,
+            score: 1.0,
+            text: format!("{section} text"),
+            metadata,
+        }
+    }
+
+    fn relevant(path: &str, section: &str, grade: u32) -> Relevant {
+        Relevant {
+            path: path.into(),
+            chunk: None,
+            section: Some(section.into()),
+            page: None,
+            contains: None,
+            grade,
+        }
+    }
+
+    #[test]
+    fn ranks_and_grades_relevant_contexts() {
+        let relevant = [
+            relevant("act.pdf", "17-31-114", 2),
+            relevant("cvss.html", "6. Vector String", 1),
+        ];
+        let contexts = [
+            ctx("act.pdf", 0, "17-31-102"),
+            ctx("cvss.html", 4, "CVSS v3.1 > 6. Vector String"),
+            ctx("act.pdf", 7, "17-31-114"),
+            // A second chunk of the same section earns nothing more.
+            ctx("act.pdf", 8, "17-31-114"),
+        ];
+        assert_eq!(recall(&relevant, &contexts[..2]), 0.5);
+        assert_eq!(recall(&relevant, &contexts), 1.0);
+        assert_eq!(reciprocal_rank(&relevant, &contexts), 0.5);
+        assert_eq!(reciprocal_rank(&relevant, &contexts[..1]), 0.0);
+
+        let ideal = 2.0 + 1.0 / 3f64.log2();
+        let got = 1.0 / 3f64.log2() + 2.0 / 4f64.log2();
+        assert!((ndcg(&relevant, &contexts, 5) - got / ideal).abs() < 1e-9);
+        let best = [contexts[2].clone(), contexts[1].clone()];
+        assert!((ndcg(&relevant, &best, 5) - 1.0).abs() < 1e-9);
+        // The grade-1 item missing from a short list still counts.
+        assert!((ndcg(&relevant, &best[..1], 5) - 2.0 / ideal).abs() < 1e-9);
+        assert_eq!(ndcg(&relevant, &best[..1], 1), 1.0);
+
+        let mut by_page = relevant[0].clone();
+        by_page.page = Some(4);
+        assert!(!by_page.matches(&contexts[2]));
+        by_page.page = Some(3);
+        by_page.contains = Some("17-31-114 TEXT".into());
+        assert!(by_page.matches(&contexts[2]));
+    }
+
+    #[test]
+    fn answer_recall_looks_for_numbers_and_content_words() {
+        let reference = "Thirty (30) days, then the secretary of state dissolves it.";
+        assert_eq!(
+            answer_recall(
+                reference,
+                "The filer has thirty (30) days; then the secretary of state dissolves the DAO."
+            ),
+            1.0
+        );
+        assert!(answer_recall(reference, "Sixty days.") < 0.2);
+    }
+}
@@ -0,0 +1,119 @@
+//! `recon-eval`: scores a running `recon-retriever` on golden sets and
+//! fails when it does worse than the stored baseline, or when there is no
+//! baseline to compare with.
+//!
+//! Regressions are not gated yet. The committed
+//! `benchmarks/rag/baseline.json` is a placeholder of zeros, so it only
+//! catches a run that skips a set or changes its `k`, and no CI job runs
+//! this. Recording a baseline with `--update-baseline` needs a retriever
+//! with the corpus ingested; a CI job needs the same.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::Parser;
+use eval::baseline::{self, DEFAULT_TOLERANCE};
+use eval::{evaluate, GoldenSet, Report, Target};
+
+/// Where golden sets live when none are named.
+const SETS_DIR: &str = "benchmarks/rag";
+
+#[derive(Parser)]
+#[command(
+    name = "recon-eval",
+    about = "Score recon retrieval on golden question sets"
+)]
+struct Args {
+    /// Golden set YAML files; every `*.yaml` in `benchmarks/rag` by
+    /// default.
+    sets: Vec<PathBuf>,
+    /// Base URL of `recon-retriever`.
+    #[arg(long, env = "RETRIEVER_URL", default_value = "http://localhost:7000")]
+    url: String,
+    #[arg(long, default_value = "benchmarks/rag/baseline.json")]
+    baseline: PathBuf,
+    /// Replace the baseline with this run instead of comparing.
+    #[arg(long)]
+    update_baseline: bool,
+    /// Drop in a metric tolerated before it counts as a regression.
+    #[arg(long, default_value_t = DEFAULT_TOLERANCE)]
+    tolerance: f64,
+    /// Also write this run's full report here.
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// Skip answer generation; faithfulness is then not measured.
+    #[arg(long)]
+    no_llm: bool,
+}
+
+fn default_sets(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut sets: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("reading {}", dir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "yaml" || e == "yml"))
+        .collect();
+    sets.sort();
+    Ok(sets)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+    let paths = if args.sets.is_empty() {
+        default_sets(Path::new(SETS_DIR))?
+    } else {
+        args.sets.clone()
+    };
+    anyhow::ensure!(!paths.is_empty(), "no golden sets found");
+
+    let target = Target::url(&args.url);
+    let mut report = Report::default();
+    for path in &paths {
+        let set = GoldenSet::load(path)?;
+        let result = evaluate(&set, &target, !args.no_llm).await?;
+        let m = result.metrics;
+        println!(
+            "{}: {} questions, recall@{k} {:.3}, mrr {:.3}, ndcg@{k} {:.3}, faithfulness {}",
+            set.name,
+            result.questions.len(),
+            m.recall_at_k,
+            m.mrr,
+            m.ndcg_at_k,
+            m.faithfulness
+                .map_or_else(|| "n/a".to_string(), |f| format!("{f:.3}")),
+            k = result.k,
+        );
+        report.sets.insert(set.name, result);
+    }
+    if let Some(path) = &args.report {
+        baseline::save(path, &report)?;
+    }
+
+    if args.update_baseline {
+        baseline::save(&args.baseline, &report)?;
+        println!("baseline written to {}", args.baseline.display());
+        return Ok(());
+    }
+    anyhow::ensure!(
+        args.baseline.exists(),
+        "no baseline at {}; run with --update-baseline to record one",
+        args.baseline.display()
+    );
+    let diff = baseline::compare(&baseline::load(&args.baseline)?, &report, args.tolerance);
+    for change in &diff.improvements {
+        println!("improved  {change}");
+    }
+    for change in &diff.regressions {
+        println!("regressed {change}");
+    }
+    anyhow::ensure!(
+        diff.regressions.is_empty(),
+        "{} regression(s) against {}",
+        diff.regressions.len(),
+        args.baseline.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Where golden questions are sent: a running retriever, or its router
+//! in process.
+
+use axum::body::Body;
+use axum::http::{header, Request};
+use axum::Router;
+use retriever::{QueryRequest, QueryResponse};
+use tower::ServiceExt;
+
+enum Kind {
+    Url { http: reqwest::Client, base: String },
+    Router(Router),
+}
+
+/// A `POST /query` endpoint.
+pub struct Target(Kind);
+
+impl Target {
+    /// A retriever listening at `base`, e.g. `http://localhost:7000`.
+    pub fn url(base: impl Into<String>) -> Self {
+        Self(Kind::Url {
+            http: reqwest::Client::new(),
+            base: base.into().trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// A router such as [`retriever::api::Api::router`], called without
+    /// a socket.
+    pub fn router(router: Router) -> Self {
+        Self(Kind::Router(router))
+    }
+
+    /// The response, or why there is none.
+    pub async fn query(&self, req: &QueryRequest) -> Result<QueryResponse, String> {
+        let (status, body) = match &self.0 {
+            Kind::Url { http, base } => {
+                let res = http
+                    .post(format!("{base}/query"))
+                    .json(req)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let status = res.status();
+                (status, res.bytes().await.map_err(|e| e.to_string())?)
+            }
+            Kind::Router(router) => {
+                let body = serde_json::to_vec(req).map_err(|e| e.to_string())?;
+                let request = Request::post("/query")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body))
+                    .map_err(|e| e.to_string())?;
+                let res = router
+                    .clone()
+                    .oneshot(request)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let status = res.status();
+                let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                (status, body)
+            }
+        };
+        if !status.is_success() {
+            let body = String::from_utf8_lossy(&body);
+            return Err(format!("HTTP {}: {}", status.as_u16(), body.trim()));
+        }
+        serde_json::from_slice(&body).map_err(|e| format!("malformed response: {e}"))
+    }
+}
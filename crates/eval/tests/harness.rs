@@ -0,0 +1,157 @@
+//! A golden set run against the retriever's router in process, and the
+//! baseline diff of its report.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use embed::{EmbedError, Embedder};
+use eval::baseline::{compare, DEFAULT_TOLERANCE};
+use eval::{evaluate, GoldenSet, Report, Target};
+use retriever::api::Api;
+use retriever::{Collection, Retriever};
+use vectors::{Config, Metadata, VectorIndex};
+
+/// Embeds a text as its vowel counts.
+struct Vowels;
+
+fn vowels(text: &str) -> Vec<f32> {
+    "aeiou"
+        .chars()
+        .map(|c| text.matches(c).count() as f32 + 1.0)
+        .collect()
+}
+
+#[async_trait]
+impl Embedder for Vowels {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        Ok(texts.iter().map(|t| vowels(t)).collect())
+    }
+}
+
+const ACT: &str = "legal/wyoming_sf0068/SF0068_2022_Enrolled_alt.pdf";
+
+const CHUNKS: &[(usize, &str, &str)] = &[
+    (
+        0,
+        "17-31-105",
+        "17-31-105. Formation. The person filing shall have thirty (30) days to provide the publicly available identifier to the secretary of state.",
+    ),
+    (
+        1,
+        "17-31-114",
+        "17-31-114. Dissolution. A decentralized autonomous organization shall be dissolved by vote of the majority of the members.",
+    ),
+    (
+        2,
+        "17-31-112",
+        "17-31-112. Members have no right to inspect records available on an open blockchain.",
+    ),
+];
+
+fn router(llm: Arc<dyn llm::Provider>) -> axum::Router {
+    let mut index = VectorIndex::new(Config::new(5));
+    for (chunk, section, text) in CHUNKS {
+        let metadata = Metadata::from([
+            ("path".to_string(), ACT.to_string()),
+            ("chunk".to_string(), chunk.to_string()),
+            ("section".to_string(), section.to_string()),
+            ("page".to_string(), "3".to_string()),
+            ("text".to_string(), text.to_string()),
+        ]);
+        index
+            .upsert(format!("{ACT}#{chunk}"), &vowels(text), metadata)
+            .unwrap();
+    }
+    let retriever = Retriever::new(Arc::new(Vowels))
+        .with_collection("sovereignty-arch", Collection::new(index));
+    Api::new(Arc::new(retriever)).with_llm(llm).router()
+}
+
+const SET: &str = r#"
+name: act
+k: 2
+min_score: 0
+questions:
+  - id: identifier-deadline
+    q: "publicly available identifier secretary of state"
+    answer: "Thirty (30) days."
+    relevant:
+      - path: legal/wyoming_sf0068/SF0068_2022_Enrolled_alt.pdf
+        section: "17-31-105"
+        grade: 2
+  - id: dissolution
+    q: "dissolved majority vote"
+    relevant:
+      - path: legal/wyoming_sf0068/SF0068_2022_Enrolled_alt.pdf
+        section: "17-31-114"
+      - path: legal/wyoming_sf0068/SF0068_2022_Enrolled_alt.pdf
+        section: "17-31-199"
+"#;
+
+#[tokio::test]
+async fn scores_a_golden_set_and_flags_regressions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("act.yaml");
+    std::fs::write(&path, SET).unwrap();
+    let set = GoldenSet::load(&path).unwrap();
+
+    let llm = llm::replay::Replay::new()
+        .respond(
+            "Question: publicly available",
+            "The filer has thirty (30) days to provide the publicly available identifier [1].",
+        )
+        .respond("Question: dissolved", "Dissolution takes 90 days [1].");
+    let target = Target::router(router(Arc::new(llm)));
+    let result = evaluate(&set, &target, true).await.unwrap();
+
+    let [deadline, dissolution] = &result.questions[..] else {
+        panic!("{result:?}");
+    };
+    assert_eq!(deadline.recall, 1.0);
+    assert_eq!(deadline.reciprocal_rank, 1.0);
+    assert_eq!(deadline.ndcg, 1.0);
+    assert_eq!(deadline.faithfulness, Some(1.0));
+    assert_eq!(deadline.answer_recall, Some(1.0));
+    assert_eq!(deadline.retrieved[0], format!("{ACT}#0"));
+
+    // One of two relevant chunks exists; the invented figure abstains.
+    assert_eq!(dissolution.recall, 0.5);
+    assert_eq!(dissolution.missed.len(), 1);
+    assert!(dissolution.missed[0].contains("17-31-199"));
+    assert_eq!(dissolution.abstained, Some(true));
+    assert_eq!(dissolution.faithfulness, Some(0.0));
+    assert_eq!(result.metrics.recall_at_k, 0.75);
+    assert_eq!(result.metrics.faithfulness, Some(0.5));
+    assert_eq!(result.metrics.answer_recall, Some(1.0));
+
+    let mut baseline = Report::default();
+    baseline.sets.insert("act".into(), result.clone());
+    let mut current = baseline.clone();
+    assert!(compare(&baseline, &current, DEFAULT_TOLERANCE)
+        .regressions
+        .is_empty());
+
+    current.sets.get_mut("act").unwrap().metrics.mrr -= 0.1;
+    current.sets.get_mut("act").unwrap().metrics.faithfulness = None;
+    let diff = compare(&baseline, &current, DEFAULT_TOLERANCE);
+    assert_eq!(diff.regressions.len(), 1);
+    assert_eq!(diff.regressions[0].metric, "mrr");
+
+    // Scores at another k are not compared, even better ones.
+    let mut wider = baseline.clone();
+    let set = wider.sets.get_mut("act").unwrap();
+    set.k = 10;
+    set.metrics.mrr += 0.5;
+    let diff = compare(&baseline, &wider, DEFAULT_TOLERANCE);
+    assert_eq!(diff.regressions.len(), 1);
+    assert_eq!(diff.regressions[0].metric, "k");
+    assert!(diff.improvements.is_empty());
+    assert_eq!(
+        diff.regressions[0].to_string(),
+        "act: scored at k=10, baseline at k=2"
+    );
+
+    current.sets.clear();
+    let diff = compare(&baseline, &current, DEFAULT_TOLERANCE);
+    assert_eq!(diff.regressions[0].metric, "set");
+}
@@ -0,0 +1,54 @@
+//! The seed golden sets point at text that exists in this repository.
+
+use std::path::Path;
+
+use eval::GoldenSet;
+use retriever::ContextResult;
+use serde_json::{json, Map};
+
+#[test]
+fn every_relevant_chunk_of_the_seed_sets_exists() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(root.join("benchmarks/rag")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "yaml") {
+            continue;
+        }
+        let set = GoldenSet::load(&path).unwrap();
+        for question in &set.questions {
+            for relevant in &question.relevant {
+                let file = root.join(&relevant.path);
+                let bytes = std::fs::read(&file).unwrap();
+                let format = extract::Format::of(&file, &bytes).unwrap();
+                let doc = extract::extract(format, &bytes).unwrap();
+                // Each block as a chunk carrying the metadata ingest gives it.
+                let found = doc.blocks.iter().any(|b| {
+                    let mut metadata = Map::new();
+                    if let Some(s) = &b.section {
+                        metadata.insert("section".into(), json!(s));
+                    }
+                    if let Some(p) = b.page {
+                        metadata.insert("page".into(), json!(p));
+                    }
+                    relevant.matches(&ContextResult {
+                        path: relevant.path.clone(),
+                        chunk: 0,
+                        score: 0.0,
+                        text: b.text.clone(),
+                        metadata,
+                    })
+                });
+                assert!(found, "{}: {}: {relevant:?}", set.name, question.id);
+            }
+        }
+        names.push(set.name);
+    }
+    names.sort();
+    assert_eq!(names, ["cyber_v2", "sf0068"]);
+
+    // The shipped baseline covers every set, so a set that stops being
+    // scored is a regression.
+    let baseline = eval::baseline::load(&root.join("benchmarks/rag/baseline.json")).unwrap();
+    assert!(baseline.sets.keys().eq(names.iter()));
+}
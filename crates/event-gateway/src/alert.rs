@@ -0,0 +1,544 @@
+//! Alertmanager webhook receiver (`infra.metrics.alertmanager_webhook`).
+//!
+//! Each notification is split by the channel its alerts' `severity` maps to
+//! (`severity_channels` on the endpoint), rendered as one embed per channel
+//! listing what fired, resolved or started flapping, and posted as a reply
+//! in a thread per alert group, so one incident reads top to bottom.
+//!
+//! Alertmanager resends whole groups — on every `group_interval` while
+//! anything changes, and again when a webhook call fails — so alerts whose
+//! status has not changed since the last post are left out, and a
+//! notification with nothing new is not posted at all. An alert that
+//! changes state [`FLAP_THRESHOLD`] times within [`FLAP_WINDOW`] is
+//! reported once as flapping and muted until it settles.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::notify::{Embed, DEFAULT_COLOR};
+
+/// Window over which state changes are counted for flap detection.
+pub const FLAP_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// State changes within [`FLAP_WINDOW`] that make an alert flapping.
+pub const FLAP_THRESHOLD: usize = 4;
+/// How long a resolved alert is remembered to drop resends.
+const RESOLVED_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Discord caps embed descriptions at 4096 characters.
+const MAX_DESCRIPTION: usize = 4000;
+
+pub const RESOLVED_COLOR: u32 = 0x2E_CC_71;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+/// Alertmanager's webhook body (`"version": "4"`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    #[serde(default)]
+    pub version: String,
+    pub group_key: String,
+    #[serde(default)]
+    pub truncated_alerts: u64,
+    pub status: AlertStatus,
+    #[serde(default)]
+    pub receiver: String,
+    #[serde(default)]
+    pub group_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub common_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub common_annotations: BTreeMap<String, String>,
+    #[serde(default, rename = "externalURL")]
+    pub external_url: String,
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub status: AlertStatus,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+    #[serde(default, rename = "generatorURL")]
+    pub generator_url: String,
+    /// Stable id of the label set; derived from the labels when absent.
+    #[serde(default)]
+    pub fingerprint: String,
+}
+
+impl Alert {
+    fn fingerprint(&self) -> String {
+        if self.fingerprint.is_empty() {
+            format!("{:?}", self.labels)
+        } else {
+            self.fingerprint.clone()
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.labels.get("alertname").map_or("alert", String::as_str)
+    }
+
+    /// `summary` annotation, else the alert name, plus the instance if any.
+    fn line(&self) -> String {
+        let text = self
+            .annotations
+            .get("summary")
+            .or_else(|| self.annotations.get("description"))
+            .map_or(self.name(), String::as_str);
+        match self.labels.get("instance") {
+            Some(instance) => format!("• {text} — `{instance}`"),
+            None => format!("• {text}"),
+        }
+    }
+}
+
+/// Severity label, ordered from least to most urgent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Other,
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn parse(label: &str) -> Self {
+        match label.to_ascii_lowercase().as_str() {
+            "critical" | "page" => Severity::Critical,
+            "warning" | "warn" => Severity::Warning,
+            "info" | "none" => Severity::Info,
+            _ => Severity::Other,
+        }
+    }
+
+    pub fn color(self) -> u32 {
+        match self {
+            Severity::Critical => 0xE7_4C_3C,
+            Severity::Warning => 0xE6_7E_22,
+            Severity::Info => 0x34_98_DB,
+            Severity::Other => DEFAULT_COLOR,
+        }
+    }
+}
+
+/// What became of one alert in a notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Fired,
+    Resolved,
+    /// Crossed [`FLAP_THRESHOLD`]; muted from here on.
+    Flapping,
+}
+
+struct Seen {
+    status: AlertStatus,
+    changes: VecDeque<Instant>,
+    flapping: bool,
+    /// Last change reported for this alert.
+    reported: Option<Change>,
+    /// Set by [`Receiver::forget`]: posting `reported` failed, so the next
+    /// webhook in the same state repeats it.
+    undelivered: bool,
+}
+
+/// An embed ready to post, threaded under `thread`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub channel: String,
+    pub thread: String,
+    pub embed: Embed,
+}
+
+#[derive(Default)]
+struct State {
+    alerts: HashMap<String, Seen>,
+    /// Group key → thread generation, bumped when a group fires again
+    /// after fully resolving so each incident gets its own thread.
+    groups: HashMap<String, (u64, bool)>,
+}
+
+/// Turns webhooks into [`Notification`]s, remembering alert states between
+/// calls.
+pub struct Receiver {
+    default_channel: String,
+    severity_channels: BTreeMap<String, String>,
+    state: Mutex<State>,
+}
+
+impl Receiver {
+    pub fn new(default_channel: impl Into<String>) -> Self {
+        Self {
+            default_channel: default_channel.into(),
+            severity_channels: BTreeMap::new(),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Severity label → channel; see `severity_channels` in `discovery.yml`.
+    pub fn with_severity_channels(mut self, map: BTreeMap<String, String>) -> Self {
+        self.severity_channels = map;
+        self
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("alert state poisoned")
+    }
+
+    pub fn receive(&self, hook: &Webhook) -> Vec<Notification> {
+        self.receive_at(hook, Instant::now())
+    }
+
+    pub fn receive_at(&self, hook: &Webhook, now: Instant) -> Vec<Notification> {
+        let mut st = self.state();
+        st.alerts.retain(|_, seen| {
+            seen.status == AlertStatus::Firing
+                || seen
+                    .changes
+                    .back()
+                    .is_some_and(|at| now.duration_since(*at) < RESOLVED_RETENTION)
+        });
+        let generation = {
+            let (generation, resolved) = st.groups.entry(hook.group_key.clone()).or_default();
+            if *resolved && hook.status == AlertStatus::Firing {
+                *generation += 1;
+            }
+            *resolved = hook.status == AlertStatus::Resolved;
+            *generation
+        };
+
+        let mut by_channel: BTreeMap<&str, Vec<(Change, &Alert)>> = BTreeMap::new();
+        for alert in &hook.alerts {
+            let Some(change) = observe(&mut st.alerts, alert, now) else {
+                continue;
+            };
+            by_channel
+                .entry(self.channel(hook, alert))
+                .or_default()
+                .push((change, alert));
+        }
+        by_channel
+            .into_iter()
+            .map(|(channel, changes)| Notification {
+                channel: channel.to_string(),
+                thread: format!("alert/{}/{generation}", hook.group_key),
+                embed: render(hook, &changes),
+            })
+            .collect()
+    }
+
+    /// Marks the alerts of `hook` that go to `channel` as undelivered, so a
+    /// redelivery after a failed post there reports them again while
+    /// channels that were posted to stay deduplicated. Flap history is kept.
+    pub fn forget(&self, hook: &Webhook, channel: &str) {
+        let mut st = self.state();
+        for alert in &hook.alerts {
+            if self.channel(hook, alert) == channel {
+                if let Some(seen) = st.alerts.get_mut(&alert.fingerprint()) {
+                    seen.undelivered = true;
+                }
+            }
+        }
+    }
+
+    fn channel(&self, hook: &Webhook, alert: &Alert) -> &str {
+        severity_label(hook, alert)
+            .and_then(|s| self.severity_channels.get(s))
+            .unwrap_or(&self.default_channel)
+    }
+}
+
+fn severity_label<'a>(hook: &'a Webhook, alert: &'a Alert) -> Option<&'a str> {
+    alert
+        .labels
+        .get("severity")
+        .or_else(|| hook.common_labels.get("severity"))
+        .map(String::as_str)
+}
+
+/// Records `alert`'s status; `None` if there is nothing to report.
+fn observe(alerts: &mut HashMap<String, Seen>, alert: &Alert, now: Instant) -> Option<Change> {
+    let seen = alerts.entry(alert.fingerprint()).or_insert_with(|| Seen {
+        status: AlertStatus::Resolved,
+        changes: VecDeque::new(),
+        flapping: false,
+        reported: None,
+        undelivered: false,
+    });
+    if std::mem::take(&mut seen.undelivered) && seen.status == alert.status {
+        return seen.reported;
+    }
+    let first = seen.changes.is_empty() && !seen.flapping;
+    if !first && seen.status == alert.status {
+        // A resend; worth posting only as the all-clear for a flapping
+        // alert that has held this state for a whole window.
+        let settled = seen.flapping
+            && seen
+                .changes
+                .back()
+                .is_some_and(|at| now.duration_since(*at) >= FLAP_WINDOW);
+        if !settled {
+            return None;
+        }
+        seen.changes.clear();
+    }
+    seen.status = alert.status;
+    while seen
+        .changes
+        .front()
+        .is_some_and(|at| now.duration_since(*at) >= FLAP_WINDOW)
+    {
+        seen.changes.pop_front();
+    }
+    seen.changes.push_back(now);
+    let change = if seen.changes.len() >= FLAP_THRESHOLD {
+        if seen.flapping {
+            return None;
+        }
+        seen.flapping = true;
+        Change::Flapping
+    } else {
+        seen.flapping = false;
+        match alert.status {
+            AlertStatus::Firing => Change::Fired,
+            AlertStatus::Resolved => Change::Resolved,
+        }
+    };
+    seen.reported = Some(change);
+    Some(change)
+}
+
+fn render(hook: &Webhook, changes: &[(Change, &Alert)]) -> Embed {
+    let of = |kind: Change| -> Vec<&Alert> {
+        changes
+            .iter()
+            .filter(|(c, _)| *c == kind)
+            .map(|(_, a)| *a)
+            .collect()
+    };
+    let (fired, resolved, flapping) = (
+        of(Change::Fired),
+        of(Change::Resolved),
+        of(Change::Flapping),
+    );
+
+    let mut counts = Vec::new();
+    if !fired.is_empty() {
+        counts.push(format!("FIRING:{}", fired.len()));
+    }
+    if !resolved.is_empty() {
+        counts.push(format!("RESOLVED:{}", resolved.len()));
+    }
+    if !flapping.is_empty() {
+        counts.push(format!("FLAPPING:{}", flapping.len()));
+    }
+    let name = hook
+        .group_labels
+        .get("alertname")
+        .or_else(|| hook.common_labels.get("alertname"))
+        .map_or_else(|| changes[0].1.name().to_string(), Clone::clone);
+    let title = format!("[{}] {name}", counts.join(", "));
+
+    let mut sections = Vec::new();
+    for (heading, alerts) in [
+        ("**Firing**", &fired),
+        ("**Resolved**", &resolved),
+        ("**Flapping** (muted until it settles)", &flapping),
+    ] {
+        if !alerts.is_empty() {
+            let lines: Vec<String> = alerts.iter().map(|a| a.line()).collect();
+            sections.push(format!("{heading}\n{}", lines.join("\n")));
+        }
+    }
+    if hook.truncated_alerts > 0 {
+        sections.push(format!(
+            "…and {} more alerts not sent by Alertmanager",
+            hook.truncated_alerts
+        ));
+    }
+    let mut description = sections.join("\n\n");
+    if description.len() > MAX_DESCRIPTION {
+        description.truncate(description.floor_char_boundary(MAX_DESCRIPTION));
+        description.push('…');
+    }
+
+    let active = fired.iter().chain(&flapping);
+    let color = match active
+        .map(|a| severity_label(hook, a).map_or(Severity::Other, Severity::parse))
+        .max()
+    {
+        Some(severity) => severity.color(),
+        None => RESOLVED_COLOR,
+    };
+    let url = [hook.external_url.as_str()]
+        .into_iter()
+        .chain(changes.iter().map(|(_, a)| a.generator_url.as_str()))
+        .find(|u| !u.is_empty())
+        .map(String::from);
+    Embed {
+        title,
+        description,
+        color,
+        url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn hook(status: &str, alerts: &[(&str, &str, &str)]) -> Webhook {
+        let alerts: Vec<_> = alerts
+            .iter()
+            .map(|(fp, status, severity)| {
+                json!({
+                    "status": status,
+                    "labels": { "alertname": "HighErrorRate", "severity": severity, "instance": "gw:8080" },
+                    "annotations": { "summary": "5xx above 5%" },
+                    "fingerprint": fp,
+                })
+            })
+            .collect();
+        serde_json::from_value(json!({
+            "version": "4",
+            "groupKey": "{}:{alertname=\"HighErrorRate\"}",
+            "status": status,
+            "receiver": "discord",
+            "groupLabels": { "alertname": "HighErrorRate" },
+            "externalURL": "http://alertmanager:9093",
+            "alerts": alerts,
+        }))
+        .unwrap()
+    }
+
+    fn receiver() -> Receiver {
+        Receiver::new("#alerts").with_severity_channels(BTreeMap::from([(
+            "info".to_string(),
+            "#cluster-status".to_string(),
+        )]))
+    }
+
+    #[test]
+    fn groups_by_severity_channel_and_colors_by_worst() {
+        let r = receiver();
+        let out = r.receive(&hook(
+            "firing",
+            &[
+                ("a", "firing", "warning"),
+                ("b", "firing", "critical"),
+                ("c", "firing", "info"),
+            ],
+        ));
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].channel, "#alerts");
+        assert_eq!(out[0].embed.title, "[FIRING:2] HighErrorRate");
+        assert_eq!(out[0].embed.color, Severity::Critical.color());
+        assert!(out[0]
+            .embed
+            .description
+            .contains("5xx above 5% — `gw:8080`"));
+        assert_eq!(out[1].channel, "#cluster-status");
+
+        let out = r.receive(&hook(
+            "resolved",
+            &[("a", "resolved", "warning"), ("b", "resolved", "critical")],
+        ));
+        assert_eq!(out[0].embed.title, "[RESOLVED:2] HighErrorRate");
+        assert_eq!(out[0].embed.color, RESOLVED_COLOR);
+    }
+
+    #[test]
+    fn drops_resends_and_threads_by_incident() {
+        let r = receiver();
+        let first = r.receive(&hook("firing", &[("a", "firing", "warning")]));
+        assert!(r
+            .receive(&hook("firing", &[("a", "firing", "warning")]))
+            .is_empty());
+        let resolved = r.receive(&hook("resolved", &[("a", "resolved", "warning")]));
+        assert_eq!(first[0].thread, resolved[0].thread);
+
+        let later = Instant::now() + FLAP_WINDOW;
+        let again = r.receive_at(&hook("firing", &[("a", "firing", "warning")]), later);
+        assert_ne!(again[0].thread, first[0].thread);
+    }
+
+    #[test]
+    fn forgets_only_the_failed_channel() {
+        let r = receiver();
+        let hook = hook(
+            "firing",
+            &[("a", "firing", "critical"), ("b", "firing", "info")],
+        );
+        let first = r.receive(&hook);
+        assert_eq!(first.len(), 2);
+        r.forget(&hook, &first[1].channel);
+        let retry = r.receive(&hook);
+        assert_eq!(retry.len(), 1);
+        assert_eq!(retry[0].channel, first[1].channel);
+        assert!(r.receive(&hook).is_empty());
+    }
+
+    #[test]
+    fn failed_posts_keep_flap_history() {
+        let r = receiver();
+        let t0 = Instant::now();
+        let at = |m: u64| t0 + Duration::from_secs(60 * m);
+        let mut titles = Vec::new();
+        for (m, status) in ["firing", "resolved", "firing", "resolved"]
+            .iter()
+            .enumerate()
+        {
+            let hook = hook(status, &[("a", status, "warning")]);
+            let out = r.receive_at(&hook, at(m as u64));
+            // Every post fails once and is redelivered.
+            r.forget(&hook, &out[0].channel);
+            let retry = r.receive_at(&hook, at(m as u64));
+            assert_eq!(retry, out);
+            titles.extend(retry.into_iter().map(|n| n.embed.title));
+        }
+        assert_eq!(titles[3], "[FLAPPING:1] HighErrorRate");
+    }
+
+    #[test]
+    fn flapping_alerts_are_reported_once_then_muted() {
+        let r = receiver();
+        let t0 = Instant::now();
+        let at = |m: u64| t0 + Duration::from_secs(60 * m);
+        let mut titles = Vec::new();
+        for (m, status) in ["firing", "resolved", "firing", "resolved", "firing"]
+            .iter()
+            .enumerate()
+        {
+            let out = r.receive_at(&hook(status, &[("a", status, "warning")]), at(m as u64));
+            titles.extend(out.into_iter().map(|n| n.embed.title));
+        }
+        assert_eq!(
+            titles,
+            [
+                "[FIRING:1] HighErrorRate",
+                "[RESOLVED:1] HighErrorRate",
+                "[FIRING:1] HighErrorRate",
+                "[FLAPPING:1] HighErrorRate",
+            ]
+        );
+
+        // Still firing a window later: it has settled, so say so once.
+        let out = r.receive_at(&hook("firing", &[("a", "firing", "warning")]), at(20));
+        assert_eq!(out[0].embed.title, "[FIRING:1] HighErrorRate");
+        let out = r.receive_at(&hook("firing", &[("a", "firing", "warning")]), at(21));
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+//! Fan-out of accepted service events onto `infra.message_bus`.
+//!
+//! Each event is appended to the Redis stream `{topic_prefix}events.{type}`
+//! (`ops.events.deploy`) with the fields `service`, `type`, `version` and
+//! `event` (the body as JSON), so consumers subscribe per event type.
+//! Events sent with an idempotency key also carry it as `key`
+//! (`{service}:{X-Idempotency-Key}`); a producer that retries after the
+//! bus failed can publish an event twice, and consumers drop repeats by
+//! it.
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::streams::{StreamAddOptions, StreamTrimStrategy, StreamTrimmingMode};
+use redis::AsyncCommands;
+use serde_json::Value;
+
+/// Approximate length each event stream is trimmed to.
+pub const STREAM_MAXLEN: usize = 10_000;
+
+/// One event as published.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Published {
+    pub service: String,
+    pub kind: String,
+    pub version: u32,
+    /// `{service}:{X-Idempotency-Key}`, when the producer sent one.
+    pub key: Option<String>,
+    pub body: Value,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BusError {
+    #[error("redis: {0}")]
+    Redis(#[from] redis::RedisError),
+}
+
+#[async_trait]
+pub trait Bus: Send + Sync {
+    async fn publish(&self, event: &Published) -> Result<(), BusError>;
+}
+
+#[derive(Clone)]
+pub struct RedisBus {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisBus {
+    /// `prefix` is `infra.message_bus.topic_prefix`.
+    pub async fn connect(url: &str, prefix: &str) -> Result<Self, BusError> {
+        let conn = ConnectionManager::new(redis::Client::open(url)?).await?;
+        Ok(Self {
+            conn,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    pub fn stream(&self, kind: &str) -> String {
+        format!("{}events.{kind}", self.prefix)
+    }
+}
+
+#[async_trait]
+impl Bus for RedisBus {
+    async fn publish(&self, event: &Published) -> Result<(), BusError> {
+        let opts = StreamAddOptions::default().trim(StreamTrimStrategy::maxlen(
+            StreamTrimmingMode::Approx,
+            STREAM_MAXLEN,
+        ));
+        let version = event.version.to_string();
+        let body = event.body.to_string();
+        let mut fields = vec![
+            ("service", event.service.as_str()),
+            ("type", event.kind.as_str()),
+            ("version", version.as_str()),
+            ("event", body.as_str()),
+        ];
+        if let Some(key) = &event.key {
+            fields.push(("key", key.as_str()));
+        }
+        let _: Option<String> = self
+            .conn
+            .clone()
+            .xadd_options(self.stream(&event.kind), "*", &fields, &opts)
+            .await?;
+        Ok(())
+    }
+}
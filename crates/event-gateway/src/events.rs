@@ -0,0 +1,448 @@
+//! Schema registry for service events (`event_gateway.schema_registry`).
+//!
+//! Each registry entry covers one event `type` at one `version` for a set of
+//! producer globs, with the JSON Schema the whole event body must satisfy
+//! and the template its Discord embed is rendered from:
+//!
+//! ```yaml
+//! events:
+//!   - type: deploy
+//!     version: 1
+//!     producers: ["valoryield", "quantum-symbolic"]
+//!     schema: { type: object, required: [env], ... }
+//!     template:
+//!       title: "{{service}} deployed {{version_tag}} to {{env}}"
+//!       description: "{{summary}}"
+//! ```
+//!
+//! Events name their `type` and `version` (default 1) in the body.
+//! Templates interpolate `{{service}}` and dotted paths into the event
+//! (`{{data.env}}`); missing values render empty.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use axum::http::StatusCode;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::notify::{Embed, DEFAULT_COLOR};
+
+/// Discord's embed title limit.
+const MAX_TITLE: usize = 256;
+/// Discord allows 4096; leave room for the ellipsis.
+const MAX_DESCRIPTION: usize = 4000;
+/// Schema violations reported back to the producer.
+const MAX_ERRORS: usize = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    #[error("reading {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("parsing registry: {0}")]
+    Parse(#[from] serde_yaml::Error),
+    #[error("{kind} v{version}: invalid schema: {message}")]
+    Schema {
+        kind: String,
+        version: u32,
+        message: String,
+    },
+    #[error("{kind} v{version}: invalid producer glob {glob:?}: {source}")]
+    Glob {
+        kind: String,
+        version: u32,
+        glob: String,
+        #[source]
+        source: globset::Error,
+    },
+    #[error("{kind} v{version}: {message}")]
+    Template {
+        kind: String,
+        version: u32,
+        message: String,
+    },
+    #[error("{kind} v{version} is registered twice for {producers:?}")]
+    Duplicate {
+        kind: String,
+        version: u32,
+        producers: Vec<String>,
+    },
+}
+
+/// Why an event was refused; the message is returned to the producer.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Rejection {
+    #[error("event has no string `type`")]
+    MissingType,
+    #[error("`version` must be a positive integer")]
+    BadVersion,
+    #[error("unknown event type {0:?}")]
+    UnknownType(String),
+    #[error("{service} is not a registered producer of {kind:?}")]
+    UnknownProducer { service: String, kind: String },
+    #[error("{kind} v{version} is not registered (have {supported:?})")]
+    UnsupportedVersion {
+        kind: String,
+        version: u32,
+        supported: Vec<u32>,
+    },
+    #[error("{kind} v{version} failed validation: {}", .errors.join("; "))]
+    Invalid {
+        kind: String,
+        version: u32,
+        errors: Vec<String>,
+    },
+}
+
+impl Rejection {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Rejection::UnknownProducer { .. } => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+/// An event that passed its schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accepted {
+    pub kind: String,
+    pub version: u32,
+    pub embed: Embed,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    events: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default = "first_version")]
+    version: u32,
+    producers: Vec<String>,
+    schema: Value,
+    template: TemplateSpec,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateSpec {
+    title: String,
+    #[serde(default)]
+    description: String,
+    color: Option<u32>,
+}
+
+struct Schema {
+    version: u32,
+    producers: GlobSet,
+    validator: jsonschema::Validator,
+    title: Template,
+    description: Template,
+    color: u32,
+}
+
+pub struct Registry {
+    types: BTreeMap<String, Vec<Schema>>,
+}
+
+impl Registry {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| RegistryError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_yaml(&text)
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, RegistryError> {
+        let file: File = serde_yaml::from_str(text)?;
+        let mut types: BTreeMap<String, Vec<Schema>> = BTreeMap::new();
+        let mut seen = BTreeSet::new();
+        for entry in file.events {
+            let (kind, version) = (entry.kind, entry.version);
+            let mut producers = entry.producers;
+            producers.sort();
+            if !seen.insert((kind.clone(), version, producers.clone())) {
+                return Err(RegistryError::Duplicate {
+                    kind,
+                    version,
+                    producers,
+                });
+            }
+            let mut globs = GlobSetBuilder::new();
+            for glob in &producers {
+                let g = Glob::new(glob).map_err(|source| RegistryError::Glob {
+                    kind: kind.clone(),
+                    version,
+                    glob: glob.clone(),
+                    source,
+                })?;
+                globs.add(g);
+            }
+            let glob_err = |source| RegistryError::Glob {
+                kind: kind.clone(),
+                version,
+                glob: producers.join(","),
+                source,
+            };
+            let producers = globs.build().map_err(glob_err)?;
+            let validator =
+                jsonschema::validator_for(&entry.schema).map_err(|e| RegistryError::Schema {
+                    kind: kind.clone(),
+                    version,
+                    message: e.to_string(),
+                })?;
+            let template = |text: &str| {
+                Template::parse(text).map_err(|message| RegistryError::Template {
+                    kind: kind.clone(),
+                    version,
+                    message,
+                })
+            };
+            let schema = Schema {
+                version,
+                producers,
+                validator,
+                title: template(&entry.template.title)?,
+                description: template(&entry.template.description)?,
+                color: entry.template.color.unwrap_or(DEFAULT_COLOR),
+            };
+            types.entry(kind).or_default().push(schema);
+        }
+        Ok(Self { types })
+    }
+
+    /// Validates `event` from `service` and renders its embed.
+    pub fn check(&self, service: &str, event: &Value) -> Result<Accepted, Rejection> {
+        let kind = event["type"].as_str().ok_or(Rejection::MissingType)?;
+        let version = match event.get("version") {
+            None => 1,
+            Some(v) => v
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .filter(|v| *v > 0)
+                .ok_or(Rejection::BadVersion)?,
+        };
+        let schemas = self
+            .types
+            .get(kind)
+            .ok_or_else(|| Rejection::UnknownType(kind.to_string()))?;
+        let mine: Vec<&Schema> = schemas
+            .iter()
+            .filter(|s| s.producers.is_match(service))
+            .collect();
+        if mine.is_empty() {
+            return Err(Rejection::UnknownProducer {
+                service: service.to_string(),
+                kind: kind.to_string(),
+            });
+        }
+        let Some(schema) = mine.iter().find(|s| s.version == version) else {
+            let mut supported: Vec<u32> = mine.iter().map(|s| s.version).collect();
+            supported.sort_unstable();
+            return Err(Rejection::UnsupportedVersion {
+                kind: kind.to_string(),
+                version,
+                supported,
+            });
+        };
+        let errors: Vec<String> = schema
+            .validator
+            .iter_errors(event)
+            .take(MAX_ERRORS)
+            .map(|e| match e.instance_path.to_string() {
+                path if path.is_empty() => e.to_string(),
+                path => format!("{path}: {e}"),
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(Rejection::Invalid {
+                kind: kind.to_string(),
+                version,
+                errors,
+            });
+        }
+        let mut embed = Embed::new(
+            truncate(schema.title.render(service, event), MAX_TITLE),
+            truncate(schema.description.render(service, event), MAX_DESCRIPTION),
+        );
+        embed.color = schema.color;
+        Ok(Accepted {
+            kind: kind.to_string(),
+            version,
+            embed,
+        })
+    }
+}
+
+fn truncate(mut s: String, max: usize) -> String {
+    if s.len() > max {
+        s.truncate(s.floor_char_boundary(max - 3));
+        s.push('…');
+    }
+    s
+}
+
+enum Part {
+    Text(String),
+    /// Dotted path; `service` is the producer.
+    Field(Vec<String>),
+}
+
+struct Template(Vec<Part>);
+
+impl Template {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after
+                .find("}}")
+                .ok_or_else(|| format!("unclosed `{{{{` in {text:?}"))?;
+            let path = after[..end].trim();
+            if path.is_empty() || path.split('.').any(str::is_empty) {
+                return Err(format!("bad placeholder {{{{{path}}}}} in {text:?}"));
+            }
+            parts.push(Part::Field(path.split('.').map(String::from).collect()));
+            rest = &after[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self(parts))
+    }
+
+    fn render(&self, service: &str, event: &Value) -> String {
+        let mut out = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(t) => out.push_str(t),
+                Part::Field(path) if path.len() == 1 && path[0] == "service" => {
+                    out.push_str(service)
+                }
+                Part::Field(path) => {
+                    let value = path.iter().fold(event, |v, key| &v[key.as_str()]);
+                    match value {
+                        Value::Null => {}
+                        Value::String(s) => out.push_str(s),
+                        other => out.push_str(&other.to_string()),
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const REGISTRY: &str = r#"
+events:
+  - type: deploy
+    producers: ["valoryield"]
+    schema:
+      type: object
+      required: [env]
+      properties:
+        env: { enum: [dev, staging, prod] }
+    template:
+      title: "{{service}} → {{env}}"
+      description: "{{meta.by}} {{missing}}"
+      color: 65280
+  - type: deploy
+    version: 2
+    producers: ["valoryield"]
+    schema: { type: object }
+    template: { title: "v2" }
+"#;
+
+    #[test]
+    fn validates_and_renders() {
+        let r = Registry::from_yaml(REGISTRY).unwrap();
+        let ok = r
+            .check(
+                "valoryield",
+                &json!({ "type": "deploy", "env": "prod", "meta": { "by": "ci" } }),
+            )
+            .unwrap();
+        assert_eq!(ok.version, 1);
+        assert_eq!(ok.embed.title, "valoryield → prod");
+        assert_eq!(ok.embed.description, "ci ");
+        assert_eq!(ok.embed.color, 65280);
+        assert_eq!(
+            r.check("valoryield", &json!({ "type": "deploy", "version": 2 }))
+                .unwrap()
+                .embed
+                .title,
+            "v2"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_producers_types_versions_and_bad_bodies() {
+        let r = Registry::from_yaml(REGISTRY).unwrap();
+        let deploy = json!({ "type": "deploy", "env": "qa" });
+        assert!(matches!(
+            r.check("valoryield", &deploy),
+            Err(Rejection::Invalid { ref errors, .. }) if errors[0].starts_with("/env:")
+        ));
+        let err = r.check("agent-7", &deploy).unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            r.check("valoryield", &json!({ "type": "deploy", "version": 3 })),
+            Err(Rejection::UnsupportedVersion {
+                kind: "deploy".into(),
+                version: 3,
+                supported: vec![1, 2],
+            })
+        );
+        assert_eq!(
+            r.check("valoryield", &json!({ "type": "nope" })),
+            Err(Rejection::UnknownType("nope".into()))
+        );
+        assert_eq!(
+            r.check("valoryield", &json!({})),
+            Err(Rejection::MissingType)
+        );
+    }
+
+    #[test]
+    fn rejects_broken_registries() {
+        let bad_template = REGISTRY.replace("{{env}}", "{{env");
+        assert!(matches!(
+            Registry::from_yaml(&bad_template),
+            Err(RegistryError::Template { .. })
+        ));
+        let bad_schema =
+            REGISTRY.replace("type: object\n      required", "type: 7\n      required");
+        assert!(matches!(
+            Registry::from_yaml(&bad_schema),
+            Err(RegistryError::Schema { .. })
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+//! GitHub webhook payloads rendered as embeds.
+
+use serde_json::Value;
+
+use crate::notify::Embed;
+
+/// Renders a delivery; which channel it goes to is decided by
+/// [`crate::routing::RouteTable`].
+pub fn render(event: &str, payload: &Value) -> Embed {
+    let s = |v: &Value| v.as_str().unwrap_or_default().to_string();
+    match event {
+        "pull_request" => {
+            let pr = &payload["pull_request"];
+            let action = if payload["action"] == "closed" && pr["merged"] == true {
+                "merged".to_string()
+            } else {
+                s(&payload["action"])
+            };
+            let title = format!("PR {action}: #{} {}", pr["number"], s(&pr["title"]));
+            let desc = format!(
+                "{} → {}\n{}",
+                s(&pr["user"]["login"]),
+                s(&pr["base"]["repo"]["full_name"]),
+                s(&pr["html_url"])
+            );
+            Embed::new(title, desc)
+        }
+        "check_suite" => {
+            let cs = &payload["check_suite"];
+            let conclusion = cs["conclusion"].as_str().unwrap_or("pending");
+            let desc = format!("{} → {conclusion}", s(&cs["app"]["name"]));
+            Embed::new(format!("Checks {}", s(&cs["status"])), desc)
+        }
+        "push" => {
+            let desc = format!(
+                "{}\n{}",
+                s(&payload["repository"]["full_name"]),
+                s(&payload["compare"])
+            );
+            Embed::new(format!("Push: {}", s(&payload["ref"])), desc)
+        }
+        "issue_comment" => {
+            let issue = &payload["issue"];
+            let comment = &payload["comment"];
+            let title = format!("Comment on #{} {}", issue["number"], s(&issue["title"]));
+            let desc = format!(
+                "{}: {}\n{}",
+                s(&comment["user"]["login"]),
+                s(&comment["body"]).chars().take(300).collect::<String>(),
+                s(&comment["html_url"])
+            );
+            Embed::new(title, desc)
+        }
+        _ => {
+            let title = match payload["action"].as_str() {
+                Some(action) => format!("{event} {action}"),
+                None => event.to_string(),
+            };
+            Embed::new(title, s(&payload["repository"]["full_name"]))
+        }
+    }
+}
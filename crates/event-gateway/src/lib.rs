@@ -0,0 +1,478 @@
+//! Event gateway: serves every `event_gateway.endpoints` entry from
+//! `discovery.yml` and enforces its verification rules before anything is
+//! posted to Discord.
+//!
+//! * `verify: github_app` endpoints check `X-Hub-Signature-256` against the
+//!   GitHub App webhook secret, then dispatch through [`routing::RouteTable`].
+//! * All other endpoints require the shared HMAC (`auth.hmac.header`, usually
+//!   `X-Sig`) and an `X-Service` header matching one of `allowed_services`.
+//!
+//! The endpoint at the path of `infra.metrics.alertmanager_webhook` parses
+//! Alertmanager notifications ([`alert`]). Since Alertmanager cannot sign
+//! bodies, it may instead send `auth.alertmanager_token_secret_ref` as
+//! `Authorization: Bearer`; the HMAC key is only ever used for signatures.
+//!
+//! With a schema registry ([`events`]), service events are validated
+//! against their versioned schema, rendered from its template and, given a
+//! [`bus::Bus`], published to the message bus as well.
+//!
+//! Deliveries carry an idempotency key — `X-GitHub-Delivery` for GitHub,
+//! an optional `X-Idempotency-Key` for services — so a queued notifier
+//! ([`outbox::Outbox`]) posts redelivered events once.
+
+pub mod alert;
+pub mod bus;
+pub mod events;
+pub mod github;
+pub mod notify;
+pub mod outbox;
+pub mod routing;
+pub mod verify;
+
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use discovery::{Discovery, Endpoint, Verify};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use secrets::Secret;
+use serde_json::Value;
+
+use crate::bus::{Bus, Published};
+use crate::events::Registry;
+use crate::notify::{Embed, Notifier};
+use crate::routing::{Decision, GitEvent, RouteError, RouteTable};
+use crate::verify::{Signer, VerifyError, GITHUB_SIGNATURE_HEADER};
+
+/// Header naming the producing service on HMAC-signed endpoints.
+pub const SERVICE_HEADER: &str = "X-Service";
+
+/// Service name implied by a bearer-authenticated Alertmanager call.
+pub const ALERTMANAGER_SERVICE: &str = "alertmanager";
+
+/// Optional header identifying a service event across retries.
+pub const IDEMPOTENCY_HEADER: &str = "X-Idempotency-Key";
+
+/// GitHub's id for a webhook delivery, stable across redeliveries.
+pub const GITHUB_DELIVERY_HEADER: &str = "X-GitHub-Delivery";
+
+/// Path served by the TypeScript gateway; kept as an alias of the first
+/// `github_app` endpoint so existing webhook configs keep working.
+pub const LEGACY_GITHUB_PATH: &str = "/webhooks/github";
+
+/// Resolved secrets the gateway verifies against.
+pub struct Keys {
+    /// Value behind `event_gateway.auth.hmac.key_secret_ref`.
+    pub hmac: Vec<u8>,
+    /// Value behind `git.app.webhook_secret_ref`.
+    pub github_webhook: Vec<u8>,
+    /// Value behind `event_gateway.auth.alertmanager_token_secret_ref`.
+    pub alertmanager_token: Option<Secret>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error("endpoint {path}: invalid service glob {glob:?}: {source}")]
+    Glob {
+        path: String,
+        glob: String,
+        #[source]
+        source: globset::Error,
+    },
+    #[error("endpoint {0} has neither `verify` nor `allowed_services`")]
+    Unprotected(String),
+    #[error("endpoint {0} has no discord_channel")]
+    NoChannel(String),
+    #[error(transparent)]
+    Route(#[from] RouteError),
+}
+
+/// One configured endpoint with its verification compiled.
+struct Route {
+    endpoint: Endpoint,
+    check: Check,
+    notifier: Arc<dyn Notifier>,
+    /// Set on the Alertmanager endpoint.
+    alerts: Option<alert::Receiver>,
+    /// Bearer token accepted in place of a signature on the Alertmanager
+    /// endpoint.
+    alert_token: Option<Secret>,
+    registry: Option<Arc<Registry>>,
+    bus: Option<Arc<dyn Bus>>,
+}
+
+enum Check {
+    GithubApp {
+        signer: Signer,
+        table: Arc<RouteTable>,
+    },
+    Hmac {
+        header: String,
+        signer: Signer,
+        services: GlobSet,
+    },
+}
+
+/// Builds the router for every endpoint in `cfg.event_gateway.endpoints`.
+pub fn router(
+    cfg: &Discovery,
+    keys: Keys,
+    notifier: Arc<dyn Notifier>,
+) -> Result<Router, BuildError> {
+    Gateway::new(cfg, keys, notifier).router()
+}
+
+/// [`router`] with the optional schema registry and message bus.
+pub struct Gateway<'a> {
+    cfg: &'a Discovery,
+    keys: Keys,
+    notifier: Arc<dyn Notifier>,
+    registry: Option<Arc<Registry>>,
+    bus: Option<Arc<dyn Bus>>,
+}
+
+impl<'a> Gateway<'a> {
+    pub fn new(cfg: &'a Discovery, keys: Keys, notifier: Arc<dyn Notifier>) -> Self {
+        Self {
+            cfg,
+            keys,
+            notifier,
+            registry: None,
+            bus: None,
+        }
+    }
+
+    /// Validates and renders service events; see [`events`].
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        self.registry = Some(Arc::new(registry));
+        self
+    }
+
+    /// Publishes accepted service events.
+    pub fn with_bus(mut self, bus: Arc<dyn Bus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn router(self) -> Result<Router, BuildError> {
+        let Self {
+            cfg,
+            keys,
+            notifier,
+            registry,
+            bus,
+        } = self;
+        let hmac = &cfg.event_gateway.auth.hmac;
+        let table = Arc::new(RouteTable::compile(cfg)?);
+        let alert_path = cfg
+            .infra
+            .metrics
+            .alertmanager_webhook
+            .as_deref()
+            .and_then(|url| reqwest::Url::parse(url).ok())
+            .map(|url| url.path().to_string());
+        let mut app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/metrics", get(|| async { ratelimit::metrics::render() }));
+
+        // The alias goes to the first `github_app` endpoint, unless some
+        // endpoint is served at the legacy path itself.
+        let mut legacy_alias = !cfg
+            .event_gateway
+            .endpoints
+            .iter()
+            .any(|e| e.path == LEGACY_GITHUB_PATH);
+        for endpoint in &cfg.event_gateway.endpoints {
+            let check = match endpoint.verify {
+                Some(Verify::GithubApp) => Check::GithubApp {
+                    signer: Signer::github(keys.github_webhook.clone()),
+                    table: table.clone(),
+                },
+                None => {
+                    if endpoint.allowed_services.is_empty() {
+                        return Err(BuildError::Unprotected(endpoint.path.clone()));
+                    }
+                    if endpoint.discord_channel.is_none() {
+                        return Err(BuildError::NoChannel(endpoint.path.clone()));
+                    }
+                    Check::Hmac {
+                        header: hmac.header.clone(),
+                        signer: Signer::new(hmac.algo, keys.hmac.clone()),
+                        services: service_globs(endpoint)?,
+                    }
+                }
+            };
+            let github = matches!(check, Check::GithubApp { .. });
+            let alerts = match (&check, &endpoint.discord_channel) {
+                (Check::Hmac { .. }, Some(channel))
+                    if alert_path.as_deref() == Some(endpoint.path.as_str()) =>
+                {
+                    Some(
+                        alert::Receiver::new(channel)
+                            .with_severity_channels(endpoint.severity_channels.clone()),
+                    )
+                }
+                _ => None,
+            };
+            let route = Arc::new(Route {
+                endpoint: endpoint.clone(),
+                check,
+                notifier: notifier.clone(),
+                alert_token: alerts.as_ref().and(keys.alertmanager_token.clone()),
+                alerts,
+                registry: registry.clone(),
+                bus: bus.clone(),
+            });
+            if github {
+                if legacy_alias {
+                    app = app.route(
+                        LEGACY_GITHUB_PATH,
+                        post(handle_github).with_state(route.clone()),
+                    );
+                    legacy_alias = false;
+                }
+                app = app.route(&endpoint.path, post(handle_github).with_state(route));
+            } else if route.alerts.is_some() {
+                app = app.route(&endpoint.path, post(handle_alert).with_state(route));
+            } else {
+                app = app.route(&endpoint.path, post(handle_service).with_state(route));
+            }
+        }
+        Ok(app)
+    }
+}
+
+fn service_globs(endpoint: &Endpoint) -> Result<GlobSet, BuildError> {
+    let mut set = GlobSetBuilder::new();
+    for glob in &endpoint.allowed_services {
+        let g = Glob::new(glob).map_err(|source| BuildError::Glob {
+            path: endpoint.path.clone(),
+            glob: glob.clone(),
+            source,
+        })?;
+        set.add(g);
+    }
+    set.build().map_err(|source| BuildError::Glob {
+        path: endpoint.path.clone(),
+        glob: endpoint.allowed_services.join(","),
+        source,
+    })
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn verify(
+    signer: &Signer,
+    headers: &HeaderMap,
+    name: &str,
+    body: &[u8],
+) -> Result<(), (StatusCode, &'static str)> {
+    let sig = header(headers, name).ok_or_else(|| VerifyError::Missing(name.to_string()));
+    sig.and_then(|sig| signer.verify(body, sig)).map_err(|e| {
+        tracing::warn!(error = %e, "rejected signature");
+        (StatusCode::UNAUTHORIZED, "bad sig")
+    })
+}
+
+async fn deliver(route: &Route, key: Option<String>, channel: &str, embed: Embed) -> Response {
+    let sent = match key {
+        Some(key) => route.notifier.send_once(&key, channel, embed).await,
+        None => route.notifier.send(channel, embed).await,
+    };
+    match sent {
+        Ok(()) => "ok".into_response(),
+        Err(e) => {
+            tracing::error!(path = %route.endpoint.path, channel, error = %e, "discord delivery failed");
+            (StatusCode::BAD_GATEWAY, "delivery failed").into_response()
+        }
+    }
+}
+
+async fn handle_github(
+    State(route): State<Arc<Route>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Check::GithubApp { signer, table } = &route.check else {
+        unreachable!("github handler mounted on hmac route")
+    };
+    if let Err(r) = verify(signer, &headers, GITHUB_SIGNATURE_HEADER, &body) {
+        return r.into_response();
+    }
+    let Some(event) = header(&headers, "X-GitHub-Event") else {
+        return (StatusCode::BAD_REQUEST, "missing X-GitHub-Event").into_response();
+    };
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid json").into_response(),
+    };
+    match table.resolve(&GitEvent::from_payload(event, &payload)) {
+        Decision::Deliver { channel, rule } => {
+            tracing::debug!(event, %channel, %rule, "routing github event");
+            let key = header(&headers, GITHUB_DELIVERY_HEADER).map(|id| format!("github:{id}"));
+            deliver(&route, key, &channel, github::render(event, &payload)).await
+        }
+        Decision::Drop { reason } => {
+            tracing::debug!(event, %reason, "dropping github event");
+            "ignored".into_response()
+        }
+    }
+}
+
+/// Checks the signature and `X-Service` of an HMAC endpoint; returns the
+/// service name.
+fn authorize_service<'a>(
+    route: &Route,
+    headers: &'a HeaderMap,
+    body: &[u8],
+) -> Result<&'a str, (StatusCode, &'static str)> {
+    let Check::Hmac {
+        header: sig_header,
+        signer,
+        services,
+    } = &route.check
+    else {
+        unreachable!("service handler mounted on github route")
+    };
+    verify(signer, headers, sig_header, body)?;
+    let Some(service) = header(headers, SERVICE_HEADER) else {
+        return Err((StatusCode::BAD_REQUEST, "missing X-Service"));
+    };
+    allow_service(route, services, service)?;
+    Ok(service)
+}
+
+fn allow_service(
+    route: &Route,
+    services: &GlobSet,
+    service: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    if services.is_match(service) {
+        return Ok(());
+    }
+    tracing::warn!(path = %route.endpoint.path, service, "service not allowed");
+    Err((StatusCode::FORBIDDEN, "service not allowed"))
+}
+
+async fn handle_service(
+    State(route): State<Arc<Route>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let service = match authorize_service(&route, &headers, &body) {
+        Ok(service) => service,
+        Err(r) => return r.into_response(),
+    };
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid json").into_response(),
+    };
+    let channel = route
+        .endpoint
+        .discord_channel
+        .as_deref()
+        .expect("checked at build");
+    let (kind, version, embed) = match &route.registry {
+        Some(registry) => match registry.check(service, &payload) {
+            Ok(accepted) => (accepted.kind, accepted.version, accepted.embed),
+            Err(rejection) => {
+                tracing::warn!(path = %route.endpoint.path, service, error = %rejection, "rejected event");
+                return (rejection.status(), rejection.to_string()).into_response();
+            }
+        },
+        None => {
+            let kind = payload["type"].as_str().unwrap_or("event").to_string();
+            (kind, 1, service_embed(service, &payload))
+        }
+    };
+    let key = header(&headers, IDEMPOTENCY_HEADER).map(|k| format!("{service}:{k}"));
+    // Published only once Discord has it: a failed delivery is retried by
+    // the producer, and that retry must not publish the event again.
+    let delivered = deliver(&route, key.clone(), channel, embed).await;
+    if !delivered.status().is_success() {
+        return delivered;
+    }
+    if let Some(bus) = &route.bus {
+        let event = Published {
+            service: service.to_string(),
+            kind,
+            version,
+            key,
+            body: payload,
+        };
+        if let Err(e) = bus.publish(&event).await {
+            tracing::error!(service, kind = %event.kind, error = %e, "publishing event failed");
+            return (StatusCode::SERVICE_UNAVAILABLE, "message bus unavailable").into_response();
+        }
+    }
+    delivered
+}
+
+async fn handle_alert(
+    State(route): State<Arc<Route>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let bearer = header(&headers, "Authorization").and_then(|v| v.strip_prefix("Bearer "));
+    let authorized = match (bearer, &route.alert_token, &route.check) {
+        (Some(presented), Some(token), Check::Hmac { services, .. }) => {
+            if token.matches(presented) {
+                allow_service(&route, services, ALERTMANAGER_SERVICE)
+            } else {
+                tracing::warn!("rejected alertmanager bearer token");
+                Err((StatusCode::UNAUTHORIZED, "bad token"))
+            }
+        }
+        _ => authorize_service(&route, &headers, &body).map(drop),
+    };
+    if let Err(r) = authorized {
+        return r.into_response();
+    }
+    let hook: alert::Webhook = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::warn!(error = %e, "unparseable alertmanager payload");
+            return (StatusCode::BAD_REQUEST, "invalid alertmanager payload").into_response();
+        }
+    };
+    let receiver = route.alerts.as_ref().expect("mounted with a receiver");
+    let mut failed = false;
+    for n in receiver.receive(&hook) {
+        if let Err(e) = route
+            .notifier
+            .send_threaded(&n.thread, &n.channel, n.embed)
+            .await
+        {
+            tracing::error!(path = %route.endpoint.path, channel = %n.channel, error = %e, "discord delivery failed");
+            // Alertmanager retries the whole group; only this channel's
+            // alerts must not be deduplicated then.
+            receiver.forget(&hook, &n.channel);
+            failed = true;
+        }
+    }
+    if failed {
+        return (StatusCode::BAD_GATEWAY, "delivery failed").into_response();
+    }
+    "ok".into_response()
+}
+
+/// Generic rendering for service events: `type`/`summary` if present,
+/// otherwise the payload itself.
+fn service_embed(service: &str, payload: &Value) -> Embed {
+    let kind = payload["type"].as_str().unwrap_or("event");
+    let desc = match payload["summary"].as_str() {
+        Some(s) => s.to_string(),
+        None => {
+            let mut s = serde_json::to_string_pretty(payload).unwrap_or_default();
+            s.truncate(s.floor_char_boundary(1800));
+            format!("```json\n{s}\n```")
+        }
+    };
+    Embed::new(format!("{service}: {kind}"), desc)
+}
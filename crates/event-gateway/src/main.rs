@@ -0,0 +1,304 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use delivery::redis::RedisStreams;
+use delivery::{Queue, Worker};
+use discovery::{Discovery, MessageBusType};
+use event_gateway::bus::RedisBus;
+use event_gateway::events::Registry;
+use event_gateway::notify::{ChannelMap, DiscordRest, DryRun, Notifier};
+use event_gateway::outbox::{NotifierSink, Outbox};
+use event_gateway::routing::{Decision, GitEvent, RouteTable};
+use event_gateway::{github, Gateway, Keys};
+use ratelimit::RateLimiter;
+use secrets::redact::Redactor;
+use secrets::{Resolver, Secret};
+
+#[derive(Parser)]
+#[command(about = "Signed webhook ingress for discovery.yml endpoints")]
+struct Args {
+    #[arg(long, global = true, env = "DISCOVERY_CONFIG_PATH", default_value = discovery::DEFAULT_PATH)]
+    config: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the configured endpoints.
+    Serve(Serve),
+    /// Route recorded GitHub payloads and print where each would be posted.
+    Replay {
+        /// `X-GitHub-Event` of the recorded deliveries.
+        #[arg(long)]
+        event: String,
+        /// Payload files (the JSON body of the delivery).
+        #[arg(required = true)]
+        payloads: Vec<PathBuf>,
+    },
+    /// Inspect, replay or discard messages Discord never accepted.
+    DeadLetters {
+        /// Overrides `infra.message_bus.url`.
+        #[arg(long, env = "REDIS_URL", hide_env_values = true)]
+        redis_url: Option<String>,
+        #[command(subcommand)]
+        action: DeadLetterAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeadLetterAction {
+    /// Print dead letters as JSON lines, oldest first.
+    List {
+        #[arg(long, default_value_t = 100)]
+        count: usize,
+    },
+    /// Put dead letters back on the queue with their attempts reset.
+    Replay {
+        #[arg(required_unless_present = "all")]
+        ids: Vec<String>,
+        /// Replay the oldest `--count` dead letters.
+        #[arg(long, conflicts_with = "ids")]
+        all: bool,
+        #[arg(long, default_value_t = 1000)]
+        count: usize,
+    },
+    /// Delete dead letters without posting them.
+    Discard {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+}
+
+#[derive(clap::Args)]
+struct Serve {
+    #[arg(long, env = "EVENT_GATEWAY_PORT", default_value_t = 8080)]
+    port: u16,
+    /// Overrides `event_gateway.auth.hmac.key_secret_ref`.
+    #[arg(long, env = "EVENTS_HMAC_KEY", hide_env_values = true)]
+    hmac_key: Option<String>,
+    /// Overrides `git.app.webhook_secret_ref`.
+    #[arg(long, env = "GITHUB_WEBHOOK_SECRET", hide_env_values = true)]
+    github_webhook_secret: Option<String>,
+    /// Overrides `event_gateway.auth.alertmanager_token_secret_ref`.
+    #[arg(long, env = "ALERTMANAGER_TOKEN", hide_env_values = true)]
+    alertmanager_token: Option<String>,
+    /// Overrides `discord.bot.token_secret_ref`.
+    #[arg(long, env = "DISCORD_TOKEN", hide_env_values = true)]
+    discord_token: Option<String>,
+    /// Log embeds instead of posting them to Discord; bypasses the queue.
+    #[arg(long)]
+    dry_run: bool,
+    /// Overrides `infra.message_bus.url`.
+    #[arg(long, env = "REDIS_URL", hide_env_values = true)]
+    redis_url: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let cfg = Discovery::load(&args.config)?;
+    let redactor =
+        Redactor::with_patterns(cfg.security.redaction.patterns.iter().map(String::as_str))?;
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(redactor.stderr_writer())
+        .init();
+    match args.command {
+        Command::Serve(serve) => {
+            let resolver = Resolver::new(secrets::backend_from_env()?).with_redactor(redactor);
+            run(cfg, &args.config, serve, resolver).await
+        }
+        Command::Replay { event, payloads } => replay(&cfg, &event, &payloads),
+        Command::DeadLetters { redis_url, action } => {
+            let queue = open_queue(&cfg, redis_url)
+                .await?
+                .context("infra.message_bus is not redis; there is no dead-letter stream")?;
+            dead_letters(&queue, action).await
+        }
+    }
+}
+
+/// Redis URL of `infra.message_bus` (or `url`), if the bus is Redis.
+fn redis_url(cfg: &Discovery, url: Option<String>) -> anyhow::Result<Option<String>> {
+    let bus = &cfg.infra.message_bus;
+    if bus.kind != Some(MessageBusType::Redis) {
+        return Ok(None);
+    }
+    url.or_else(|| bus.url.clone())
+        .context("infra.message_bus.url is not set")
+        .map(Some)
+}
+
+/// The outbound queue on `infra.message_bus`, if that is Redis.
+async fn open_queue(cfg: &Discovery, url: Option<String>) -> anyhow::Result<Option<RedisStreams>> {
+    let Some(url) = redis_url(cfg, url)? else {
+        return Ok(None);
+    };
+    let queue = RedisStreams::connect(&url, &cfg.infra.message_bus.topic_prefix)
+        .await
+        .context("connecting to the message bus")?;
+    Ok(Some(queue))
+}
+
+/// Uses the explicit value when given, otherwise resolves `reference`.
+async fn secret(
+    resolver: &Resolver,
+    explicit: Option<String>,
+    reference: &str,
+) -> anyhow::Result<String> {
+    match explicit {
+        Some(value) => {
+            resolver.redactor().add_literal(&value);
+            Ok(value)
+        }
+        None => Ok(resolver
+            .resolve_str(reference)
+            .await
+            .with_context(|| format!("resolving {reference}"))?
+            .expose()
+            .to_string()),
+    }
+}
+
+async fn run(
+    cfg: Discovery,
+    config_path: &Path,
+    args: Serve,
+    resolver: Resolver,
+) -> anyhow::Result<()> {
+    let mut notifier: Arc<dyn Notifier> = if args.dry_run {
+        Arc::new(DryRun)
+    } else {
+        let token = secret(
+            &resolver,
+            args.discord_token,
+            &cfg.discord.bot.token_secret_ref,
+        )
+        .await?;
+        let channels = ChannelMap::from_env(cfg.channel_names());
+        let limiter = Arc::new(RateLimiter::from_discovery(&cfg));
+        Arc::new(DiscordRest::new(token, channels).with_limiter(limiter))
+    };
+    if !args.dry_run {
+        if let Some(queue) = open_queue(&cfg, args.redis_url.clone()).await? {
+            let queue: Arc<dyn Queue> = Arc::new(queue);
+            let worker = Worker::new(queue.clone(), Arc::new(NotifierSink(notifier)));
+            tokio::spawn(worker.run(async {
+                let _ = tokio::signal::ctrl_c().await;
+            }));
+            tracing::info!("posting through the delivery queue");
+            notifier = Arc::new(Outbox::new(queue));
+        }
+    }
+    let keys = Keys {
+        hmac: secret(
+            &resolver,
+            args.hmac_key,
+            &cfg.event_gateway.auth.hmac.key_secret_ref,
+        )
+        .await?
+        .into_bytes(),
+        github_webhook: secret(
+            &resolver,
+            args.github_webhook_secret,
+            &cfg.git.app.webhook_secret_ref,
+        )
+        .await?
+        .into_bytes(),
+        alertmanager_token: match (
+            args.alertmanager_token,
+            &cfg.event_gateway.auth.alertmanager_token_secret_ref,
+        ) {
+            (None, None) => None,
+            (explicit, reference) => Some(Secret::new(
+                secret(
+                    &resolver,
+                    explicit,
+                    reference.as_deref().unwrap_or_default(),
+                )
+                .await?,
+            )),
+        },
+    };
+    let mut gateway = Gateway::new(&cfg, keys, notifier);
+    if let Some(registry) = &cfg.event_gateway.schema_registry {
+        let path = config_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(registry);
+        let registry =
+            Registry::load(&path).with_context(|| format!("loading {}", path.display()))?;
+        gateway = gateway.with_registry(registry);
+    }
+    if !args.dry_run {
+        if let Some(url) = redis_url(&cfg, args.redis_url)? {
+            let bus = RedisBus::connect(&url, &cfg.infra.message_bus.topic_prefix)
+                .await
+                .context("connecting to the message bus")?;
+            gateway = gateway.with_bus(Arc::new(bus));
+        }
+    }
+    let app = gateway.router().context("building routes")?;
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(%addr, "event gateway listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+async fn dead_letters(queue: &RedisStreams, action: DeadLetterAction) -> anyhow::Result<()> {
+    match action {
+        DeadLetterAction::List { count } => {
+            for dead in queue.dead_letters(count).await? {
+                println!("{}", serde_json::to_string(&dead)?);
+            }
+        }
+        DeadLetterAction::Replay { ids, all, count } => {
+            let ids = if all {
+                let dead = queue.dead_letters(count).await?;
+                dead.into_iter().map(|d| d.id).collect()
+            } else {
+                ids
+            };
+            for id in ids {
+                let found = queue.replay(&id).await?;
+                println!("{id}: {}", if found { "replayed" } else { "not found" });
+            }
+        }
+        DeadLetterAction::Discard { ids } => {
+            for id in ids {
+                let found = queue.discard(&id).await?;
+                println!("{id}: {}", if found { "discarded" } else { "not found" });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn replay(cfg: &Discovery, event: &str, payloads: &[PathBuf]) -> anyhow::Result<()> {
+    let table = RouteTable::compile(cfg)?;
+    for path in payloads {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let payload: serde_json::Value =
+            serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        match table.resolve(&GitEvent::from_payload(event, &payload)) {
+            Decision::Deliver { channel, rule } => {
+                let embed = github::render(event, &payload);
+                println!("{}: {channel} (via {rule})", path.display());
+                println!("{}", serde_json::to_string_pretty(&embed)?);
+            }
+            Decision::Drop { reason } => println!("{}: dropped ({reason})", path.display()),
+        }
+    }
+    Ok(())
+}
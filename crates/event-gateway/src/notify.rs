@@ -0,0 +1,269 @@
+//! Outbound Discord posts.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ratelimit::{DiscordRateLimit, Key, RateLimiter};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+/// Embed colour used by the TypeScript gateway.
+pub const DEFAULT_COLOR: u32 = 3_099_199;
+
+const DISCORD_API: &str = "https://discord.com/api/v10";
+
+/// Posts attempted per embed before a `429` is reported as an error.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// How long a thread's first message id is remembered for replies.
+const THREAD_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Embed {
+    pub title: String,
+    pub description: String,
+    pub color: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Embed {
+    pub fn new(title: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            description: description.into(),
+            color: DEFAULT_COLOR,
+            url: None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("no channel id configured for {channel} (set {env})")]
+    UnknownChannel { channel: String, env: String },
+    #[error("discord kept rate limiting posts to {0}")]
+    RateLimited(String),
+    #[error("discord request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("enqueueing failed: {0}")]
+    Queue(#[from] delivery::QueueError),
+}
+
+/// Anything that can deliver an embed to a named channel (`#prs`).
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, channel: &str, embed: Embed) -> Result<(), NotifyError>;
+
+    /// Like [`Notifier::send`], but `key` identifies the event so notifiers
+    /// that deduplicate (see [`crate::outbox::Outbox`]) post it once however
+    /// often it is redelivered.
+    async fn send_once(&self, key: &str, channel: &str, embed: Embed) -> Result<(), NotifyError> {
+        let _ = key;
+        self.send(channel, embed).await
+    }
+
+    /// Posts `embed` as a reply to the first message sent under `thread`, so
+    /// updates about one thing (an alert group) read as a conversation.
+    /// Notifiers without message ids post it plainly.
+    async fn send_threaded(
+        &self,
+        thread: &str,
+        channel: &str,
+        embed: Embed,
+    ) -> Result<(), NotifyError> {
+        let _ = thread;
+        self.send(channel, embed).await
+    }
+}
+
+/// Environment variable holding the id for a channel name, following the
+/// `CH_<NAME>_ID` convention in `docker-compose.yml` (`#cluster-status` →
+/// `CH_CLUSTER_STATUS_ID`).
+pub fn channel_env(channel: &str) -> String {
+    let name: String = channel
+        .trim_start_matches('#')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("CH_{name}_ID")
+}
+
+/// Channel name → Discord channel id.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelMap(BTreeMap<String, String>);
+
+impl ChannelMap {
+    /// Looks up every channel in `names` via [`channel_env`]; unset ones are
+    /// reported when something is first sent there.
+    pub fn from_env<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let ids = names
+            .into_iter()
+            .filter_map(|n| {
+                std::env::var(channel_env(n))
+                    .ok()
+                    .map(|id| (n.to_string(), id))
+            })
+            .collect();
+        Self(ids)
+    }
+
+    pub fn insert(&mut self, channel: impl Into<String>, id: impl Into<String>) {
+        self.0.insert(channel.into(), id.into());
+    }
+
+    pub fn resolve(&self, channel: &str) -> Result<&str, NotifyError> {
+        self.0
+            .get(channel)
+            .map(String::as_str)
+            .ok_or_else(|| NotifyError::UnknownChannel {
+                channel: channel.to_string(),
+                env: channel_env(channel),
+            })
+    }
+}
+
+/// Posts through the Discord REST API with a bot token, waiting on the
+/// channel and global buckets of a [`RateLimiter`] and backing off when
+/// Discord answers `429`.
+///
+/// Thread roots are remembered in memory, so replicas (or a restart) start
+/// a fresh thread rather than replying to one they did not post.
+pub struct DiscordRest {
+    http: reqwest::Client,
+    token: String,
+    channels: ChannelMap,
+    base_url: String,
+    limiter: Option<Arc<RateLimiter>>,
+    threads: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl DiscordRest {
+    pub fn new(token: impl Into<String>, channels: ChannelMap) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            token: token.into(),
+            channels,
+            base_url: DISCORD_API.to_string(),
+            limiter: None,
+            threads: Mutex::default(),
+        }
+    }
+
+    /// Shares buckets with other senders, e.g. one built by
+    /// [`RateLimiter::from_discovery`].
+    pub fn with_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Points the client at a different API root, e.g. a local fake.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Posts `body` to `channel` and returns the new message's id.
+    async fn post(&self, channel: &str, body: serde_json::Value) -> Result<String, NotifyError> {
+        let id = self.channels.resolve(channel)?;
+        let keys = [Key::channel(channel), Key::Global];
+        for _ in 0..MAX_ATTEMPTS {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire(&keys).await;
+            }
+            let res = self
+                .http
+                .post(format!("{}/channels/{id}/messages", self.base_url))
+                .header("Authorization", format!("Bot {}", self.token))
+                .json(&body)
+                .send()
+                .await?;
+            if res.status() != StatusCode::TOO_MANY_REQUESTS {
+                let message: serde_json::Value = res.error_for_status()?.json().await?;
+                return Ok(message["id"].as_str().unwrap_or_default().to_string());
+            }
+            let limit: DiscordRateLimit = res.json().await?;
+            match &self.limiter {
+                Some(limiter) => limiter.note_retry_after(limit.key(channel), limit.retry_after()),
+                None => tokio::time::sleep(limit.retry_after()).await,
+            }
+        }
+        Err(NotifyError::RateLimited(channel.to_string()))
+    }
+
+    fn threads(&self) -> std::sync::MutexGuard<'_, HashMap<String, (String, Instant)>> {
+        self.threads.lock().expect("thread map poisoned")
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordRest {
+    async fn send(&self, channel: &str, embed: Embed) -> Result<(), NotifyError> {
+        self.post(channel, serde_json::json!({ "embeds": [embed] }))
+            .await
+            .map(drop)
+    }
+
+    async fn send_threaded(
+        &self,
+        thread: &str,
+        channel: &str,
+        embed: Embed,
+    ) -> Result<(), NotifyError> {
+        let thread = format!("{channel}/{thread}");
+        let root = self.threads().get(&thread).map(|(id, _)| id.clone());
+        let mut body = serde_json::json!({ "embeds": [embed] });
+        if let Some(root) = &root {
+            body["message_reference"] =
+                serde_json::json!({ "message_id": root, "fail_if_not_exists": false });
+        }
+        let id = self.post(channel, body).await?;
+        if root.is_none() && !id.is_empty() {
+            let now = Instant::now();
+            let mut threads = self.threads();
+            threads.retain(|_, (_, at)| now.duration_since(*at) < THREAD_TTL);
+            threads.insert(thread, (id, now));
+        }
+        Ok(())
+    }
+}
+
+/// Logs embeds instead of posting them (`testing.dry_run`).
+pub struct DryRun;
+
+#[async_trait]
+impl Notifier for DryRun {
+    async fn send(&self, channel: &str, embed: Embed) -> Result<(), NotifyError> {
+        tracing::info!(channel, title = %embed.title, description = %embed.description, "dry run");
+        Ok(())
+    }
+
+    async fn send_threaded(
+        &self,
+        thread: &str,
+        channel: &str,
+        embed: Embed,
+    ) -> Result<(), NotifyError> {
+        tracing::info!(channel, thread, title = %embed.title, description = %embed.description, "dry run");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_env_follows_compose_names() {
+        assert_eq!(channel_env("#cluster-status"), "CH_CLUSTER_STATUS_ID");
+        assert_eq!(channel_env("#prs"), "CH_PRS_ID");
+    }
+}
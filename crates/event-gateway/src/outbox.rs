@@ -0,0 +1,134 @@
+//! Queued delivery: handlers enqueue onto a [`delivery::Queue`] and a
+//! [`delivery::Worker`] posts through the real [`Notifier`] with retries,
+//! so a Discord outage delays messages instead of losing them.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use delivery::{Deliver, DeliverError, Enqueued, Envelope, Queue};
+
+use crate::notify::{Embed, Notifier, NotifyError};
+
+/// A [`Notifier`] that enqueues instead of posting.
+pub struct Outbox {
+    queue: Arc<dyn Queue>,
+}
+
+impl Outbox {
+    pub fn new(queue: Arc<dyn Queue>) -> Self {
+        Self { queue }
+    }
+
+    async fn enqueue(&self, envelope: Envelope) -> Result<(), NotifyError> {
+        let key = envelope.key.clone();
+        if self.queue.enqueue(envelope).await? == Enqueued::Duplicate {
+            tracing::info!(%key, "duplicate event, not posting again");
+        }
+        Ok(())
+    }
+}
+
+fn payload(embed: &Embed) -> serde_json::Value {
+    serde_json::to_value(embed).expect("embeds serialize")
+}
+
+#[async_trait]
+impl Notifier for Outbox {
+    async fn send(&self, channel: &str, embed: Embed) -> Result<(), NotifyError> {
+        self.enqueue(Envelope::unkeyed(channel, payload(&embed)))
+            .await
+    }
+
+    async fn send_once(&self, key: &str, channel: &str, embed: Embed) -> Result<(), NotifyError> {
+        self.enqueue(Envelope::new(key, channel, payload(&embed)))
+            .await
+    }
+
+    async fn send_threaded(
+        &self,
+        thread: &str,
+        channel: &str,
+        embed: Embed,
+    ) -> Result<(), NotifyError> {
+        self.enqueue(Envelope::unkeyed(channel, payload(&embed)).with_thread(thread))
+            .await
+    }
+}
+
+/// Delivers queued embeds through `notifier`, usually
+/// [`crate::notify::DiscordRest`].
+pub struct NotifierSink(pub Arc<dyn Notifier>);
+
+#[async_trait]
+impl Deliver for NotifierSink {
+    async fn deliver(&self, envelope: &Envelope) -> Result<(), DeliverError> {
+        let embed: Embed = serde_json::from_value(envelope.payload.clone())
+            .map_err(|e| DeliverError::Permanent(format!("not an embed: {e}")))?;
+        let sent = match &envelope.thread {
+            Some(thread) => self.0.send_threaded(thread, &envelope.channel, embed).await,
+            None => self.0.send(&envelope.channel, embed).await,
+        };
+        sent.map_err(classify)
+    }
+}
+
+/// Missing channel ids and payloads Discord rejects outright will not get
+/// better on retry; everything else might.
+fn classify(e: NotifyError) -> DeliverError {
+    let permanent = match &e {
+        NotifyError::UnknownChannel { .. } => true,
+        NotifyError::Http(http) => http
+            .status()
+            .is_some_and(|s| s.is_client_error() && s.as_u16() != 429),
+        NotifyError::RateLimited(_) | NotifyError::Queue(_) => false,
+    };
+    if permanent {
+        DeliverError::Permanent(e.to_string())
+    } else {
+        DeliverError::Retryable(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use delivery::memory::InMemory;
+    use delivery::Worker;
+
+    use super::*;
+    use crate::notify::ChannelMap;
+
+    struct Channels(ChannelMap);
+
+    #[async_trait]
+    impl Notifier for Channels {
+        async fn send(&self, channel: &str, _: Embed) -> Result<(), NotifyError> {
+            self.0.resolve(channel).map(drop)
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_channels_are_dead_lettered_and_keys_deduplicate() {
+        let queue = Arc::new(InMemory::new());
+        let outbox = Outbox::new(queue.clone());
+        for _ in 0..2 {
+            outbox
+                .send_once("github:1", "#nope", Embed::new("t", "d"))
+                .await
+                .unwrap();
+        }
+        let mut channels = ChannelMap::default();
+        channels.insert("#prs", "1");
+        let sink = Arc::new(NotifierSink(Arc::new(Channels(channels))));
+        let tick = Worker::new(queue.clone(), sink)
+            .with_wait(Duration::ZERO)
+            .tick()
+            .await
+            .unwrap();
+        assert_eq!((tick.delivered, tick.dead), (0, 1));
+        let dead = queue.dead_letters(10).await.unwrap();
+        assert_eq!(dead[0].envelope.key, "github:1");
+        assert!(dead[0].error.contains("CH_NOPE_ID"), "{}", dead[0].error);
+    }
+}
@@ -0,0 +1,288 @@
+//! Declarative GitHub event → Discord channel routing.
+//!
+//! Compiled from the `routes` of the `github_app` endpoint plus
+//! `git.repos[]`. Resolution for one delivery:
+//!
+//! 1. A repo listed in `git.repos` only posts its `post_events`.
+//! 2. A repo `channel_overrides` entry for the event wins outright.
+//! 3. Otherwise the first route whose event, actions and branches match.
+//! 4. If no route mentions the event at all, a listed repo falls back to its
+//!    own `channel`; if routes exist but none matched, the event is dropped.
+
+use std::collections::HashMap;
+
+use discovery::{Discovery, GitRoute, Repo, Verify};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde_json::Value;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouteError {
+    #[error("route for {event}: invalid branch glob {glob:?}: {source}")]
+    Glob {
+        event: String,
+        glob: String,
+        #[source]
+        source: globset::Error,
+    },
+}
+
+/// The routing-relevant facts of one webhook delivery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitEvent {
+    pub event: String,
+    /// `repository.name`, without the owner.
+    pub repo: Option<String>,
+    /// Effective actions: the payload `action`, plus `merged` for a closed
+    /// pull request that was merged.
+    pub actions: Vec<String>,
+    /// Branch the event concerns; `None` for tags and branchless events.
+    pub branch: Option<String>,
+}
+
+impl GitEvent {
+    pub fn from_payload(event: &str, payload: &Value) -> Self {
+        let mut actions: Vec<String> = payload["action"]
+            .as_str()
+            .map(str::to_string)
+            .into_iter()
+            .collect();
+        if event == "pull_request"
+            && payload["action"] == "closed"
+            && payload["pull_request"]["merged"] == true
+        {
+            actions.push("merged".to_string());
+        }
+        let branch = match event {
+            "push" => payload["ref"]
+                .as_str()
+                .and_then(|r| r.strip_prefix("refs/heads/")),
+            "pull_request" => payload["pull_request"]["base"]["ref"].as_str(),
+            "check_suite" => payload["check_suite"]["head_branch"].as_str(),
+            "check_run" => payload["check_run"]["check_suite"]["head_branch"].as_str(),
+            _ => None,
+        };
+        Self {
+            event: event.to_string(),
+            repo: payload["repository"]["name"].as_str().map(str::to_string),
+            actions,
+            branch: branch.map(str::to_string),
+        }
+    }
+}
+
+/// Outcome of routing one event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Deliver { channel: String, rule: String },
+    Drop { reason: String },
+}
+
+struct CompiledRoute {
+    event: String,
+    actions: Vec<String>,
+    branches: Option<GlobSet>,
+    channel: String,
+    rule: String,
+}
+
+impl CompiledRoute {
+    fn compile(idx: usize, route: &GitRoute) -> Result<Self, RouteError> {
+        let branches = if route.branches.is_empty() {
+            None
+        } else {
+            let mut set = GlobSetBuilder::new();
+            for glob in &route.branches {
+                let err = |source| RouteError::Glob {
+                    event: route.event.clone(),
+                    glob: glob.clone(),
+                    source,
+                };
+                set.add(
+                    GlobBuilder::new(glob)
+                        .literal_separator(true)
+                        .build()
+                        .map_err(err)?,
+                );
+            }
+            Some(set.build().map_err(|source| RouteError::Glob {
+                event: route.event.clone(),
+                glob: route.branches.join(","),
+                source,
+            })?)
+        };
+        let mut rule = format!("routes[{idx}] event={}", route.event);
+        if !route.actions.is_empty() {
+            rule.push_str(&format!(" actions={}", route.actions.join(",")));
+        }
+        if !route.branches.is_empty() {
+            rule.push_str(&format!(" branches={}", route.branches.join(",")));
+        }
+        Ok(Self {
+            event: route.event.clone(),
+            actions: route.actions.clone(),
+            branches,
+            channel: route.discord_channel.clone(),
+            rule,
+        })
+    }
+
+    fn matches(&self, ev: &GitEvent) -> bool {
+        let action_ok =
+            self.actions.is_empty() || ev.actions.iter().any(|a| self.actions.contains(a));
+        let branch_ok = match (&self.branches, &ev.branch) {
+            (None, _) => true,
+            (Some(set), Some(branch)) => set.is_match(branch),
+            (Some(_), None) => false,
+        };
+        action_ok && branch_ok
+    }
+}
+
+/// Compiled routing rules for GitHub deliveries.
+pub struct RouteTable {
+    routes: Vec<CompiledRoute>,
+    repos: HashMap<String, Repo>,
+}
+
+impl RouteTable {
+    /// Compiles the routes of every `github_app` endpoint and `git.repos`.
+    pub fn compile(cfg: &Discovery) -> Result<Self, RouteError> {
+        let routes = cfg
+            .event_gateway
+            .endpoints
+            .iter()
+            .filter(|e| e.verify == Some(Verify::GithubApp))
+            .flat_map(|e| e.routes.iter())
+            .enumerate()
+            .map(|(i, r)| CompiledRoute::compile(i, r))
+            .collect::<Result<_, _>>()?;
+        let repos = cfg
+            .git
+            .repos
+            .iter()
+            .map(|r| (r.name.clone(), r.clone()))
+            .collect();
+        Ok(Self { routes, repos })
+    }
+
+    pub fn resolve(&self, ev: &GitEvent) -> Decision {
+        let repo = ev.repo.as_deref().and_then(|name| self.repos.get(name));
+        if let Some(repo) = repo {
+            if !repo.post_events.contains(&ev.event) {
+                return Decision::Drop {
+                    reason: format!("{} is not in post_events for {}", ev.event, repo.name),
+                };
+            }
+            if let Some(channel) = repo.channel_overrides.get(&ev.event) {
+                return Decision::Deliver {
+                    channel: channel.clone(),
+                    rule: format!("repos[{}].channel_overrides.{}", repo.name, ev.event),
+                };
+            }
+        }
+
+        let mut declared = false;
+        for route in self.routes.iter().filter(|r| r.event == ev.event) {
+            declared = true;
+            if route.matches(ev) {
+                return Decision::Deliver {
+                    channel: route.channel.clone(),
+                    rule: route.rule.clone(),
+                };
+            }
+        }
+
+        match repo {
+            Some(repo) if !declared => Decision::Deliver {
+                channel: repo.channel.clone(),
+                rule: format!("repos[{}].channel", repo.name),
+            },
+            _ if declared => Decision::Drop {
+                reason: format!(
+                    "no {} route matches actions={:?} branch={:?}",
+                    ev.event, ev.actions, ev.branch
+                ),
+            },
+            _ => Decision::Drop {
+                reason: format!("no route for {}", ev.event),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table() -> RouteTable {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml");
+        RouteTable::compile(&Discovery::load(path).unwrap()).unwrap()
+    }
+
+    fn channel(d: Decision) -> Option<String> {
+        match d {
+            Decision::Deliver { channel, .. } => Some(channel),
+            Decision::Drop { .. } => None,
+        }
+    }
+
+    #[test]
+    fn push_branch_globs() {
+        let t = table();
+        let push = |r: &str| {
+            GitEvent::from_payload("push", &json!({"ref": r, "repository": {"name": "x"}}))
+        };
+        assert_eq!(
+            channel(t.resolve(&push("refs/heads/main"))).as_deref(),
+            Some("#deployments")
+        );
+        assert_eq!(
+            channel(t.resolve(&push("refs/heads/release/1.2"))).as_deref(),
+            Some("#deployments")
+        );
+        assert_eq!(channel(t.resolve(&push("refs/heads/release/1/2"))), None);
+        assert_eq!(channel(t.resolve(&push("refs/heads/feature/x"))), None);
+        assert_eq!(channel(t.resolve(&push("refs/tags/v1"))), None);
+    }
+
+    #[test]
+    fn pull_request_actions_and_merged() {
+        let t = table();
+        let pr = |action: &str, merged: bool| {
+            GitEvent::from_payload(
+                "pull_request",
+                &json!({"action": action, "pull_request": {"merged": merged}, "repository": {"name": "x"}}),
+            )
+        };
+        assert_eq!(
+            channel(t.resolve(&pr("opened", false))).as_deref(),
+            Some("#prs")
+        );
+        assert_eq!(channel(t.resolve(&pr("synchronize", false))), None);
+        assert_eq!(pr("closed", true).actions, ["closed", "merged"]);
+    }
+
+    #[test]
+    fn repo_post_events_and_fallback() {
+        let t = table();
+        let ev = |event: &str, repo: &str| {
+            GitEvent::from_payload(
+                event,
+                &json!({"action": "created", "repository": {"name": repo}}),
+            )
+        };
+        // infra does not post pushes at all.
+        let push = GitEvent::from_payload(
+            "push",
+            &json!({"ref": "refs/heads/main", "repository": {"name": "infra"}}),
+        );
+        assert_eq!(channel(t.resolve(&push)), None);
+        // issue_comment has no route, so the repo channel is used.
+        assert_eq!(
+            channel(t.resolve(&ev("issue_comment", "quantum-symbolic-emulator"))).as_deref(),
+            Some("#deployments")
+        );
+        assert_eq!(channel(t.resolve(&ev("issue_comment", "unlisted"))), None);
+    }
+}
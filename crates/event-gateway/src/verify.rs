@@ -0,0 +1,140 @@
+//! Request signature checks.
+//!
+//! Every comparison goes through `Mac::verify_slice`, which is constant-time;
+//! the old `sigOk` in `src/routes/github.ts` compared hex strings with `===`.
+
+use discovery::HmacAlgo;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+/// Header GitHub uses for its SHA-256 webhook signature.
+pub const GITHUB_SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VerifyError {
+    #[error("missing {0} header")]
+    Missing(String),
+    #[error("signature is not {expected}=<hex>")]
+    Malformed { expected: &'static str },
+    #[error("signature mismatch")]
+    Mismatch,
+}
+
+/// Shared-secret HMAC over the raw request body.
+#[derive(Clone)]
+pub struct Signer {
+    algo: HmacAlgo,
+    key: Vec<u8>,
+}
+
+impl std::fmt::Debug for Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signer")
+            .field("algo", &self.algo)
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Signer {
+    pub fn new(algo: HmacAlgo, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            algo,
+            key: key.into(),
+        }
+    }
+
+    /// GitHub App webhooks are always SHA-256.
+    pub fn github(secret: impl Into<Vec<u8>>) -> Self {
+        Self::new(HmacAlgo::Sha256, secret)
+    }
+
+    /// `<algo>=<hex>` signature for `body`, as a sender would compute it.
+    pub fn sign(&self, body: &[u8]) -> String {
+        let tag = match self.algo {
+            HmacAlgo::Sha1 => mac::<Hmac<Sha1>>(&self.key, body)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+            HmacAlgo::Sha256 => mac::<Hmac<Sha256>>(&self.key, body)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+            HmacAlgo::Sha512 => mac::<Hmac<Sha512>>(&self.key, body)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+        };
+        format!("{}={}", self.algo.as_str(), hex::encode(tag))
+    }
+
+    /// Checks a header value of the form `<algo>=<hex>`; a bare hex digest is
+    /// accepted too, but an explicit prefix must name the configured algo.
+    pub fn verify(&self, body: &[u8], header: &str) -> Result<(), VerifyError> {
+        let malformed = VerifyError::Malformed {
+            expected: self.algo.as_str(),
+        };
+        let digest = match header.split_once('=') {
+            Some((algo, digest)) if algo.eq_ignore_ascii_case(self.algo.as_str()) => digest,
+            Some(_) => return Err(malformed),
+            None => header,
+        };
+        let tag = hex::decode(digest.trim()).map_err(|_| malformed)?;
+        let ok = match self.algo {
+            HmacAlgo::Sha1 => mac::<Hmac<Sha1>>(&self.key, body).verify_slice(&tag),
+            HmacAlgo::Sha256 => mac::<Hmac<Sha256>>(&self.key, body).verify_slice(&tag),
+            HmacAlgo::Sha512 => mac::<Hmac<Sha512>>(&self.key, body).verify_slice(&tag),
+        };
+        ok.map_err(|_| VerifyError::Mismatch)
+    }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], body: &[u8]) -> M {
+    let mut m =
+        <M as hmac::digest::KeyInit>::new_from_slice(key).expect("HMAC accepts any key length");
+    m.update(body);
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_algo() {
+        for algo in [HmacAlgo::Sha1, HmacAlgo::Sha256, HmacAlgo::Sha512] {
+            let s = Signer::new(algo, "k");
+            let sig = s.sign(b"{}");
+            assert_eq!(s.verify(b"{}", &sig), Ok(()));
+            assert_eq!(s.verify(b"{ }", &sig), Err(VerifyError::Mismatch));
+        }
+    }
+
+    #[test]
+    fn matches_github_test_vector() {
+        // https://docs.github.com/en/webhooks/using-webhooks/validating-webhook-deliveries
+        let s = Signer::github("It's a Secret to Everybody");
+        assert_eq!(
+            s.verify(
+                b"Hello, World!",
+                "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_prefix_and_bad_hex() {
+        let s = Signer::new(HmacAlgo::Sha256, "k");
+        let sig = Signer::new(HmacAlgo::Sha1, "k").sign(b"x");
+        assert!(matches!(
+            s.verify(b"x", &sig),
+            Err(VerifyError::Malformed { .. })
+        ));
+        assert!(matches!(
+            s.verify(b"x", "sha256=zz"),
+            Err(VerifyError::Malformed { .. })
+        ));
+    }
+}
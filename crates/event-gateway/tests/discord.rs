@@ -0,0 +1,60 @@
+//! `DiscordRest` against a local fake of the Discord API.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use event_gateway::notify::{ChannelMap, DiscordRest, Embed, Notifier};
+use ratelimit::{metrics, Quota, RateLimiter};
+use serde_json::json;
+
+/// Answers the first post with a 250ms channel rate limit.
+async fn messages(
+    State(calls): State<Arc<AtomicUsize>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    assert_eq!(id, "123");
+    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+        let body = json!({ "message": "You are being rate limited.", "retry_after": 0.25, "global": false });
+        return (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+    }
+    Json(json!({ "id": "1" })).into_response()
+}
+
+#[tokio::test]
+async fn retries_after_discord_429() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route("/channels/{id}/messages", post(messages))
+        .with_state(calls.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let mut channels = ChannelMap::default();
+    channels.insert("#prs", "123");
+    let limiter = Arc::new(RateLimiter::new(Quota::new(60, 5), Quota::new(60, 5)));
+    let discord = DiscordRest::new("token", channels)
+        .with_base_url(format!("http://{addr}"))
+        .with_limiter(limiter);
+    let hits = || {
+        metrics::HITS
+            .with_label_values(&["channel", "discord"])
+            .get()
+    };
+    let before = hits();
+
+    let started = Instant::now();
+    discord
+        .send("#prs", Embed::new("PR merged", "#42"))
+        .await
+        .unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert!(started.elapsed() >= Duration::from_millis(250));
+    assert_eq!(hits(), before + 1);
+}
@@ -0,0 +1,346 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use delivery::memory::InMemory;
+use delivery::Queue;
+use discovery::{Discovery, HmacAlgo};
+use event_gateway::bus::{Bus, BusError, Published};
+use event_gateway::events::Registry;
+use event_gateway::notify::{Embed, Notifier, NotifyError};
+use event_gateway::outbox::Outbox;
+use event_gateway::verify::Signer;
+use event_gateway::{Gateway, Keys};
+use secrets::Secret;
+use tower::ServiceExt;
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<(String, Embed)>>);
+
+#[async_trait]
+impl Notifier for Recorder {
+    async fn send(&self, channel: &str, embed: Embed) -> Result<(), NotifyError> {
+        self.0.lock().unwrap().push((channel.to_string(), embed));
+        Ok(())
+    }
+}
+
+fn router(notifier: Arc<dyn Notifier>) -> Router {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml");
+    let cfg = Discovery::load(path).unwrap();
+    let keys = Keys {
+        hmac: b"hmac-key".to_vec(),
+        github_webhook: b"gh-secret".to_vec(),
+        alertmanager_token: Some(Secret::new("am-token")),
+    };
+    event_gateway::router(&cfg, keys, notifier).unwrap()
+}
+
+fn app() -> (Router, Arc<Recorder>) {
+    let rec = Arc::new(Recorder::default());
+    (router(rec.clone()), rec)
+}
+
+fn signed(path: &str, service: &str, body: &str, key: &str) -> Request<Body> {
+    Request::post(path)
+        .header(
+            "X-Sig",
+            Signer::new(HmacAlgo::Sha256, key).sign(body.as_bytes()),
+        )
+        .header("X-Service", service)
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn event_accepts_glob_matched_service() {
+    let (app, rec) = app();
+    let body = r#"{"type":"deploy","summary":"rolled out"}"#;
+    let res = app
+        .oneshot(signed("/event", "agent-7", body, "hmac-key"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let sent = rec.0.lock().unwrap();
+    assert_eq!(sent[0].0, "#cluster-status");
+    assert_eq!(sent[0].1.title, "agent-7: deploy");
+}
+
+#[tokio::test]
+async fn event_rejects_unlisted_service_and_bad_sig() {
+    let (app, rec) = app();
+    let res = app
+        .clone()
+        .oneshot(signed("/event", "alertmanager", "{}", "hmac-key"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = app
+        .oneshot(signed("/alert", "alertmanager", "{}", "wrong"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(rec.0.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn git_verifies_github_signature_on_both_paths() {
+    let (app, rec) = app();
+    let body = r#"{"ref":"refs/heads/main","repository":{"full_name":"o/r"},"compare":"u"}"#;
+    for path in ["/git", "/webhooks/github"] {
+        let req = Request::post(path)
+            .header("X-GitHub-Event", "push")
+            .header(
+                "X-Hub-Signature-256",
+                Signer::github("gh-secret").sign(body.as_bytes()),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    let req = Request::post("/git")
+        .header("X-GitHub-Event", "push")
+        .header(
+            "X-Sig",
+            Signer::new(HmacAlgo::Sha256, "hmac-key").sign(body.as_bytes()),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    assert_eq!(
+        app.oneshot(req).await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let sent = rec.0.lock().unwrap();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].0, "#deployments");
+}
+
+#[test]
+fn legacy_github_alias_is_routed_once() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml");
+    let mut cfg = Discovery::load(path).unwrap();
+    let endpoints = &mut cfg.event_gateway.endpoints;
+    let git = endpoints.iter().find(|e| e.path == "/git").unwrap().clone();
+    for path in ["/git-mirror", "/webhooks/github"] {
+        let mut extra = git.clone();
+        extra.path = path.to_string();
+        endpoints.push(extra);
+    }
+    let keys = || Keys {
+        hmac: b"hmac-key".to_vec(),
+        github_webhook: b"gh-secret".to_vec(),
+        alertmanager_token: Some(Secret::new("am-token")),
+    };
+    // Axum panics on a route registered twice.
+    let _ = event_gateway::router(&cfg, keys(), Arc::new(Recorder::default())).unwrap();
+    cfg.event_gateway.endpoints.pop();
+    let _ = event_gateway::router(&cfg, keys(), Arc::new(Recorder::default())).unwrap();
+}
+
+#[tokio::test]
+async fn github_redeliveries_are_queued_once() {
+    let queue = Arc::new(InMemory::new());
+    let app = router(Arc::new(Outbox::new(queue.clone())));
+    let body = r#"{"ref":"refs/heads/main","repository":{"full_name":"o/r"},"compare":"u"}"#;
+    for _ in 0..2 {
+        let req = Request::post("/git")
+            .header("X-GitHub-Event", "push")
+            .header("X-GitHub-Delivery", "72d3162e-cc78-11e3-81ab-4c9367dc0958")
+            .header(
+                "X-Hub-Signature-256",
+                Signer::github("gh-secret").sign(body.as_bytes()),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    let claimed = queue.claim(10, std::time::Duration::ZERO).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    let envelope = &claimed[0].envelope;
+    assert_eq!(envelope.key, "github:72d3162e-cc78-11e3-81ab-4c9367dc0958");
+    assert_eq!(envelope.channel, "#deployments");
+}
+
+#[tokio::test]
+async fn alert_accepts_alertmanager_bearer_and_drops_resends() {
+    let (app, rec) = app();
+    let body = serde_json::json!({
+        "version": "4",
+        "groupKey": "{}:{alertname=\"EventGatewayDown\"}",
+        "status": "firing",
+        "groupLabels": { "alertname": "EventGatewayDown" },
+        "alerts": [
+            { "status": "firing", "fingerprint": "a",
+              "labels": { "alertname": "EventGatewayDown", "severity": "critical" } },
+            { "status": "firing", "fingerprint": "b",
+              "labels": { "alertname": "EventGatewayDown", "severity": "info" } },
+        ],
+    })
+    .to_string();
+    let alert = |token: &str| {
+        Request::post("/alert")
+            .header("Authorization", format!("Bearer {token}"))
+            .body(Body::from(body.clone()))
+            .unwrap()
+    };
+    for token in ["nope", "hmac-key"] {
+        let res = app.clone().oneshot(alert(token)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+    for _ in 0..2 {
+        let res = app.clone().oneshot(alert("am-token")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    let sent = rec.0.lock().unwrap();
+    let channels: Vec<&str> = sent.iter().map(|(c, _)| c.as_str()).collect();
+    assert_eq!(channels, ["#alerts", "#cluster-status"]);
+    assert_eq!(sent[0].1.title, "[FIRING:1] EventGatewayDown");
+}
+
+/// Fails the first post to `#cluster-status`.
+#[derive(Default)]
+struct FailsOnce {
+    sent: Recorder,
+    failed: Mutex<bool>,
+}
+
+#[async_trait]
+impl Notifier for FailsOnce {
+    async fn send(&self, channel: &str, embed: Embed) -> Result<(), NotifyError> {
+        if channel == "#cluster-status"
+            && !std::mem::replace(&mut *self.failed.lock().unwrap(), true)
+        {
+            return Err(NotifyError::RateLimited(channel.to_string()));
+        }
+        self.sent.send(channel, embed).await
+    }
+}
+
+#[tokio::test]
+async fn alert_retry_reposts_only_to_failed_channels() {
+    let notifier = Arc::new(FailsOnce::default());
+    let app = router(notifier.clone());
+    let body = serde_json::json!({
+        "version": "4",
+        "groupKey": "{}:{alertname=\"EventGatewayDown\"}",
+        "status": "firing",
+        "alerts": [
+            { "status": "firing", "fingerprint": "a",
+              "labels": { "alertname": "EventGatewayDown", "severity": "critical" } },
+            { "status": "firing", "fingerprint": "b",
+              "labels": { "alertname": "EventGatewayDown", "severity": "info" } },
+        ],
+    })
+    .to_string();
+    let mut statuses = Vec::new();
+    for _ in 0..2 {
+        let req = Request::post("/alert")
+            .header("Authorization", "Bearer am-token")
+            .body(Body::from(body.clone()))
+            .unwrap();
+        statuses.push(app.clone().oneshot(req).await.unwrap().status());
+    }
+    assert_eq!(statuses, [StatusCode::BAD_GATEWAY, StatusCode::OK]);
+    let sent = notifier.sent.0.lock().unwrap();
+    let channels: Vec<&str> = sent.iter().map(|(c, _)| c.as_str()).collect();
+    assert_eq!(channels, ["#alerts", "#cluster-status"]);
+}
+
+#[derive(Default)]
+struct BusRecorder(Mutex<Vec<Published>>);
+
+#[async_trait]
+impl Bus for BusRecorder {
+    async fn publish(&self, event: &Published) -> Result<(), BusError> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn event_validates_against_registry_and_publishes() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let cfg = Discovery::load(root.join("discovery.yml")).unwrap();
+    let registry = Registry::load(root.join("events/registry.yml")).unwrap();
+    let rec = Arc::new(Recorder::default());
+    let bus = Arc::new(BusRecorder::default());
+    let keys = Keys {
+        hmac: b"hmac-key".to_vec(),
+        github_webhook: b"gh-secret".to_vec(),
+        alertmanager_token: Some(Secret::new("am-token")),
+    };
+    let app = Gateway::new(&cfg, keys, rec.clone())
+        .with_registry(registry)
+        .with_bus(bus.clone())
+        .router()
+        .unwrap();
+
+    let deploy = r#"{"type":"deploy","env":"prod","tag":"v1.4.0","status":"succeeded"}"#;
+    let res = app
+        .clone()
+        .oneshot(signed("/event", "valoryield", deploy, "hmac-key"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = app
+        .clone()
+        .oneshot(signed("/event", "agent-7", deploy, "hmac-key"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let bad = r#"{"type":"deploy","env":"qa","tag":"v1","status":"succeeded"}"#;
+    let res = app
+        .oneshot(signed("/event", "valoryield", bad, "hmac-key"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let sent = rec.0.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].1.title, "🚀 valoryield v1.4.0 → prod: succeeded");
+    let published = bus.0.lock().unwrap();
+    assert_eq!(published.len(), 1);
+    assert_eq!(
+        (published[0].kind.as_str(), published[0].version),
+        ("deploy", 1)
+    );
+}
+
+#[tokio::test]
+async fn event_is_published_only_after_delivery_with_its_key() {
+    let cfg =
+        Discovery::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml")).unwrap();
+    let bus = Arc::new(BusRecorder::default());
+    let notifier = Arc::new(FailsOnce::default());
+    let app = Gateway::new(
+        &cfg,
+        Keys {
+            hmac: b"hmac-key".to_vec(),
+            github_webhook: b"gh-secret".to_vec(),
+            alertmanager_token: Some(Secret::new("am-token")),
+        },
+        notifier.clone(),
+    )
+    .with_bus(bus.clone())
+    .router()
+    .unwrap();
+
+    let body = r#"{"type":"deploy","summary":"rolled out"}"#;
+    let mut statuses = Vec::new();
+    for _ in 0..2 {
+        let mut req = signed("/event", "agent-7", body, "hmac-key");
+        req.headers_mut()
+            .insert("X-Idempotency-Key", "run-42".parse().unwrap());
+        statuses.push(app.clone().oneshot(req).await.unwrap().status());
+    }
+    // `/event` posts to `#cluster-status`, whose first post fails.
+    assert_eq!(statuses, [StatusCode::BAD_GATEWAY, StatusCode::OK]);
+    let published = bus.0.lock().unwrap();
+    assert_eq!(published.len(), 1);
+    assert_eq!(published[0].key.as_deref(), Some("agent-7:run-42"));
+}
@@ -0,0 +1,104 @@
+//! The `sources` of recon configs such as `cyber_recon_v2.yaml` and
+//! `llm_recon_v1.yaml`, indexed by where each source was saved.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CatalogError {
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("{path}: {source}")]
+    Parse {
+        path: String,
+        source: serde_yaml::Error,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Source {
+    pub id: String,
+    pub url: String,
+    pub file: String,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+/// The fields of a recon config this crate reads; the rest are ignored.
+#[derive(Deserialize)]
+struct Config {
+    output_dir: String,
+    #[serde(default)]
+    sources: Vec<Source>,
+}
+
+/// Sources by the repository-relative path they are saved at,
+/// `{output_dir}/{file}`.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    sources: BTreeMap<String, Source>,
+}
+
+impl Catalog {
+    /// Adds the sources of the recon config at `path`. A later config's
+    /// source wins when two save to the same file.
+    pub fn load(&mut self, path: &Path) -> Result<(), CatalogError> {
+        let display = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|source| CatalogError::Io {
+            path: display.clone(),
+            source,
+        })?;
+        let config: Config = serde_yaml::from_str(&text).map_err(|source| CatalogError::Parse {
+            path: display,
+            source,
+        })?;
+        let dir = config.output_dir.trim_end_matches('/');
+        for source in config.sources {
+            let file = source.file.trim_start_matches("./");
+            self.sources.insert(format!("{dir}/{file}"), source);
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// The source saved at `rel`, a `/`-separated repository-relative path.
+    pub fn source(&self, rel: &str) -> Option<&Source> {
+        self.sources.get(rel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_saved_files_to_source_ids() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+        let mut catalog = Catalog::default();
+        catalog.load(&root.join("cyber_recon_v2.yaml")).unwrap();
+        catalog.load(&root.join("llm_recon_v1.yaml")).unwrap();
+
+        let nist = catalog.source("recon/cyber_v2/nist_sp800-53.html").unwrap();
+        assert_eq!(nist.id, "nist_sp80053");
+        assert_eq!(nist.category.as_deref(), Some("controls"));
+        let paper = catalog
+            .source("recon/llm_v1/attention_is_all_you_need.pdf")
+            .unwrap();
+        assert_eq!(paper.url, "https://arxiv.org/pdf/1706.03762.pdf");
+        assert!(catalog
+            .source("legal/wyoming_sf0068/SF0068_votes.html")
+            .is_none());
+    }
+}
@@ -0,0 +1,307 @@
+//! Readable text of a saved HTML page, split on its headings.
+
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::{squeeze, Block, Document};
+
+/// Never content.
+const SKIP: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object", "form",
+    "button", "select", "nav", "aside", "dialog", "head",
+];
+
+/// Non-content landmarks.
+const SKIP_ROLES: &[&str] = &[
+    "navigation",
+    "banner",
+    "contentinfo",
+    "search",
+    "complementary",
+    "dialog",
+    "alertdialog",
+];
+
+/// Parts of `class` and `id` tokens that mark site chrome, as in
+/// `site-nav`, `cookie_banner`, `breadcrumbs` or `visually-hidden`.
+const CHROME: &[&str] = &[
+    "nav",
+    "navbar",
+    "navigation",
+    "menu",
+    "breadcrumb",
+    "breadcrumbs",
+    "cookie",
+    "cookies",
+    "consent",
+    "sidebar",
+    "footer",
+    "skip",
+    "social",
+    "share",
+    "banner",
+    "ads",
+    "advert",
+    "advertisement",
+    "newsletter",
+    "subscribe",
+    "toc",
+    "hidden",
+];
+
+/// Starts a new line of text.
+const BLOCK: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "header",
+    "footer",
+    "li",
+    "ul",
+    "ol",
+    "dl",
+    "dt",
+    "dd",
+    "tr",
+    "table",
+    "thead",
+    "tbody",
+    "blockquote",
+    "pre",
+    "figure",
+    "figcaption",
+    "address",
+    "details",
+    "summary",
+    "hr",
+    "br",
+];
+
+fn level(name: &str) -> Option<usize> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+struct Walk {
+    /// With `class`/`id` chrome filtering; dropped if it leaves nothing.
+    strict: bool,
+    /// Whether the walk started at `<body>`, where `<header>` and
+    /// `<footer>` are the site's rather than the article's.
+    page: bool,
+    trail: Vec<(usize, String)>,
+    lines: Vec<String>,
+    line: String,
+    blocks: Vec<Block>,
+}
+
+impl Walk {
+    fn chrome(&self, e: &Element) -> bool {
+        let name = e.name();
+        if SKIP.contains(&name) || (self.page && matches!(name, "header" | "footer")) {
+            return true;
+        }
+        let invisible = e.attr("hidden").is_some()
+            || e.attr("aria-hidden") == Some("true")
+            || e.attr("style")
+                .is_some_and(|s| squeeze(s).replace(' ', "").contains("display:none"));
+        if invisible {
+            return true;
+        }
+        if e.attr("role").is_some_and(|r| SKIP_ROLES.contains(&r)) {
+            return true;
+        }
+        self.strict
+            && e.attr("class")
+                .into_iter()
+                .chain(e.attr("id"))
+                .flat_map(str::split_whitespace)
+                .flat_map(|t| t.split(['-', '_']))
+                .any(|part| CHROME.contains(&part.to_lowercase().as_str()))
+    }
+
+    fn break_line(&mut self) {
+        let line = squeeze(&self.line);
+        self.line.clear();
+        if !line.is_empty() && self.lines.last() != Some(&line) {
+            self.lines.push(line);
+        }
+    }
+
+    /// Ends the current block; one holding only its heading is dropped, the
+    /// trail of the next block still names it.
+    fn flush(&mut self) {
+        self.break_line();
+        let heading_only = !self.trail.is_empty() && self.lines.len() <= 1;
+        let lines = std::mem::take(&mut self.lines);
+        if lines.is_empty() || heading_only {
+            return;
+        }
+        let section = (!self.trail.is_empty()).then(|| {
+            self.trail
+                .iter()
+                .map(|(_, t)| t.as_str())
+                .collect::<Vec<_>>()
+                .join(" > ")
+        });
+        self.blocks.push(Block {
+            page: None,
+            section,
+            text: lines.join("\n"),
+        });
+    }
+
+    fn visit(&mut self, node: ego_tree::NodeRef<'_, Node>) {
+        match node.value() {
+            Node::Text(text) => self.line.push_str(text),
+            Node::Element(e) => {
+                if self.chrome(e) {
+                    return;
+                }
+                let name = e.name();
+                if let Some(level) = level(name) {
+                    let title = ElementRef::wrap(node)
+                        .map(|h| squeeze(&h.text().collect::<String>()))
+                        .unwrap_or_default();
+                    if title.is_empty() {
+                        return;
+                    }
+                    self.flush();
+                    self.trail.retain(|(l, _)| *l < level);
+                    self.trail.push((level, title.clone()));
+                    self.lines.push(title);
+                    return;
+                }
+                let block = BLOCK.contains(&name);
+                if block {
+                    self.break_line();
+                }
+                if name == "li" {
+                    self.line.push_str("- ");
+                }
+                if matches!(name, "td" | "th") {
+                    self.line.push(' ');
+                }
+                for child in node.children() {
+                    self.visit(child);
+                }
+                if block {
+                    self.break_line();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The element holding the page's content: `<main>`, the main landmark or
+/// the only `<article>`, else `<body>`.
+fn content(html: &Html) -> (ElementRef<'_>, bool) {
+    for selector in ["main", "[role=main]"] {
+        let selector = Selector::parse(selector).unwrap();
+        if let Some(e) = html.select(&selector).next() {
+            return (e, false);
+        }
+    }
+    let article = Selector::parse("article").unwrap();
+    let mut articles = html.select(&article);
+    if let (Some(e), None) = (articles.next(), articles.next()) {
+        return (e, false);
+    }
+    let body = Selector::parse("body").unwrap();
+    let root = html.select(&body).next().unwrap_or(html.root_element());
+    (root, true)
+}
+
+pub(crate) fn document(bytes: &[u8]) -> Document {
+    let html = Html::parse_document(&String::from_utf8_lossy(bytes));
+    let title = Selector::parse("title").unwrap();
+    let title = html
+        .select(&title)
+        .next()
+        .map(|t| squeeze(&t.text().collect::<String>()))
+        .filter(|t| !t.is_empty());
+    let (root, page) = content(&html);
+    let mut blocks = Vec::new();
+    for strict in [true, false] {
+        let mut walk = Walk {
+            strict,
+            page,
+            trail: Vec::new(),
+            lines: Vec::new(),
+            line: String::new(),
+            blocks: Vec::new(),
+        };
+        for child in root.children() {
+            walk.visit(child);
+        }
+        walk.flush();
+        blocks = walk.blocks;
+        if !blocks.is_empty() {
+            break;
+        }
+    }
+    Document { title, blocks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_main_content_split_by_heading() {
+        let page = r##"<!DOCTYPE html>
+<html><head><title> NIST  CSF </title><script>var x = 1;</script></head>
+<body class="has-sidebar">
+  <a class="skip-link" href="#main">Skip to main content</a>
+  <header><nav><ul><li>Home</li><li>About</li></ul></nav></header>
+  <div class="cookie-banner">We use cookies.</div>
+  <div id="content">
+    <h1>Cybersecurity Framework</h1>
+    <p>The CSF helps   organizations
+       manage risk.</p>
+    <h2>Functions</h2>
+    <h3>Govern</h3>
+    <ul><li>Context</li><li>Strategy &amp; policy</li></ul>
+    <table><tr><th>ID</th><th>Name</th></tr><tr><td>GV</td><td>Govern</td></tr></table>
+    <div class="breadcrumbs">Home / CSF</div>
+  </div>
+  <aside>Related links</aside>
+  <footer>Contact us</footer>
+</body></html>"##;
+        let doc = document(page.as_bytes());
+        assert_eq!(doc.title.as_deref(), Some("NIST CSF"));
+        let got: Vec<(Option<&str>, &str)> = doc
+            .blocks
+            .iter()
+            .map(|b| (b.section.as_deref(), b.text.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (
+                    Some("Cybersecurity Framework"),
+                    "Cybersecurity Framework\nThe CSF helps organizations manage risk."
+                ),
+                (
+                    Some("Cybersecurity Framework > Functions > Govern"),
+                    "Govern\n- Context\n- Strategy & policy\nID Name\nGV Govern"
+                ),
+            ]
+        );
+
+        // Content under `<main>` keeps the article's own header.
+        let doc = document(
+            b"<body><nav>Menu</nav><main><header><h1>Top 10</h1></header><p>A01</p></main></body>",
+        );
+        assert_eq!(doc.blocks[0].text, "Top 10\nA01");
+    }
+}
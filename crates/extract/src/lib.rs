@@ -0,0 +1,93 @@
+//! Clean text from the PDF and saved HTML sources the recon configs fetch
+//! (`recon/llm_v1/*.pdf`, `recon/cyber_v2/*.html`, `legal/wyoming_sf0068/`).
+//!
+//! [`extract`] turns a document into [`Block`]s: runs of text that share a
+//! page and a section, so every chunk cut from a block can be cited as
+//! "page 4, 3.2 Attention". PDFs lose their running headers and footers,
+//! page numbers and end-of-line hyphenation; HTML pages lose scripts,
+//! navigation, banners, footers and cookie notices, keeping the `<main>`
+//! or `<article>` content when the page marks it. [`Catalog`] maps the
+//! fetched files back to the source ids of the recon configs.
+
+mod catalog;
+mod html;
+mod pdf;
+
+use std::path::Path;
+
+pub use catalog::{Catalog, CatalogError, Source};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pdf,
+    Html,
+}
+
+impl Format {
+    /// The format a document path implies, if any.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "pdf" => Some(Format::Pdf),
+            "html" | "htm" | "xhtml" => Some(Format::Html),
+            _ => None,
+        }
+    }
+
+    /// The format of a document at `path`, trusting its content over its
+    /// extension: fetches sometimes save an HTML error page as `.pdf`.
+    pub fn of(path: impl AsRef<Path>, bytes: &[u8]) -> Option<Self> {
+        let by_path = Self::from_path(path)?;
+        if bytes.starts_with(b"%PDF-") {
+            return Some(Format::Pdf);
+        }
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_lowercase();
+        if head.contains("<html") || head.contains("<!doctype html") {
+            return Some(Format::Html);
+        }
+        Some(by_path)
+    }
+}
+
+/// Text from one page (PDF only) and one section of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// 1-based.
+    pub page: Option<u32>,
+    /// Heading the text falls under; for HTML the heading trail
+    /// (`Overview > Scope`).
+    pub section: Option<String>,
+    /// The page's lines for PDFs; paragraphs and list items, one per
+    /// line, for HTML.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    /// The `<title>` of an HTML page.
+    pub title: Option<String>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExtractError {
+    #[error("pdf: {0}")]
+    Pdf(String),
+    /// The PDF parser panicked, which some malformed files make it do.
+    #[error("pdf parser panicked: {0}")]
+    Panicked(String),
+}
+
+/// Extracts `bytes`, a document in `format`. CPU-bound; large PDFs take
+/// seconds.
+pub fn extract(format: Format, bytes: &[u8]) -> Result<Document, ExtractError> {
+    match format {
+        Format::Pdf => pdf::document(bytes),
+        Format::Html => Ok(html::document(bytes)),
+    }
+}
+
+/// Collapses runs of whitespace to one space and trims.
+pub(crate) fn squeeze(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
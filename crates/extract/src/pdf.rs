@@ -0,0 +1,189 @@
+//! PDF text per page, via `pdf-extract`, cleaned of page furniture.
+
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{squeeze, Block, Document, ExtractError};
+
+/// Lines at either end of a page checked for running headers and footers.
+const EDGE_LINES: usize = 6;
+
+static PAGE_NUMBER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(page\s+)?[-–]?\s*\d{1,4}\s*[-–]?(\s+of\s+\d{1,4})?$").unwrap()
+});
+
+/// `3 Model Architecture`, `3.2.1 Scaled Dot-Product Attention`.
+static NUMBERED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d{1,2}(\.\d{1,2}){0,3}\.?\s+[A-Z][A-Za-z]").unwrap());
+
+static NAMED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(abstract|introduction|background|related work|discussion|conclusions?|references|bibliography|acknowledge?ments?|appendix(\s+[A-Z0-9].*)?)$",
+    )
+    .unwrap()
+});
+
+/// `Section 1.` of a bill, or a statute like `17-31-104.`.
+static LEGAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(Section \d+\.|\d{1,2}-\d{1,2}-\d{3,4}\.)").unwrap());
+
+/// The section a line opens, if it looks like a heading.
+fn heading(line: &str) -> Option<String> {
+    if let Some(m) = LEGAL.find(line) {
+        return Some(m.as_str().trim_end_matches('.').to_string());
+    }
+    let words = line.split_whitespace().count();
+    let short = line.len() <= 80 && words <= 12;
+    let sentence = line.ends_with(['.', ',', ';', ':']);
+    if short && !sentence && (NUMBERED.is_match(line) || NAMED.is_match(line)) {
+        return Some(line.to_string());
+    }
+    None
+}
+
+/// A line with its digits masked, so `Page 3 of 8` matches `Page 4 of 8`.
+fn shape(line: &str) -> String {
+    line.chars()
+        .map(|c| if c.is_ascii_digit() { '#' } else { c })
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Shapes that sit at the top or bottom of more than half of the pages.
+fn running_lines(pages: &[Vec<String>]) -> HashSet<String> {
+    if pages.len() < 3 {
+        return HashSet::new();
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for page in pages {
+        let tail = page
+            .len()
+            .saturating_sub(EDGE_LINES)
+            .max(EDGE_LINES.min(page.len()));
+        let edges: HashSet<String> = page[..EDGE_LINES.min(page.len())]
+            .iter()
+            .chain(&page[tail..])
+            .map(|l| shape(l))
+            .collect();
+        for s in edges {
+            *seen.entry(s).or_default() += 1;
+        }
+    }
+    seen.into_iter()
+        .filter(|(_, n)| n * 2 > pages.len())
+        .map(|(s, _)| s)
+        .collect()
+}
+
+/// Non-empty, whitespace-collapsed lines with words hyphenated across a
+/// line break joined back up.
+fn lines(page: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for line in page.lines().map(squeeze).filter(|l| !l.is_empty()) {
+        if let Some(last) = out.last_mut() {
+            let broken = last.ends_with('-')
+                && last[..last.len() - 1].ends_with(|c: char| c.is_alphabetic())
+                && line.starts_with(|c: char| c.is_lowercase());
+            if broken {
+                last.pop();
+                last.push_str(&line);
+                continue;
+            }
+        }
+        out.push(line);
+    }
+    out
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+pub(crate) fn document(bytes: &[u8]) -> Result<Document, ExtractError> {
+    let pages = panic::catch_unwind(AssertUnwindSafe(|| {
+        pdf_extract::extract_text_from_mem_by_pages(bytes)
+    }))
+    .map_err(|p| ExtractError::Panicked(panic_message(p)))?
+    .map_err(|e| ExtractError::Pdf(e.to_string()))?;
+    let pages: Vec<Vec<String>> = pages.iter().map(|p| lines(p)).collect();
+    let running = running_lines(&pages);
+
+    let mut doc = Document::default();
+    let mut section: Option<String> = None;
+    for (n, page) in pages.iter().enumerate() {
+        let number = Some(n as u32 + 1);
+        let edge = |i: usize| i < EDGE_LINES || i + EDGE_LINES >= page.len();
+        let mut text: Vec<&str> = Vec::new();
+        for (i, line) in page.iter().enumerate() {
+            if PAGE_NUMBER.is_match(line) || (edge(i) && running.contains(&shape(line))) {
+                continue;
+            }
+            if let Some(h) = heading(line) {
+                push(&mut doc.blocks, number, &section, &mut text);
+                section = Some(h);
+            }
+            text.push(line);
+        }
+        push(&mut doc.blocks, number, &section, &mut text);
+    }
+    Ok(doc)
+}
+
+fn push(
+    blocks: &mut Vec<Block>,
+    page: Option<u32>,
+    section: &Option<String>,
+    text: &mut Vec<&str>,
+) {
+    if !text.is_empty() {
+        blocks.push(Block {
+            page,
+            section: section.clone(),
+            text: text.join("\n"),
+        });
+        text.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_page_furniture_and_tracks_sections() {
+        let pages: Vec<Vec<String>> = ["ships", "tokens", "members", "votes", "funds", "names"]
+            .iter()
+            .enumerate()
+            .map(|(n, word)| {
+                let n = n + 1;
+                lines(&format!(
+                    "ENROLLED ACT NO. 7\n\nSection {n}.  W.S. 17-31-10{n} covers {word} and is cre-\nated to read\n\n{n}\n"
+                ))
+            })
+            .collect();
+        assert_eq!(
+            pages[0][1],
+            "Section 1. W.S. 17-31-101 covers ships and is created to read"
+        );
+        let running = running_lines(&pages);
+        assert!(running.contains(&shape("ENROLLED ACT NO. 7")));
+        assert!(!running.contains(&shape(&pages[0][1])));
+        assert_eq!(heading(&pages[1][1]).as_deref(), Some("Section 2"));
+        assert_eq!(
+            heading("3.2 Multi-Head Attention").as_deref(),
+            Some("3.2 Multi-Head Attention")
+        );
+        assert_eq!(heading("12 layers were used in all runs."), None);
+        assert!(PAGE_NUMBER.is_match("Page 3 of 8"));
+
+        let err = document(b"<!DOCTYPE html><html></html>").unwrap_err();
+        assert!(matches!(err, ExtractError::Pdf(_)));
+    }
+}
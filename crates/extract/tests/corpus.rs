@@ -0,0 +1,55 @@
+//! Extraction over documents saved in this repository.
+
+use std::path::Path;
+
+use extract::{extract, ExtractError, Format};
+
+fn open(rel: &str) -> Result<extract::Document, ExtractError> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../..")
+        .join(rel);
+    let bytes = std::fs::read(&path).unwrap();
+    extract(Format::of(&path, &bytes).unwrap(), &bytes)
+}
+
+#[test]
+fn enrolled_act_keeps_pages_and_statute_sections() {
+    let doc = open("legal/wyoming_sf0068/SF0068_2022_Enrolled_alt.pdf").unwrap();
+    assert!(doc.blocks.iter().all(
+        |b| !b.text.contains("ORIGINAL SENATE") && !b.text.contains("SIXTY-SIXTH LEGISLATURE")
+    ));
+    let definitions = doc
+        .blocks
+        .iter()
+        .find(|b| b.section.as_deref() == Some("17-31-102"))
+        .unwrap();
+    assert_eq!(definitions.page, Some(1));
+    assert!(definitions.text.starts_with("17-31-102. Definitions."));
+    // The section carries over the page break.
+    assert!(doc
+        .blocks
+        .iter()
+        .any(|b| b.page == Some(2) && b.section.as_deref() == Some("17-31-102")));
+    assert_eq!(doc.blocks.last().unwrap().page, Some(8));
+}
+
+#[test]
+fn saved_pages_lose_site_chrome() {
+    let doc = open("recon/cyber_v2/nist_csf.html").unwrap();
+    assert_eq!(doc.title.as_deref(), Some("Cybersecurity Framework | NIST"));
+    let text: Vec<&str> = doc.blocks.iter().map(|b| b.text.as_str()).collect();
+    let text = text.join("\n");
+    assert!(text.contains("Quick Start Guides"));
+    assert!(!text.contains("Skip to main content"));
+    assert!(doc
+        .blocks
+        .iter()
+        .any(|b| b.section.as_deref() == Some("CSF 2.0 Profiles")));
+
+    // A broken fetch: the page is a script shell with no text.
+    let doc = open("recon/cyber_v2/mitre_attack_evals_fixed.html").unwrap();
+    assert!(doc.blocks.is_empty());
+    // A "PDF" that is really a plain-text error message.
+    let err = open("legal/wyoming_sf0068/legal/ml_research/hf_transformers_docs.pdf").unwrap_err();
+    assert!(matches!(err, ExtractError::Pdf(_)));
+}
@@ -0,0 +1,523 @@
+//! Incremental repository ingestion for recon retrieval.
+//!
+//! [`Ingestor::run`] walks a repository, hashes every file it would index
+//! and compares the hash with the manifest kept next to the vector index.
+//! Only new and changed files are chunked and embedded; the chunks of
+//! changed, deleted and newly ignored files are removed from the index.
+//!
+//! Files are split with [`chunker`], so a chunk is a function, YAML list
+//! item or Markdown section where the file's structure allows. Chunk `n`
+//! of `path` is stored as `{path}#{n}` with metadata `path`, `chunk`,
+//! `total_chunks`, `extension`, `start_line`, `end_line` and `text`, plus
+//! `symbol` and `key_path` when the chunker found them. A file only enters
+//! the manifest once all of its chunks are indexed, so a run that fails
+//! part-way resumes where it stopped.
+//!
+//! PDFs and saved HTML pages go through [`extract`] first and are chunked
+//! per page and section. Their chunks carry what a citation needs instead
+//! of line numbers: `source_id` (the recon config's id for the file, see
+//! [`Ingestor::with_catalog`], else the file stem), `url` and `category`
+//! for catalogued sources, `page`, `section` and `title`.
+
+mod manifest;
+mod walk;
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chunker::Chunker;
+use embed::{EmbedError, Embedder};
+use extract::{Catalog, Document, Format};
+use sha2::{Digest, Sha256};
+use vectors::{Config, HnswParams, IndexError, Metadata, VectorIndex};
+
+use manifest::{FileEntry, Manifest};
+
+/// `RELEVANT_EXTENSIONS` from `recon/ingest/ingest.py`.
+pub const EXTENSIONS: &[&str] = &[
+    ".py",
+    ".ts",
+    ".tsx",
+    ".js",
+    ".java",
+    ".go",
+    ".rs",
+    ".cs",
+    ".cpp",
+    ".h",
+    ".hpp",
+    ".c",
+    ".md",
+    ".yaml",
+    ".yml",
+    ".toml",
+    ".json",
+    ".txt",
+    ".sh",
+    ".ps1",
+    ".dockerfile",
+];
+
+/// Documents whose text is extracted before chunking.
+pub const DOCUMENT_EXTENSIONS: &[&str] = &[".pdf", ".html", ".htm"];
+
+/// `IGNORE_DIRECTORIES` from `recon/ingest/ingest.py`.
+pub const IGNORE_DIRECTORIES: &[&str] = &[
+    "node_modules",
+    "dist",
+    ".git",
+    "__pycache__",
+    ".venv",
+    "venv",
+    "build",
+    "target",
+    ".next",
+    ".nuxt",
+    "coverage",
+    ".pytest_cache",
+    ".mypy_cache",
+    "*.egg-info",
+];
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Lower-case, with the leading dot.
+    pub extensions: BTreeSet<String>,
+    /// Directory names, or globs over them, that are never descended into.
+    pub ignore_dirs: Vec<String>,
+    /// Larger files are skipped.
+    pub max_file_size: u64,
+    /// The same for PDFs and HTML pages, whose text is a fraction of
+    /// their size.
+    pub max_document_size: u64,
+    /// Word budget per chunk; see [`Chunker::new`].
+    pub chunk_words: usize,
+    pub overlap_words: usize,
+    /// Chunks per embedding request.
+    pub batch_size: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            extensions: EXTENSIONS
+                .iter()
+                .chain(DOCUMENT_EXTENSIONS)
+                .map(|e| e.to_string())
+                .collect(),
+            ignore_dirs: IGNORE_DIRECTORIES.iter().map(|d| d.to_string()).collect(),
+            max_file_size: 2_000_000,
+            max_document_size: 64_000_000,
+            chunk_words: 400,
+            overlap_words: 60,
+            batch_size: 32,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IngestError {
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("walking the repository: {0}")]
+    Walk(String),
+    #[error("manifest: {0}")]
+    Manifest(String),
+    #[error("index: {0}")]
+    Index(#[from] IndexError),
+    #[error("embedding: {0}")]
+    Embed(#[from] EmbedError),
+    #[error("{0}")]
+    Config(String),
+}
+
+/// What a run did and how fast.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Files with an indexed extension.
+    pub files: usize,
+    /// New or changed files, now indexed.
+    pub indexed: usize,
+    pub unchanged: usize,
+    /// Files whose chunks were dropped because they are gone or ignored.
+    pub removed: usize,
+    /// Files too large or unreadable.
+    pub skipped: usize,
+    /// Chunks embedded.
+    pub chunks: usize,
+    /// Bytes read from new and changed files.
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    pub fn chunks_per_sec(&self) -> f64 {
+        self.chunks as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files: {} indexed, {} unchanged, {} removed, {} skipped; \
+             {} chunks in {:.2}s ({:.1} chunks/s, {:.1} KiB/s)",
+            self.files,
+            self.indexed,
+            self.unchanged,
+            self.removed,
+            self.skipped,
+            self.chunks,
+            self.elapsed.as_secs_f64(),
+            self.chunks_per_sec(),
+            self.bytes_per_sec() / 1024.0,
+        )
+    }
+}
+
+pub fn chunk_id(path: &str, n: usize) -> String {
+    format!("{path}#{n}")
+}
+
+/// A chunk's text and the metadata it is stored with, beyond what
+/// [`commit`] adds.
+struct Piece {
+    text: String,
+    metadata: Metadata,
+}
+
+/// A new or changed file waiting for its embeddings.
+struct Pending {
+    rel: String,
+    sha256: String,
+    chunks: Vec<Piece>,
+    vectors: Vec<Vec<f32>>,
+}
+
+pub struct Ingestor {
+    embedder: Arc<dyn Embedder>,
+    dir: PathBuf,
+    settings: Settings,
+    catalog: Catalog,
+}
+
+impl Ingestor {
+    /// Keeps the vector index and manifest in `dir`.
+    pub fn new(embedder: Arc<dyn Embedder>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            embedder,
+            dir: dir.into(),
+            settings: Settings::default(),
+            catalog: Catalog::default(),
+        }
+    }
+
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Cites documents saved by the recon configs in `catalog` by their
+    /// source id and URL.
+    pub fn with_catalog(mut self, catalog: Catalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    fn chunker(&self) -> Chunker {
+        Chunker::new(self.settings.chunk_words, self.settings.overlap_words)
+    }
+
+    /// Brings the index up to date with the repository at `root`.
+    pub async fn run(&self, root: &Path) -> Result<Report, IngestError> {
+        let started = Instant::now();
+        let mut report = Report::default();
+        let mut index = match self.dir.join("index.json").exists() {
+            true => Some(VectorIndex::open(&self.dir)?),
+            false => None,
+        };
+        // Without an index the manifest's hashes vouch for nothing.
+        let mut manifest = match index {
+            Some(_) => Manifest::load(&self.dir)?,
+            None => Manifest::default(),
+        };
+
+        let found = {
+            let (root, settings) = (root.to_path_buf(), self.settings.clone());
+            tokio::task::spawn_blocking(move || walk::walk(&root, &settings))
+                .await
+                .expect("walk panicked")?
+        };
+        report.files = found.len();
+
+        let mut kept = HashSet::new();
+        let mut pending = Vec::new();
+        for file in found {
+            let limit = match Format::from_path(&file.rel) {
+                Some(_) => self.settings.max_document_size,
+                None => self.settings.max_file_size,
+            };
+            if file.size > limit {
+                tracing::warn!(path = %file.rel, size = file.size, "skipping large file");
+                report.skipped += 1;
+                continue;
+            }
+            let bytes = match tokio::fs::read(&file.path).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::warn!(path = %file.rel, error = %e, "skipping unreadable file");
+                    report.skipped += 1;
+                    continue;
+                }
+            };
+            kept.insert(file.rel.clone());
+            let sha256 = hex::encode(Sha256::digest(&bytes));
+            if manifest
+                .files
+                .get(&file.rel)
+                .is_some_and(|e| e.sha256 == sha256)
+            {
+                report.unchanged += 1;
+                continue;
+            }
+            report.bytes += bytes.len() as u64;
+            let chunks = match Format::of(&file.rel, &bytes) {
+                Some(format) => self.document(&file.rel, format, bytes).await,
+                None => self.text(&file.rel, &bytes),
+            };
+            pending.push(Pending {
+                rel: file.rel,
+                sha256,
+                chunks,
+                vectors: Vec::new(),
+            });
+        }
+
+        let gone: Vec<String> = manifest
+            .files
+            .keys()
+            .filter(|rel| !kept.contains(*rel))
+            .cloned()
+            .collect();
+        for rel in gone {
+            if let Some(index) = &mut index {
+                remove_chunks(index, &rel);
+            }
+            manifest.files.remove(&rel);
+            report.removed += 1;
+        }
+
+        let outcome = self
+            .embed(&mut index, &mut manifest, pending, &mut report)
+            .await;
+        if report.indexed + report.removed > 0 {
+            self.save(index.as_mut(), &manifest)?;
+        }
+        outcome?;
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+
+    fn text(&self, rel: &str, bytes: &[u8]) -> Vec<Piece> {
+        let text = String::from_utf8_lossy(bytes).replace('\u{FFFD}', "");
+        if text.trim().chars().count() < 10 {
+            return Vec::new();
+        }
+        let mut pieces = Vec::new();
+        for chunk in self.chunker().chunk(rel, &text) {
+            let mut metadata = Metadata::from([
+                ("start_line".to_string(), chunk.start_line.to_string()),
+                ("end_line".to_string(), chunk.end_line.to_string()),
+            ]);
+            if let Some(symbol) = chunk.symbol {
+                metadata.insert("symbol".to_string(), symbol);
+            }
+            if let Some(key_path) = chunk.key_path {
+                metadata.insert("key_path".to_string(), key_path);
+            }
+            pieces.push(Piece {
+                text: chunk.text,
+                metadata,
+            });
+        }
+        pieces
+    }
+
+    /// Chunks a PDF or HTML page per block of extracted text. A document
+    /// with no extractable text, such as a failed fetch, has no chunks.
+    async fn document(&self, rel: &str, format: Format, bytes: Vec<u8>) -> Vec<Piece> {
+        let extracted = tokio::task::spawn_blocking(move || extract::extract(format, &bytes))
+            .await
+            .expect("extraction panicked");
+        let doc = match extracted {
+            Ok(doc) => doc,
+            Err(e) => {
+                tracing::warn!(path = %rel, error = %e, "no text extracted");
+                Document::default()
+            }
+        };
+        let source = self.catalog.source(rel);
+        let mut cite = Metadata::new();
+        let source_id = match source {
+            Some(s) => s.id.clone(),
+            None => Path::new(rel)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        cite.insert("source_id".to_string(), source_id);
+        if let Some(source) = source {
+            cite.insert("url".to_string(), source.url.clone());
+            if let Some(category) = &source.category {
+                cite.insert("category".to_string(), category.clone());
+            }
+        }
+        if let Some(title) = doc.title {
+            cite.insert("title".to_string(), title);
+        }
+
+        let mut pieces = Vec::new();
+        for block in doc.blocks {
+            // Blocks are plain text; the chunker only windows them.
+            for chunk in self.chunker().chunk("block.txt", &block.text) {
+                let mut metadata = cite.clone();
+                if let Some(page) = block.page {
+                    metadata.insert("page".to_string(), page.to_string());
+                }
+                if let Some(section) = &block.section {
+                    metadata.insert("section".to_string(), section.clone());
+                }
+                pieces.push(Piece {
+                    text: chunk.text,
+                    metadata,
+                });
+            }
+        }
+        pieces
+    }
+
+    /// Embeds the pending chunks in batches, committing each file to the
+    /// index as soon as all of its chunks have vectors.
+    async fn embed(
+        &self,
+        index: &mut Option<VectorIndex>,
+        manifest: &mut Manifest,
+        mut pending: Vec<Pending>,
+        report: &mut Report,
+    ) -> Result<(), IngestError> {
+        let queue: Vec<(usize, usize)> = pending
+            .iter()
+            .enumerate()
+            .flat_map(|(f, p)| (0..p.chunks.len()).map(move |c| (f, c)))
+            .collect();
+        let mut batches = queue.chunks(self.settings.batch_size.max(1));
+        let mut committed = 0;
+        loop {
+            while committed < pending.len()
+                && pending[committed].vectors.len() == pending[committed].chunks.len()
+            {
+                commit(index, manifest, &pending[committed])?;
+                report.indexed += 1;
+                committed += 1;
+            }
+            let Some(batch) = batches.next() else {
+                return Ok(());
+            };
+            let texts: Vec<String> = batch
+                .iter()
+                .map(|&(f, c)| pending[f].chunks[c].text.clone())
+                .collect();
+            let vectors = self.embedder.embed(&texts).await?;
+            if vectors.len() != texts.len() {
+                return Err(EmbedError::Malformed(format!(
+                    "{} embeddings for {} texts",
+                    vectors.len(),
+                    texts.len()
+                ))
+                .into());
+            }
+            for (&(f, _), v) in batch.iter().zip(vectors) {
+                pending[f].vectors.push(v);
+            }
+            report.chunks += texts.len();
+        }
+    }
+
+    fn save(
+        &self,
+        index: Option<&mut VectorIndex>,
+        manifest: &Manifest,
+    ) -> Result<(), IngestError> {
+        std::fs::create_dir_all(&self.dir).map_err(|source| IngestError::Io {
+            path: self.dir.display().to_string(),
+            source,
+        })?;
+        // The index goes first: a manifest entry must never claim chunks
+        // the saved index lacks.
+        if let Some(index) = index {
+            if index.tombstones() > index.len() {
+                index.compact();
+            }
+            index.save(&self.dir)?;
+        }
+        manifest.save(&self.dir)
+    }
+}
+
+/// Drops every chunk of `rel`, returning how many there were.
+fn remove_chunks(index: &mut VectorIndex, rel: &str) -> usize {
+    let mut n = 0;
+    while index.remove(&chunk_id(rel, n)) {
+        n += 1;
+    }
+    n
+}
+
+fn commit(
+    index: &mut Option<VectorIndex>,
+    manifest: &mut Manifest,
+    p: &Pending,
+) -> Result<(), IngestError> {
+    if index.is_none() {
+        if let Some(v) = p.vectors.first() {
+            let config = Config::new(v.len()).with_hnsw(HnswParams::default());
+            *index = Some(VectorIndex::new(config));
+        }
+    }
+    if let Some(index) = index {
+        remove_chunks(index, &p.rel);
+        let extension = Path::new(&p.rel)
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy().to_lowercase()))
+            .unwrap_or_default();
+        for (n, (piece, vector)) in p.chunks.iter().zip(&p.vectors).enumerate() {
+            let mut metadata = piece.metadata.clone();
+            metadata.extend([
+                ("path".to_string(), p.rel.clone()),
+                ("chunk".to_string(), n.to_string()),
+                ("total_chunks".to_string(), p.chunks.len().to_string()),
+                ("extension".to_string(), extension.clone()),
+                ("text".to_string(), piece.text.clone()),
+            ]);
+            index.upsert(chunk_id(&p.rel, n), vector, metadata)?;
+        }
+    }
+    manifest.files.insert(
+        p.rel.clone(),
+        FileEntry {
+            sha256: p.sha256.clone(),
+            chunks: p.chunks.len(),
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! `recon-ingest`: indexes a repository for recon retrieval, re-embedding
+//! only the files that changed since the last run.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::Parser;
+use embed::batch::Batching;
+use embed::cache::{Cache, Cached};
+use embed::Embedder;
+use extract::Catalog;
+use ingest::{Ingestor, Settings};
+
+/// Recon configs read from the repository root when `--catalog` is not
+/// given.
+const CATALOGS: &[&str] = &["cyber_recon_v2.yaml", "llm_recon_v1.yaml"];
+
+#[derive(Parser)]
+#[command(
+    name = "recon-ingest",
+    about = "Incrementally index a repository for recon"
+)]
+struct Args {
+    /// Repository to index.
+    repo: PathBuf,
+    /// Directory holding the vector index and its manifest.
+    #[arg(long, env = "INDEX_DIR", default_value = "recon-index")]
+    index: PathBuf,
+    #[arg(long, env = "EMBED_URL", default_value = embed::http::DEFAULT_URL)]
+    embed_url: String,
+    /// Model behind `EMBED_URL`; cached vectors are kept per model.
+    #[arg(long, env = "EMBED_MODEL", default_value = embed::http::DEFAULT_MODEL)]
+    embed_model: String,
+    /// Sentence-transformers checkpoint to embed with in process instead
+    /// of calling `EMBED_URL`, in builds with the `local` feature; its
+    /// directory name keys the cache.
+    #[arg(long, env = "EMBED_MODEL_DIR")]
+    embed_model_dir: Option<PathBuf>,
+    /// Embedding cache shared with `recon-retriever`.
+    #[arg(long, env = "EMBED_CACHE", default_value = "recon-embed-cache")]
+    embed_cache: PathBuf,
+    #[arg(long)]
+    no_embed_cache: bool,
+    /// Embedding requests in flight.
+    #[arg(long, env = "EMBED_CONCURRENCY", default_value_t = embed::batch::DEFAULT_CONCURRENCY)]
+    embed_concurrency: usize,
+    /// Words per chunk.
+    #[arg(long, env = "CHUNK_SIZE", default_value_t = 400)]
+    chunk_size: usize,
+    /// Words shared by consecutive chunks.
+    #[arg(long, env = "OVERLAP", default_value_t = 60)]
+    overlap: usize,
+    /// Chunks per embedding request.
+    #[arg(long, env = "BATCH_SIZE", default_value_t = 32)]
+    batch_size: usize,
+    /// Recon config whose `sources` name the fetched documents; repeatable.
+    #[arg(long = "catalog", env = "RECON_CATALOGS", value_delimiter = ',')]
+    catalogs: Vec<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+    let settings = Settings {
+        chunk_words: args.chunk_size,
+        overlap_words: args.overlap,
+        // Each call is split back into requests of `batch_size`, sent
+        // concurrently.
+        batch_size: args.batch_size * args.embed_concurrency.max(1),
+        ..Settings::default()
+    };
+    let mut catalog = Catalog::default();
+    if args.catalogs.is_empty() {
+        for name in CATALOGS {
+            let path = args.repo.join(name);
+            if path.exists() {
+                catalog.load(&path)?;
+            }
+        }
+    } else {
+        for path in &args.catalogs {
+            catalog.load(path)?;
+        }
+    }
+    let (backend, model) = embed::backend(
+        &args.embed_url,
+        &args.embed_model,
+        args.embed_model_dir.as_deref(),
+    )?;
+    let batching = Batching::new(backend)
+        .with_batch_size(args.batch_size)
+        .with_concurrency(args.embed_concurrency);
+    let embedder: Arc<dyn Embedder> = if args.no_embed_cache {
+        Arc::new(batching)
+    } else {
+        let cache = Cache::open(&args.embed_cache, &model)?;
+        Arc::new(Cached::new(Arc::new(batching), cache))
+    };
+    let report = Ingestor::new(embedder, args.index)
+        .with_settings(settings)
+        .with_catalog(catalog)
+        .run(&args.repo)
+        .await?;
+    println!("{}: {report}", args.repo.display());
+    Ok(())
+}
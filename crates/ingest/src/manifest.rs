@@ -0,0 +1,52 @@
+//! What the index holds for each file, so unchanged files are skipped.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::IngestError;
+
+pub(crate) const FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FileEntry {
+    /// Hex SHA-256 of the file's bytes.
+    pub sha256: String,
+    /// Chunks indexed under `{path}#0` … `{path}#{chunks - 1}`.
+    pub chunks: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+impl Manifest {
+    /// An empty manifest if `dir` has none yet.
+    pub fn load(dir: &Path) -> Result<Self, IngestError> {
+        let path = dir.join(FILE);
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| IngestError::Manifest(format!("{}: {e}", path.display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(IngestError::Io {
+                path: path.display().to_string(),
+                source: e,
+            }),
+        }
+    }
+
+    /// Replaces `dir`'s manifest atomically.
+    pub fn save(&self, dir: &Path) -> Result<(), IngestError> {
+        let path = dir.join(FILE);
+        let tmp = dir.join(format!("{FILE}.tmp"));
+        let io = |path: &Path| {
+            let path = path.display().to_string();
+            move |source| IngestError::Io { path, source }
+        };
+        let body = serde_json::to_vec_pretty(self).expect("manifest serializes");
+        std::fs::write(&tmp, body).map_err(io(&tmp))?;
+        std::fs::rename(&tmp, &path).map_err(io(&path))
+    }
+}
@@ -0,0 +1,109 @@
+//! Finding the files worth indexing.
+
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+
+use crate::{IngestError, Settings};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Found {
+    pub path: PathBuf,
+    /// Relative to the repository root, `/`-separated.
+    pub rel: String,
+    pub size: u64,
+}
+
+fn ignored_dirs(patterns: &[String]) -> Result<GlobSet, IngestError> {
+    let mut set = GlobSetBuilder::new();
+    for p in patterns {
+        set.add(Glob::new(p).map_err(|e| IngestError::Config(format!("ignore_dirs: {e}")))?);
+    }
+    set.build()
+        .map_err(|e| IngestError::Config(format!("ignore_dirs: {e}")))
+}
+
+/// Files under `root` with an indexed extension, in path order, honouring
+/// `.gitignore` files (inside a git checkout or not) and skipping
+/// directories named in [`Settings::ignore_dirs`].
+pub(crate) fn walk(root: &Path, settings: &Settings) -> Result<Vec<Found>, IngestError> {
+    let skip = ignored_dirs(&settings.ignore_dirs)?;
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .parents(false)
+        .git_global(false)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |e| {
+            !(e.depth() > 0
+                && e.file_type().is_some_and(|t| t.is_dir())
+                && skip.is_match(e.file_name()))
+        })
+        .build();
+
+    let mut found = Vec::new();
+    for entry in walker {
+        let entry = entry.map_err(|e| IngestError::Walk(e.to_string()))?;
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        let indexed = path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+            settings
+                .extensions
+                .contains(&format!(".{}", e.to_lowercase()))
+        });
+        if !indexed {
+            continue;
+        }
+        let rel = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let size = entry
+            .metadata()
+            .map_err(|e| IngestError::Walk(e.to_string()))?
+            .len();
+        found.push(Found {
+            path: path.to_path_buf(),
+            rel,
+            size,
+        });
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn honours_gitignore_ignored_dirs_and_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (path, body) in [
+            (".gitignore", "secrets/\n*.log.md\n"),
+            ("README.md", "# readme"),
+            ("src/main.RS", "fn main() {}"),
+            ("src/image.png", "png"),
+            ("secrets/keys.yaml", "k: v"),
+            ("notes.log.md", "log"),
+            ("node_modules/lib/index.js", "x"),
+            ("pkg.egg-info/PKG-INFO.txt", "x"),
+            ("docs/.hidden/guide.md", "hidden but kept"),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, body).unwrap();
+        }
+
+        let found = walk(root, &Settings::default()).unwrap();
+        let rels: Vec<&str> = found.iter().map(|f| f.rel.as_str()).collect();
+        assert_eq!(rels, ["README.md", "docs/.hidden/guide.md", "src/main.RS"]);
+        assert_eq!(found[0].size, 8);
+    }
+}
@@ -0,0 +1,86 @@
+//! PDFs and HTML pages are indexed from their extracted text, with
+//! citation metadata.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use embed::{EmbedError, Embedder};
+use extract::Catalog;
+use ingest::Ingestor;
+use vectors::{Filter, VectorIndex};
+
+struct Length;
+
+#[async_trait]
+impl Embedder for Length {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        Ok(texts.iter().map(|t| vec![1.0, t.len() as f32]).collect())
+    }
+}
+
+fn write(root: &Path, path: &str, body: &str) {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, body).unwrap();
+}
+
+#[tokio::test]
+async fn documents_are_cited_by_source_page_and_section() {
+    let repo = tempfile::tempdir().unwrap();
+    let index_dir = tempfile::tempdir().unwrap();
+    let root = repo.path();
+    write(
+        root,
+        "recon.yaml",
+        "output_dir: \"recon/cyber_v2\"\nsources:\n  - id: owasp_top10\n    url: \"https://owasp.org/www-project-top-ten/\"\n    file: \"owasp_top10.html\"\n    category: \"appsec\"\n",
+    );
+    write(
+        root,
+        "recon/cyber_v2/owasp_top10.html",
+        "<html><head><title>OWASP Top Ten</title></head><body><nav>Home | Projects</nav>\
+         <main><h1>Top 10</h1><h2>A01 Broken Access Control</h2>\
+         <p>Access control enforces policy such that users cannot act outside their permissions.</p>\
+         </main></body></html>",
+    );
+    write(
+        root,
+        "notes/page.htm",
+        "<p>An uncatalogued page with enough words to index.</p>",
+    );
+    let pdf = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../legal/wyoming_sf0068/SF0068_2022_Enrolled_alt.pdf");
+    std::fs::create_dir_all(root.join("legal")).unwrap();
+    std::fs::copy(pdf, root.join("legal/SF0068.pdf")).unwrap();
+
+    let mut catalog = Catalog::default();
+    catalog.load(&root.join("recon.yaml")).unwrap();
+    let report = Ingestor::new(Arc::new(Length), index_dir.path())
+        .with_catalog(catalog)
+        .run(root)
+        .await
+        .unwrap();
+    assert_eq!(report.indexed, 4);
+
+    let index = VectorIndex::open(index_dir.path()).unwrap();
+    let owasp = index.metadata("recon/cyber_v2/owasp_top10.html#0").unwrap();
+    assert_eq!(owasp["source_id"], "owasp_top10");
+    assert_eq!(owasp["url"], "https://owasp.org/www-project-top-ten/");
+    assert_eq!(owasp["category"], "appsec");
+    assert_eq!(owasp["title"], "OWASP Top Ten");
+    assert_eq!(owasp["section"], "Top 10 > A01 Broken Access Control");
+    assert!(!owasp["text"].contains("Projects"));
+    assert!(!owasp.contains_key("page"));
+
+    assert_eq!(
+        index.metadata("notes/page.htm#0").unwrap()["source_id"],
+        "page"
+    );
+
+    let statute = Filter::new().eq("section", "17-31-105");
+    let hits = index.search(&[1.0, 500.0], 3, Some(&statute)).unwrap();
+    assert!(!hits.is_empty());
+    assert!(hits
+        .iter()
+        .all(|h| h.metadata["source_id"] == "SF0068" && h.metadata["page"] == "3"));
+}
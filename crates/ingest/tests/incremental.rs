@@ -0,0 +1,118 @@
+//! Re-running the ingestor only embeds what changed.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use embed::{EmbedError, Embedder};
+use ingest::{Ingestor, Settings};
+use vectors::{Filter, VectorIndex};
+
+/// Embeds each text as its counts of a few letters and records the texts.
+#[derive(Default)]
+struct Letters {
+    seen: Mutex<Vec<String>>,
+    fail: Mutex<bool>,
+}
+
+#[async_trait]
+impl Embedder for Letters {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if *self.fail.lock().unwrap() {
+            return Err(EmbedError::Status {
+                status: 503,
+                body: "down".into(),
+            });
+        }
+        self.seen.lock().unwrap().extend_from_slice(texts);
+        Ok(texts
+            .iter()
+            .map(|t| {
+                "aeiost"
+                    .chars()
+                    .map(|c| t.matches(c).count() as f32 + 1.0)
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+impl Letters {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.seen.lock().unwrap())
+    }
+}
+
+fn write(root: &Path, path: &str, body: &str) {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, body).unwrap();
+}
+
+#[tokio::test]
+async fn reembeds_only_changed_files_and_drops_removed_ones() {
+    let repo = tempfile::tempdir().unwrap();
+    let index_dir = tempfile::tempdir().unwrap();
+    let root = repo.path();
+    let long: String = (0..25).map(|i| format!("word{i} ")).collect();
+    write(root, "README.md", "# Sovereignty architecture overview");
+    write(root, "src/lib.rs", &long);
+    write(root, "src/old.py", "def handler(event): return event");
+    write(root, "tiny.txt", "hi");
+
+    let embedder = Arc::new(Letters::default());
+    let settings = Settings {
+        chunk_words: 10,
+        overlap_words: 2,
+        batch_size: 2,
+        ..Settings::default()
+    };
+    let ingestor =
+        Ingestor::new(embedder.clone(), index_dir.path()).with_settings(settings.clone());
+
+    let first = ingestor.run(root).await.unwrap();
+    assert_eq!((first.files, first.indexed, first.chunks), (4, 4, 5));
+    assert_eq!(embedder.take().len(), 5);
+
+    let second = ingestor.run(root).await.unwrap();
+    assert_eq!((second.unchanged, second.indexed, second.chunks), (4, 0, 0));
+    assert!(embedder.take().is_empty());
+
+    write(root, "src/lib.rs", "pub fn shorter() {}");
+    std::fs::remove_file(root.join("src/old.py")).unwrap();
+    let third = ingestor.run(root).await.unwrap();
+    assert_eq!(
+        (third.indexed, third.unchanged, third.removed, third.chunks),
+        (1, 2, 1, 1)
+    );
+    assert_eq!(embedder.take(), ["pub fn shorter() {}"]);
+
+    let index = VectorIndex::open(index_dir.path()).unwrap();
+    assert_eq!(index.len(), 2);
+    assert!(!index.contains("src/old.py#0") && !index.contains("src/lib.rs#1"));
+    let hits = index
+        .search(&[1.0; 6], 5, Some(&Filter::new().prefix("path", "src/")))
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].metadata["text"], "pub fn shorter() {}");
+    assert_eq!(hits[0].metadata["total_chunks"], "1");
+    assert_eq!(hits[0].metadata["symbol"], "shorter");
+
+    // Files a failed run could not embed are picked up by the next one.
+    write(root, "a.md", "first changed file here");
+    write(root, "b.md", "second changed file here");
+    *embedder.fail.lock().unwrap() = true;
+    assert!(ingestor.run(root).await.is_err());
+    *embedder.fail.lock().unwrap() = false;
+    let resumed = ingestor.run(root).await.unwrap();
+    assert_eq!(resumed.indexed, 2);
+    assert_eq!(
+        Ingestor::new(embedder, index_dir.path())
+            .with_settings(settings)
+            .run(root)
+            .await
+            .unwrap()
+            .indexed,
+        0
+    );
+}
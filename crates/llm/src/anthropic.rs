@@ -0,0 +1,190 @@
+//! The Anthropic Messages API.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::sse::Reader;
+use crate::{status_error, ChatRequest, Completion, LlmError, Provider, Role, Usage};
+
+pub const ANTHROPIC_API: &str = "https://api.anthropic.com";
+
+pub const API_VERSION: &str = "2023-06-01";
+
+const NAME: &str = "anthropic";
+
+pub struct Anthropic {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    model: String,
+    content: Vec<Block>,
+    stop_reason: Option<String>,
+    usage: WireUsage,
+}
+
+#[derive(Deserialize)]
+struct Block {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize, Default)]
+struct WireUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+fn malformed(reason: impl ToString) -> LlmError {
+    LlmError::Malformed {
+        provider: NAME,
+        reason: reason.to_string(),
+    }
+}
+
+impl Anthropic {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: ANTHROPIC_API.to_string(),
+            api_key: api_key.into(),
+        }
+    }
+
+    /// Points the client at a different API root, e.g. a local fake.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn send(
+        &self,
+        request: &ChatRequest,
+        stream: bool,
+    ) -> Result<reqwest::Response, LlmError> {
+        // The API takes system text separately from the turns.
+        let mut system: Vec<&str> = request.system.iter().map(String::as_str).collect();
+        let mut messages = Vec::new();
+        for m in &request.messages {
+            match m.role {
+                Role::System => system.push(&m.content),
+                role => messages.push(json!({ "role": role, "content": m.content })),
+            }
+        }
+        let mut body = json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens,
+            "messages": messages,
+        });
+        if !system.is_empty() {
+            body["system"] = json!(system.join("\n\n"));
+        }
+        if let Some(t) = request.temperature {
+            body["temperature"] = json!(t);
+        }
+        if stream {
+            body["stream"] = json!(true);
+        }
+        let res = self
+            .http
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&body)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(status_error(NAME, res).await);
+        }
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl Provider for Anthropic {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, LlmError> {
+        let res: Response = self.send(request, false).await?.json().await?;
+        let text = res
+            .content
+            .into_iter()
+            .filter(|b| b.kind == "text")
+            .map(|b| b.text)
+            .collect();
+        Ok(Completion {
+            text,
+            usage: Usage {
+                input_tokens: res.usage.input_tokens,
+                output_tokens: res.usage.output_tokens,
+            },
+            model: res.model,
+            stop_reason: res.stop_reason,
+        })
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        deltas: mpsc::Sender<String>,
+    ) -> Result<Completion, LlmError> {
+        let mut events = Reader::new(self.send(request, true).await?);
+        let mut completion = Completion {
+            text: String::new(),
+            usage: Usage::default(),
+            model: String::new(),
+            stop_reason: None,
+        };
+        while let Some(event) = events.next().await? {
+            let value: Value = serde_json::from_str(&event.data).map_err(malformed)?;
+            match value["type"].as_str().unwrap_or_default() {
+                "message_start" => {
+                    let message = &value["message"];
+                    completion.model = message["model"].as_str().unwrap_or_default().to_string();
+                    let usage: WireUsage =
+                        serde_json::from_value(message["usage"].clone()).unwrap_or_default();
+                    completion.usage.input_tokens = usage.input_tokens;
+                    completion.usage.output_tokens = usage.output_tokens;
+                }
+                "content_block_delta" => {
+                    if let Some(delta) = value["delta"]["text"].as_str() {
+                        completion.text.push_str(delta);
+                        let _ = deltas.send(delta.to_string()).await;
+                    }
+                }
+                "message_delta" => {
+                    if let Some(reason) = value["delta"]["stop_reason"].as_str() {
+                        completion.stop_reason = Some(reason.to_string());
+                    }
+                    // Cumulative, not incremental.
+                    if let Some(out) = value["usage"]["output_tokens"].as_u64() {
+                        completion.usage.output_tokens = out as u32;
+                    }
+                }
+                "error" => {
+                    return Err(LlmError::Stream {
+                        provider: NAME,
+                        message: value["error"]["message"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                    })
+                }
+                "message_stop" => break,
+                _ => {}
+            }
+        }
+        Ok(completion)
+    }
+}
@@ -0,0 +1,215 @@
+//! Token accounting and spend limits.
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+use crate::{estimate_tokens, metrics, ChatRequest, Completion, LlmError, Provider, Usage};
+
+/// US dollars per million tokens.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl Pricing {
+    pub fn new(input_per_mtok: f64, output_per_mtok: f64) -> Self {
+        Self {
+            input_per_mtok,
+            output_per_mtok,
+        }
+    }
+
+    /// List prices of the models `ai_agents.model_name` is set to, dated
+    /// snapshots included; `None` for any other model.
+    pub fn for_model(model: &str) -> Option<Self> {
+        if model.starts_with("gpt-4o-mini") {
+            Some(Self::new(0.15, 0.60))
+        } else if model.starts_with("gpt-4o") {
+            Some(Self::new(2.50, 10.00))
+        } else {
+            None
+        }
+    }
+
+    pub fn cost(&self, usage: Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_mtok
+            + usage.output_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// A spend limit shared by every call made for one piece of work.
+#[derive(Debug)]
+pub struct Budget {
+    limit: f64,
+    spent: Mutex<(f64, Usage)>,
+}
+
+impl Budget {
+    /// `limit` in US dollars.
+    pub fn new(limit: f64) -> Self {
+        Self {
+            limit,
+            spent: Mutex::new((0.0, Usage::default())),
+        }
+    }
+
+    pub fn limit(&self) -> f64 {
+        self.limit
+    }
+
+    pub fn spent(&self) -> f64 {
+        self.spent.lock().unwrap().0
+    }
+
+    pub fn remaining(&self) -> f64 {
+        (self.limit - self.spent()).max(0.0)
+    }
+
+    /// Tokens charged so far.
+    pub fn usage(&self) -> Usage {
+        self.spent.lock().unwrap().1
+    }
+
+    fn charge(&self, cost: f64, usage: Usage) {
+        let mut spent = self.spent.lock().unwrap();
+        spent.0 += cost;
+        spent.1 += usage;
+    }
+
+    fn exceeded(&self) -> LlmError {
+        LlmError::BudgetExceeded {
+            spent: self.spent(),
+            limit: self.limit,
+        }
+    }
+}
+
+/// Charges every call to a [`Budget`] and records token metrics.
+///
+/// Before each call, `max_tokens` is lowered to what the remaining budget
+/// can pay for, after the estimated prompt cost. A call that cannot afford
+/// one output token is refused. The actual usage is charged afterwards, so
+/// a budget can overrun by at most one prompt's estimation error. Under
+/// zero [`Pricing`] nothing is ever charged, so the budget never binds; see
+/// [`Pricing::for_model`].
+pub struct Metered {
+    inner: Arc<dyn Provider>,
+    pricing: Pricing,
+    budget: Arc<Budget>,
+}
+
+impl Metered {
+    pub fn new(inner: Arc<dyn Provider>, pricing: Pricing, budget: Arc<Budget>) -> Self {
+        Self {
+            inner,
+            pricing,
+            budget,
+        }
+    }
+
+    pub fn budget(&self) -> &Arc<Budget> {
+        &self.budget
+    }
+
+    /// `request` with `max_tokens` capped to what the budget affords.
+    fn afford(&self, request: &ChatRequest) -> Result<ChatRequest, LlmError> {
+        let prompt = Usage {
+            input_tokens: estimate_tokens(&request.prompt_text()),
+            output_tokens: 0,
+        };
+        let left = self.budget.remaining() - self.pricing.cost(prompt);
+        let mut request = request.clone();
+        if self.pricing.output_per_mtok > 0.0 {
+            let affordable = (left * 1_000_000.0 / self.pricing.output_per_mtok).floor();
+            if affordable < 1.0 {
+                return Err(self.budget.exceeded());
+            }
+            request.max_tokens = request.max_tokens.min(affordable as u32);
+        } else if left < 0.0 {
+            return Err(self.budget.exceeded());
+        }
+        Ok(request)
+    }
+
+    fn charge(&self, completion: &Completion) {
+        let cost = self.pricing.cost(completion.usage);
+        self.budget.charge(cost, completion.usage);
+        let name = self.inner.name();
+        metrics::TOKENS
+            .with_label_values(&[name, "input"])
+            .inc_by(completion.usage.input_tokens.into());
+        metrics::TOKENS
+            .with_label_values(&[name, "output"])
+            .inc_by(completion.usage.output_tokens.into());
+        metrics::COST.with_label_values(&[name]).inc_by(cost);
+    }
+}
+
+#[async_trait]
+impl Provider for Metered {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, LlmError> {
+        let completion = self.inner.complete(&self.afford(request)?).await?;
+        self.charge(&completion);
+        Ok(completion)
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        deltas: mpsc::Sender<String>,
+    ) -> Result<Completion, LlmError> {
+        let completion = self.inner.stream(&self.afford(request)?, deltas).await?;
+        self.charge(&completion);
+        Ok(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::Replay;
+
+    #[tokio::test]
+    async fn calls_are_capped_then_refused_once_the_budget_is_spent() {
+        let replay = Arc::new(Replay::new().respond("plan", "word ".repeat(100)));
+        // $1 per token each way makes the arithmetic readable.
+        let pricing = Pricing::new(1_000_000.0, 1_000_000.0);
+        let budget = Arc::new(Budget::new(60.0));
+        let metered = Metered::new(replay.clone(), pricing, budget.clone());
+        let request = ChatRequest::new("m").with_user("plan").with_max_tokens(500);
+        let output_tokens = || {
+            metrics::TOKENS
+                .with_label_values(&["replay", "output"])
+                .get()
+        };
+        let before = output_tokens();
+
+        let first = metered.complete(&request).await.unwrap();
+        // One prompt token, so 59 output tokens are affordable.
+        assert_eq!(replay.calls()[0].max_tokens, 59);
+        assert_eq!(
+            first.usage,
+            Usage {
+                input_tokens: 1,
+                output_tokens: 59
+            }
+        );
+        assert_eq!(budget.spent(), 60.0);
+        assert_eq!(budget.usage().total(), 60);
+
+        assert!(matches!(
+            metered.complete(&request).await,
+            Err(LlmError::BudgetExceeded { limit, .. }) if limit == 60.0
+        ));
+        assert_eq!(replay.calls().len(), 1);
+        assert_eq!(output_tokens(), before + 59);
+    }
+}
@@ -0,0 +1,298 @@
+//! Chat-completion providers behind one [`Provider`] trait.
+//!
+//! Adapters cover the values of `ai_agents.model_provider`:
+//! [`openai::OpenAi`] (OpenAI and Azure OpenAI, and any OpenAI-compatible
+//! server), [`anthropic::Anthropic`] and [`llamacpp::LlamaCpp`] for a local
+//! llama.cpp server. [`replay::Replay`] answers from a script without the
+//! network, for tests and offline runs.
+//!
+//! Every [`Completion`] reports its token [`Usage`]. [`budget::Metered`]
+//! wraps a provider, prices that usage and stops calling once a
+//! [`budget::Budget`] is spent. Give each refinory request its own budget.
+
+pub mod anthropic;
+pub mod budget;
+pub mod llamacpp;
+pub mod metrics;
+pub mod openai;
+pub mod replay;
+mod sse;
+
+use std::ops::AddAssign;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use discovery::{AiAgents, ModelProvider};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatRequest {
+    pub model: String,
+    pub system: Option<String>,
+    pub messages: Vec<Message>,
+    pub temperature: Option<f32>,
+    /// Upper bound on output tokens. [`budget::Metered`] may lower it.
+    pub max_tokens: u32,
+}
+
+impl ChatRequest {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            system: None,
+            messages: Vec::new(),
+            temperature: None,
+            max_tokens: 1024,
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_user(mut self, content: impl Into<String>) -> Self {
+        self.messages.push(Message {
+            role: Role::User,
+            content: content.into(),
+        });
+        self
+    }
+
+    pub fn with_assistant(mut self, content: impl Into<String>) -> Self {
+        self.messages.push(Message {
+            role: Role::Assistant,
+            content: content.into(),
+        });
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// System prompt and messages, as providers without a separate system
+    /// field expect them.
+    pub(crate) fn transcript(&self) -> Vec<Message> {
+        let system = self.system.iter().map(|s| Message {
+            role: Role::System,
+            content: s.clone(),
+        });
+        system.chain(self.messages.iter().cloned()).collect()
+    }
+
+    /// All prompt text, for token estimates.
+    pub(crate) fn prompt_text(&self) -> String {
+        self.transcript()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+impl Usage {
+    pub fn total(&self) -> u32 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.input_tokens += rhs.input_tokens;
+        self.output_tokens += rhs.output_tokens;
+    }
+}
+
+/// Rough token count (four characters per token) for servers that do not
+/// report usage, so budgets still apply.
+pub fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(4) as u32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    pub usage: Usage,
+    /// Model that answered, as reported by the server.
+    pub model: String,
+    /// `stop`, `length`, `end_turn`… as the provider names it.
+    pub stop_reason: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{provider} returned HTTP {status}: {body}")]
+    Status {
+        provider: &'static str,
+        status: u16,
+        body: String,
+    },
+    #[error("{provider} stream error: {message}")]
+    Stream {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("{provider}: malformed response: {reason}")]
+    Malformed {
+        provider: &'static str,
+        reason: String,
+    },
+    #[error("budget exhausted: spent ${spent:.4} of ${limit:.4}")]
+    BudgetExceeded { spent: f64, limit: f64 },
+    #[error("no scripted reply for {0:?}")]
+    Unscripted(String),
+    #[error("{0}")]
+    Config(String),
+}
+
+impl LlmError {
+    /// Whether the same request may succeed later: transport errors,
+    /// rate limits, server errors and mid-stream failures.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Http(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+            Self::Status { status, .. } => *status == 429 || *status >= 500,
+            Self::Stream { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// Label for logs and metrics, e.g. `openai`.
+    fn name(&self) -> &'static str;
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, LlmError>;
+
+    /// Like [`Provider::complete`], but sends text to `deltas` as it is
+    /// generated. The returned completion holds the full text. A dropped
+    /// receiver does not abort the request.
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        deltas: mpsc::Sender<String>,
+    ) -> Result<Completion, LlmError> {
+        let completion = self.complete(request).await?;
+        let _ = deltas.send(completion.text.clone()).await;
+        Ok(completion)
+    }
+}
+
+/// Reads an error response into [`LlmError::Status`].
+pub(crate) async fn status_error(provider: &'static str, res: reqwest::Response) -> LlmError {
+    let status = res.status().as_u16();
+    let mut body = res.text().await.unwrap_or_default();
+    if body.len() > 512 {
+        let mut end = 512;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    LlmError::Status {
+        provider,
+        status,
+        body,
+    }
+}
+
+/// The provider `ai_agents.model_provider` names. `base_url` overrides the
+/// public endpoint. Azure and local servers have no public endpoint and
+/// need it. Hosted providers need `api_key`. Without one this returns
+/// an error instead of silently skipping analysis.
+pub fn from_discovery(
+    agents: &AiAgents,
+    base_url: Option<&str>,
+    api_key: Option<String>,
+) -> Result<Arc<dyn Provider>, LlmError> {
+    let key = |provider: &str| {
+        api_key
+            .clone()
+            .ok_or_else(|| LlmError::Config(format!("{provider} needs an API key")))
+    };
+    let provider: Arc<dyn Provider> = match agents.model_provider {
+        ModelProvider::Openai => Arc::new(
+            openai::OpenAi::new(base_url.unwrap_or(openai::OPENAI_API))
+                .with_api_key(key("openai")?),
+        ),
+        ModelProvider::Azure => {
+            let endpoint = base_url.ok_or_else(|| {
+                LlmError::Config("azure needs the resource endpoint as base URL".into())
+            })?;
+            Arc::new(openai::OpenAi::azure(endpoint).with_api_key(key("azure")?))
+        }
+        ModelProvider::Anthropic => {
+            let client = anthropic::Anthropic::new(key("anthropic")?);
+            Arc::new(match base_url {
+                Some(url) => client.with_base_url(url),
+                None => client,
+            })
+        }
+        ModelProvider::Local => Arc::new(llamacpp::LlamaCpp::new(
+            base_url.unwrap_or(llamacpp::DEFAULT_URL),
+        )),
+    };
+    Ok(provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosted_providers_refuse_to_start_without_a_key() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml");
+        let mut agents = discovery::Discovery::load(path).unwrap().ai_agents;
+        let err = from_discovery(&agents, None, None).err().unwrap();
+        assert_eq!(err.to_string(), "openai needs an API key");
+        assert_eq!(
+            from_discovery(&agents, None, Some("sk".into()))
+                .unwrap()
+                .name(),
+            "openai"
+        );
+        agents.model_provider = ModelProvider::Local;
+        assert_eq!(
+            from_discovery(&agents, None, None).unwrap().name(),
+            "llamacpp"
+        );
+    }
+
+    #[test]
+    fn token_estimates_round_up() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(estimate_tokens("ééé"), 1);
+    }
+}
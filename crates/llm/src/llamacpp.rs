@@ -0,0 +1,143 @@
+//! A local `llama-server` (llama.cpp) through its native `/completion`
+//! endpoint, which reports evaluated and predicted token counts.
+//!
+//! The endpoint takes a raw prompt. Messages are rendered with the ChatML
+//! template that most instruction-tuned GGUF models are trained on.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::sse::Reader;
+use crate::{status_error, ChatRequest, Completion, LlmError, Provider, Role, Usage};
+
+pub const DEFAULT_URL: &str = "http://localhost:8080";
+
+const NAME: &str = "llamacpp";
+
+pub struct LlamaCpp {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    stop: bool,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    tokens_evaluated: u32,
+    #[serde(default)]
+    tokens_predicted: u32,
+    #[serde(default)]
+    stopped_limit: bool,
+}
+
+impl Response {
+    fn finish(self, text: String) -> Completion {
+        Completion {
+            text,
+            usage: Usage {
+                input_tokens: self.tokens_evaluated,
+                output_tokens: self.tokens_predicted,
+            },
+            model: self.model,
+            stop_reason: Some(if self.stopped_limit { "length" } else { "stop" }.to_string()),
+        }
+    }
+}
+
+/// Renders `request` as a ChatML prompt ending in an open assistant turn.
+pub fn chatml(request: &ChatRequest) -> String {
+    let mut prompt = String::new();
+    for m in request.transcript() {
+        let role = match m.role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        };
+        prompt.push_str(&format!("<|im_start|>{role}\n{}<|im_end|>\n", m.content));
+    }
+    prompt.push_str("<|im_start|>assistant\n");
+    prompt
+}
+
+impl LlamaCpp {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn send(
+        &self,
+        request: &ChatRequest,
+        stream: bool,
+    ) -> Result<reqwest::Response, LlmError> {
+        let mut body = json!({
+            "prompt": chatml(request),
+            "n_predict": request.max_tokens,
+            "stop": ["<|im_end|>"],
+            "cache_prompt": true,
+            "stream": stream,
+        });
+        if let Some(t) = request.temperature {
+            body["temperature"] = json!(t);
+        }
+        let res = self
+            .http
+            .post(format!("{}/completion", self.base_url))
+            .json(&body)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(status_error(NAME, res).await);
+        }
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl Provider for LlamaCpp {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, LlmError> {
+        let res: Response = self.send(request, false).await?.json().await?;
+        let text = res.content.clone();
+        Ok(res.finish(text))
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        deltas: mpsc::Sender<String>,
+    ) -> Result<Completion, LlmError> {
+        let mut events = Reader::new(self.send(request, true).await?);
+        let mut text = String::new();
+        while let Some(event) = events.next().await? {
+            let chunk: Response =
+                serde_json::from_str(&event.data).map_err(|e| LlmError::Malformed {
+                    provider: NAME,
+                    reason: e.to_string(),
+                })?;
+            if !chunk.content.is_empty() {
+                text.push_str(&chunk.content);
+                let _ = deltas.send(chunk.content.clone()).await;
+            }
+            if chunk.stop {
+                return Ok(chunk.finish(text));
+            }
+        }
+        Err(LlmError::Stream {
+            provider: NAME,
+            message: "stream ended before the final chunk".into(),
+        })
+    }
+}
@@ -0,0 +1,26 @@
+//! Prometheus metrics, registered in the process-wide default registry.
+
+use std::sync::LazyLock;
+
+use prometheus::{register_counter_vec, register_int_counter_vec, CounterVec, IntCounterVec};
+
+/// `llm_tokens_total{provider, direction}`, with `direction` either
+/// `input` or `output`.
+pub static TOKENS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "llm_tokens_total",
+        "Tokens consumed by metered LLM calls",
+        &["provider", "direction"]
+    )
+    .expect("metric registered twice")
+});
+
+/// `llm_cost_dollars_total{provider}`, priced by [`crate::budget::Pricing`].
+pub static COST: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "llm_cost_dollars_total",
+        "Estimated spend of metered LLM calls in US dollars",
+        &["provider"]
+    )
+    .expect("metric registered twice")
+});
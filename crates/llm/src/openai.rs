@@ -0,0 +1,219 @@
+//! OpenAI chat completions, also spoken by Azure OpenAI, vLLM and most
+//! self-hosted gateways.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::sse::Reader;
+use crate::{estimate_tokens, status_error, ChatRequest, Completion, LlmError, Provider, Usage};
+
+pub const OPENAI_API: &str = "https://api.openai.com/v1";
+
+/// `api-version` sent to Azure OpenAI.
+pub const AZURE_API_VERSION: &str = "2024-10-21";
+
+const NAME: &str = "openai";
+
+enum Flavor {
+    OpenAi,
+    /// Deployments are named after the model.
+    Azure,
+}
+
+pub struct OpenAi {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    flavor: Flavor,
+}
+
+#[derive(Deserialize)]
+struct WireUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
+impl From<WireUsage> for Usage {
+    fn from(u: WireUsage) -> Self {
+        Usage {
+            input_tokens: u.prompt_tokens,
+            output_tokens: u.completion_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    model: String,
+    choices: Vec<Choice>,
+    usage: Option<WireUsage>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    #[serde(default)]
+    message: Option<Content>,
+    #[serde(default)]
+    delta: Option<Content>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    content: Option<String>,
+}
+
+impl OpenAi {
+    /// `base_url` is the API root including `/v1`, e.g. [`OPENAI_API`].
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            flavor: Flavor::OpenAi,
+        }
+    }
+
+    /// An Azure OpenAI resource, e.g. `https://example.openai.azure.com`.
+    pub fn azure(endpoint: impl Into<String>) -> Self {
+        Self {
+            flavor: Flavor::Azure,
+            ..Self::new(endpoint)
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    async fn send(
+        &self,
+        request: &ChatRequest,
+        stream: bool,
+    ) -> Result<reqwest::Response, LlmError> {
+        let mut body = json!({
+            "model": request.model,
+            "messages": request.transcript(),
+            "max_tokens": request.max_tokens,
+        });
+        if let Some(t) = request.temperature {
+            body["temperature"] = json!(t);
+        }
+        if stream {
+            body["stream"] = json!(true);
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+        let builder = match self.flavor {
+            Flavor::OpenAi => self
+                .http
+                .post(format!("{}/chat/completions", self.base_url)),
+            Flavor::Azure => self
+                .http
+                .post(format!(
+                    "{}/openai/deployments/{}/chat/completions",
+                    self.base_url, request.model
+                ))
+                .query(&[("api-version", AZURE_API_VERSION)]),
+        };
+        let builder = match (&self.flavor, &self.api_key) {
+            (_, None) => builder,
+            (Flavor::OpenAi, Some(key)) => builder.bearer_auth(key),
+            (Flavor::Azure, Some(key)) => builder.header("api-key", key),
+        };
+        let res = builder.json(&body).send().await?;
+        if !res.status().is_success() {
+            return Err(status_error(NAME, res).await);
+        }
+        Ok(res)
+    }
+}
+
+fn malformed(reason: impl ToString) -> LlmError {
+    LlmError::Malformed {
+        provider: NAME,
+        reason: reason.to_string(),
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAi {
+    fn name(&self) -> &'static str {
+        match self.flavor {
+            Flavor::OpenAi => NAME,
+            Flavor::Azure => "azure",
+        }
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, LlmError> {
+        let res: Response = self.send(request, false).await?.json().await?;
+        let choice = res
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| malformed("no choices"))?;
+        let text = choice.message.and_then(|m| m.content).unwrap_or_default();
+        let usage = match res.usage {
+            Some(u) => u.into(),
+            None => Usage {
+                input_tokens: estimate_tokens(&request.prompt_text()),
+                output_tokens: estimate_tokens(&text),
+            },
+        };
+        Ok(Completion {
+            text,
+            usage,
+            model: res.model,
+            stop_reason: choice.finish_reason,
+        })
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        deltas: mpsc::Sender<String>,
+    ) -> Result<Completion, LlmError> {
+        let mut events = Reader::new(self.send(request, true).await?);
+        let mut text = String::new();
+        let mut model = String::new();
+        let mut usage = None;
+        let mut stop_reason = None;
+        while let Some(event) = events.next().await? {
+            if event.data == "[DONE]" {
+                break;
+            }
+            let value: Value = serde_json::from_str(&event.data).map_err(malformed)?;
+            if let Some(error) = value.get("error") {
+                return Err(LlmError::Stream {
+                    provider: NAME,
+                    message: error["message"].as_str().unwrap_or_default().to_string(),
+                });
+            }
+            let chunk: Response = serde_json::from_value(value).map_err(malformed)?;
+            if !chunk.model.is_empty() {
+                model = chunk.model;
+            }
+            usage = chunk.usage.map(Usage::from).or(usage);
+            for choice in chunk.choices {
+                stop_reason = choice.finish_reason.or(stop_reason);
+                if let Some(delta) = choice.delta.and_then(|d| d.content) {
+                    text.push_str(&delta);
+                    let _ = deltas.send(delta).await;
+                }
+            }
+        }
+        let usage = usage.unwrap_or_else(|| Usage {
+            input_tokens: estimate_tokens(&request.prompt_text()),
+            output_tokens: estimate_tokens(&text),
+        });
+        Ok(Completion {
+            text,
+            usage,
+            model,
+            stop_reason,
+        })
+    }
+}
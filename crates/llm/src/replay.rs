@@ -0,0 +1,168 @@
+//! A deterministic provider that answers from a script.
+//!
+//! Rules match on a substring of the last user message; the first match
+//! wins. Unmatched prompts get an echo of the prompt, or an error once
+//! [`Replay::strict`] is set. Usage comes from [`estimate_tokens`], so
+//! budgets behave as they would against a real provider.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::{estimate_tokens, ChatRequest, Completion, LlmError, Provider, Role, Usage};
+
+/// One entry of a JSON cassette, `[{"match": "...", "reply": "..."}]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    #[serde(rename = "match")]
+    pub needle: String,
+    pub reply: String,
+}
+
+#[derive(Default)]
+pub struct Replay {
+    rules: Vec<Rule>,
+    strict: bool,
+    calls: Mutex<Vec<ChatRequest>>,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads rules from a JSON cassette.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, LlmError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| LlmError::Config(format!("{}: {e}", path.display())))?;
+        let rules = serde_json::from_str(&text)
+            .map_err(|e| LlmError::Config(format!("{}: {e}", path.display())))?;
+        Ok(Self {
+            rules,
+            ..Self::default()
+        })
+    }
+
+    pub fn respond(mut self, needle: impl Into<String>, reply: impl Into<String>) -> Self {
+        self.rules.push(Rule {
+            needle: needle.into(),
+            reply: reply.into(),
+        });
+        self
+    }
+
+    /// Fails unmatched prompts with [`LlmError::Unscripted`] instead of
+    /// echoing them.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Requests received so far, in order.
+    pub fn calls(&self) -> Vec<ChatRequest> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn reply(&self, request: &ChatRequest) -> Result<String, LlmError> {
+        self.calls.lock().unwrap().push(request.clone());
+        let prompt = request
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::User)
+            .map(|m| m.content.as_str())
+            .unwrap_or_default();
+        if let Some(rule) = self.rules.iter().find(|r| prompt.contains(&r.needle)) {
+            return Ok(rule.reply.clone());
+        }
+        if self.strict {
+            return Err(LlmError::Unscripted(prompt.to_string()));
+        }
+        Ok(format!("[{}] {prompt}", request.model))
+    }
+}
+
+#[async_trait]
+impl Provider for Replay {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<Completion, LlmError> {
+        let mut text = self.reply(request)?;
+        let mut output_tokens = estimate_tokens(&text);
+        let mut stop_reason = "stop";
+        if output_tokens > request.max_tokens {
+            text = text.chars().take(request.max_tokens as usize * 4).collect();
+            output_tokens = request.max_tokens;
+            stop_reason = "length";
+        }
+        Ok(Completion {
+            text,
+            usage: Usage {
+                input_tokens: estimate_tokens(&request.prompt_text()),
+                output_tokens,
+            },
+            model: request.model.clone(),
+            stop_reason: Some(stop_reason.to_string()),
+        })
+    }
+
+    /// Streams the reply word by word.
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        deltas: mpsc::Sender<String>,
+    ) -> Result<Completion, LlmError> {
+        let completion = self.complete(request).await?;
+        for word in completion.text.split_inclusive(' ') {
+            let _ = deltas.send(word.to_string()).await;
+        }
+        Ok(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replies_are_scripted_streamed_and_truncated_to_max_tokens() {
+        let replay = Replay::new()
+            .respond("threat model", "Use mTLS between services.")
+            .strict();
+        let request = ChatRequest::new("gpt-4o-mini")
+            .with_system("You are a security expert.")
+            .with_user("Analyze the threat model for: billing");
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let done = replay.stream(&request, tx).await.unwrap();
+        let mut streamed = String::new();
+        while let Some(delta) = rx.recv().await {
+            streamed.push_str(&delta);
+        }
+        assert_eq!(streamed, "Use mTLS between services.");
+        assert_eq!(done.text, streamed);
+        assert_eq!(done.usage.output_tokens, 7);
+
+        let short = replay
+            .complete(&request.clone().with_max_tokens(2))
+            .await
+            .unwrap();
+        assert_eq!(
+            (short.text.as_str(), short.stop_reason.as_deref()),
+            ("Use mTLS", Some("length"))
+        );
+
+        let other = ChatRequest::new("m").with_user("hello");
+        assert!(matches!(
+            replay.complete(&other).await,
+            Err(LlmError::Unscripted(p)) if p == "hello"
+        ));
+        assert_eq!(replay.calls().len(), 3);
+    }
+}
@@ -0,0 +1,74 @@
+//! Minimal server-sent events reader over a streaming response body.
+
+/// One event. `event` is `None` unless the server names its events.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Event {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+pub(crate) struct Reader {
+    res: reqwest::Response,
+    buf: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl Reader {
+    pub fn new(res: reqwest::Response) -> Self {
+        Self {
+            res,
+            buf: Vec::new(),
+            event: None,
+            data: Vec::new(),
+        }
+    }
+
+    /// The next event, or `None` at the end of the body.
+    pub async fn next(&mut self) -> Result<Option<Event>, reqwest::Error> {
+        loop {
+            while let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if let Some(event) = self.line(line.trim_end_matches(['\r', '\n'])) {
+                    return Ok(Some(event));
+                }
+            }
+            match self.res.chunk().await? {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => {
+                    // A final event without its blank line.
+                    let rest = std::mem::take(&mut self.buf);
+                    let rest = String::from_utf8_lossy(&rest);
+                    let mut event = None;
+                    for line in rest.lines().chain([""]) {
+                        event = event.or(self.line(line));
+                    }
+                    return Ok(event);
+                }
+            }
+        }
+    }
+
+    /// Feeds one line; a blank line completes the pending event.
+    fn line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            if self.data.is_empty() {
+                self.event = None;
+                return None;
+            }
+            return Some(Event {
+                event: self.event.take(),
+                data: std::mem::take(&mut self.data).join("\n"),
+            });
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
@@ -0,0 +1,188 @@
+//! HTTP adapters against local fakes of each provider's API.
+
+use std::sync::{Arc, Mutex};
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use llm::anthropic::Anthropic;
+use llm::llamacpp::LlamaCpp;
+use llm::openai::OpenAi;
+use llm::{ChatRequest, LlmError, Provider, Usage};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+type Seen = Arc<Mutex<Vec<(HeaderMap, Value)>>>;
+
+/// Serves `app` on a free port and returns its root URL.
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+fn sse(events: &[Value]) -> impl IntoResponse {
+    let body: String = events.iter().map(|e| format!("data: {e}\n\n")).collect();
+    ([("content-type", "text/event-stream")], body)
+}
+
+async fn collect(provider: &dyn Provider, request: &ChatRequest) -> (Vec<String>, llm::Completion) {
+    let (tx, mut rx) = mpsc::channel(64);
+    let completion = provider.stream(request, tx).await.unwrap();
+    let mut deltas = Vec::new();
+    while let Some(d) = rx.recv().await {
+        deltas.push(d);
+    }
+    (deltas, completion)
+}
+
+#[tokio::test]
+async fn openai_streams_deltas_and_reports_usage_from_the_last_chunk() {
+    let seen = Seen::default();
+    let app = Router::new()
+        .route(
+            "/v1/chat/completions",
+            post(|State(seen): State<Seen>, headers: HeaderMap, Json(body): Json<Value>| async move {
+                let stream = body["stream"] == true;
+                seen.lock().unwrap().push((headers, body));
+                if !stream {
+                    return (
+                        axum::http::StatusCode::TOO_MANY_REQUESTS,
+                        "slow down",
+                    )
+                        .into_response();
+                }
+                sse(&[
+                    json!({"model": "gpt-4o-mini", "choices": [{"delta": {"content": "Use "}}]}),
+                    json!({"model": "gpt-4o-mini", "choices": [{"delta": {"content": "Postgres."}, "finish_reason": "stop"}]}),
+                    json!({"model": "gpt-4o-mini", "choices": [], "usage": {"prompt_tokens": 21, "completion_tokens": 3}}),
+                ])
+                .into_response()
+            }),
+        )
+        .with_state(seen.clone());
+    let openai = OpenAi::new(format!("{}/v1", serve(app).await)).with_api_key("sk-test");
+    let request = ChatRequest::new("gpt-4o-mini")
+        .with_system("You are a backend expert.")
+        .with_user("Pick a database.");
+
+    let (deltas, done) = collect(&openai, &request).await;
+    assert_eq!(deltas, ["Use ", "Postgres."]);
+    assert_eq!(done.text, "Use Postgres.");
+    assert_eq!(
+        done.usage,
+        Usage {
+            input_tokens: 21,
+            output_tokens: 3
+        }
+    );
+    assert_eq!(done.stop_reason.as_deref(), Some("stop"));
+
+    let err = openai.complete(&request).await.unwrap_err();
+    assert!(matches!(err, LlmError::Status { status: 429, .. }), "{err}");
+    assert!(err.is_retryable());
+
+    let seen = seen.lock().unwrap();
+    let (headers, body) = &seen[0];
+    assert_eq!(headers["authorization"], "Bearer sk-test");
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert_eq!(body["stream_options"]["include_usage"], true);
+}
+
+#[tokio::test]
+async fn anthropic_separates_the_system_prompt_and_counts_streamed_tokens() {
+    let seen = Seen::default();
+    let app = Router::new()
+        .route(
+            "/v1/messages",
+            post(|State(seen): State<Seen>, headers: HeaderMap, Json(body): Json<Value>| async move {
+                let stream = body["stream"] == true;
+                seen.lock().unwrap().push((headers, body));
+                if !stream {
+                    return Json(json!({
+                        "model": "claude-test",
+                        "content": [{"type": "text", "text": "Shard by tenant."}],
+                        "stop_reason": "end_turn",
+                        "usage": {"input_tokens": 12, "output_tokens": 4},
+                    }))
+                    .into_response();
+                }
+                sse(&[
+                    json!({"type": "message_start", "message": {"model": "claude-test", "usage": {"input_tokens": 12, "output_tokens": 1}}}),
+                    json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Shard "}}),
+                    json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "by tenant."}}),
+                    json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 4}}),
+                    json!({"type": "message_stop"}),
+                ])
+                .into_response()
+            }),
+        )
+        .with_state(seen.clone());
+    let anthropic = Anthropic::new("key").with_base_url(serve(app).await);
+    let request = ChatRequest::new("claude-test")
+        .with_system("You are a data expert.")
+        .with_user("How do we scale writes?");
+
+    let plain = anthropic.complete(&request).await.unwrap();
+    let (deltas, streamed) = collect(&anthropic, &request).await;
+    assert_eq!(plain.text, "Shard by tenant.");
+    assert_eq!(deltas, ["Shard ", "by tenant."]);
+    assert_eq!(streamed, plain);
+
+    let seen = seen.lock().unwrap();
+    let (headers, body) = &seen[0];
+    assert_eq!(headers["x-api-key"], "key");
+    assert_eq!(headers["anthropic-version"], llm::anthropic::API_VERSION);
+    assert_eq!(body["system"], "You are a data expert.");
+    assert_eq!(
+        body["messages"],
+        json!([{"role": "user", "content": "How do we scale writes?"}])
+    );
+}
+
+#[tokio::test]
+async fn llamacpp_renders_chatml_and_reads_token_counts() {
+    let seen = Seen::default();
+    let app = Router::new()
+        .route(
+            "/completion",
+            post(|State(seen): State<Seen>, headers: HeaderMap, Json(body): Json<Value>| async move {
+                seen.lock().unwrap().push((headers, body));
+                sse(&[
+                    json!({"content": "Run it ", "stop": false}),
+                    json!({"content": "on k3s.", "stop": false}),
+                    json!({"content": "", "stop": true, "model": "qwen2.5-7b", "tokens_evaluated": 30, "tokens_predicted": 5, "stopped_limit": false}),
+                ])
+            }),
+        )
+        .with_state(seen.clone());
+    let local = LlamaCpp::new(serve(app).await);
+    let request = ChatRequest::new("local")
+        .with_system("You are a devops expert.")
+        .with_user("Where do we deploy?")
+        .with_max_tokens(64);
+
+    let (deltas, done) = collect(&local, &request).await;
+    assert_eq!(deltas.concat(), "Run it on k3s.");
+    assert_eq!(
+        done.usage,
+        Usage {
+            input_tokens: 30,
+            output_tokens: 5
+        }
+    );
+    assert_eq!(done.model, "qwen2.5-7b");
+
+    let body = &seen.lock().unwrap()[0].1;
+    assert_eq!(body["n_predict"], 64);
+    assert_eq!(
+        body["prompt"],
+        "<|im_start|>system\nYou are a devops expert.<|im_end|>\n\
+         <|im_start|>user\nWhere do we deploy?<|im_end|>\n\
+         <|im_start|>assistant\n"
+    );
+}
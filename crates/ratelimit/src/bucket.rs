@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+/// Sustained rate and burst size for one bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+impl Quota {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            per_minute: per_minute.max(1),
+            burst: burst.max(1),
+        }
+    }
+
+    fn per_sec(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Bucket {
+    tokens: f64,
+    last: Instant,
+    /// Set from a Discord `retry_after`; nothing passes until then.
+    blocked_until: Option<Instant>,
+}
+
+impl Bucket {
+    pub(crate) fn full(quota: Quota, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(quota.burst),
+            last: now,
+            blocked_until: None,
+        }
+    }
+
+    fn refill(&mut self, quota: Quota, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * quota.per_sec()).min(f64::from(quota.burst));
+        self.last = now;
+        if self.blocked_until.is_some_and(|t| t <= now) {
+            self.blocked_until = None;
+        }
+    }
+
+    /// Time until a token is available; `None` when one is available now.
+    pub(crate) fn wait(&mut self, quota: Quota, now: Instant) -> Option<Duration> {
+        self.refill(quota, now);
+        if let Some(until) = self.blocked_until {
+            return Some(until - now);
+        }
+        (self.tokens < 1.0).then(|| Duration::from_secs_f64((1.0 - self.tokens) / quota.per_sec()))
+    }
+
+    pub(crate) fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+
+    pub(crate) fn block_until(&mut self, until: Instant) {
+        self.blocked_until = Some(self.blocked_until.map_or(until, |t| t.max(until)));
+    }
+
+    /// Idle buckets that have refilled completely carry no state worth keeping.
+    pub(crate) fn is_idle(&mut self, quota: Quota, now: Instant) -> bool {
+        self.refill(quota, now);
+        self.blocked_until.is_none() && self.tokens >= f64::from(quota.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_then_refills_at_the_sustained_rate() {
+        let quota = Quota::new(30, 10);
+        let t0 = Instant::now();
+        let mut b = Bucket::full(quota, t0);
+        for _ in 0..10 {
+            assert_eq!(b.wait(quota, t0), None);
+            b.take();
+        }
+        assert_eq!(b.wait(quota, t0), Some(Duration::from_secs(2)));
+        assert_eq!(b.wait(quota, t0 + Duration::from_secs(2)), None);
+
+        b.block_until(t0 + Duration::from_secs(60));
+        assert_eq!(
+            b.wait(quota, t0 + Duration::from_secs(50)),
+            Some(Duration::from_secs(10))
+        );
+        assert!(b.is_idle(quota, t0 + Duration::from_secs(61)));
+    }
+}
@@ -0,0 +1,268 @@
+//! Token-bucket rate limiting for Discord traffic.
+//!
+//! `discord.bot.rate_limits` sizes the per-user and per-channel buckets;
+//! `limits.discord_api` sizes one global bucket.
+//! Bot commands use [`RateLimiter::check`] and are refused when a bucket is
+//! empty; outbound posts use [`RateLimiter::acquire`] and wait. A Discord
+//! `429` is fed back with [`RateLimiter::note_retry_after`], which blocks
+//! the affected bucket for the `retry_after` Discord asked for.
+
+mod bucket;
+pub mod metrics;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use discovery::Discovery;
+use serde::Deserialize;
+
+use crate::bucket::Bucket;
+pub use crate::bucket::Quota;
+
+/// Buckets kept before idle ones are dropped.
+const PRUNE_AT: usize = 4096;
+
+/// Longest `retry_after` honoured; anything larger is treated as this.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    User(String),
+    Channel(String),
+    Global,
+}
+
+impl Key {
+    pub fn user(id: impl Into<String>) -> Self {
+        Self::User(id.into())
+    }
+
+    pub fn channel(name: impl Into<String>) -> Self {
+        Self::Channel(name.into())
+    }
+
+    /// Metric label.
+    pub fn scope(&self) -> &'static str {
+        match self {
+            Key::User(_) => "user",
+            Key::Channel(_) => "channel",
+            Key::Global => "global",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{scope} rate limit reached; retry in {retry_after:?}")]
+pub struct Limited {
+    pub scope: &'static str,
+    pub retry_after: Duration,
+}
+
+/// Body of a Discord `429 Too Many Requests`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordRateLimit {
+    /// Seconds, possibly fractional.
+    pub retry_after: f64,
+    #[serde(default)]
+    pub global: bool,
+}
+
+impl DiscordRateLimit {
+    pub fn retry_after(&self) -> Duration {
+        Duration::try_from_secs_f64(self.retry_after)
+            .unwrap_or(MAX_RETRY_AFTER)
+            .min(MAX_RETRY_AFTER)
+    }
+
+    /// Bucket the limit applies to when posting to `channel`.
+    pub fn key(&self, channel: &str) -> Key {
+        if self.global {
+            Key::Global
+        } else {
+            Key::channel(channel)
+        }
+    }
+}
+
+pub struct RateLimiter {
+    per_key: Quota,
+    global: Quota,
+    buckets: Mutex<HashMap<Key, Bucket>>,
+}
+
+impl RateLimiter {
+    /// `per_key` applies to every user and channel; `global` to the process.
+    pub fn new(per_key: Quota, global: Quota) -> Self {
+        Self {
+            per_key,
+            global,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_discovery(cfg: &Discovery) -> Self {
+        let limits = &cfg.discord.bot.rate_limits;
+        let api = &cfg.limits.discord_api;
+        Self::new(
+            Quota::new(limits.max_msgs_per_min, limits.burst),
+            Quota::new(
+                api.max_messages_per_minute,
+                api.burst.unwrap_or(api.max_messages_per_minute),
+            ),
+        )
+    }
+
+    fn quota(&self, key: &Key) -> Quota {
+        match key {
+            Key::Global => self.global,
+            _ => self.per_key,
+        }
+    }
+
+    /// Takes one token from every bucket in `keys`, or from none of them.
+    fn try_take(&self, keys: &[Key], now: Instant) -> Result<(), Limited> {
+        let mut buckets = self.buckets.lock().expect("rate limiter poisoned");
+        if buckets.len() > PRUNE_AT {
+            buckets.retain(|k, b| !b.is_idle(self.quota(k), now));
+        }
+        for key in keys {
+            let quota = self.quota(key);
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| Bucket::full(quota, now));
+            if let Some(retry_after) = bucket.wait(quota, now) {
+                return Err(Limited {
+                    scope: key.scope(),
+                    retry_after,
+                });
+            }
+        }
+        for key in keys {
+            buckets.get_mut(key).expect("inserted above").take();
+        }
+        Ok(())
+    }
+
+    /// Non-blocking check, e.g. for an incoming slash command. Refusals are
+    /// counted in [`metrics::HITS`].
+    pub fn check(&self, keys: &[Key]) -> Result<(), Limited> {
+        self.try_take(keys, Instant::now()).inspect_err(|limited| {
+            metrics::HITS
+                .with_label_values(&[limited.scope, "local"])
+                .inc();
+        })
+    }
+
+    /// A command by `user` in `channel`: user, channel and global buckets.
+    pub fn check_command(&self, user: &str, channel: &str) -> Result<(), Limited> {
+        self.check(&[Key::user(user), Key::channel(channel), Key::Global])
+    }
+
+    /// Waits until every bucket in `keys` has a token and takes them.
+    pub async fn acquire(&self, keys: &[Key]) {
+        let mut counted = false;
+        while let Err(limited) = self.try_take(keys, Instant::now()) {
+            if !counted {
+                metrics::HITS
+                    .with_label_values(&[limited.scope, "local"])
+                    .inc();
+                counted = true;
+            }
+            tracing::debug!(scope = limited.scope, wait = ?limited.retry_after, "rate limited");
+            tokio::time::sleep(limited.retry_after).await;
+        }
+    }
+
+    /// Blocks `key` for `retry_after` after Discord answered `429`.
+    pub fn note_retry_after(&self, key: Key, retry_after: Duration) {
+        metrics::HITS
+            .with_label_values(&[key.scope(), "discord"])
+            .inc();
+        tracing::warn!(scope = key.scope(), ?retry_after, "discord rate limit");
+        let now = Instant::now();
+        let quota = self.quota(&key);
+        self.buckets
+            .lock()
+            .expect("rate limiter poisoned")
+            .entry(key)
+            .or_insert_with(|| Bucket::full(quota, now))
+            .block_until(now + retry_after.min(MAX_RETRY_AFTER));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(scope: &str, source: &str) -> u64 {
+        metrics::HITS.with_label_values(&[scope, source]).get()
+    }
+
+    #[test]
+    fn all_buckets_must_have_a_token() {
+        let limiter = RateLimiter::new(Quota::new(60, 2), Quota::new(60, 3));
+        let before = hits("user", "local");
+        assert!(limiter.check_command("alice", "#ops").is_ok());
+        assert!(limiter.check_command("alice", "#ops").is_ok());
+        let err = limiter.check_command("alice", "#ops").unwrap_err();
+        assert_eq!(err.scope, "user");
+        assert_eq!(hits("user", "local"), before + 1);
+
+        // Bob has tokens but #ops is empty; the refusal takes none of them.
+        assert_eq!(
+            limiter.check_command("bob", "#ops").unwrap_err().scope,
+            "channel"
+        );
+        assert!(limiter.check_command("bob", "#dev").is_ok());
+        assert_eq!(
+            limiter.check_command("carol", "#qa").unwrap_err().scope,
+            "global"
+        );
+        assert!(metrics::render().contains("discord_rate_limit_hits_total"));
+    }
+
+    #[test]
+    fn users_share_the_global_burst() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml");
+        let cfg = Discovery::load(path).unwrap();
+        let (burst, global) = (
+            cfg.discord.bot.rate_limits.burst,
+            cfg.limits.discord_api.burst.unwrap(),
+        );
+        assert!(global > burst);
+        let limiter = RateLimiter::from_discovery(&cfg);
+        // Each user bursts in their own channel until the global bucket is
+        // spent, which takes more than one user's worth.
+        let mut sent = 0;
+        for user in 0.. {
+            let (user, channel) = (format!("u{user}"), format!("#c{user}"));
+            for _ in 0..burst {
+                if sent == global {
+                    let err = limiter.check_command(&user, &channel).unwrap_err();
+                    assert_eq!(err.scope, "global");
+                    return;
+                }
+                limiter.check_command(&user, &channel).unwrap();
+                sent += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn discord_retry_after_blocks_the_bucket() {
+        let limiter = RateLimiter::new(Quota::new(60, 5), Quota::new(600, 50));
+        let body: DiscordRateLimit = serde_json::from_str(
+            r#"{"message":"You are being rate limited.","retry_after":1.5,"global":false}"#,
+        )
+        .unwrap();
+        assert_eq!(body.retry_after(), Duration::from_millis(1500));
+        let before = hits("channel", "discord");
+        limiter.note_retry_after(body.key("#prs"), body.retry_after());
+        assert_eq!(hits("channel", "discord"), before + 1);
+
+        let err = limiter.check(&[Key::channel("#prs")]).unwrap_err();
+        assert!(err.retry_after > Duration::from_secs(1));
+        assert!(limiter.check(&[Key::channel("#alerts")]).is_ok());
+    }
+}
@@ -0,0 +1,26 @@
+//! Prometheus metrics, registered in the process-wide default registry.
+
+use std::sync::LazyLock;
+
+use prometheus::{register_int_counter_vec, Encoder, IntCounterVec, TextEncoder};
+
+/// `discord_rate_limit_hits_total{scope, source}`, alerted on by
+/// `DiscordRateLimitHit` in `monitoring/alerts.yml`. `source` is `local`
+/// for our own buckets and `discord` for `429` responses.
+pub static HITS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "discord_rate_limit_hits_total",
+        "Requests held back by a Discord rate limit",
+        &["scope", "source"]
+    )
+    .expect("metric registered twice")
+});
+
+/// Everything in the default registry, in the text exposition format.
+pub fn render() -> String {
+    let mut buf = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buf)
+        .expect("text encoding cannot fail");
+    String::from_utf8(buf).expect("text encoding is UTF-8")
+}
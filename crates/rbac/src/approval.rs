@@ -0,0 +1,136 @@
+//! Two-person confirmation of prod-protected commands.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::Invocation;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ApprovalError {
+    #[error("no pending approval {0}")]
+    Unknown(String),
+    #[error("approval {0} expired")]
+    Expired(String),
+    #[error("requester cannot approve their own command")]
+    SelfApproval,
+    #[error("approver needs one of the roles {0:?}")]
+    MissingRole(Vec<String>),
+}
+
+/// A request waiting for its second approver.
+#[derive(Debug, Clone)]
+pub struct Pending {
+    /// Opaque id, short enough for a button `custom_id`.
+    pub id: String,
+    pub invocation: Invocation,
+    pub expires: Instant,
+}
+
+/// In-memory pending requests; each can be confirmed once before it expires.
+pub struct Approvals {
+    ttl: Duration,
+    next: AtomicU64,
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Approvals {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            next: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn open(&self, invocation: Invocation) -> Pending {
+        let now = Instant::now();
+        let pending = Pending {
+            id: format!("apr-{}", self.next.fetch_add(1, Ordering::Relaxed)),
+            invocation,
+            expires: now + self.ttl,
+        };
+        let mut map = self.pending.lock().expect("approvals poisoned");
+        map.retain(|_, p| p.expires > now);
+        map.insert(pending.id.clone(), pending.clone());
+        pending
+    }
+
+    /// Consumes pending request `id` if `approver` is someone else holding
+    /// one of `roles`. A rejected approver leaves the request open.
+    pub fn confirm(
+        &self,
+        id: &str,
+        approver: &Invocation,
+        roles: &[String],
+    ) -> Result<Invocation, ApprovalError> {
+        let mut map = self.pending.lock().expect("approvals poisoned");
+        let pending = map
+            .get(id)
+            .ok_or_else(|| ApprovalError::Unknown(id.to_string()))?;
+        if pending.expires <= Instant::now() {
+            map.remove(id);
+            return Err(ApprovalError::Expired(id.to_string()));
+        }
+        if pending.invocation.user_id == approver.user_id {
+            return Err(ApprovalError::SelfApproval);
+        }
+        if !roles.iter().any(|r| approver.roles.contains(r)) {
+            return Err(ApprovalError::MissingRole(roles.to_vec()));
+        }
+        Ok(map.remove(id).expect("checked above").invocation)
+    }
+
+    /// Withdraws a pending request; returns whether it existed.
+    pub fn cancel(&self, id: &str) -> bool {
+        self.pending
+            .lock()
+            .expect("approvals poisoned")
+            .remove(id)
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_member_with_role_confirms_once() {
+        let approvals = Approvals::new(Duration::from_secs(60));
+        let roles = vec!["ReleaseMgr".to_string()];
+        let request = Invocation::new("alice", "deploy")
+            .with_env("prod")
+            .with_roles(["ReleaseMgr"]);
+        let id = approvals.open(request.clone()).id;
+
+        let alice = Invocation::new("alice", "approve").with_roles(["ReleaseMgr"]);
+        let bob = Invocation::new("bob", "approve");
+        let carol = Invocation::new("carol", "approve").with_roles(["ReleaseMgr"]);
+        assert_eq!(
+            approvals.confirm(&id, &alice, &roles),
+            Err(ApprovalError::SelfApproval)
+        );
+        assert_eq!(
+            approvals.confirm(&id, &bob, &roles),
+            Err(ApprovalError::MissingRole(roles.clone()))
+        );
+        assert_eq!(approvals.confirm(&id, &carol, &roles), Ok(request));
+        assert_eq!(
+            approvals.confirm(&id, &carol, &roles),
+            Err(ApprovalError::Unknown(id))
+        );
+    }
+
+    #[test]
+    fn expired_requests_cannot_be_confirmed() {
+        let approvals = Approvals::new(Duration::ZERO);
+        let id = approvals.open(Invocation::new("alice", "scale")).id;
+        let carol = Invocation::new("carol", "approve").with_roles(["ReleaseMgr"]);
+        assert_eq!(
+            approvals.confirm(&id, &carol, &["ReleaseMgr".to_string()]),
+            Err(ApprovalError::Expired(id))
+        );
+    }
+}
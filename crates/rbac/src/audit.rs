@@ -0,0 +1,164 @@
+//! Audit trail for authorization decisions (`security.audit_log_sink`).
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::Invocation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Denied,
+    ApprovalRequested,
+    Approved,
+    ApprovalRejected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    /// Unix seconds.
+    pub at: u64,
+    pub user_id: String,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    pub outcome: AuditOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approver_id: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(inv: &Invocation, outcome: AuditOutcome) -> Self {
+        Self {
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            user_id: inv.user_id.clone(),
+            command: inv.slash_command(),
+            env: inv.env.clone(),
+            outcome,
+            reason: None,
+            approval_id: None,
+            approver_id: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_approval(mut self, id: impl Into<String>) -> Self {
+        self.approval_id = Some(id.into());
+        self
+    }
+
+    pub fn with_approver(mut self, user_id: impl Into<String>) -> Self {
+        self.approver_id = Some(user_id.into());
+        self
+    }
+}
+
+/// Where audit events go. Recording must not fail the command path, so
+/// sinks report their own errors.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, event: &AuditEvent);
+}
+
+/// Emits events on the `audit` tracing target for the log shipper to route
+/// (CloudWatch, Loki, ...).
+pub struct LogSink {
+    sink: String,
+}
+
+impl LogSink {
+    pub fn new(sink: impl Into<String>) -> Self {
+        Self { sink: sink.into() }
+    }
+}
+
+impl AuditSink for LogSink {
+    fn record(&self, event: &AuditEvent) {
+        match serde_json::to_string(event) {
+            Ok(json) => tracing::info!(target: "audit", sink = %self.sink, "{json}"),
+            Err(e) => tracing::error!(target: "audit", error = %e, "unserializable audit event"),
+        }
+    }
+}
+
+/// Appends one JSON object per line.
+pub struct FileSink {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record(&self, event: &AuditEvent) {
+        let _guard = self.lock.lock().expect("audit file poisoned");
+        let written = serde_json::to_string(event)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?
+                    .write_all(format!("{json}\n").as_bytes())
+            });
+        if let Err(e) = written {
+            tracing::error!(target: "audit", path = %self.path.display(), error = %e, "audit write failed");
+        }
+    }
+}
+
+/// `file:///path` appends to a local file; anything else (including unset)
+/// logs on the `audit` target.
+pub fn sink_for(uri: Option<&str>) -> Arc<dyn AuditSink> {
+    match uri.and_then(|u| u.strip_prefix("file://")) {
+        Some(path) => Arc::new(FileSink::new(path)),
+        None => Arc::new(LogSink::new(uri.unwrap_or("log"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_sink_appends_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let sink = sink_for(Some(&format!("file://{}", path.display())));
+        let inv = Invocation::new("u1", "deploy").with_env("prod");
+        sink.record(&AuditEvent::new(&inv, AuditOutcome::Denied).with_reason("no role"));
+        sink.record(&AuditEvent::new(&inv, AuditOutcome::ApprovalRequested).with_approval("apr-1"));
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["outcome"], "denied");
+        assert_eq!(lines[0]["command"], "/deploy");
+        assert_eq!(lines[0]["reason"], "no role");
+        assert_eq!(lines[1]["approval_id"], "apr-1");
+    }
+}
@@ -0,0 +1,273 @@
+//! Authorization for Discord slash commands.
+//!
+//! A [`Policy`] built from `discord.bot.rbac` and `governance.approvals`
+//! decides each [`Invocation`]:
+//!
+//! * commands outside `allow_commands` are denied;
+//! * `prod_protected_commands` aimed at prod need `prod_role`, and with
+//!   `governance.approvals.two_person` also a second member's confirmation
+//!   (see [`approval::Approvals`]);
+//! * everything else is allowed.
+//!
+//! [`Authorizer`] ties the policy to pending approvals and an
+//! [`audit::AuditSink`] so every denial leaves a record.
+
+pub mod approval;
+pub mod audit;
+pub mod roles;
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use discovery::Discovery;
+
+use crate::approval::{ApprovalError, Approvals, Pending};
+use crate::audit::{AuditEvent, AuditOutcome, AuditSink};
+
+/// Environment name that `prod_protected_commands` protect.
+pub const PROD_ENV: &str = "prod";
+
+/// One slash-command interaction, reduced to what authorization needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub user_id: String,
+    /// Role names held by the member; [`roles::RoleMap`] converts the ids
+    /// Discord sends.
+    pub roles: BTreeSet<String>,
+    /// Command name, with or without the leading `/`.
+    pub command: String,
+    /// The `env` option, for commands that take one.
+    pub env: Option<String>,
+}
+
+impl Invocation {
+    pub fn new(user_id: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            user_id: user_id.into(),
+            roles: BTreeSet::new(),
+            command: command.into(),
+            env: None,
+        }
+    }
+
+    pub fn with_roles<S: Into<String>>(mut self, roles: impl IntoIterator<Item = S>) -> Self {
+        self.roles.extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_env(mut self, env: impl Into<String>) -> Self {
+        self.env = Some(env.into());
+        self
+    }
+
+    /// Command name with a single leading `/`, as written in `discovery.yml`.
+    pub fn slash_command(&self) -> String {
+        format!("/{}", self.command.trim_start_matches('/'))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Denial {
+    #[error("{0} is not an allowed command")]
+    NotAllowed(String),
+    #[error("{command} against {env} requires the {role} role")]
+    MissingRole {
+        command: String,
+        env: String,
+        role: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Deny(Denial),
+    /// Allowed once a second member with an approver role confirms.
+    NeedsApproval,
+}
+
+#[derive(Debug, Clone)]
+pub struct Policy {
+    allowed: BTreeSet<String>,
+    protected: BTreeSet<String>,
+    prod_role: String,
+    approver_roles: Vec<String>,
+    two_person: bool,
+}
+
+impl Policy {
+    pub fn from_discovery(cfg: &Discovery) -> Self {
+        let rbac = &cfg.discord.bot.rbac;
+        let approvals = &cfg.governance.approvals;
+        let approver_roles = if approvals.prod_commands_require.is_empty() {
+            vec![rbac.prod_role.clone()]
+        } else {
+            approvals.prod_commands_require.clone()
+        };
+        Self {
+            allowed: rbac.allow_commands.iter().cloned().collect(),
+            protected: rbac.prod_protected_commands.iter().cloned().collect(),
+            prod_role: rbac.prod_role.clone(),
+            approver_roles,
+            two_person: approvals.two_person,
+        }
+    }
+
+    /// Overrides `governance.approvals.two_person`.
+    pub fn with_two_person(mut self, enabled: bool) -> Self {
+        self.two_person = enabled;
+        self
+    }
+
+    /// Roles any one of which lets a member confirm someone else's request.
+    pub fn approver_roles(&self) -> &[String] {
+        &self.approver_roles
+    }
+
+    /// Protected commands without an `env` option (`/scale`) are treated as
+    /// targeting prod.
+    pub fn targets_prod(&self, inv: &Invocation) -> bool {
+        self.protected.contains(&inv.slash_command())
+            && inv.env.as_deref().is_none_or(|e| e == PROD_ENV)
+    }
+
+    pub fn decide(&self, inv: &Invocation) -> Decision {
+        let command = inv.slash_command();
+        if !self.allowed.contains(&command) {
+            return Decision::Deny(Denial::NotAllowed(command));
+        }
+        if !self.targets_prod(inv) {
+            return Decision::Allow;
+        }
+        if !inv.roles.contains(&self.prod_role) {
+            return Decision::Deny(Denial::MissingRole {
+                command,
+                env: inv.env.clone().unwrap_or_else(|| PROD_ENV.to_string()),
+                role: self.prod_role.clone(),
+            });
+        }
+        if self.two_person {
+            Decision::NeedsApproval
+        } else {
+            Decision::Allow
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Allow,
+    Deny(Denial),
+    /// Waiting for [`Authorizer::confirm`] with this id.
+    Pending(Pending),
+}
+
+/// [`Policy`] plus pending two-person confirmations and auditing.
+pub struct Authorizer {
+    policy: Policy,
+    approvals: Approvals,
+    audit: Arc<dyn AuditSink>,
+}
+
+impl Authorizer {
+    pub fn new(policy: Policy, ttl: Duration, audit: Arc<dyn AuditSink>) -> Self {
+        Self {
+            policy,
+            approvals: Approvals::new(ttl),
+            audit,
+        }
+    }
+
+    /// Policy, TTL and audit sink from `discovery.yml`.
+    pub fn from_discovery(cfg: &Discovery) -> Self {
+        Self::new(
+            Policy::from_discovery(cfg),
+            Duration::from_secs(cfg.governance.approvals.approval_ttl_secs),
+            audit::sink_for(cfg.security.audit_log_sink.as_deref()),
+        )
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    pub fn authorize(&self, inv: &Invocation) -> Outcome {
+        match self.policy.decide(inv) {
+            Decision::Allow => Outcome::Allow,
+            Decision::Deny(denial) => {
+                self.audit.record(
+                    &AuditEvent::new(inv, AuditOutcome::Denied).with_reason(denial.to_string()),
+                );
+                Outcome::Deny(denial)
+            }
+            Decision::NeedsApproval => {
+                let pending = self.approvals.open(inv.clone());
+                self.audit.record(
+                    &AuditEvent::new(inv, AuditOutcome::ApprovalRequested)
+                        .with_approval(&pending.id),
+                );
+                Outcome::Pending(pending)
+            }
+        }
+    }
+
+    /// Records `approver`'s confirmation of pending request `id` and returns
+    /// the original invocation, which may now run.
+    pub fn confirm(&self, id: &str, approver: &Invocation) -> Result<Invocation, ApprovalError> {
+        let result = self
+            .approvals
+            .confirm(id, approver, self.policy.approver_roles());
+        let event = match &result {
+            Ok(inv) => AuditEvent::new(inv, AuditOutcome::Approved),
+            Err(e) => {
+                AuditEvent::new(approver, AuditOutcome::ApprovalRejected).with_reason(e.to_string())
+            }
+        };
+        self.audit
+            .record(&event.with_approval(id).with_approver(&approver.user_id));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        let cfg =
+            Discovery::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../discovery.yml")).unwrap();
+        Policy::from_discovery(&cfg)
+    }
+
+    #[test]
+    fn prod_protection() {
+        let p = policy();
+        let deploy = |env: &str| Invocation::new("u1", "deploy").with_env(env);
+
+        assert_eq!(p.decide(&Invocation::new("u1", "status")), Decision::Allow);
+        assert_eq!(p.decide(&deploy("staging")), Decision::Allow);
+        assert!(matches!(
+            p.decide(&deploy("prod")),
+            Decision::Deny(Denial::MissingRole { role, .. }) if role == "ReleaseMgr"
+        ));
+        assert!(matches!(
+            p.decide(&Invocation::new("u1", "/scale")),
+            Decision::Deny(Denial::MissingRole { .. })
+        ));
+        assert_eq!(
+            p.decide(&deploy("prod").with_roles(["ReleaseMgr"])),
+            Decision::Allow
+        );
+        assert_eq!(
+            p.clone()
+                .with_two_person(true)
+                .decide(&deploy("prod").with_roles(["ReleaseMgr"])),
+            Decision::NeedsApproval
+        );
+        assert_eq!(
+            p.decide(&Invocation::new("u1", "nuke").with_roles(["ReleaseMgr"])),
+            Decision::Deny(Denial::NotAllowed("/nuke".to_string()))
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Discord role ids → the role names used in `discovery.yml`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Environment variable holding the id of a role, in the style of the
+/// `CH_<NAME>_ID` channel variables (`ReleaseMgr` → `ROLE_RELEASEMGR_ID`).
+pub fn role_env(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("ROLE_{name}_ID")
+}
+
+/// Role id → role name.
+#[derive(Debug, Clone, Default)]
+pub struct RoleMap(BTreeMap<String, String>);
+
+impl RoleMap {
+    /// Looks up every role in `names` via [`role_env`]; unset ones never
+    /// match.
+    pub fn from_env<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let ids = names
+            .into_iter()
+            .filter_map(|n| {
+                std::env::var(role_env(n))
+                    .ok()
+                    .map(|id| (id, n.to_string()))
+            })
+            .collect();
+        Self(ids)
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, id: impl Into<String>) {
+        self.0.insert(id.into(), name.into());
+    }
+
+    /// Names for the member's role ids; ids without a name are dropped.
+    pub fn names<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        ids.into_iter()
+            .filter_map(|id| self.0.get(id).cloned())
+            .collect()
+    }
+}
@@ -0,0 +1,88 @@
+//! Denials and two-person confirmations end up in the audit sink.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use discovery::Discovery;
+use rbac::approval::ApprovalError;
+use rbac::audit::{AuditEvent, AuditOutcome, AuditSink};
+use rbac::{Authorizer, Denial, Invocation, Outcome, Policy};
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<AuditEvent>>);
+
+impl AuditSink for Recorder {
+    fn record(&self, event: &AuditEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+impl Recorder {
+    fn outcomes(&self) -> Vec<AuditOutcome> {
+        self.0.lock().unwrap().iter().map(|e| e.outcome).collect()
+    }
+}
+
+fn authorizer(two_person: bool) -> (Authorizer, Arc<Recorder>) {
+    let cfg = Discovery::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../discovery.yml")).unwrap();
+    let audit = Arc::new(Recorder::default());
+    let policy = Policy::from_discovery(&cfg).with_two_person(two_person);
+    (
+        Authorizer::new(policy, Duration::from_secs(60), audit.clone()),
+        audit,
+    )
+}
+
+#[test]
+fn denials_are_audited() {
+    let (authz, audit) = authorizer(false);
+    let deploy = Invocation::new("u1", "deploy").with_env("prod");
+    assert!(matches!(
+        authz.authorize(&deploy),
+        Outcome::Deny(Denial::MissingRole { .. })
+    ));
+    assert!(matches!(
+        authz.authorize(&deploy.clone().with_roles(["ReleaseMgr"])),
+        Outcome::Allow
+    ));
+    assert!(matches!(
+        authz.authorize(&Invocation::new("u1", "status")),
+        Outcome::Allow
+    ));
+    let events = audit.0.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].outcome, AuditOutcome::Denied);
+    assert_eq!(events[0].user_id, "u1");
+    assert_eq!(events[0].env.as_deref(), Some("prod"));
+}
+
+#[test]
+fn prod_deploy_waits_for_second_approver() {
+    let (authz, audit) = authorizer(true);
+    let deploy = Invocation::new("alice", "deploy")
+        .with_env("prod")
+        .with_roles(["ReleaseMgr"]);
+    let Outcome::Pending(pending) = authz.authorize(&deploy) else {
+        panic!("expected a pending approval");
+    };
+
+    let alice = Invocation::new("alice", "approve").with_roles(["ReleaseMgr"]);
+    assert_eq!(
+        authz.confirm(&pending.id, &alice),
+        Err(ApprovalError::SelfApproval)
+    );
+    let bob = Invocation::new("bob", "approve").with_roles(["ReleaseMgr"]);
+    assert_eq!(authz.confirm(&pending.id, &bob), Ok(deploy));
+
+    assert_eq!(
+        audit.outcomes(),
+        [
+            AuditOutcome::ApprovalRequested,
+            AuditOutcome::ApprovalRejected,
+            AuditOutcome::Approved
+        ]
+    );
+    let approved = audit.0.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(approved.user_id, "alice");
+    assert_eq!(approved.approver_id.as_deref(), Some("bob"));
+}
@@ -0,0 +1,367 @@
+//! Drives [`Run`]s through their phases, checkpointing after every step.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::{InvalidTransition, Phase, Request, RequestStatus, Run, Store, StoreError};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PhaseError {
+    /// Worth retrying: provider timeouts, rate limits, 5xx.
+    #[error("{0}")]
+    Retryable(String),
+    /// Will fail again: rejected input, policy violations.
+    #[error("{0}")]
+    Permanent(String),
+}
+
+/// Does the work of a phase, e.g. by consulting the experts.
+#[async_trait]
+pub trait Executor: Send + Sync {
+    /// Output of `phase` for `run`. Earlier phases' output is in
+    /// `run.outputs`. A phase can be executed more than once: on retry, and
+    /// on resume when the process died before its output was saved.
+    async fn execute(&self, phase: Phase, run: &Run) -> Result<Value, PhaseError>;
+
+    /// US dollars spent on request `id` by attempts that failed, timed out
+    /// or were cancelled since the last call. The engine adds it to
+    /// [`Run::attempt_spend`] after every attempt.
+    fn take_spend(&self, id: &str) -> f64 {
+        let _ = id;
+        0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Delay after the first failure. It doubles after each further one.
+    pub base: Duration,
+    pub max_delay: Duration,
+    /// Attempts before the request fails. Attempts cut short by a crash
+    /// count too.
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(5),
+            max_delay: Duration::from_secs(120),
+            max_attempts: 3,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before attempt `attempt + 1`, after `attempt` failures.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhasePolicy {
+    /// Limit on one attempt. An attempt that runs out counts as a
+    /// retryable failure.
+    pub timeout: Duration,
+    pub retry: RetryPolicy,
+}
+
+impl Default for PhasePolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(600),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EngineError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error(transparent)]
+    Transition(#[from] InvalidTransition),
+    #[error("invalid request id {0:?}")]
+    InvalidId(String),
+    #[error("request {0} already exists")]
+    Exists(String),
+    #[error("no request {0}")]
+    NotFound(String),
+    #[error("request {0} is already running")]
+    Running(String),
+}
+
+/// Ids name checkpoint files, so they are restricted to a safe alphabet.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Resolves once cancellation is requested.
+async fn cancelled(rx: &mut watch::Receiver<bool>) {
+    if rx.wait_for(|c| *c).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+pub struct Engine {
+    store: Arc<dyn Store>,
+    executor: Arc<dyn Executor>,
+    default: PhasePolicy,
+    policies: HashMap<Phase, PhasePolicy>,
+    /// Cancellation senders of the runs this process is driving.
+    running: Mutex<HashMap<String, watch::Sender<bool>>>,
+    /// Held while a run is checked out of the store and claimed, or
+    /// cancelled in place, so the two cannot interleave.
+    claim: tokio::sync::Mutex<()>,
+}
+
+/// Releases a claim on `running` however the drive ends, including by its
+/// future being dropped.
+struct Claim<'a> {
+    running: &'a Mutex<HashMap<String, watch::Sender<bool>>>,
+    id: &'a str,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.running.lock().unwrap().remove(self.id);
+    }
+}
+
+impl Engine {
+    pub fn new(store: Arc<dyn Store>, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            store,
+            executor,
+            default: PhasePolicy::default(),
+            policies: HashMap::new(),
+            running: Mutex::new(HashMap::new()),
+            claim: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Policy for phases without one of their own.
+    pub fn with_default_policy(mut self, policy: PhasePolicy) -> Self {
+        self.default = policy;
+        self
+    }
+
+    pub fn with_policy(mut self, phase: Phase, policy: PhasePolicy) -> Self {
+        self.policies.insert(phase, policy);
+        self
+    }
+
+    pub fn policy(&self, phase: Phase) -> PhasePolicy {
+        self.policies.get(&phase).copied().unwrap_or(self.default)
+    }
+
+    /// Stores `request` as `pending`. Nothing runs until
+    /// [`Engine::drive`] or [`Engine::spawn`] is called.
+    pub async fn submit(&self, request: Request) -> Result<Run, EngineError> {
+        if !valid_id(&request.id) {
+            return Err(EngineError::InvalidId(request.id));
+        }
+        if self.store.load(&request.id).await?.is_some() {
+            return Err(EngineError::Exists(request.id));
+        }
+        let run = Run::new(request);
+        self.store.save(&run).await?;
+        Ok(run)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Run>, EngineError> {
+        Ok(self.store.load(id).await?)
+    }
+
+    /// Runs request `id` from its last checkpoint to a terminal status and
+    /// returns the final checkpoint. Retries that run out fail the request
+    /// but are not an `Err`.
+    pub async fn drive(&self, id: &str) -> Result<Run, EngineError> {
+        let (run, rx, _claim) = {
+            let _gate = self.claim.lock().await;
+            let (tx, rx) = watch::channel(false);
+            {
+                let mut running = self.running.lock().unwrap();
+                if running.contains_key(id) {
+                    return Err(EngineError::Running(id.to_string()));
+                }
+                running.insert(id.to_string(), tx);
+            }
+            let claim = Claim {
+                running: &self.running,
+                id,
+            };
+            let run = self
+                .store
+                .load(id)
+                .await?
+                .ok_or_else(|| EngineError::NotFound(id.to_string()))?;
+            (run, rx, claim)
+        };
+        self.phases(run, rx).await
+    }
+
+    /// [`Engine::drive`] on a background task.
+    pub fn spawn(self: &Arc<Self>, id: &str) -> JoinHandle<Result<Run, EngineError>> {
+        let engine = self.clone();
+        let id = id.to_string();
+        tokio::spawn(async move {
+            let result = engine.drive(&id).await;
+            if let Err(e) = &result {
+                tracing::error!(%id, error = %e, "refinory request aborted");
+            }
+            result
+        })
+    }
+
+    /// Spawns every unfinished run in the store; call once at startup.
+    pub async fn resume(
+        self: &Arc<Self>,
+    ) -> Result<Vec<JoinHandle<Result<Run, EngineError>>>, EngineError> {
+        let unfinished: Vec<Run> = self
+            .store
+            .list()
+            .await?
+            .into_iter()
+            .filter(|r| !r.status.is_terminal())
+            .collect();
+        Ok(unfinished
+            .iter()
+            .map(|run| {
+                tracing::info!(id = %run.id(), status = %run.status, "resuming refinory request");
+                self.spawn(run.id())
+            })
+            .collect())
+    }
+
+    /// Cancels request `id`. A run this process is driving stops at once,
+    /// dropping its in-flight phase, and is saved as `cancelled` by its
+    /// driver. `false` if the request had already finished.
+    pub async fn cancel(&self, id: &str) -> Result<bool, EngineError> {
+        let _gate = self.claim.lock().await;
+        if let Some(tx) = self.running.lock().unwrap().get(id) {
+            tx.send_replace(true);
+            return Ok(true);
+        }
+        let mut run = self
+            .store
+            .load(id)
+            .await?
+            .ok_or_else(|| EngineError::NotFound(id.to_string()))?;
+        if run.status.is_terminal() {
+            return Ok(false);
+        }
+        run.transition(RequestStatus::Cancelled)?;
+        self.store.save(&run).await?;
+        Ok(true)
+    }
+
+    async fn finish(
+        &self,
+        mut run: Run,
+        status: RequestStatus,
+        error: Option<String>,
+    ) -> Result<Run, EngineError> {
+        run.transition(status)?;
+        if error.is_some() {
+            run.error = error;
+        }
+        self.store.save(&run).await?;
+        tracing::info!(id = %run.id(), %status, "refinory request finished");
+        Ok(run)
+    }
+
+    async fn phases(
+        &self,
+        mut run: Run,
+        mut rx: watch::Receiver<bool>,
+    ) -> Result<Run, EngineError> {
+        loop {
+            if run.status.is_terminal() {
+                return Ok(run);
+            }
+            if *rx.borrow() {
+                return self.finish(run, RequestStatus::Cancelled, None).await;
+            }
+            let Some(phase) = run.next_phase() else {
+                return self.finish(run, RequestStatus::Completed, None).await;
+            };
+            let policy = self.policy(phase);
+            if run.attempts >= policy.retry.max_attempts {
+                let error = format!("{phase} gave up after {} attempts", run.attempts);
+                return self.finish(run, RequestStatus::Failed, Some(error)).await;
+            }
+            run.transition(phase.status())?;
+            run.attempts += 1;
+            self.store.save(&run).await?;
+            tracing::info!(id = %run.id(), %phase, attempt = run.attempts, "phase started");
+
+            let outcome = tokio::select! {
+                biased;
+                _ = cancelled(&mut rx) => None,
+                result = tokio::time::timeout(policy.timeout, self.executor.execute(phase, &run)) => {
+                    Some(result.unwrap_or_else(|_| {
+                        Err(PhaseError::Retryable(format!(
+                            "{phase} timed out after {:?}",
+                            policy.timeout
+                        )))
+                    }))
+                }
+            };
+            run.attempt_spend += self.executor.take_spend(run.id());
+            let error = match outcome {
+                None => return self.finish(run, RequestStatus::Cancelled, None).await,
+                Some(Ok(output)) => {
+                    run.outputs.insert(phase, output);
+                    run.attempts = 0;
+                    run.error = None;
+                    self.store.save(&run).await?;
+                    continue;
+                }
+                Some(Err(e)) => e,
+            };
+            tracing::warn!(id = %run.id(), %phase, attempt = run.attempts, error = %error, "phase failed");
+            let exhausted = run.attempts >= policy.retry.max_attempts;
+            if matches!(error, PhaseError::Permanent(_)) || exhausted {
+                return self
+                    .finish(run, RequestStatus::Failed, Some(error.to_string()))
+                    .await;
+            }
+            run.error = Some(error.to_string());
+            self.store.save(&run).await?;
+            tokio::select! {
+                biased;
+                _ = cancelled(&mut rx) => {
+                    return self.finish(run, RequestStatus::Cancelled, None).await;
+                }
+                _ = tokio::time::sleep(policy.retry.delay(run.attempts)) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_must_be_safe_file_names() {
+        assert!(valid_id("0d6f1c2e-93a1-4b5e-8f0c-1f2e3d4c5b6a"));
+        assert!(valid_id("atlas_1"));
+        for bad in ["", "../etc/passwd", "a/b", "a.json", &"x".repeat(129)] {
+            assert!(!valid_id(bad), "{bad}");
+        }
+    }
+}
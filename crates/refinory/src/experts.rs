@@ -0,0 +1,443 @@
+//! An [`Executor`] that runs each phase by consulting experts through an
+//! [`llm::Provider`].
+//!
+//! Every phase output records the experts' reports, the tokens used and
+//! their cost. The spend limit is per request and survives a restart: the
+//! cost of checkpointed phases, and of failed attempts the engine saved in
+//! [`Run::attempt_spend`], is deducted before the next attempt runs.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use llm::budget::{Budget, Metered, Pricing};
+use llm::{ChatRequest, LlmError, Provider, Usage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::engine::{Executor, PhaseError};
+use crate::scheduler::{ExpertName, Scheduler, TaskSpec};
+use crate::{Phase, Run};
+
+/// Task types each phase hands out, one expert each. With the built-in
+/// capabilities they reach the experts `selectExpertsForPhase` used to
+/// list.
+pub fn phase_tasks(phase: Phase) -> &'static [&'static str] {
+    match phase {
+        Phase::Analysis => &["technology_selection", "threat_modeling", "api_design"],
+        Phase::Architecture => &[
+            "system_design",
+            "database_architecture",
+            "infrastructure_design",
+        ],
+        Phase::Implementation => &[
+            "microservices_design",
+            "component_design",
+            "ci_cd_pipeline",
+            "test_strategy",
+        ],
+        Phase::Review => &["architecture_review", "security_architecture"],
+    }
+}
+
+/// What the expert is asked to focus on during analysis.
+fn focus(expert: &str) -> &'static str {
+    match expert {
+        "frontend" => "UI/UX requirements and frontend architecture needs",
+        "backend" => "API design, data architecture, and backend service needs",
+        "devops" => "deployment, infrastructure, and operational requirements",
+        "security" => "security requirements, threat model, and compliance needs",
+        "ai_ml" => "AI/ML requirements and model architecture needs",
+        "mobile" => "mobile app requirements and cross-platform considerations",
+        "blockchain" => "blockchain/Web3 requirements and smart contract needs",
+        "testing" => "testing strategy, QA processes, and automation needs",
+        "architecture" => "overall system architecture and integration requirements",
+        "data_science" => "data pipeline, analytics, and ML ops requirements",
+        _ => "technical requirements",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub expert: String,
+    pub task_type: String,
+    pub text: String,
+}
+
+/// The output of one phase as stored in [`Run::outputs`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseOutput {
+    pub reports: Vec<Report>,
+    pub usage: Usage,
+    /// US dollars.
+    pub cost: f64,
+}
+
+pub struct ExpertExecutor {
+    provider: Arc<dyn Provider>,
+    model: String,
+    pricing: Option<Pricing>,
+    budget: Option<f64>,
+    max_tokens: u32,
+    scheduler: Arc<Scheduler>,
+    /// Budgets of attempts, by request id, whose spend no phase output
+    /// records yet. A timed-out attempt is dropped, so its spend is only
+    /// known here.
+    unrecorded: Mutex<HashMap<String, Vec<Arc<Budget>>>>,
+}
+
+impl ExpertExecutor {
+    /// `model` is usually `ai_agents.model_name`.
+    pub fn new(provider: Arc<dyn Provider>, model: impl Into<String>) -> Self {
+        let model = model.into();
+        Self {
+            provider,
+            pricing: Pricing::for_model(&model),
+            model,
+            budget: None,
+            max_tokens: 800,
+            scheduler: Scheduler::shared(),
+            unrecorded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Schedules on `scheduler` rather than [`Scheduler::shared`], which
+    /// has every built-in expert and no global cap.
+    pub fn with_scheduler(mut self, scheduler: Arc<Scheduler>) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Overrides [`Pricing::for_model`].
+    pub fn with_pricing(mut self, pricing: Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
+    /// Spend limit per request, in US dollars. A model [`Pricing::for_model`]
+    /// does not know needs [`Self::with_pricing`] too, or every phase fails
+    /// rather than run unmetered.
+    pub fn with_budget(mut self, dollars: f64) -> Self {
+        self.budget = Some(dollars);
+        self
+    }
+
+    /// The phase's tasks, limited to tasks an expert the request asked for
+    /// can take. If none is left, the first task goes to anyone.
+    fn tasks(&self, phase: Phase, run: &Run) -> Vec<TaskSpec> {
+        let req = &run.request;
+        let preferred: Vec<ExpertName> =
+            req.experts.iter().filter_map(|e| e.parse().ok()).collect();
+        let context = format!(
+            "{}\n{}\n{}",
+            req.project,
+            req.description,
+            req.requirements.join("\n")
+        );
+        let all: Vec<TaskSpec> = phase_tasks(phase)
+            .iter()
+            .map(|task_type| TaskSpec {
+                task_type: task_type.to_string(),
+                priority: req.priority,
+                preferred: preferred.clone(),
+                context: context.clone(),
+            })
+            .collect();
+        if req.experts.is_empty() {
+            return all;
+        }
+        let wanted: Vec<TaskSpec> = all
+            .iter()
+            .filter(|t| {
+                self.scheduler
+                    .candidates(t)
+                    .first()
+                    .is_some_and(|e| preferred.contains(e))
+            })
+            .cloned()
+            .collect();
+        if wanted.is_empty() {
+            all.into_iter().take(1).collect()
+        } else {
+            wanted
+        }
+    }
+
+    fn prompt(&self, phase: Phase, expert: &str, run: &Run) -> ChatRequest {
+        let req = &run.request;
+        let mut context = format!(
+            "Project: {}\nDescription: {}\n",
+            req.project, req.description
+        );
+        if !req.requirements.is_empty() {
+            context.push_str(&format!(
+                "Requirements:\n- {}\n",
+                req.requirements.join("\n- ")
+            ));
+        }
+        for (done, output) in &run.outputs {
+            let Ok(output) = serde_json::from_value::<PhaseOutput>(output.clone()) else {
+                continue;
+            };
+            for report in output.reports {
+                context.push_str(&format!(
+                    "\n## {done} ({})\n{}\n",
+                    report.expert, report.text
+                ));
+            }
+        }
+        let task = match phase {
+            Phase::Analysis => format!("Analyze {} for this project.", focus(expert)),
+            Phase::Architecture => "Design the system architecture: components, technology \
+                 stack, data flow, security considerations and scalability plan."
+                .to_string(),
+            Phase::Implementation => "Plan the implementation: the files and services to \
+                 create, their responsibilities, and how they are built and deployed."
+                .to_string(),
+            Phase::Review => "Review the architecture and implementation plan. Rate its \
+                 quality from 1 to 10, then list risks with mitigations and next steps."
+                .to_string(),
+        };
+        ChatRequest::new(&self.model)
+            .with_system(format!(
+                "You are a {expert} expert providing technical analysis. Be specific and actionable."
+            ))
+            .with_user(format!("{context}\n{task}"))
+            .with_max_tokens(self.max_tokens)
+    }
+}
+
+fn phase_error(e: LlmError) -> PhaseError {
+    if e.is_retryable() {
+        PhaseError::Retryable(e.to_string())
+    } else {
+        PhaseError::Permanent(e.to_string())
+    }
+}
+
+#[async_trait]
+impl Executor for ExpertExecutor {
+    async fn execute(&self, phase: Phase, run: &Run) -> Result<Value, PhaseError> {
+        let pricing = match (self.pricing, self.budget) {
+            (Some(pricing), _) => pricing,
+            (None, None) => Pricing::default(),
+            (None, Some(_)) => {
+                return Err(PhaseError::Permanent(format!(
+                    "no pricing for model {}; its budget cannot be enforced",
+                    self.model
+                )))
+            }
+        };
+        let spent: f64 = run
+            .outputs
+            .values()
+            .filter_map(|o| o["cost"].as_f64())
+            .sum::<f64>()
+            + run.attempt_spend;
+        let budget = Arc::new(Budget::new(
+            self.budget.map_or(f64::INFINITY, |limit| limit - spent),
+        ));
+        self.unrecorded
+            .lock()
+            .unwrap()
+            .entry(run.id().to_string())
+            .or_default()
+            .push(budget.clone());
+        let metered = Metered::new(self.provider.clone(), pricing, budget.clone());
+        let mut reports = Vec::new();
+        for task in self.tasks(phase, run) {
+            let assignment = self
+                .scheduler
+                .acquire(&task)
+                .await
+                .map_err(|e| PhaseError::Permanent(e.to_string()))?;
+            let expert = assignment.expert().as_str();
+            let completion = metered
+                .complete(&self.prompt(phase, expert, run))
+                .await
+                .map_err(phase_error)?;
+            reports.push(Report {
+                expert: expert.to_string(),
+                task_type: task.task_type,
+                text: completion.text,
+            });
+        }
+        // The output records this attempt's spend from here on.
+        if let Some(budgets) = self.unrecorded.lock().unwrap().get_mut(run.id()) {
+            budgets.retain(|b| !Arc::ptr_eq(b, &budget));
+        }
+        let output = PhaseOutput {
+            reports,
+            usage: budget.usage(),
+            cost: budget.spent(),
+        };
+        Ok(serde_json::to_value(output).expect("phase output serializes"))
+    }
+
+    fn take_spend(&self, id: &str) -> f64 {
+        self.unrecorded
+            .lock()
+            .unwrap()
+            .remove(id)
+            .into_iter()
+            .flatten()
+            .map(|b| b.spent())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use llm::replay::Replay;
+    use llm::Completion;
+
+    use super::*;
+    use crate::Request;
+
+    /// Replays, except that call `fail` (from 1) gets a 503.
+    struct FailsCall {
+        replay: Replay,
+        fail: usize,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Provider for FailsCall {
+        fn name(&self) -> &'static str {
+            "fails-call"
+        }
+
+        async fn complete(&self, request: &ChatRequest) -> Result<Completion, LlmError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) + 1 == self.fail {
+                return Err(LlmError::Status {
+                    provider: "fails-call",
+                    status: 503,
+                    body: String::new(),
+                });
+            }
+            self.replay.complete(request).await
+        }
+    }
+
+    #[test]
+    fn phases_consult_requested_experts_or_the_architect() {
+        let executor = ExpertExecutor::new(Arc::new(Replay::new()), "m");
+        let experts = |phase, requested: &[&str]| {
+            let request = Request::new("p", "d")
+                .with_experts(requested.iter().map(|e| e.to_string()).collect());
+            let run = Run::new(request);
+            executor
+                .tasks(phase, &run)
+                .iter()
+                .map(|t| executor.scheduler.candidates(t)[0])
+                .collect::<Vec<_>>()
+        };
+        use ExpertName::*;
+        assert_eq!(
+            experts(Phase::Analysis, &[]),
+            [Architecture, Security, Backend]
+        );
+        assert_eq!(
+            experts(Phase::Analysis, &["security", "frontend"]),
+            [Security]
+        );
+        assert_eq!(
+            experts(Phase::Architecture, &["security", "frontend"]),
+            [Architecture]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_budget_spans_phases_and_failures_are_permanent() {
+        let replay = Arc::new(Replay::new().respond("threat model", "Use mTLS."));
+        // $1 per thousand tokens: the analysis prompt takes most of the 6¢.
+        let executor = ExpertExecutor::new(replay.clone(), "gpt-4o-mini")
+            .with_pricing(Pricing::new(1000.0, 1000.0))
+            .with_budget(0.06);
+        let mut run = Run::new(
+            Request::new("billing", "invoices").with_experts(vec!["security".to_string()]),
+        );
+
+        let analysis = executor.execute(Phase::Analysis, &run).await.unwrap();
+        let output: PhaseOutput = serde_json::from_value(analysis.clone()).unwrap();
+        assert_eq!(output.reports[0].text, "Use mTLS.");
+        assert!(output.cost > 0.0);
+        run.outputs.insert(Phase::Analysis, analysis);
+
+        // The architecture prompt carries the analysis and no longer fits.
+        let err = executor
+            .execute(Phase::Architecture, &run)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, PhaseError::Permanent(m) if m.contains("budget")),
+            "{err}"
+        );
+        assert_eq!(replay.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_budget_is_enforced_without_explicit_pricing() {
+        let replay = Arc::new(Replay::new().respond("Analyze", "Noted."));
+        let run = Run::new(Request::new("billing", "invoices"));
+
+        // gpt-4o-mini has list prices; a thousandth of a cent does not pay
+        // for a whole phase.
+        let executor = ExpertExecutor::new(replay.clone(), "gpt-4o-mini").with_budget(0.00001);
+        let err = executor.execute(Phase::Analysis, &run).await.unwrap_err();
+        assert!(
+            matches!(&err, PhaseError::Permanent(m) if m.contains("budget")),
+            "{err}"
+        );
+
+        // An unknown model cannot be metered, so it is not called at all.
+        let calls = replay.calls().len();
+        let executor = ExpertExecutor::new(replay.clone(), "m").with_budget(100.0);
+        let err = executor.execute(Phase::Analysis, &run).await.unwrap_err();
+        assert!(
+            matches!(&err, PhaseError::Permanent(m) if m.contains("no pricing")),
+            "{err}"
+        );
+        assert_eq!(replay.calls().len(), calls);
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_count_against_the_budget() {
+        let pricing = Pricing::new(1000.0, 1000.0);
+        let provider = Arc::new(FailsCall {
+            replay: Replay::new().respond("Analyze", "Noted."),
+            fail: 2,
+            calls: AtomicUsize::new(0),
+        });
+        let run = Run::new(Request::new("billing", "invoices"));
+
+        // The second of the three analysis tasks fails after the first was
+        // paid for.
+        let measuring = ExpertExecutor::new(provider.clone(), "m").with_pricing(pricing);
+        let err = measuring.execute(Phase::Analysis, &run).await.unwrap_err();
+        assert!(matches!(err, PhaseError::Retryable(_)), "{err}");
+        let first = measuring.take_spend(run.id());
+        assert!(first > 0.0);
+        assert_eq!(measuring.take_spend(run.id()), 0.0);
+
+        // With that spend saved on the run, a budget it used up refuses
+        // the retry before any call.
+        let mut run = run;
+        run.attempt_spend = first;
+        let executor = ExpertExecutor::new(provider.clone(), "m")
+            .with_pricing(pricing)
+            .with_budget(first);
+        let err = executor.execute(Phase::Analysis, &run).await.unwrap_err();
+        assert!(
+            matches!(&err, PhaseError::Permanent(m) if m.contains("budget")),
+            "{err}"
+        );
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+
+        // A successful attempt's spend is in its output, not reported again.
+        measuring.execute(Phase::Analysis, &run).await.unwrap();
+        assert_eq!(measuring.take_spend(run.id()), 0.0);
+    }
+}
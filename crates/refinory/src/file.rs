@@ -0,0 +1,103 @@
+//! One JSON checkpoint per request in a directory, e.g. under
+//! `refinory.storage.outputs_dir`.
+//!
+//! Each checkpoint is written to a temporary file, synced and renamed over
+//! `{id}.json`. A crash mid-write leaves the previous checkpoint intact.
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+
+use crate::{Run, Store, StoreError};
+
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+fn io(path: &Path) -> impl FnOnce(std::io::Error) -> StoreError + '_ {
+    move |source| StoreError::Io {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+impl FileStore {
+    /// Creates `dir` if needed.
+    pub async fn open(dir: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let dir = dir.into();
+        tokio::fs::create_dir_all(&dir).await.map_err(io(&dir))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    async fn read(path: &Path) -> Result<Option<Run>, StoreError> {
+        let bytes = match tokio::fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(io(path)(e)),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|source| StoreError::Malformed {
+                path: path.display().to_string(),
+                source,
+            })
+    }
+}
+
+#[async_trait]
+impl Store for FileStore {
+    async fn save(&self, run: &Run) -> Result<(), StoreError> {
+        let path = self.path(run.id());
+        let tmp = path.with_extension("json.tmp");
+        let body = serde_json::to_vec_pretty(run).expect("runs serialize");
+        tokio::fs::write(&tmp, body).await.map_err(io(&tmp))?;
+        let file = tokio::fs::File::open(&tmp).await.map_err(io(&tmp))?;
+        file.sync_all().await.map_err(io(&tmp))?;
+        tokio::fs::rename(&tmp, &path).await.map_err(io(&path))
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Run>, StoreError> {
+        Self::read(&self.path(id)).await
+    }
+
+    async fn list(&self) -> Result<Vec<Run>, StoreError> {
+        let mut entries = tokio::fs::read_dir(&self.dir)
+            .await
+            .map_err(io(&self.dir))?;
+        let mut runs = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(io(&self.dir))? {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                runs.extend(Self::read(&path).await?);
+            }
+        }
+        runs.sort_by_key(|r| r.created_at);
+        Ok(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Phase, Request, RequestStatus};
+
+    #[tokio::test]
+    async fn checkpoints_round_trip_and_ignore_partial_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::open(dir.path().join("runs")).await.unwrap();
+        let mut run = Run::new(Request::new("atlas", "service mesh"));
+        run.transition(RequestStatus::Analyzing).unwrap();
+        run.outputs
+            .insert(Phase::Analysis, serde_json::json!({"complexity": "high"}));
+        store.save(&run).await.unwrap();
+        std::fs::write(dir.path().join("runs/other.json.tmp"), "{trunc").unwrap();
+
+        assert_eq!(store.load(run.id()).await.unwrap(), Some(run.clone()));
+        assert_eq!(store.list().await.unwrap(), [run]);
+        assert_eq!(store.load("missing").await.unwrap(), None);
+    }
+}
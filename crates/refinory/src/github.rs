@@ -0,0 +1,310 @@
+//! The slice of the GitHub REST API the PR generator needs: repository
+//! metadata, the Git Data API (blobs, trees, commits, refs) and pulls.
+//!
+//! [`GitHub::with_base_url`] points the client at GitHub Enterprise or at
+//! a local fake in tests.
+
+use base64::Engine as _;
+use reqwest::{Method, StatusCode};
+use secrets::Secret;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const GITHUB_API: &str = "https://api.github.com";
+/// Sent as `X-GitHub-Api-Version`.
+pub const API_VERSION: &str = "2022-11-28";
+
+#[derive(Debug, thiserror::Error)]
+pub enum GitHubError {
+    #[error("github request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{method} {path}: github returned {status}: {body}")]
+    Status {
+        method: Method,
+        path: String,
+        status: u16,
+        body: String,
+    },
+    #[error("{path}: unexpected response: {message}")]
+    Malformed { path: String, message: String },
+}
+
+impl GitHubError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            GitHubError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+/// `owner/name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repo {
+    pub owner: String,
+    pub name: String,
+}
+
+impl Repo {
+    /// Accepts `owner/name`, `https://github.com/owner/name(.git)` or a
+    /// bare `name`, which is taken to be in `default_org`
+    /// (`refinory.git.default_org`).
+    pub fn parse(s: &str, default_org: &str) -> Option<Self> {
+        let s = s.trim().trim_end_matches('/');
+        let s = s.strip_prefix("https://github.com/").unwrap_or(s);
+        let s = s.strip_suffix(".git").unwrap_or(s);
+        let valid = |part: &str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        };
+        let (owner, name) = s.split_once('/').unwrap_or((default_org, s));
+        (valid(owner) && valid(name)).then(|| Self {
+            owner: owner.to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for Repo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+/// An entry of a `POST /git/trees` request.
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeEntry {
+    pub path: String,
+    pub mode: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub sha: String,
+}
+
+impl TreeEntry {
+    pub fn file(path: impl Into<String>, blob_sha: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            mode: "100644",
+            kind: "blob",
+            sha: blob_sha.into(),
+        }
+    }
+}
+
+/// Author or committer of a `POST /git/commits` request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Person {
+    pub name: String,
+    pub email: String,
+    /// RFC 3339.
+    pub date: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub html_url: String,
+}
+
+pub struct GitHub {
+    http: reqwest::Client,
+    base_url: String,
+    token: Secret,
+}
+
+impl GitHub {
+    /// `token` is usually resolved from `refinory.secrets.github_token_ref`.
+    pub fn new(token: Secret) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: GITHUB_API.to_string(),
+            token,
+        }
+    }
+
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<T, GitHubError> {
+        let mut req = self
+            .http
+            .request(method.clone(), format!("{}{path}", self.base_url))
+            .bearer_auth(self.token.expose())
+            .header("accept", "application/vnd.github+json")
+            .header("x-github-api-version", API_VERSION)
+            .header("user-agent", "refinory");
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+        let res = req.send().await?;
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(GitHubError::Status {
+                method,
+                path: path.to_string(),
+                status: status.as_u16(),
+                body: body.chars().take(512).collect(),
+            });
+        }
+        res.json().await.map_err(|e| GitHubError::Malformed {
+            path: path.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    async fn sha(&self, method: Method, path: &str, body: Value) -> Result<String, GitHubError> {
+        let v: Value = self.call(method, path, Some(body)).await?;
+        string_at(&v, "/sha", path)
+    }
+
+    pub async fn default_branch(&self, repo: &Repo) -> Result<String, GitHubError> {
+        let path = format!("/repos/{repo}");
+        let v: Value = self.call(Method::GET, &path, None).await?;
+        string_at(&v, "/default_branch", &path)
+    }
+
+    /// The commit `branch` points at, or `None` if there is no such branch.
+    pub async fn branch_head(
+        &self,
+        repo: &Repo,
+        branch: &str,
+    ) -> Result<Option<String>, GitHubError> {
+        let path = format!("/repos/{repo}/git/ref/heads/{branch}");
+        match self.call::<Value>(Method::GET, &path, None).await {
+            Ok(v) => string_at(&v, "/object/sha", &path).map(Some),
+            Err(e) if e.status() == Some(StatusCode::NOT_FOUND.as_u16()) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The tree of `commit`.
+    pub async fn commit_tree(&self, repo: &Repo, commit: &str) -> Result<String, GitHubError> {
+        let path = format!("/repos/{repo}/git/commits/{commit}");
+        let v: Value = self.call(Method::GET, &path, None).await?;
+        string_at(&v, "/tree/sha", &path)
+    }
+
+    pub async fn create_blob(&self, repo: &Repo, content: &[u8]) -> Result<String, GitHubError> {
+        let content = base64::engine::general_purpose::STANDARD.encode(content);
+        let body = json!({ "content": content, "encoding": "base64" });
+        self.sha(Method::POST, &format!("/repos/{repo}/git/blobs"), body)
+            .await
+    }
+
+    /// `entries` on top of `base_tree`.
+    pub async fn create_tree(
+        &self,
+        repo: &Repo,
+        base_tree: Option<&str>,
+        entries: &[TreeEntry],
+    ) -> Result<String, GitHubError> {
+        let mut body = json!({ "tree": entries });
+        if let Some(base) = base_tree {
+            body["base_tree"] = json!(base);
+        }
+        self.sha(Method::POST, &format!("/repos/{repo}/git/trees"), body)
+            .await
+    }
+
+    pub async fn create_commit(
+        &self,
+        repo: &Repo,
+        message: &str,
+        tree: &str,
+        parents: &[String],
+        author: &Person,
+    ) -> Result<String, GitHubError> {
+        let body = json!({
+            "message": message,
+            "tree": tree,
+            "parents": parents,
+            "author": author,
+            "committer": author,
+        });
+        self.sha(Method::POST, &format!("/repos/{repo}/git/commits"), body)
+            .await
+    }
+
+    /// Points `branch` at `commit`, creating the branch or moving it.
+    pub async fn set_branch(
+        &self,
+        repo: &Repo,
+        branch: &str,
+        commit: &str,
+    ) -> Result<(), GitHubError> {
+        if self.branch_head(repo, branch).await?.is_some() {
+            let path = format!("/repos/{repo}/git/refs/heads/{branch}");
+            let body = json!({ "sha": commit, "force": true });
+            self.call::<Value>(Method::PATCH, &path, Some(body)).await?;
+        } else {
+            let path = format!("/repos/{repo}/git/refs");
+            let body = json!({ "ref": format!("refs/heads/{branch}"), "sha": commit });
+            self.call::<Value>(Method::POST, &path, Some(body)).await?;
+        }
+        Ok(())
+    }
+
+    /// A pull request, open or closed, from `head` in `repo` itself.
+    pub async fn find_pull(
+        &self,
+        repo: &Repo,
+        head: &str,
+    ) -> Result<Option<PullRequest>, GitHubError> {
+        let path = format!("/repos/{repo}/pulls?head={}:{head}&state=all", repo.owner);
+        let pulls: Vec<PullRequest> = self.call(Method::GET, &path, None).await?;
+        Ok(pulls.into_iter().next())
+    }
+
+    pub async fn create_pull(
+        &self,
+        repo: &Repo,
+        title: &str,
+        head: &str,
+        base: &str,
+        body: &str,
+    ) -> Result<PullRequest, GitHubError> {
+        let body = json!({ "title": title, "head": head, "base": base, "body": body });
+        self.call(Method::POST, &format!("/repos/{repo}/pulls"), Some(body))
+            .await
+    }
+}
+
+fn string_at(v: &Value, pointer: &str, path: &str) -> Result<String, GitHubError> {
+    v.pointer(pointer)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| GitHubError::Malformed {
+            path: path.to_string(),
+            message: format!("no string at {pointer}"),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_repository_references() {
+        let repo = |s| Repo::parse(s, "acme").map(|r| r.to_string());
+        assert_eq!(repo("acme/billing").as_deref(), Some("acme/billing"));
+        assert_eq!(
+            repo("https://github.com/other/api.git").as_deref(),
+            Some("other/api")
+        );
+        assert_eq!(repo("ref-billing").as_deref(), Some("acme/ref-billing"));
+        assert_eq!(repo("a/b/c"), None);
+        assert_eq!(repo("/billing"), None);
+    }
+}
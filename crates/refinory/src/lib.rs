@@ -0,0 +1,346 @@
+//! Durable execution of refinory architecture requests.
+//!
+//! A [`Request`] moves through four [`Phase`]s (analysis, architecture,
+//! implementation, review). [`engine::Engine`] runs each phase through an
+//! [`engine::Executor`] under a per-phase timeout and retry policy, and
+//! after every step writes a [`Run`] checkpoint to a [`Store`]. A restarted
+//! process resumes each unfinished run at its first phase without output
+//! instead of starting over.
+//!
+//! [`RequestStatus`] is the state machine the Python orchestrator exposes
+//! (`pending` … `completed`, plus `failed` and `cancelled`).
+//! [`file::FileStore`] keeps one JSON file per request. [`memory::InMemory`]
+//! is for tests. [`experts::ExpertExecutor`] does the phase work with an
+//! [`llm::Provider`], handing each task to an expert through a
+//! [`scheduler::Scheduler`]. [`pr::PrGenerator`] turns a completed run
+//! into a pull request.
+
+pub mod engine;
+pub mod experts;
+pub mod file;
+pub mod github;
+pub mod memory;
+pub mod metrics;
+pub mod pr;
+pub mod scheduler;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub(crate) fn unix_ms(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestStatus {
+    Pending,
+    Analyzing,
+    ExpertReview,
+    Generating,
+    Reviewing,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl RequestStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Analyzing => "analyzing",
+            Self::ExpertReview => "expert_review",
+            Self::Generating => "generating",
+            Self::Reviewing => "reviewing",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+
+    /// Requests only move forward through the phases. Any unfinished
+    /// request may fail or be cancelled. Terminal states are final.
+    pub fn can_become(self, to: Self) -> bool {
+        use RequestStatus::*;
+        match (self, to) {
+            (from, _) if from.is_terminal() => false,
+            (_, Failed | Cancelled) => true,
+            (Pending, Analyzing)
+            | (Analyzing, ExpertReview)
+            | (ExpertReview, Generating)
+            | (Generating, Reviewing)
+            | (Reviewing, Completed) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for RequestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+/// The phases of `ExpertEngine.processRequest`, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Analysis,
+    Architecture,
+    Implementation,
+    Review,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [
+        Phase::Analysis,
+        Phase::Architecture,
+        Phase::Implementation,
+        Phase::Review,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Analysis => "analysis",
+            Self::Architecture => "architecture",
+            Self::Implementation => "implementation",
+            Self::Review => "review",
+        }
+    }
+
+    /// The status a request shows while this phase runs.
+    pub fn status(self) -> RequestStatus {
+        match self {
+            Self::Analysis => RequestStatus::Analyzing,
+            Self::Architecture => RequestStatus::ExpertReview,
+            Self::Implementation => RequestStatus::Generating,
+            Self::Review => RequestStatus::Reviewing,
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An architecture request as submitted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub id: String,
+    pub project: String,
+    pub description: String,
+    #[serde(default)]
+    pub requirements: Vec<String>,
+    /// Experts asked for by name (`refinory.experts.team`).
+    #[serde(default)]
+    pub experts: Vec<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_repo: Option<String>,
+}
+
+impl Request {
+    pub fn new(project: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            project: project.into(),
+            description: description.into(),
+            requirements: Vec::new(),
+            experts: Vec::new(),
+            priority: Priority::default(),
+            github_repo: None,
+        }
+    }
+
+    pub fn with_requirements(mut self, requirements: Vec<String>) -> Self {
+        self.requirements = requirements;
+        self
+    }
+
+    pub fn with_experts(mut self, experts: Vec<String>) -> Self {
+        self.experts = experts;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// `owner/name`, a GitHub URL, or a name in `refinory.git.default_org`.
+    pub fn with_github_repo(mut self, repo: impl Into<String>) -> Self {
+        self.github_repo = Some(repo.into());
+        self
+    }
+}
+
+/// The persisted state of one request: the checkpoint a restart resumes
+/// from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Run {
+    pub request: Request,
+    pub status: RequestStatus,
+    /// Output of each finished phase. Phases with output are never run
+    /// again.
+    #[serde(default)]
+    pub outputs: BTreeMap<Phase, Value>,
+    /// Attempts started on the current phase.
+    #[serde(default)]
+    pub attempts: u32,
+    /// US dollars spent by attempts that produced no output; finished
+    /// phases record their own cost.
+    #[serde(default)]
+    pub attempt_spend: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// URL of the pull request [`pr::PrGenerator`] opened for the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_request: Option<String>,
+    /// Commit [`pr::PrGenerator`] pointed the pull request's branch at,
+    /// saved before the pull request is opened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_commit: Option<String>,
+    /// Unix milliseconds.
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("request cannot go from {from} to {to}")]
+pub struct InvalidTransition {
+    pub from: RequestStatus,
+    pub to: RequestStatus,
+}
+
+impl Run {
+    pub fn new(request: Request) -> Self {
+        let now = unix_ms(SystemTime::now());
+        Self {
+            request,
+            status: RequestStatus::Pending,
+            outputs: BTreeMap::new(),
+            attempts: 0,
+            attempt_spend: 0.0,
+            error: None,
+            pull_request: None,
+            pull_commit: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.request.id
+    }
+
+    /// The first phase without output; `None` once all have finished.
+    pub fn next_phase(&self) -> Option<Phase> {
+        Phase::ALL
+            .into_iter()
+            .find(|p| !self.outputs.contains_key(p))
+    }
+
+    /// Moves to `to`. Staying in the current status is allowed, because
+    /// a resumed run re-enters the phase it crashed in.
+    pub fn transition(&mut self, to: RequestStatus) -> Result<(), InvalidTransition> {
+        if self.status != to && !self.status.can_become(to) {
+            return Err(InvalidTransition {
+                from: self.status,
+                to,
+            });
+        }
+        self.status = to;
+        self.updated_at = unix_ms(SystemTime::now());
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("malformed checkpoint {path}: {source}")]
+    Malformed {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// Where [`Run`] checkpoints live.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Replaces the checkpoint for `run.request.id`. When this returns, the
+    /// checkpoint must survive a crash.
+    async fn save(&self, run: &Run) -> Result<(), StoreError>;
+
+    async fn load(&self, id: &str) -> Result<Option<Run>, StoreError>;
+
+    /// Every stored run, oldest first.
+    async fn list(&self) -> Result<Vec<Run>, StoreError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_only_moves_forward_and_terminal_states_are_final() {
+        use RequestStatus::*;
+        let mut status = Pending;
+        for phase in Phase::ALL {
+            assert!(status.can_become(phase.status()), "{status} -> {phase}");
+            status = phase.status();
+        }
+        assert!(status.can_become(Completed));
+        assert!(!Pending.can_become(Generating));
+        assert!(!Reviewing.can_become(Analyzing));
+        assert!(Generating.can_become(Cancelled));
+        for terminal in [Completed, Failed, Cancelled] {
+            assert!(!terminal.can_become(Failed));
+            assert!(!terminal.can_become(Analyzing));
+        }
+        assert_eq!(serde_json::to_value(ExpertReview).unwrap(), "expert_review");
+    }
+
+    #[test]
+    fn next_phase_skips_checkpointed_output() {
+        let mut run = Run::new(Request::new("p", "d"));
+        assert_eq!(run.next_phase(), Some(Phase::Analysis));
+        run.outputs.insert(Phase::Analysis, Value::Null);
+        run.outputs.insert(Phase::Architecture, Value::Null);
+        assert_eq!(run.next_phase(), Some(Phase::Implementation));
+        let json = serde_json::to_string(&run).unwrap();
+        assert!(json.contains(r#""analysis":null"#), "{json}");
+        assert_eq!(serde_json::from_str::<Run>(&json).unwrap(), run);
+    }
+}
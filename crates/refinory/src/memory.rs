@@ -0,0 +1,40 @@
+//! Process-local checkpoints. They do not survive a restart.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::{Run, Store, StoreError};
+
+#[derive(Default)]
+pub struct InMemory {
+    runs: Mutex<HashMap<String, Run>>,
+}
+
+impl InMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Store for InMemory {
+    async fn save(&self, run: &Run) -> Result<(), StoreError> {
+        self.runs
+            .lock()
+            .unwrap()
+            .insert(run.id().to_string(), run.clone());
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<Run>, StoreError> {
+        Ok(self.runs.lock().unwrap().get(id).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<Run>, StoreError> {
+        let mut runs: Vec<Run> = self.runs.lock().unwrap().values().cloned().collect();
+        runs.sort_by_key(|r| r.created_at);
+        Ok(runs)
+    }
+}
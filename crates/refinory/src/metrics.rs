@@ -0,0 +1,39 @@
+//! Prometheus metrics, registered in the process-wide default registry.
+
+use std::sync::LazyLock;
+
+use prometheus::{
+    register_histogram_vec, register_int_gauge, register_int_gauge_vec, HistogramVec, IntGauge,
+    IntGaugeVec,
+};
+
+/// `refinory_scheduler_queue_depth`: tasks waiting for an expert.
+pub static SCHEDULER_QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "refinory_scheduler_queue_depth",
+        "Expert tasks waiting for a free expert"
+    )
+    .expect("metric registered twice")
+});
+
+/// `refinory_scheduler_wait_seconds{priority}`: time from asking for an
+/// expert to getting one.
+pub static SCHEDULER_WAIT: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "refinory_scheduler_wait_seconds",
+        "Time expert tasks waited before starting",
+        &["priority"],
+        vec![0.01, 0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0]
+    )
+    .expect("metric registered twice")
+});
+
+/// `refinory_expert_tasks_running{expert}`.
+pub static EXPERT_TASKS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "refinory_expert_tasks_running",
+        "Expert tasks currently running",
+        &["expert"]
+    )
+    .expect("metric registered twice")
+});
@@ -0,0 +1,681 @@
+//! Opens a pull request with a completed request's output, replacing the
+//! unfinished flow in `refinory/refinory/github_integration.py`.
+//!
+//! The branch holds every artifact stored for the request, an ADR and an
+//! RFC filled in from `templates/`, and a `proof_of_non_hallucination`
+//! record. [`PrGenerator::publish`] commits it on top of the base branch
+//! through the Git Data API, so publishing needs only an API token and no
+//! git transport, and saves the commit and then the PR URL on the [`Run`].
+//! [`PrGenerator::write_branch`] commits the same files to a local bare
+//! repository under the work directory instead, for review before anything
+//! is pushed; it builds on the base branch only if that has been fetched
+//! there.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use artifacts::{rfc3339, ArtifactError, ArtifactStore, Digest};
+use git2::{IndexEntry, IndexTime, Oid, Repository, Signature, Time};
+use serde_yaml::Value as Yaml;
+
+use crate::experts::PhaseOutput;
+use crate::github::{GitHub, GitHubError, Person, Repo, TreeEntry};
+use crate::{Phase, RequestStatus, Run, Store, StoreError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PrError {
+    #[error("no request {0}")]
+    NotFound(String),
+    #[error("request is {0}, not completed")]
+    NotCompleted(RequestStatus),
+    #[error("request has no github_repo")]
+    NoRepo,
+    #[error("invalid github_repo {0:?}")]
+    InvalidRepo(String),
+    #[error("{path}: {source}")]
+    TemplateIo {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("template {template} has no {placeholder:?}")]
+    Template {
+        template: &'static str,
+        placeholder: String,
+    },
+    #[error("two files would be written to {0}")]
+    PathConflict(String),
+    #[error(transparent)]
+    Artifacts(#[from] ArtifactError),
+    #[error("git: {0}")]
+    Git(#[from] git2::Error),
+    #[error(transparent)]
+    GitHub(#[from] GitHubError),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+/// The templates in the repository's `templates/` directory.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    pub adr: String,
+    pub rfc: String,
+    pub proof: String,
+    pub disclaimer: String,
+}
+
+impl Templates {
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, PrError> {
+        let read = |name: &str| {
+            let path = dir.as_ref().join(name);
+            std::fs::read_to_string(&path).map_err(|source| PrError::TemplateIo {
+                path: path.display().to_string(),
+                source,
+            })
+        };
+        Ok(Self {
+            adr: read("adr_template.md")?,
+            rfc: read("rfc_template.md")?,
+            proof: read("proof_of_non_hallucination_template.yaml")?,
+            disclaimer: read("standard_disclaimer.txt")?,
+        })
+    }
+}
+
+/// Replaces each placeholder's first occurrence. A missing placeholder
+/// means the template changed and is an error rather than a silently
+/// half-filled document.
+fn fill(template: &'static str, text: &str, values: &[(&str, &str)]) -> Result<String, PrError> {
+    let mut out = text.to_string();
+    for (placeholder, value) in values {
+        let Some(at) = out.find(placeholder) else {
+            return Err(PrError::Template {
+                template,
+                placeholder: placeholder.to_string(),
+            });
+        };
+        out.replace_range(at..at + placeholder.len(), value);
+    }
+    Ok(out)
+}
+
+/// Drops list items that are still `- [placeholder]` and marks headings
+/// left without content.
+fn prune(text: &str) -> String {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|l| {
+            let l = l.trim();
+            !(l.starts_with("- [") && l.ends_with(']'))
+        })
+        .collect();
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        out.push_str(line);
+        out.push('\n');
+        let next = lines[i + 1..].iter().find(|l| !l.trim().is_empty());
+        if line.starts_with('#') && next.is_none_or(|n| n.starts_with('#') || n.starts_with("---"))
+        {
+            out.push_str("_To be completed in review._\n");
+        }
+    }
+    out
+}
+
+fn slug(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches('-');
+    out.chars()
+        .take(40)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// The experts' reports for `phase`, one section each.
+fn reports(run: &Run, phase: Phase) -> Option<String> {
+    let output: PhaseOutput = serde_json::from_value(run.outputs.get(&phase)?.clone()).ok()?;
+    let text: Vec<String> = output
+        .reports
+        .iter()
+        .map(|r| format!("**{}** ({}):\n\n{}", r.expert, r.task_type, r.text.trim()))
+        .collect();
+    (!text.is_empty()).then(|| text.join("\n\n"))
+}
+
+/// Document names and the date they carry, shared by every file of a PR.
+struct Names {
+    id: String,
+    date: String,
+    review_date: String,
+    adr_path: String,
+    rfc_path: String,
+    proof_path: String,
+}
+
+impl Names {
+    fn new(run: &Run) -> Self {
+        let secs = run.updated_at / 1000;
+        let id: String = run.id().chars().take(8).collect();
+        let slug = slug(&run.request.project);
+        Self {
+            date: rfc3339(secs)[..10].to_string(),
+            review_date: rfc3339(secs + 182 * 86_400)[..10].to_string(),
+            adr_path: format!("docs/adr/ADR-{id}-{slug}.md"),
+            rfc_path: format!("docs/rfc/RFC-{id}-{slug}.md"),
+            proof_path: format!("docs/proof_of_non_hallucination/{}.yaml", run.id()),
+            id,
+        }
+    }
+}
+
+fn render_adr(templates: &Templates, run: &Run, names: &Names) -> Result<String, PrError> {
+    let req = &run.request;
+    let mut context = req.description.clone();
+    if !req.requirements.is_empty() {
+        context.push_str(&format!("\n\n- {}", req.requirements.join("\n- ")));
+    }
+    let decision = reports(run, Phase::Architecture).unwrap_or_default();
+    let consequences = reports(run, Phase::Review).unwrap_or_default();
+    let number = format!("ADR-{}", names.id);
+    let title = format!("Architecture for {}", req.project);
+    let review = format!("**Review Date:** {}", names.review_date);
+    let rfc = format!("- [RFC-{}]({})", names.id, names.rfc_path);
+    let text = fill(
+        "adr_template.md",
+        &templates.adr,
+        &[
+            ("ADR-XXXX", &number),
+            ("[Decision Title]", &title),
+            ("**Review Date:** [YYYY-MM-DD]", &review),
+            ("[YYYY-MM-DD]", &names.date),
+            ("[Proposed/Accepted/Deprecated/Superseded]", "Proposed"),
+            ("[What is the issue motivating this decision?]", &context),
+            ("[What is the change we're proposing/making?]", &decision),
+            (
+                "[What becomes easier or more difficult to do because of this change?]",
+                &consequences,
+            ),
+            ("- [Link to RFC/Issue/Discussion]", &rfc),
+        ],
+    )?;
+    Ok(prune(&text))
+}
+
+fn render_rfc(templates: &Templates, run: &Run, names: &Names) -> Result<String, PrError> {
+    let req = &run.request;
+    let motivation = match (req.requirements.is_empty(), reports(run, Phase::Analysis)) {
+        (false, analysis) => format!(
+            "- {}\n\n{}",
+            req.requirements.join("\n- "),
+            analysis.unwrap_or_default()
+        ),
+        (true, analysis) => analysis.unwrap_or_default(),
+    };
+    let number = format!("RFC-{}", names.id);
+    let author = format!("refinory (request {})", run.id());
+    let design = reports(run, Phase::Architecture).unwrap_or_default();
+    let plan = reports(run, Phase::Implementation).unwrap_or_default();
+    let metrics = reports(run, Phase::Review).unwrap_or_default();
+    let text = fill(
+        "rfc_template.md",
+        &templates.rfc,
+        &[
+            ("RFC-XXXX", &number),
+            ("[Feature/System Name]", &req.project),
+            ("[Your Name]", &author),
+            ("[YYYY-MM-DD]", &names.date),
+            ("[Draft/Review/Accepted/Rejected]", "Draft"),
+            (
+                "[One paragraph explaining what this RFC proposes]",
+                &req.description,
+            ),
+            (
+                "[Why are we doing this? What problem does it solve?]",
+                &motivation,
+            ),
+            ("[Technical specification with diagrams]", &design),
+            ("[What other approaches were evaluated?]", ""),
+            ("[Legal/compliance/security implications]", ""),
+            ("[Phases, timeline, dependencies]", &plan),
+            ("[How do we know this worked?]", &metrics),
+        ],
+    )?;
+    Ok(prune(&text))
+}
+
+/// The proof record: one log entry per expert report. Nothing was
+/// retrieved to back the reports, so none has sources or a score and all
+/// are `unverified`. `sha256_hash` covers `files` as `sha256sum` would
+/// list them, sorted by path.
+fn render_proof(
+    templates: &Templates,
+    run: &Run,
+    names: &Names,
+    files: &[(String, Vec<u8>)],
+) -> Result<String, PrError> {
+    let missing = |placeholder: &str| PrError::Template {
+        template: "proof_of_non_hallucination_template.yaml",
+        placeholder: placeholder.to_string(),
+    };
+    let mut doc: Yaml =
+        serde_yaml::from_str(&templates.proof).map_err(|_| missing("a YAML mapping"))?;
+    let experts: Vec<String> = run
+        .outputs
+        .values()
+        .filter_map(|o| serde_json::from_value::<PhaseOutput>(o.clone()).ok())
+        .flat_map(|o| o.reports)
+        .map(|r| r.expert)
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let drafter = doc["drafter"]
+        .as_str()
+        .ok_or_else(|| missing("drafter"))?
+        .replace("TBD", &format!("refinory ({})", experts.join(", ")));
+    let map = doc
+        .as_mapping_mut()
+        .ok_or_else(|| missing("a YAML mapping"))?;
+    let mut set = |key: &str, value: Yaml| match map.get_mut(key) {
+        Some(slot) => {
+            *slot = value;
+            Ok(())
+        }
+        None => Err(missing(key)),
+    };
+
+    let mut log = Vec::new();
+    for (phase, output) in &run.outputs {
+        let Ok(output) = serde_json::from_value::<PhaseOutput>(output.clone()) else {
+            continue;
+        };
+        for report in output.reports {
+            log.push(
+                serde_yaml::to_value(serde_json::json!({
+                    "query": format!("{phase}: {} ({})", report.task_type, report.expert),
+                    "answer": report.text,
+                    "sources": [],
+                    "hallucination_score": null,
+                    "verdict": "unverified",
+                }))
+                .expect("log entries serialize"),
+            );
+        }
+    }
+
+    let mut listing: Vec<(&str, Digest)> = files
+        .iter()
+        .map(|(p, b)| (p.as_str(), Digest::of(b)))
+        .collect();
+    listing.sort();
+    let listing: String = listing
+        .iter()
+        .map(|(path, digest)| format!("{digest}  {path}\n"))
+        .collect();
+
+    set("document_id", run.id().into())?;
+    set(
+        "document_name",
+        format!(
+            "{} ({}, {})",
+            run.request.project, names.adr_path, names.rfc_path
+        )
+        .into(),
+    )?;
+    set("drafter", drafter.into())?;
+    set("timestamp", rfc3339(run.updated_at / 1000).into())?;
+    set("rag_query_log", Yaml::Sequence(log))?;
+    set("sha256_hash", Digest::of(listing.as_bytes()).hex().into())?;
+    let body = serde_yaml::to_string(&doc).expect("proof serializes");
+    Ok(format!(
+        "# proof_of_non_hallucination for refinory request {}\n{body}",
+        run.id()
+    ))
+}
+
+/// A pull request ready to be written: its branch, files and text.
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub repo: Repo,
+    pub branch: String,
+    /// Sorted by path.
+    pub files: Vec<(String, Vec<u8>)>,
+    pub message: String,
+    pub title: String,
+    pub body: String,
+    /// Unix seconds; the commit's author and committer time.
+    pub time: u64,
+}
+
+pub struct PrGenerator {
+    github: GitHub,
+    artifacts: Arc<ArtifactStore>,
+    templates: Templates,
+    workdir: PathBuf,
+    default_org: String,
+    author: (String, String),
+}
+
+impl PrGenerator {
+    /// Local repositories are kept under `workdir`, one bare repository
+    /// per GitHub repository.
+    pub fn new(
+        github: GitHub,
+        artifacts: Arc<ArtifactStore>,
+        templates: Templates,
+        workdir: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            github,
+            artifacts,
+            templates,
+            workdir: workdir.into(),
+            default_org: String::new(),
+            author: (
+                "refinory".to_string(),
+                "refinory@users.noreply.github.com".to_string(),
+            ),
+        }
+    }
+
+    /// Owner of repositories given by name only (`refinory.git.default_org`).
+    pub fn with_default_org(mut self, org: impl Into<String>) -> Self {
+        self.default_org = org.into();
+        self
+    }
+
+    pub fn with_author(mut self, name: impl Into<String>, email: impl Into<String>) -> Self {
+        self.author = (name.into(), email.into());
+        self
+    }
+
+    /// Everything that goes into the PR for `run`.
+    pub async fn draft(&self, run: &Run) -> Result<Draft, PrError> {
+        if run.status != RequestStatus::Completed {
+            return Err(PrError::NotCompleted(run.status));
+        }
+        let repo = run.request.github_repo.as_deref().ok_or(PrError::NoRepo)?;
+        let repo = Repo::parse(repo, &self.default_org)
+            .ok_or_else(|| PrError::InvalidRepo(repo.to_string()))?;
+
+        let names = Names::new(run);
+        let mut files = Vec::new();
+        if let Some(manifest) = self.artifacts.manifest(run.id()).await? {
+            for entry in &manifest.entries {
+                let file = self.artifacts.read(run.id(), &entry.filename, None).await?;
+                files.push((entry.filename.clone(), file.body));
+            }
+        }
+        files.push((
+            names.adr_path.clone(),
+            render_adr(&self.templates, run, &names)?.into(),
+        ));
+        files.push((
+            names.rfc_path.clone(),
+            render_rfc(&self.templates, run, &names)?.into(),
+        ));
+        let proof = render_proof(&self.templates, run, &names, &files)?;
+        files.push((names.proof_path.clone(), proof.into()));
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        if let Some(pair) = files.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(PrError::PathConflict(pair[0].0.clone()));
+        }
+
+        let project = &run.request.project;
+        let listing: String = files.iter().map(|(p, _)| format!("- `{p}`\n")).collect();
+        Ok(Draft {
+            branch: format!("refinory/arch-{}-{}", slug(project), names.id),
+            message: format!(
+                "Add refinory architecture for {project}\n\nRequest: {}\n",
+                run.id()
+            ),
+            title: format!("Architecture: {project}"),
+            body: format!(
+                "{}\n\nGenerated by refinory from request `{}`.\n\n\
+                 ### Files\n\n{listing}\n\
+                 Expert reports are logged in `{}`; none has been verified against sources.\n\n\
+                 ---\n\n{}",
+                run.request.description,
+                run.id(),
+                names.proof_path,
+                self.templates.disclaimer.trim()
+            ),
+            repo,
+            files,
+            time: run.updated_at / 1000,
+        })
+    }
+
+    /// Commits `draft` to its branch in the local repository, replacing any
+    /// earlier commit there, and returns the commit id. The parent is the
+    /// local `base` branch; without one the commit has none.
+    pub async fn write_branch(&self, draft: &Draft, base: &str) -> Result<Oid, PrError> {
+        let dir = self
+            .workdir
+            .join(&draft.repo.owner)
+            .join(format!("{}.git", draft.repo.name));
+        let draft = draft.clone();
+        let base = base.to_string();
+        let author = self.author.clone();
+        tokio::task::spawn_blocking(move || commit(&dir, &draft, &base, &author))
+            .await
+            .expect("git task panicked")
+    }
+
+    /// Drafts the PR for request `id`, commits it to its branch on GitHub,
+    /// opens the PR and records its URL on the run. A run that already has
+    /// a PR keeps it. A retry after a failure reuses the branch commit it
+    /// saved and a PR GitHub already opened for the branch.
+    pub async fn publish(&self, store: &dyn Store, id: &str) -> Result<String, PrError> {
+        let mut run = store
+            .load(id)
+            .await?
+            .ok_or_else(|| PrError::NotFound(id.to_string()))?;
+        if let Some(url) = &run.pull_request {
+            return Ok(url.clone());
+        }
+        let draft = self.draft(&run).await?;
+        let repo = &draft.repo;
+        let base = self.github.default_branch(repo).await?;
+
+        let head = self.github.branch_head(repo, &draft.branch).await?;
+        let commit = match run.pull_commit.clone() {
+            Some(commit) if head.as_ref() == Some(&commit) => commit,
+            _ => {
+                let commit = self.push(&draft, &base).await?;
+                run.pull_commit = Some(commit.clone());
+                store.save(&run).await?;
+                commit
+            }
+        };
+        let pr = match self.github.find_pull(repo, &draft.branch).await? {
+            Some(pr) => pr,
+            None => {
+                self.github
+                    .create_pull(repo, &draft.title, &draft.branch, &base, &draft.body)
+                    .await?
+            }
+        };
+        tracing::info!(request = %id, %repo, branch = %draft.branch, %commit, url = %pr.html_url, "opened pull request");
+
+        run.pull_request = Some(pr.html_url.clone());
+        store.save(&run).await?;
+        Ok(pr.html_url)
+    }
+
+    /// Commits `draft` on top of `base` on GitHub, points its branch at the
+    /// commit and returns the commit's SHA.
+    async fn push(&self, draft: &Draft, base: &str) -> Result<String, PrError> {
+        let repo = &draft.repo;
+        let head = self.github.branch_head(repo, base).await?;
+        let base_tree = match &head {
+            Some(commit) => Some(self.github.commit_tree(repo, commit).await?),
+            None => None,
+        };
+        let mut entries = Vec::new();
+        for (path, body) in &draft.files {
+            entries.push(TreeEntry::file(
+                path,
+                self.github.create_blob(repo, body).await?,
+            ));
+        }
+        let tree = self
+            .github
+            .create_tree(repo, base_tree.as_deref(), &entries)
+            .await?;
+        let author = Person {
+            name: self.author.0.clone(),
+            email: self.author.1.clone(),
+            date: rfc3339(draft.time),
+        };
+        let parents: Vec<String> = head.into_iter().collect();
+        let commit = self
+            .github
+            .create_commit(repo, &draft.message, &tree, &parents, &author)
+            .await?;
+        self.github.set_branch(repo, &draft.branch, &commit).await?;
+        Ok(commit)
+    }
+}
+
+fn commit(
+    dir: &Path,
+    draft: &Draft,
+    base: &str,
+    author: &(String, String),
+) -> Result<Oid, PrError> {
+    let repo = match Repository::open_bare(dir) {
+        Ok(repo) => repo,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Repository::init_bare(dir)?,
+        Err(e) => return Err(e.into()),
+    };
+    let parent = match repo.find_reference(&format!("refs/heads/{base}")) {
+        Ok(r) => Some(r.peel_to_commit()?),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let mut index = git2::Index::new()?;
+    if let Some(parent) = &parent {
+        index.read_tree(&parent.tree()?)?;
+    }
+    for (path, body) in &draft.files {
+        let entry = IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: body.len() as u32,
+            id: repo.blob(body)?,
+            flags: path.len().min(0xfff) as u16,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        };
+        index.add(&entry)?;
+    }
+    let tree = repo.find_tree(index.write_tree_to(&repo)?)?;
+    let signature = Signature::new(&author.0, &author.1, &Time::new(draft.time as i64, 0))?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let oid = repo.commit(
+        None,
+        &signature,
+        &signature,
+        &draft.message,
+        &tree,
+        &parents,
+    )?;
+    repo.reference(
+        &format!("refs/heads/{}", draft.branch),
+        oid,
+        true,
+        "refinory: architecture branch",
+    )?;
+    Ok(oid)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::Request;
+
+    fn templates() -> Templates {
+        Templates::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("../../templates")).unwrap()
+    }
+
+    fn run() -> Run {
+        let mut run = Run::new(
+            Request::new("Billing Service", "Invoices for tenants.")
+                .with_requirements(vec!["PCI scope minimal".to_string()]),
+        );
+        run.updated_at = 1_709_211_909_000;
+        let output = |expert: &str, text: &str| {
+            json!({
+                "reports": [{"expert": expert, "task_type": "system_design", "text": text}],
+                "usage": {"input_tokens": 1, "output_tokens": 1},
+                "cost": 0.0,
+            })
+        };
+        run.outputs.insert(
+            Phase::Architecture,
+            output("architecture", "Postgres + queue."),
+        );
+        run.outputs
+            .insert(Phase::Review, output("security", "Rotate keys."));
+        run
+    }
+
+    #[test]
+    fn fills_the_repository_templates() {
+        let (templates, run) = (templates(), run());
+        let names = Names::new(&run);
+        let adr = render_adr(&templates, &run, &names).unwrap();
+        assert!(adr.starts_with(&format!(
+            "# ADR-{}: Architecture for Billing Service",
+            &run.id()[..8]
+        )));
+        assert!(adr.contains("**Date:** 2024-02-29"));
+        assert!(adr.contains("**Review Date:** 2024-08-29"));
+        assert!(adr.contains("**architecture** (system_design):\n\nPostgres + queue."));
+        assert!(adr.contains("### Positive\n_To be completed in review._"));
+        assert!(!adr.contains("[Benefit 1]"));
+
+        let rfc = render_rfc(&templates, &run, &names).unwrap();
+        assert!(rfc.contains("- PCI scope minimal"));
+        assert!(rfc.contains("**Attorney Review Required:** [ ] Yes [ ] No"));
+        assert!(!rfc.contains("[Technical specification"));
+
+        let files = vec![("a.md".to_string(), b"a".to_vec())];
+        let proof: Yaml =
+            serde_yaml::from_str(&render_proof(&templates, &run, &names, &files).unwrap()).unwrap();
+        assert_eq!(proof["document_id"].as_str(), Some(run.id()));
+        assert_eq!(proof["rag_query_log"].as_sequence().unwrap().len(), 2);
+        assert_eq!(
+            proof["rag_query_log"][0]["verdict"].as_str(),
+            Some("unverified")
+        );
+        assert_eq!(proof["attorney_review"]["approved"].as_bool(), Some(false));
+        assert_eq!(
+            proof["drafter"].as_str(),
+            Some("Domenic Garza + AI Node: refinory (architecture, security)")
+        );
+
+        let err = fill("adr_template.md", "# ADR", &[("[Decision Title]", "x")]).unwrap_err();
+        assert!(matches!(err, PrError::Template { .. }), "{err}");
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug("Billing Service"), "billing-service");
+        assert_eq!(slug("  AI/ML  pipeline! "), "ai-ml-pipeline");
+    }
+}
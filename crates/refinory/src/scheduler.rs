@@ -0,0 +1,561 @@
+//! Assigns phase tasks to experts.
+//!
+//! A task names a task type. The experts whose [`Capability`] lists that
+//! type can take it. Among those, experts the request asked for come first,
+//! then experts with more technologies mentioned in the request. An expert
+//! runs at most [`Capability::max_concurrent_tasks`] tasks at once, and the
+//! whole team at most the global cap (`refinory.runtime.max_concurrency`).
+//!
+//! Tasks that cannot start wait in [`Priority`] order, first come first
+//! served within a priority. A lower-priority task can start ahead of a
+//! higher one only if the experts it can use are free and the higher one's
+//! are not.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::{metrics, Priority};
+
+/// `ExpertName` in `refinory/refinory/experts.py`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpertName {
+    Frontend,
+    Backend,
+    Devops,
+    Security,
+    AiMl,
+    Mobile,
+    Blockchain,
+    Testing,
+    Architecture,
+    DataScience,
+}
+
+impl ExpertName {
+    pub const ALL: [ExpertName; 10] = [
+        ExpertName::Frontend,
+        ExpertName::Backend,
+        ExpertName::Devops,
+        ExpertName::Security,
+        ExpertName::AiMl,
+        ExpertName::Mobile,
+        ExpertName::Blockchain,
+        ExpertName::Testing,
+        ExpertName::Architecture,
+        ExpertName::DataScience,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Frontend => "frontend",
+            Self::Backend => "backend",
+            Self::Devops => "devops",
+            Self::Security => "security",
+            Self::AiMl => "ai_ml",
+            Self::Mobile => "mobile",
+            Self::Blockchain => "blockchain",
+            Self::Testing => "testing",
+            Self::Architecture => "architecture",
+            Self::DataScience => "data_science",
+        }
+    }
+}
+
+impl fmt::Display for ExpertName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown expert {0:?}")]
+pub struct UnknownExpert(pub String);
+
+impl FromStr for ExpertName {
+    type Err = UnknownExpert;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|e| e.as_str() == s)
+            .ok_or_else(|| UnknownExpert(s.to_string()))
+    }
+}
+
+/// `ExpertCapability` in `refinory/refinory/experts.py`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capability {
+    pub expert: ExpertName,
+    pub title: String,
+    pub technologies: Vec<String>,
+    pub task_types: Vec<String>,
+    pub max_concurrent_tasks: u32,
+}
+
+impl Capability {
+    pub fn new(
+        expert: ExpertName,
+        title: &str,
+        technologies: &[&str],
+        task_types: &[&str],
+    ) -> Self {
+        Self {
+            expert,
+            title: title.to_string(),
+            technologies: technologies.iter().map(|t| t.to_string()).collect(),
+            task_types: task_types.iter().map(|t| t.to_string()).collect(),
+            max_concurrent_tasks: 3,
+        }
+    }
+
+    pub fn with_max_concurrent_tasks(mut self, max: u32) -> Self {
+        self.max_concurrent_tasks = max;
+        self
+    }
+
+    /// Technologies mentioned in `text`, case-insensitively.
+    pub fn mentions(&self, text: &str) -> usize {
+        let text = text.to_lowercase();
+        self.technologies
+            .iter()
+            .filter(|t| text.contains(&t.to_lowercase()))
+            .count()
+    }
+}
+
+/// A built-in capability from comma-separated lists.
+fn builtin(expert: ExpertName, title: &str, technologies: &str, task_types: &str) -> Capability {
+    let technologies: Vec<&str> = technologies.split(", ").collect();
+    let task_types: Vec<&str> = task_types.split(", ").collect();
+    Capability::new(expert, title, &technologies, &task_types)
+}
+
+/// The experts on the team and what each can do.
+#[derive(Debug, Clone)]
+pub struct Capabilities(BTreeMap<ExpertName, Capability>);
+
+impl Capabilities {
+    /// The capabilities defined in `refinory/refinory/experts.py`.
+    pub fn builtin() -> Self {
+        use ExpertName::*;
+        Self::from_iter([
+            builtin(
+                Frontend,
+                "Frontend Specialist",
+                "React, Vue, Angular, TypeScript, JavaScript, HTML5, CSS3, SASS/SCSS, Tailwind, Material-UI, Next.js, Nuxt.js, Svelte, WebAssembly, Progressive Web Apps",
+                "ui_architecture, component_design, state_management, routing_strategy, performance_optimization, accessibility_audit",
+            ),
+            builtin(
+                Backend,
+                "Backend Specialist",
+                "Python, FastAPI, Django, Flask, Node.js, Express, Go, Gin, Rust, Actix, Java, Spring Boot, GraphQL, REST APIs, gRPC, WebSockets, Message Queues",
+                "api_design, database_architecture, microservices_design, caching_strategy, message_queue_design, scalability_planning",
+            ),
+            builtin(
+                Devops,
+                "DevOps Specialist",
+                "Docker, Kubernetes, Terraform, Ansible, Jenkins, GitHub Actions, AWS, GCP, Azure, Prometheus, Grafana, ELK Stack, Istio, Helm, ArgoCD",
+                "infrastructure_design, ci_cd_pipeline, monitoring_setup, disaster_recovery, security_hardening, cost_optimization",
+            ),
+            builtin(
+                Security,
+                "Security Specialist",
+                "OAuth2, JWT, TLS/SSL, OWASP, Penetration Testing, Vulnerability Assessment, SIEM, WAF, IAM, Zero Trust, Container Security, Secrets Management, Compliance Frameworks",
+                "threat_modeling, security_architecture, vulnerability_assessment, compliance_review, penetration_testing_plan, security_policies",
+            ),
+            builtin(
+                AiMl,
+                "AI/ML Specialist",
+                "TensorFlow, PyTorch, Scikit-learn, Transformers, OpenAI API, LangChain, Vector Databases, MLflow, Kubeflow, Ray, ONNX, TensorRT, Edge AI, Computer Vision, NLP",
+                "model_architecture, training_pipeline, inference_optimization, data_pipeline, feature_engineering, model_deployment",
+            ),
+            builtin(
+                Mobile,
+                "Mobile Specialist",
+                "React Native, Flutter, Swift, Kotlin, Xamarin, Ionic, PWA, Mobile CI/CD, App Store Optimization, Push Notifications, Mobile Analytics, Offline-first Architecture",
+                "mobile_architecture, cross_platform_strategy, performance_optimization, offline_capabilities, native_integrations, app_store_strategy",
+            ),
+            builtin(
+                Blockchain,
+                "Blockchain Specialist",
+                "Ethereum, Solidity, Web3.js, Truffle, Hardhat, IPFS, The Graph, Layer 2, DeFi Protocols, NFTs, Smart Contracts, Consensus Mechanisms, Tokenomics",
+                "smart_contract_design, tokenomics_design, defi_architecture, consensus_strategy, layer2_integration, security_audit",
+            ),
+            builtin(
+                Testing,
+                "Testing Specialist",
+                "Jest, Cypress, Selenium, Playwright, pytest, JUnit, TestNG, Load Testing, Performance Testing, Chaos Engineering, Property-based Testing, Contract Testing",
+                "test_strategy, automation_framework, performance_testing, integration_testing, chaos_engineering, quality_metrics",
+            ),
+            builtin(
+                Architecture,
+                "System Architecture Specialist",
+                "Microservices, Event-Driven Architecture, CQRS, Event Sourcing, Domain-Driven Design, Clean Architecture, Hexagonal Architecture, Service Mesh, API Gateway, Load Balancers, CDN, Caching",
+                "system_design, architecture_patterns, scalability_analysis, technology_selection, integration_strategy, architecture_review",
+            ),
+            builtin(
+                DataScience,
+                "Data Science Specialist",
+                "Pandas, NumPy, Apache Spark, Airflow, Kafka, ClickHouse, PostgreSQL, MongoDB, Redis, ElasticSearch, Data Warehousing, ETL/ELT, Stream Processing, Time Series",
+                "data_pipeline_design, analytics_architecture, data_modeling, stream_processing, data_governance, warehouse_design",
+            ),
+        ])
+    }
+
+    /// The built-in capabilities of the experts in `refinory.experts.team`.
+    pub fn for_team(team: &[discovery::ExpertEntry]) -> Result<Self, UnknownExpert> {
+        let builtin = Self::builtin();
+        let mut caps = BTreeMap::new();
+        for entry in team {
+            let name: ExpertName = entry.name.parse()?;
+            caps.insert(name, builtin.0[&name].clone());
+        }
+        Ok(Self(caps))
+    }
+
+    pub fn get(&self, expert: ExpertName) -> Option<&Capability> {
+        self.0.get(&expert)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Capability> {
+        self.0.values()
+    }
+}
+
+impl FromIterator<Capability> for Capabilities {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        Self(iter.into_iter().map(|c| (c.expert, c)).collect())
+    }
+}
+
+/// One unit of phase work to hand to an expert.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskSpec {
+    pub task_type: String,
+    pub priority: Priority,
+    /// Experts the request asked for; preferred over the rest.
+    pub preferred: Vec<ExpertName>,
+    /// Request text matched against each expert's technologies.
+    pub context: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("no expert on the team handles {0:?}")]
+pub struct NoCapableExpert(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("the global task cap must be at least 1")]
+pub struct ZeroGlobalCap;
+
+struct Waiter {
+    candidates: Vec<ExpertName>,
+    grant: oneshot::Sender<ExpertName>,
+}
+
+#[derive(Default)]
+struct State {
+    running: HashMap<ExpertName, u32>,
+    total: usize,
+    next: u64,
+    /// Highest priority first, then oldest.
+    waiting: BTreeMap<(Reverse<Priority>, u64), Waiter>,
+}
+
+pub struct Scheduler {
+    capabilities: Capabilities,
+    global: usize,
+    state: Mutex<State>,
+}
+
+/// See [`Scheduler::shared`].
+static SHARED: LazyLock<Arc<Scheduler>> = LazyLock::new(|| {
+    Arc::new(Scheduler::new(Capabilities::builtin(), usize::MAX).expect("cap is not zero"))
+});
+
+impl Scheduler {
+    /// `global` caps tasks across all experts. With a cap of 0 no task
+    /// could ever start.
+    pub fn new(capabilities: Capabilities, global: usize) -> Result<Self, ZeroGlobalCap> {
+        if global == 0 {
+            return Err(ZeroGlobalCap);
+        }
+        Ok(Self {
+            capabilities,
+            global,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    /// The process's scheduler for the built-in experts, without a global
+    /// cap. The queue and running gauges are process-wide, so a process
+    /// should schedule through one scheduler: this one, or one it shares
+    /// the same way.
+    pub fn shared() -> Arc<Scheduler> {
+        SHARED.clone()
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Experts able to take `task`, best match first.
+    pub fn candidates(&self, task: &TaskSpec) -> Vec<ExpertName> {
+        let mut capable: Vec<&Capability> = self
+            .capabilities
+            .iter()
+            .filter(|c| c.task_types.contains(&task.task_type))
+            .collect();
+        capable.sort_by_key(|c| {
+            (
+                !task.preferred.contains(&c.expert),
+                Reverse(c.mentions(&task.context)),
+                c.expert,
+            )
+        });
+        capable.into_iter().map(|c| c.expert).collect()
+    }
+
+    /// Tasks waiting for an expert.
+    pub fn queue_depth(&self) -> usize {
+        self.state.lock().unwrap().waiting.len()
+    }
+
+    /// Tasks running on `expert`.
+    pub fn running(&self, expert: ExpertName) -> u32 {
+        let state = self.state.lock().unwrap();
+        state.running.get(&expert).copied().unwrap_or(0)
+    }
+
+    /// Waits for a free expert able to take `task`. The slot is held until
+    /// the returned [`Assignment`] is dropped.
+    pub async fn acquire(self: &Arc<Self>, task: &TaskSpec) -> Result<Assignment, NoCapableExpert> {
+        let candidates = self.candidates(task);
+        if candidates.is_empty() {
+            return Err(NoCapableExpert(task.task_type.clone()));
+        }
+        let started = Instant::now();
+        let (grant, rx) = oneshot::channel();
+        let key = {
+            let mut state = self.state.lock().unwrap();
+            let key = (Reverse(task.priority), state.next);
+            state.next += 1;
+            state.waiting.insert(key, Waiter { candidates, grant });
+            self.dispatch(&mut state);
+            key
+        };
+        let mut pending = Pending {
+            scheduler: self,
+            key,
+            rx: Some(rx),
+        };
+        let expert = pending
+            .rx
+            .as_mut()
+            .expect("receiver present until granted")
+            .await
+            .expect("waiters are only dropped after a grant");
+        pending.rx = None;
+        metrics::SCHEDULER_WAIT
+            .with_label_values(&[priority_label(task.priority)])
+            .observe(started.elapsed().as_secs_f64());
+        Ok(Assignment {
+            scheduler: self.clone(),
+            expert,
+        })
+    }
+
+    /// Starts every waiting task that can start, in queue order.
+    fn dispatch(&self, state: &mut State) {
+        let keys: Vec<_> = state.waiting.keys().copied().collect();
+        for key in keys {
+            if state.total >= self.global {
+                break;
+            }
+            let waiter = &state.waiting[&key];
+            let free = waiter.candidates.iter().copied().find(|e| {
+                let max = self
+                    .capabilities
+                    .get(*e)
+                    .map_or(0, |c| c.max_concurrent_tasks);
+                state.running.get(e).copied().unwrap_or(0) < max
+            });
+            let Some(expert) = free else { continue };
+            let waiter = state.waiting.remove(&key).expect("key just seen");
+            if waiter.grant.send(expert).is_ok() {
+                *state.running.entry(expert).or_default() += 1;
+                state.total += 1;
+            }
+        }
+        metrics::SCHEDULER_QUEUE_DEPTH.set(state.waiting.len() as i64);
+        for expert in ExpertName::ALL {
+            metrics::EXPERT_TASKS
+                .with_label_values(&[expert.as_str()])
+                .set(state.running.get(&expert).copied().unwrap_or(0).into());
+        }
+    }
+
+    fn release(&self, expert: ExpertName) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(n) = state.running.get_mut(&expert) {
+            *n = n.saturating_sub(1);
+        }
+        state.total = state.total.saturating_sub(1);
+        self.dispatch(&mut state);
+    }
+}
+
+fn priority_label(p: Priority) -> &'static str {
+    match p {
+        Priority::Low => "low",
+        Priority::Normal => "normal",
+        Priority::High => "high",
+        Priority::Critical => "critical",
+    }
+}
+
+/// Cleans up after an [`Scheduler::acquire`] that was dropped while
+/// waiting, including one granted a slot it never received.
+struct Pending<'a> {
+    scheduler: &'a Scheduler,
+    key: (Reverse<Priority>, u64),
+    rx: Option<oneshot::Receiver<ExpertName>>,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        let Some(mut rx) = self.rx.take() else { return };
+        let removed = {
+            let mut state = self.scheduler.state.lock().unwrap();
+            let removed = state.waiting.remove(&self.key).is_some();
+            if removed {
+                metrics::SCHEDULER_QUEUE_DEPTH.set(state.waiting.len() as i64);
+            }
+            removed
+        };
+        if !removed {
+            if let Ok(expert) = rx.try_recv() {
+                self.scheduler.release(expert);
+            }
+        }
+    }
+}
+
+/// A running task's hold on its expert.
+pub struct Assignment {
+    scheduler: Arc<Scheduler>,
+    expert: ExpertName,
+}
+
+impl Assignment {
+    pub fn expert(&self) -> ExpertName {
+        self.expert
+    }
+}
+
+impl Drop for Assignment {
+    fn drop(&mut self) {
+        self.scheduler.release(self.expert);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn task(task_type: &str, priority: Priority) -> TaskSpec {
+        TaskSpec {
+            task_type: task_type.to_string(),
+            priority,
+            preferred: Vec::new(),
+            context: String::new(),
+        }
+    }
+
+    #[test]
+    fn one_scheduler_per_process_and_never_a_zero_cap() {
+        assert!(Arc::ptr_eq(&Scheduler::shared(), &Scheduler::shared()));
+        assert_eq!(
+            Scheduler::new(Capabilities::builtin(), 0).err(),
+            Some(ZeroGlobalCap)
+        );
+    }
+
+    #[test]
+    fn candidates_prefer_requested_experts_then_technology_overlap() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../discovery.yml");
+        let refinory = discovery::Discovery::load(path).unwrap().refinory.unwrap();
+        let caps = Capabilities::for_team(&refinory.experts.team).unwrap();
+        assert_eq!(caps.iter().count(), 10);
+        let scheduler = Scheduler::new(caps, refinory.runtime.max_concurrency as usize).unwrap();
+        let mut perf = task("performance_optimization", Priority::Normal);
+        assert_eq!(
+            scheduler.candidates(&perf),
+            [ExpertName::Frontend, ExpertName::Mobile]
+        );
+        perf.context = "A Flutter app with offline sync".into();
+        assert_eq!(scheduler.candidates(&perf)[0], ExpertName::Mobile);
+        perf.preferred = vec![ExpertName::Frontend];
+        assert_eq!(scheduler.candidates(&perf)[0], ExpertName::Frontend);
+        assert!(scheduler
+            .candidates(&task("astrology", Priority::Low))
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn busy_experts_queue_tasks_by_priority() {
+        let caps = Capabilities::from_iter([Capability::new(
+            ExpertName::Security,
+            "Security Specialist",
+            &[],
+            &["threat_modeling"],
+        )
+        .with_max_concurrent_tasks(1)]);
+        let scheduler = Arc::new(Scheduler::new(caps, 8).unwrap());
+        let first = scheduler
+            .acquire(&task("threat_modeling", Priority::Normal))
+            .await
+            .unwrap();
+
+        let (order_tx, mut order) = tokio::sync::mpsc::unbounded_channel();
+        for priority in [Priority::Low, Priority::Critical, Priority::High] {
+            let scheduler = scheduler.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _held = scheduler
+                    .acquire(&task("threat_modeling", priority))
+                    .await
+                    .unwrap();
+                order_tx.send(priority).unwrap();
+            });
+        }
+        while scheduler.queue_depth() < 3 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        // An abandoned waiter must not hold up the queue.
+        let abandoned = tokio::time::timeout(
+            Duration::from_millis(5),
+            scheduler.acquire(&task("threat_modeling", Priority::Critical)),
+        )
+        .await;
+        assert!(abandoned.is_err());
+        assert_eq!(scheduler.queue_depth(), 3);
+
+        drop(first);
+        let mut served = Vec::new();
+        for _ in 0..3 {
+            served.push(order.recv().await.unwrap());
+        }
+        assert_eq!(served, [Priority::Critical, Priority::High, Priority::Low]);
+        assert_eq!(scheduler.running(ExpertName::Security), 0);
+    }
+}
@@ -0,0 +1,210 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use refinory::engine::{Engine, Executor, PhaseError, PhasePolicy, RetryPolicy};
+use refinory::file::FileStore;
+use refinory::memory::InMemory;
+use refinory::{Phase, Request, RequestStatus, Run, Store};
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy)]
+enum Step {
+    Done,
+    Flaky,
+    Hang,
+}
+
+/// Records calls and answers from a script of phase and attempt number to
+/// [`Step`].
+struct Script {
+    calls: Mutex<Vec<Phase>>,
+    step: fn(Phase, usize) -> Step,
+}
+
+impl Script {
+    fn new(step: fn(Phase, usize) -> Step) -> Arc<Self> {
+        Arc::new(Self {
+            calls: Mutex::new(Vec::new()),
+            step,
+        })
+    }
+
+    fn calls(&self) -> Vec<Phase> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Executor for Script {
+    async fn execute(&self, phase: Phase, run: &Run) -> Result<Value, PhaseError> {
+        let attempt = {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(phase);
+            calls.iter().filter(|p| **p == phase).count()
+        };
+        match (self.step)(phase, attempt) {
+            Step::Done => Ok(json!({ "phase": phase, "after": run.outputs.len() })),
+            Step::Flaky => Err(PhaseError::Retryable("provider returned 503".into())),
+            Step::Hang => std::future::pending().await,
+        }
+    }
+}
+
+fn fast(max_attempts: u32) -> PhasePolicy {
+    PhasePolicy {
+        timeout: Duration::from_millis(50),
+        retry: RetryPolicy {
+            base: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            max_attempts,
+        },
+    }
+}
+
+#[tokio::test]
+async fn resumes_after_a_crash_without_rerunning_finished_phases() {
+    let dir = tempfile::tempdir().unwrap();
+    let store: Arc<dyn Store> = Arc::new(FileStore::open(dir.path()).await.unwrap());
+    let crashing = Script::new(|phase, _| match phase {
+        Phase::Analysis => Step::Done,
+        _ => Step::Hang,
+    });
+    let engine = Engine::new(store, crashing.clone());
+    let id = engine
+        .submit(Request::new("atlas", "service mesh"))
+        .await
+        .unwrap()
+        .request
+        .id;
+    // The process "dies" while the architecture phase is in flight.
+    let _ = tokio::time::timeout(Duration::from_millis(100), engine.drive(&id)).await;
+    assert_eq!(crashing.calls(), [Phase::Analysis, Phase::Architecture]);
+
+    let store: Arc<dyn Store> = Arc::new(FileStore::open(dir.path()).await.unwrap());
+    let checkpoint = store.load(&id).await.unwrap().unwrap();
+    assert_eq!(checkpoint.status, RequestStatus::ExpertReview);
+    assert_eq!(checkpoint.attempts, 1);
+
+    let healthy = Script::new(|_, _| Step::Done);
+    let engine = Arc::new(Engine::new(store, healthy.clone()));
+    let mut resumed = engine.resume().await.unwrap();
+    assert_eq!(resumed.len(), 1);
+    let run = resumed.pop().unwrap().await.unwrap().unwrap();
+
+    assert_eq!(run.status, RequestStatus::Completed);
+    assert_eq!(
+        healthy.calls(),
+        [Phase::Architecture, Phase::Implementation, Phase::Review]
+    );
+    assert_eq!(run.outputs[&Phase::Architecture]["after"], 1);
+    assert_eq!(run.outputs.len(), 4);
+    assert!(engine.resume().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn retryable_failures_and_timeouts_are_retried_until_the_policy_gives_up() {
+    let script = Script::new(|phase, attempt| match (phase, attempt) {
+        (Phase::Analysis, 1) => Step::Flaky,
+        (Phase::Architecture, _) => Step::Hang,
+        _ => Step::Done,
+    });
+    let engine = Engine::new(Arc::new(InMemory::new()), script.clone())
+        .with_default_policy(fast(2))
+        .with_policy(Phase::Architecture, fast(3));
+    let id = engine
+        .submit(Request::new("p", "d"))
+        .await
+        .unwrap()
+        .request
+        .id;
+
+    let run = engine.drive(&id).await.unwrap();
+    assert_eq!(run.status, RequestStatus::Failed);
+    assert_eq!(run.attempts, 3);
+    assert!(run
+        .error
+        .as_deref()
+        .unwrap()
+        .contains("architecture timed out"));
+    assert_eq!(
+        script.calls(),
+        [
+            Phase::Analysis,
+            Phase::Analysis,
+            Phase::Architecture,
+            Phase::Architecture,
+            Phase::Architecture
+        ]
+    );
+    assert!(!engine.cancel(&id).await.unwrap());
+}
+
+#[tokio::test]
+async fn cancel_interrupts_the_running_phase() {
+    let script = Script::new(|phase, _| match phase {
+        Phase::Analysis => Step::Done,
+        _ => Step::Hang,
+    });
+    let engine = Arc::new(Engine::new(Arc::new(InMemory::new()), script.clone()));
+    let id = engine
+        .submit(Request::new("p", "d"))
+        .await
+        .unwrap()
+        .request
+        .id;
+    let driving = engine.spawn(&id);
+    while script.calls().len() < 2 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert!(matches!(
+        engine.drive(&id).await,
+        Err(refinory::engine::EngineError::Running(_))
+    ));
+
+    assert!(engine.cancel(&id).await.unwrap());
+    let run = driving.await.unwrap().unwrap();
+    assert_eq!(run.status, RequestStatus::Cancelled);
+    assert!(run.outputs.contains_key(&Phase::Analysis));
+    assert_eq!(engine.get(&id).await.unwrap().unwrap(), run);
+
+    // A pending request that is not being driven is cancelled in place.
+    let idle = engine
+        .submit(Request::new("q", "d"))
+        .await
+        .unwrap()
+        .request
+        .id;
+    assert!(engine.cancel(&idle).await.unwrap());
+    let run = engine.drive(&idle).await.unwrap();
+    assert_eq!(run.status, RequestStatus::Cancelled);
+    assert_eq!(script.calls().len(), 2);
+}
+
+#[tokio::test]
+async fn a_dropped_drive_releases_the_request() {
+    let script = Script::new(|phase, _| match phase {
+        Phase::Analysis => Step::Done,
+        _ => Step::Hang,
+    });
+    let engine = Arc::new(Engine::new(Arc::new(InMemory::new()), script.clone()));
+    let id = engine
+        .submit(Request::new("p", "d"))
+        .await
+        .unwrap()
+        .request
+        .id;
+    let driving = engine.spawn(&id);
+    while script.calls().len() < 2 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    driving.abort();
+    assert!(driving.await.unwrap_err().is_cancelled());
+
+    // Nothing drives the request any more, so cancelling it is saved.
+    assert!(engine.cancel(&id).await.unwrap());
+    let run = engine.get(&id).await.unwrap().unwrap();
+    assert_eq!(run.status, RequestStatus::Cancelled);
+    let run = engine.drive(&id).await.unwrap();
+    assert_eq!(run.status, RequestStatus::Cancelled);
+}
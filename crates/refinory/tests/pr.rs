@@ -0,0 +1,243 @@
+//! Publishing a completed request against a local fake of the GitHub API.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use artifacts::local::LocalDisk;
+use artifacts::{ArtifactStore, NewArtifact};
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::Engine as _;
+use refinory::github::GitHub;
+use refinory::memory::InMemory;
+use refinory::pr::{PrGenerator, Templates};
+use refinory::{Request, RequestStatus, Run, Store};
+use secrets::Secret;
+use serde_json::{json, Value};
+
+#[derive(Default)]
+struct Fake {
+    /// Decoded content of each blob, in order of creation.
+    blobs: Vec<Vec<u8>>,
+    trees: Vec<Value>,
+    commits: Vec<Value>,
+    refs: Vec<(String, String)>,
+    pulls: Vec<Value>,
+    auth: Vec<String>,
+    /// Opens the next pull request but answers 502, as if the response
+    /// was lost.
+    lose_pull: bool,
+}
+
+type Shared = Arc<Mutex<Fake>>;
+
+/// Serves `app` on a free port and returns its root URL.
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+fn github(fake: Shared) -> Router {
+    async fn record(fake: &Shared, headers: &HeaderMap) {
+        let auth = headers["authorization"].to_str().unwrap().to_string();
+        fake.lock().unwrap().auth.push(auth);
+    }
+    Router::new()
+        .route(
+            "/repos/{owner}/{repo}",
+            get(|| async { Json(json!({"default_branch": "main"})) }),
+        )
+        .route(
+            "/repos/{owner}/{repo}/git/ref/heads/{*branch}",
+            get(
+                |State(fake): State<Shared>, UrlPath((_, _, branch)): UrlPath<(String, String, String)>| async move {
+                    let refs = &fake.lock().unwrap().refs;
+                    match branch.as_str() {
+                        "main" => Json(json!({"object": {"sha": "base-commit"}})).into_response(),
+                        b => match refs.iter().find(|(r, _)| r == &format!("refs/heads/{b}")) {
+                            Some((_, sha)) => Json(json!({"object": {"sha": sha}})).into_response(),
+                            None => StatusCode::NOT_FOUND.into_response(),
+                        },
+                    }
+                },
+            ),
+        )
+        .route(
+            "/repos/{owner}/{repo}/git/commits/{sha}",
+            get(|| async { Json(json!({"tree": {"sha": "base-tree"}})) }),
+        )
+        .route(
+            "/repos/{owner}/{repo}/git/blobs",
+            post(|State(fake): State<Shared>, headers: HeaderMap, Json(body): Json<Value>| async move {
+                record(&fake, &headers).await;
+                let content = base64::engine::general_purpose::STANDARD
+                    .decode(body["content"].as_str().unwrap())
+                    .unwrap();
+                let mut fake = fake.lock().unwrap();
+                fake.blobs.push(content);
+                Json(json!({"sha": format!("blob-{}", fake.blobs.len() - 1)}))
+            }),
+        )
+        .route(
+            "/repos/{owner}/{repo}/git/trees",
+            post(|State(fake): State<Shared>, Json(body): Json<Value>| async move {
+                fake.lock().unwrap().trees.push(body);
+                Json(json!({"sha": "new-tree"}))
+            }),
+        )
+        .route(
+            "/repos/{owner}/{repo}/git/commits",
+            post(|State(fake): State<Shared>, Json(body): Json<Value>| async move {
+                fake.lock().unwrap().commits.push(body);
+                Json(json!({"sha": "new-commit"}))
+            }),
+        )
+        .route(
+            "/repos/{owner}/{repo}/git/refs",
+            post(|State(fake): State<Shared>, Json(body): Json<Value>| async move {
+                let r = (body["ref"].as_str().unwrap().into(), body["sha"].as_str().unwrap().into());
+                fake.lock().unwrap().refs.push(r);
+                (StatusCode::CREATED, Json(body))
+            }),
+        )
+        .route(
+            "/repos/{owner}/{repo}/pulls",
+            get(
+                |State(fake): State<Shared>, UrlPath((owner, repo)): UrlPath<(String, String)>, Query(q): Query<Vec<(String, String)>>| async move {
+                    let head = &q.iter().find(|(k, _)| k == "head").unwrap().1;
+                    let found: Vec<Value> = fake
+                        .lock()
+                        .unwrap()
+                        .pulls
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, p)| format!("{owner}:{}", p["head"].as_str().unwrap()) == *head)
+                        .map(|(i, _)| {
+                            let url = format!("https://github.com/{owner}/{repo}/pull/{}", i + 1);
+                            json!({"number": i + 1, "html_url": url})
+                        })
+                        .collect();
+                    Json(found)
+                },
+            )
+            .post(
+                |State(fake): State<Shared>, UrlPath((owner, repo)): UrlPath<(String, String)>, Json(body): Json<Value>| async move {
+                    let mut fake = fake.lock().unwrap();
+                    fake.pulls.push(body);
+                    if std::mem::take(&mut fake.lose_pull) {
+                        return StatusCode::BAD_GATEWAY.into_response();
+                    }
+                    let number = fake.pulls.len();
+                    let url = format!("https://github.com/{owner}/{repo}/pull/{number}");
+                    (StatusCode::CREATED, Json(json!({"number": number, "html_url": url}))).into_response()
+                },
+            ),
+        )
+        .with_state(fake)
+}
+
+#[tokio::test]
+async fn publishes_a_completed_request_as_a_branch_and_pull_request() {
+    let fake = Shared::default();
+    let api = serve(github(fake.clone())).await;
+    let dir = tempfile::tempdir().unwrap();
+    let artifacts = Arc::new(ArtifactStore::new(Arc::new(LocalDisk::new(
+        dir.path().join("artifacts"),
+    ))));
+    let templates =
+        Templates::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("../../templates")).unwrap();
+    let generator = PrGenerator::new(
+        GitHub::new(Secret::new("ghp_test")).with_base_url(api),
+        artifacts.clone(),
+        templates,
+        dir.path().join("repos"),
+    )
+    .with_default_org("acme");
+
+    let mut run =
+        Run::new(Request::new("Billing", "Invoices for tenants.").with_github_repo("ref-billing"));
+    let store = InMemory::default();
+    store.save(&run).await.unwrap();
+    let err = generator.publish(&store, run.id()).await.unwrap_err();
+    assert!(err.to_string().contains("not completed"), "{err}");
+
+    run.status = RequestStatus::Completed;
+    store.save(&run).await.unwrap();
+    artifacts
+        .put(
+            run.id(),
+            NewArtifact::new("docker-compose.yml", "deployment"),
+            b"services: {}\n".to_vec(),
+        )
+        .await
+        .unwrap();
+
+    // The PR is opened but the answer is lost; the retry finds it and
+    // makes no second commit.
+    fake.lock().unwrap().lose_pull = true;
+    let err = generator.publish(&store, run.id()).await.unwrap_err();
+    assert!(err.to_string().contains("502"), "{err}");
+    let saved = store.load(run.id()).await.unwrap().unwrap();
+    assert_eq!(saved.pull_commit.as_deref(), Some("new-commit"));
+    assert_eq!(saved.pull_request, None);
+    let url = generator.publish(&store, run.id()).await.unwrap();
+    assert_eq!(url, "https://github.com/acme/ref-billing/pull/1");
+    let saved = store.load(run.id()).await.unwrap().unwrap();
+    assert_eq!(saved.pull_request.as_deref(), Some(url.as_str()));
+    // Publishing again keeps the first PR.
+    assert_eq!(generator.publish(&store, run.id()).await.unwrap(), url);
+
+    let draft = generator.draft(&saved).await.unwrap();
+    generator.write_branch(&draft, "main").await.unwrap();
+    let fake = fake.lock().unwrap();
+    assert_eq!(fake.pulls.len(), 1);
+    assert_eq!(fake.commits.len(), 1);
+    let short = &run.id()[..8];
+    let branch = format!("refinory/arch-billing-{short}");
+    assert_eq!(fake.pulls[0]["head"], branch.as_str());
+    assert_eq!(fake.pulls[0]["base"], "main");
+    assert!(fake.auth.iter().all(|a| a == "Bearer ghp_test"));
+    assert_eq!(fake.trees[0]["base_tree"], "base-tree");
+    let paths: Vec<&str> = fake.trees[0]["tree"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["path"].as_str().unwrap())
+        .collect();
+    let proof = format!("docs/proof_of_non_hallucination/{}.yaml", run.id());
+    assert_eq!(
+        paths,
+        [
+            "docker-compose.yml".to_string(),
+            format!("docs/adr/ADR-{short}-billing.md"),
+            proof,
+            format!("docs/rfc/RFC-{short}-billing.md"),
+        ]
+    );
+    assert_eq!(fake.commits[0]["parents"], json!(["base-commit"]));
+    assert_eq!(
+        fake.refs,
+        [(format!("refs/heads/{branch}"), "new-commit".to_string())]
+    );
+
+    // A local branch holds the same files as the one on GitHub.
+    let repo = git2::Repository::open_bare(dir.path().join("repos/acme/ref-billing.git")).unwrap();
+    let tree = repo
+        .find_reference(&format!("refs/heads/{branch}"))
+        .unwrap()
+        .peel_to_tree()
+        .unwrap();
+    for (i, path) in paths.iter().enumerate() {
+        let blob = tree
+            .get_path(Path::new(path))
+            .unwrap()
+            .to_object(&repo)
+            .unwrap();
+        assert_eq!(blob.as_blob().unwrap().content(), fake.blobs[i], "{path}");
+    }
+}
@@ -0,0 +1,453 @@
+//! Grounded `include_llm` answers.
+//!
+//! `api.py` sent the contexts to the LLM and returned whatever came back.
+//! Here the contexts are numbered sources and the prompt requires every
+//! sentence to end with the markers of the sources backing it, e.g. `[1]`
+//! or `[2][3]`. [`check`] then splits the answer into sentences and scores
+//! each against the text of the chunks it cites: the share of its content
+//! words found there, with numbers and identifiers required verbatim. The
+//! share of supported sentences is the answer's support; below the
+//! threshold the answer is replaced by [`ABSTENTION`]. Either way the
+//! [`SupportReport`] goes out with the response.
+//!
+//! The check is lexical. It catches invented figures, statute numbers
+//! and uncited claims, not a paraphrase that inverts its source.
+
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use llm::{ChatRequest, Provider};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::bm25::tokens;
+use crate::ContextResult;
+
+/// `MAX_CONTEXT_LENGTH` in `api.py`: characters of context in a prompt.
+pub const MAX_CONTEXT_LENGTH: usize = 4000;
+
+/// Least share of supported sentences for an answer to be returned.
+pub const DEFAULT_MIN_SUPPORT: f32 = 0.8;
+
+/// Least share of a sentence's content words its sources must contain.
+pub const MIN_SENTENCE_SUPPORT: f32 = 0.6;
+
+/// What the LLM is told to reply when the sources do not answer.
+pub const NO_ANSWER: &str = "INSUFFICIENT_CONTEXT";
+
+/// The answer given in place of one that failed the check.
+pub const ABSTENTION: &str =
+    "The retrieved context does not support a reliable answer to this question.";
+
+/// `[1]`, `[2, 3]`.
+static MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[(\d+(?:\s*,\s*\d+)*)\]").expect("static regex"));
+
+/// A list bullet or number opening a line.
+static BULLET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?:[-*•]|\d+[.)])\s+").expect("static regex"));
+
+/// Words that carry no claim of their own.
+const STOPWORDS: &[&str] = &[
+    "about", "also", "an", "and", "any", "are", "as", "at", "be", "been", "but", "by", "can",
+    "does", "each", "for", "from", "has", "have", "how", "if", "in", "into", "is", "it", "its",
+    "may", "more", "must", "no", "not", "of", "on", "only", "or", "other", "shall", "so", "such",
+    "than", "that", "the", "their", "them", "then", "there", "these", "they", "this", "those",
+    "through", "to", "under", "use", "used", "uses", "was", "were", "what", "when", "where",
+    "which", "while", "who", "will", "with", "within", "would",
+];
+
+/// Letters two words must share to count as the same, so that
+/// `verifies` is supported by `verification`.
+const STEM: usize = 5;
+
+/// A context the prompt offered, as its marker number and chunk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub marker: usize,
+    pub path: String,
+    pub chunk: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentenceSupport {
+    /// As written, markers included.
+    pub text: String,
+    /// Markers naming an offered source.
+    pub cites: Vec<usize>,
+    /// Markers naming none.
+    pub invalid_cites: Vec<usize>,
+    /// Share of content words found in the cited sources, 0 to 1.
+    pub support: f32,
+    pub supported: bool,
+    /// Content words the cited sources lack.
+    pub missing: Vec<String>,
+}
+
+/// How well an answer is backed by its sources.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SupportReport {
+    /// Share of sentences supported, 0 to 1; 0 for an empty answer.
+    pub support: f32,
+    /// `1 - support`.
+    pub hallucination_score: f32,
+    pub min_support: f32,
+    /// Whether the LLM's answer was replaced by [`ABSTENTION`].
+    pub abstained: bool,
+    pub sources: Vec<Source>,
+    /// Sentences with at least one content word.
+    pub sentences: Vec<SentenceSupport>,
+}
+
+/// An answer and the report behind it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grounded {
+    pub text: String,
+    pub report: SupportReport,
+}
+
+/// The contexts, in rank order, until the next would pass `max_context`
+/// characters; these are the sources numbered in the prompt.
+pub fn offered(contexts: &[ContextResult], max_context: usize) -> &[ContextResult] {
+    let mut total = 0;
+    for (i, ctx) in contexts.iter().enumerate() {
+        total += source(i + 1, ctx).len();
+        if total > max_context {
+            return &contexts[..i];
+        }
+    }
+    contexts
+}
+
+fn source(marker: usize, ctx: &ContextResult) -> String {
+    format!(
+        "[{marker}] {} (chunk {}, score: {:.3})\n{}",
+        ctx.path, ctx.chunk, ctx.score, ctx.text
+    )
+}
+
+/// The `api.py` prompt, asking for a cited answer from the
+/// [`offered`] contexts.
+pub fn prompt(query: &str, contexts: &[ContextResult], max_context: usize) -> String {
+    let sources: Vec<String> = offered(contexts, max_context)
+        .iter()
+        .enumerate()
+        .map(|(i, ctx)| source(i + 1, ctx))
+        .collect();
+    format!(
+        "You are an expert software architect analyzing the Strategic Khaos sovereignty architecture.
+
+Answer using ONLY the numbered sources below. End every sentence with the
+numbers of the sources that support it, for example [1] or [2][3]. Do not
+state anything the sources do not say. If they do not answer the question,
+reply with exactly {NO_ANSWER}.
+
+Sources:
+{}
+
+Question: {query}
+
+Provide a detailed, technical answer, citing the sources above:",
+        sources.join("\n\n")
+    )
+}
+
+/// Splits an answer into sentences. Lines are split apart, list bullets
+/// dropped, and a sentence ends at `.`, `!` or `?` plus any markers right
+/// after it, when followed by the end or by a space and a capital. So
+/// `W.S. 17-31-101` stays whole and `... DAOs. [1] The` splits after
+/// `[1]`.
+pub fn sentences(answer: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    for line in answer.lines() {
+        let line = match BULLET.find(line) {
+            Some(m) => &line[m.end()..],
+            None => line,
+        };
+        let mut start = 0;
+        let mut i = 0;
+        while i < line.len() {
+            let b = line.as_bytes()[i];
+            i += 1;
+            if !matches!(b, b'.' | b'!' | b'?') {
+                continue;
+            }
+            let mut end = i;
+            while let Some(m) = MARKER.find(&line[end..]) {
+                if !line[end..end + m.start()].trim().is_empty() {
+                    break;
+                }
+                end += m.end();
+            }
+            let rest = &line[end..];
+            let after = rest.trim_start();
+            let spaced = after.len() < rest.len();
+            let ends = match after.chars().next() {
+                None => true,
+                Some(c) => spaced && (c.is_uppercase() || c == '"' || c == '`'),
+            };
+            if ends {
+                out.push(line[start..end].trim());
+                start = end;
+                i = end;
+            }
+        }
+        out.push(line[start..].trim());
+    }
+    out.retain(|s| !s.is_empty());
+    out
+}
+
+/// A lone letter, such as the `s` of `W.S`.
+fn letter(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(char::is_alphabetic) && chars.next().is_none()
+}
+
+fn content(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = tokens(&MARKER.replace_all(text, " "))
+        .into_iter()
+        .filter(|t| !STOPWORDS.contains(&t.as_str()) && !letter(t))
+        .collect();
+    let mut seen = HashSet::new();
+    words.retain(|w| seen.insert(w.clone()));
+    words
+}
+
+fn literal(word: &str) -> bool {
+    word.chars().any(|c| !c.is_alphabetic())
+}
+
+fn stem(word: &str) -> Option<&str> {
+    let (i, _) = word.char_indices().nth(STEM - 1)?;
+    Some(&word[..i + 1])
+}
+
+/// The words of the sources a sentence cites.
+#[derive(Default)]
+struct Vocabulary {
+    words: HashSet<String>,
+    stems: HashSet<String>,
+}
+
+impl Vocabulary {
+    fn add(&mut self, text: &str) {
+        for word in tokens(text) {
+            if !literal(&word) {
+                if let Some(stem) = stem(&word) {
+                    self.stems.insert(stem.to_string());
+                }
+            }
+            self.words.insert(word);
+        }
+    }
+
+    /// Numbers and identifiers must appear as written; words may share
+    /// a stem instead.
+    fn covers(&self, word: &str) -> bool {
+        self.words.contains(word)
+            || (!literal(word) && stem(word).is_some_and(|s| self.stems.contains(s)))
+    }
+}
+
+fn markers(sentence: &str) -> Vec<usize> {
+    let mut out = Vec::new();
+    for caps in MARKER.captures_iter(sentence) {
+        for n in caps[1].split(',') {
+            if let Ok(n) = n.trim().parse() {
+                if !out.contains(&n) {
+                    out.push(n);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Scores `answer` against `sources`, the contexts its markers number
+/// from 1, and decides whether to abstain.
+pub fn check(answer: &str, sources: &[ContextResult], min_support: f32) -> SupportReport {
+    let mut report = SupportReport {
+        support: 0.0,
+        hallucination_score: 1.0,
+        min_support,
+        abstained: true,
+        sources: sources
+            .iter()
+            .enumerate()
+            .map(|(i, ctx)| Source {
+                marker: i + 1,
+                path: ctx.path.clone(),
+                chunk: ctx.chunk,
+            })
+            .collect(),
+        sentences: Vec::new(),
+    };
+    if answer.trim().starts_with(NO_ANSWER) {
+        return report;
+    }
+    for text in sentences(answer) {
+        let words = content(text);
+        if words.is_empty() {
+            continue;
+        }
+        let (cites, invalid_cites): (Vec<usize>, Vec<usize>) = markers(text)
+            .into_iter()
+            .partition(|&n| (1..=sources.len()).contains(&n));
+        let mut vocabulary = Vocabulary::default();
+        for &n in &cites {
+            vocabulary.add(&sources[n - 1].text);
+        }
+        let missing: Vec<String> = words
+            .iter()
+            .filter(|w| !vocabulary.covers(w))
+            .cloned()
+            .collect();
+        let support = if cites.is_empty() {
+            0.0
+        } else {
+            1.0 - missing.len() as f32 / words.len() as f32
+        };
+        let supported = support >= MIN_SENTENCE_SUPPORT && !missing.iter().any(|w| literal(w));
+        report.sentences.push(SentenceSupport {
+            text: text.to_string(),
+            cites,
+            invalid_cites,
+            support,
+            supported,
+            missing,
+        });
+    }
+    if !report.sentences.is_empty() {
+        let supported = report.sentences.iter().filter(|s| s.supported).count();
+        report.support = supported as f32 / report.sentences.len() as f32;
+        report.hallucination_score = 1.0 - report.support;
+        report.abstained = report.support < min_support;
+    }
+    report
+}
+
+/// `None` without contexts, or when the LLM fails or says nothing;
+/// otherwise the checked answer, or [`ABSTENTION`] when its support is
+/// below `min_support`.
+pub async fn generate(
+    provider: &dyn Provider,
+    query: &str,
+    contexts: &[ContextResult],
+    max_context: usize,
+    min_support: f32,
+) -> Option<Grounded> {
+    if contexts.is_empty() {
+        return None;
+    }
+    let request = ChatRequest::new("local")
+        .with_user(prompt(query, contexts, max_context))
+        .with_temperature(0.1)
+        .with_max_tokens(512);
+    let text = match provider.complete(&request).await {
+        Ok(completion) => completion.text.trim().to_string(),
+        Err(e) => {
+            tracing::warn!(provider = provider.name(), error = %e, "answer generation failed");
+            return None;
+        }
+    };
+    if text.is_empty() {
+        return None;
+    }
+    let report = check(&text, offered(contexts, max_context), min_support);
+    if report.abstained {
+        tracing::info!(
+            support = report.support,
+            sentences = report.sentences.len(),
+            "answer below support threshold, abstaining"
+        );
+    }
+    Some(Grounded {
+        text: if report.abstained {
+            ABSTENTION.to_string()
+        } else {
+            text
+        },
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Map;
+
+    use super::*;
+
+    fn ctx(path: &str, text: &str) -> ContextResult {
+        ContextResult {
+            path: path.into(),
+            chunk: 0,
+            score: 1.0,
+            text: text.into(),
+            metadata: Map::new(),
+        }
+    }
+
+    #[test]
+    fn splits_sentences_without_breaking_citations_or_statutes() {
+        assert_eq!(
+            sentences(
+                "W.S. 17-31-101 covers DAOs. [1] It was amended in 2022 [2].\n- Members vote."
+            ),
+            [
+                "W.S. 17-31-101 covers DAOs. [1]",
+                "It was amended in 2022 [2].",
+                "Members vote."
+            ]
+        );
+    }
+
+    #[test]
+    fn scores_sentences_against_the_chunks_they_cite() {
+        let sources = [
+            ctx(
+                "legal/sf0068.md",
+                "Wyoming SF0068 amends W.S. 17-31-101 for decentralized autonomous organizations.",
+            ),
+            ctx(
+                "src/gateway.rs",
+                "Signature verification rejects unsigned webhook deliveries.",
+            ),
+        ];
+
+        let report = check(
+            "SF0068 amends W.S. 17-31-101 for decentralized autonomous organizations [1]. \
+             The gateway verifies signatures and rejects unsigned deliveries [2].",
+            &sources,
+            DEFAULT_MIN_SUPPORT,
+        );
+        assert!(!report.abstained);
+        assert_eq!(report.support, 1.0);
+        assert_eq!(report.sentences[0].cites, [1]);
+        assert_eq!(report.sources[1].path, "src/gateway.rs");
+
+        // An invented figure, a wrong source, and a claim citing nothing.
+        let report = check(
+            "SF0068 amends W.S. 17-31-105 for decentralized autonomous organizations [1]. \
+             Webhook deliveries are rejected when unsigned [1]. \
+             Gateways scale horizontally.",
+            &sources,
+            DEFAULT_MIN_SUPPORT,
+        );
+        assert!(report.abstained);
+        assert_eq!(report.support, 0.0);
+        assert!(report.sentences[0]
+            .missing
+            .contains(&"17-31-105".to_string()));
+        assert!(report.sentences[0].support >= MIN_SENTENCE_SUPPORT);
+        assert!(!report.sentences[1].supported);
+        assert!(report.sentences[2].cites.is_empty());
+
+        let report = check("The gateway verifies signatures [3].", &sources, 0.5);
+        assert_eq!(report.sentences[0].invalid_cites, [3]);
+        assert!(report.abstained);
+
+        let report = check(NO_ANSWER, &sources, DEFAULT_MIN_SUPPORT);
+        assert!(report.abstained && report.sentences.is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! The HTTP API of `recon/retriever/api.py`.
+//!
+//! | Route               | Body            | Response                 |
+//! |---------------------|-----------------|--------------------------|
+//! | `POST /query`       | [`QueryRequest`] | [`QueryResponse`]       |
+//! | `GET /health`       |                 | [`HealthResponse`]       |
+//! | `GET /collections`  |                 | `{"collections": [...]}` |
+//! | `GET /metrics`      |                 | Prometheus text          |
+//!
+//! Errors are FastAPI-style `{"detail": ...}` bodies.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use llm::Provider;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::answer::{self, DEFAULT_MIN_SUPPORT, MAX_CONTEXT_LENGTH};
+use crate::{metrics, QueryError, QueryRequest, QueryResponse, Retriever};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthResponse {
+    /// `healthy` when a collection is loaded and the embedder answers,
+    /// else `degraded`.
+    pub status: String,
+    pub index_status: String,
+    pub embedder_status: String,
+    pub llm_status: String,
+    /// Per collection, its `vectors_count`.
+    pub collection_info: Map<String, Value>,
+    /// Seconds.
+    pub uptime: f64,
+}
+
+impl IntoResponse for QueryError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            QueryError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            QueryError::UnknownCollection(_) => StatusCode::NOT_FOUND,
+            QueryError::Index(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        if status.is_server_error() {
+            tracing::error!(error = %self, "query failed");
+        }
+        (status, Json(json!({ "detail": self.to_string() }))).into_response()
+    }
+}
+
+#[derive(Clone)]
+struct App {
+    retriever: Arc<Retriever>,
+    llm: Option<Arc<dyn Provider>>,
+    max_context: usize,
+    min_support: f32,
+    embedder_health: Option<String>,
+    llm_health: Option<String>,
+    http: reqwest::Client,
+    started: Instant,
+}
+
+/// Builds the router over a [`Retriever`].
+pub struct Api {
+    retriever: Arc<Retriever>,
+    llm: Option<Arc<dyn Provider>>,
+    max_context: usize,
+    min_support: f32,
+    embedder_health: Option<String>,
+    llm_health: Option<String>,
+}
+
+impl Api {
+    pub fn new(retriever: Arc<Retriever>) -> Self {
+        Self {
+            retriever,
+            llm: None,
+            max_context: MAX_CONTEXT_LENGTH,
+            min_support: DEFAULT_MIN_SUPPORT,
+            embedder_health: None,
+            llm_health: None,
+        }
+    }
+
+    /// Answers `include_llm` queries; without it `answer` is always null.
+    pub fn with_llm(mut self, llm: Arc<dyn Provider>) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    /// Characters of context per prompt; [`MAX_CONTEXT_LENGTH`] by default.
+    pub fn with_max_context(mut self, chars: usize) -> Self {
+        self.max_context = chars;
+        self
+    }
+
+    /// Least share of supported sentences below which an answer is
+    /// replaced by [`answer::ABSTENTION`]; [`DEFAULT_MIN_SUPPORT`] by
+    /// default.
+    pub fn with_min_support(mut self, share: f32) -> Self {
+        self.min_support = share;
+        self
+    }
+
+    /// URLs `/health` probes with `GET`, expecting `200`.
+    pub fn with_health_urls(mut self, embedder: Option<String>, llm: Option<String>) -> Self {
+        self.embedder_health = embedder;
+        self.llm_health = llm;
+        self
+    }
+
+    pub fn router(self) -> Router {
+        let app = App {
+            retriever: self.retriever,
+            llm: self.llm,
+            max_context: self.max_context,
+            min_support: self.min_support,
+            embedder_health: self.embedder_health,
+            llm_health: self.llm_health,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .expect("static client config"),
+            started: Instant::now(),
+        };
+        Router::new()
+            .route("/", get(root))
+            .route("/query", post(query))
+            .route("/health", get(health))
+            .route("/collections", get(collections))
+            .route("/metrics", get(|| async { metrics::render() }))
+            .with_state(app)
+    }
+}
+
+async fn root() -> Json<Value> {
+    Json(json!({
+        "service": "RECON RAG API",
+        "version": env!("CARGO_PKG_VERSION"),
+        "description": "Strategic Khaos Repository Analysis via RAG",
+        "endpoints": {
+            "query": "/query",
+            "health": "/health",
+            "collections": "/collections",
+            "metrics": "/metrics"
+        }
+    }))
+}
+
+async fn query(
+    State(app): State<App>,
+    Json(req): Json<QueryRequest>,
+) -> Result<Json<QueryResponse>, QueryError> {
+    let started = Instant::now();
+    let _timer = metrics::DURATION
+        .with_label_values(&["total"])
+        .start_timer();
+    let collection = req
+        .collection
+        .clone()
+        .unwrap_or_else(|| app.retriever.default_collection().to_string());
+    let contexts = match app.retriever.retrieve(&req).await {
+        Ok(contexts) => contexts,
+        Err(e) => {
+            metrics::QUERIES
+                .with_label_values(&[collection.as_str(), "error"])
+                .inc();
+            return Err(e);
+        }
+    };
+    if !contexts.is_empty() {
+        let mean = contexts.iter().map(|c| c.score as f64).sum::<f64>() / contexts.len() as f64;
+        metrics::RELEVANCE.set(mean);
+    }
+    let grounded = match (&app.llm, req.include_llm) {
+        (Some(llm), true) => {
+            let _timer = metrics::DURATION.with_label_values(&["llm"]).start_timer();
+            answer::generate(
+                llm.as_ref(),
+                &req.q,
+                &contexts,
+                app.max_context,
+                app.min_support,
+            )
+            .await
+        }
+        _ => None,
+    };
+    if let Some(grounded) = &grounded {
+        let outcome = if grounded.report.abstained {
+            "abstained"
+        } else {
+            "grounded"
+        };
+        metrics::ANSWERS.with_label_values(&[outcome]).inc();
+    }
+    let (answer, support) = match grounded {
+        Some(g) => (Some(g.text), Some(g.report)),
+        None => (None, None),
+    };
+    metrics::QUERIES
+        .with_label_values(&[collection.as_str(), "success"])
+        .inc();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(Json(QueryResponse {
+        query: req.q,
+        answer,
+        support,
+        total_contexts: contexts.len(),
+        contexts,
+        processing_time: started.elapsed().as_secs_f64(),
+        timestamp: artifacts::rfc3339(now),
+        collection,
+    }))
+}
+
+async fn probe(http: &reqwest::Client, url: Option<&str>) -> &'static str {
+    let Some(url) = url else {
+        return "unknown";
+    };
+    match http.get(url).send().await {
+        Ok(res) if res.status().is_success() => "healthy",
+        _ => "unhealthy",
+    }
+}
+
+async fn health(State(app): State<App>) -> Json<HealthResponse> {
+    let (embedder, llm) = tokio::join!(
+        probe(&app.http, app.embedder_health.as_deref()),
+        probe(&app.http, app.llm_health.as_deref()),
+    );
+    let mut info = Map::new();
+    for (name, c) in app.retriever.collections() {
+        info.insert(name.into(), json!({ "vectors_count": c.index().len() }));
+    }
+    let index = if info.is_empty() { "empty" } else { "healthy" };
+    let healthy = index == "healthy" && embedder != "unhealthy";
+    Json(HealthResponse {
+        status: if healthy { "healthy" } else { "degraded" }.into(),
+        index_status: index.into(),
+        embedder_status: embedder.into(),
+        llm_status: llm.into(),
+        collection_info: info,
+        uptime: app.started.elapsed().as_secs_f64(),
+    })
+}
+
+async fn collections(State(app): State<App>) -> Json<Value> {
+    let list: Vec<Value> = app
+        .retriever
+        .collections()
+        .map(|(name, c)| json!({ "name": name, "vectors_count": c.index().len() }))
+        .collect();
+    Json(json!({ "collections": list }))
+}
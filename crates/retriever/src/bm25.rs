@@ -0,0 +1,173 @@
+//! Okapi BM25 over chunk text, with a tokenizer that keeps identifiers.
+//!
+//! Semantic search blurs exact strings such as `SF0068`, `W.S. 17-31-101`
+//! or `O3`. Tokens are lower-cased runs of letters and digits, and a run
+//! joined by `-`, `.`, `_`, `/` or `:` is also kept whole, so
+//! `17-31-101(a)` yields `17-31-101`, `17`, `31`, `101` and `a`.
+
+use std::collections::HashMap;
+
+/// Term-frequency saturation.
+const K1: f32 = 1.2;
+/// Length normalization.
+const B: f32 = 0.75;
+
+fn connector(c: char) -> bool {
+    matches!(c, '-' | '.' | '_' | '/' | ':')
+}
+
+pub fn tokens(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_alphanumeric() {
+            i += 1;
+            continue;
+        }
+        // A compound: alphanumeric runs joined by single connectors.
+        let start = i;
+        let mut parts = Vec::new();
+        loop {
+            let from = i;
+            while i < chars.len() && chars[i].is_alphanumeric() {
+                i += 1;
+            }
+            parts.push(chars[from..i].iter().collect::<String>().to_lowercase());
+            let joined =
+                i + 1 < chars.len() && connector(chars[i]) && chars[i + 1].is_alphanumeric();
+            if !joined {
+                break;
+            }
+            i += 1;
+        }
+        if parts.len() > 1 {
+            out.push(chars[start..i].iter().collect::<String>().to_lowercase());
+        }
+        out.extend(parts);
+    }
+    out
+}
+
+struct Doc {
+    id: String,
+    len: u32,
+}
+
+#[derive(Default)]
+pub struct Bm25 {
+    docs: Vec<Doc>,
+    /// Per term, the documents holding it and how often.
+    postings: HashMap<String, Vec<(u32, u32)>>,
+    total_len: u64,
+}
+
+impl Bm25 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Adds a document; ids are not deduplicated.
+    pub fn insert(&mut self, id: impl Into<String>, text: &str) {
+        let doc = self.docs.len() as u32;
+        let tokens = tokens(text);
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for t in &tokens {
+            *counts.entry(t.clone()).or_default() += 1;
+        }
+        for (term, tf) in counts {
+            self.postings.entry(term).or_default().push((doc, tf));
+        }
+        self.total_len += tokens.len() as u64;
+        self.docs.push(Doc {
+            id: id.into(),
+            len: tokens.len() as u32,
+        });
+    }
+
+    /// The `k` best-scoring documents `accept` lets through, best first.
+    pub fn search(
+        &self,
+        query: &str,
+        k: usize,
+        accept: impl Fn(&str) -> bool,
+    ) -> Vec<(String, f32)> {
+        if self.docs.is_empty() || k == 0 {
+            return Vec::new();
+        }
+        let n = self.docs.len() as f32;
+        let avg = (self.total_len as f32 / n).max(1.0);
+        let mut terms = tokens(query);
+        terms.sort();
+        terms.dedup();
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let df = postings.len() as f32;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            for &(doc, tf) in postings {
+                let tf = tf as f32;
+                let len = self.docs[doc as usize].len as f32;
+                let norm = tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / avg));
+                *scores.entry(doc).or_default() += idf * norm;
+            }
+        }
+        let mut ranked: Vec<(u32, f32)> = scores
+            .into_iter()
+            .filter(|&(doc, _)| accept(&self.docs[doc as usize].id))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(k);
+        ranked
+            .into_iter()
+            .map(|(doc, score)| (self.docs[doc as usize].id.clone(), score))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_survive_tokenizing_and_rank_exact_matches_first() {
+        assert_eq!(
+            tokens("See W.S. 17-31-101(a) in SF0068_amendments."),
+            [
+                "see",
+                "w.s",
+                "w",
+                "s",
+                "17-31-101",
+                "17",
+                "31",
+                "101",
+                "a",
+                "in",
+                "sf0068_amendments",
+                "sf0068",
+                "amendments"
+            ]
+        );
+
+        let mut bm25 = Bm25::new();
+        bm25.insert("a", "The O3 model card describes reasoning models.");
+        bm25.insert("b", "Wyoming SF0068 amends W.S. 17-31-101 on DAOs.");
+        bm25.insert("c", "Statutes 17 and 31 cover LLC law, section 101 too.");
+        let hits = bm25.search("W.S. 17-31-101", 3, |_| true);
+        assert_eq!(hits[0].0, "b");
+        assert!(hits[0].1 > hits[1].1);
+        assert_eq!(bm25.search("o3", 3, |_| true)[0].0, "a");
+        assert!(bm25.search("sf0068", 3, |id| id != "b").is_empty());
+    }
+}
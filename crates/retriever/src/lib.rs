@@ -0,0 +1,439 @@
+//! Hybrid retrieval for the recon RAG API, replacing the pure vector
+//! search of `recon/retriever/api.py`.
+//!
+//! A [`Collection`] is an ingest index (see the `ingest` crate) plus a
+//! [`bm25::Bm25`] index over the same chunks' path, symbol, section and
+//! text. [`Retriever::retrieve`] runs both searches and merges them with
+//! reciprocal rank fusion, so a chunk naming `SF0068` or `W.S. 17-31-101`
+//! verbatim ranks even when its embedding is not close to the query's.
+//! `min_score` keeps its `api.py` meaning, a floor on cosine similarity,
+//! but only gates the vector side: a keyword match is never dropped for
+//! a low similarity. Given a [`rerank::Reranker`], the fused candidates
+//! are reordered by it before the top `k` are returned.
+//!
+//! With `include_llm`, [`answer`] asks the LLM for an answer citing the
+//! contexts and checks it against them before it is returned.
+//!
+//! [`api`] serves the same `QueryRequest`/`QueryResponse` JSON as `api.py`
+//! on `POST /query`, so existing clients keep working.
+
+pub mod answer;
+pub mod api;
+pub mod bm25;
+pub mod metrics;
+pub mod rerank;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+use embed::Embedder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use vectors::{Filter, IndexError, Metadata, Metric, VectorIndex};
+
+use crate::bm25::Bm25;
+use crate::rerank::Reranker;
+
+/// `COLLECTION` in `api.py`.
+pub const DEFAULT_COLLECTION: &str = "sovereignty-arch";
+pub const DEFAULT_K: usize = 8;
+pub const MAX_K: usize = 20;
+/// `RELEVANCE_THRESHOLD` in `api.py`.
+pub const DEFAULT_MIN_SCORE: f32 = 0.7;
+/// The usual reciprocal rank fusion constant: a candidate at rank `r` of
+/// a list earns `1 / (RRF_K + r)`.
+pub const RRF_K: f32 = 60.0;
+
+fn default_k() -> usize {
+    DEFAULT_K
+}
+
+fn default_min_score() -> Option<f32> {
+    Some(DEFAULT_MIN_SCORE)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRequest {
+    pub q: String,
+    /// 1 to [`MAX_K`].
+    #[serde(default = "default_k")]
+    pub k: usize,
+    /// The retriever's default collection when absent.
+    #[serde(default)]
+    pub collection: Option<String>,
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    /// Least cosine similarity of a vector-only match; `null` for none.
+    #[serde(default = "default_min_score")]
+    pub min_score: Option<f32>,
+    #[serde(default = "default_true")]
+    pub include_llm: bool,
+}
+
+impl QueryRequest {
+    pub fn new(q: impl Into<String>) -> Self {
+        Self {
+            q: q.into(),
+            k: DEFAULT_K,
+            collection: None,
+            path_prefix: None,
+            min_score: default_min_score(),
+            include_llm: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextResult {
+    pub path: String,
+    pub chunk: usize,
+    /// The reranker's score if one ran, else the fusion score scaled so
+    /// that first place in every search is 1.
+    pub score: f32,
+    pub text: String,
+    /// The chunk's index metadata, and under `retrieval` the ranks and
+    /// scores behind `score`.
+    pub metadata: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryResponse {
+    pub query: String,
+    pub answer: Option<String>,
+    /// How well `answer` is backed by the contexts it cites; present
+    /// whenever `answer` is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub support: Option<answer::SupportReport>,
+    pub contexts: Vec<ContextResult>,
+    pub total_contexts: usize,
+    /// Seconds.
+    pub processing_time: f64,
+    pub timestamp: String,
+    pub collection: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueryError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("no collection {0:?}")]
+    UnknownCollection(String),
+    #[error("index: {0}")]
+    Index(#[from] IndexError),
+}
+
+/// A chunk found by either search, with what each said about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub id: String,
+    pub metadata: Metadata,
+    /// Similarity to the query embedding (cosine for cosine indexes).
+    pub similarity: Option<f32>,
+    /// 1-based.
+    pub vector_rank: Option<usize>,
+    pub bm25: Option<f32>,
+    /// 1-based.
+    pub bm25_rank: Option<usize>,
+    /// Reciprocal rank fusion score.
+    pub fused: f32,
+    pub rerank: Option<f32>,
+}
+
+impl Candidate {
+    fn new(id: String, metadata: Metadata) -> Self {
+        Self {
+            id,
+            metadata,
+            similarity: None,
+            vector_rank: None,
+            bm25: None,
+            bm25_rank: None,
+            fused: 0.0,
+            rerank: None,
+        }
+    }
+}
+
+/// Parameters of [`Collection::search`].
+#[derive(Debug, Clone, Copy)]
+pub struct Search<'a> {
+    pub text: &'a str,
+    /// Without it the search is keyword-only.
+    pub vector: Option<&'a [f32]>,
+    pub filter: Option<&'a Filter>,
+    /// Least similarity of a vector match.
+    pub min_similarity: Option<f32>,
+    /// Candidates taken from each search before fusion.
+    pub depth: usize,
+}
+
+/// Higher is closer, whatever the metric.
+fn similarity(metric: Metric, distance: f32) -> f32 {
+    match metric {
+        Metric::Cosine => 1.0 - distance,
+        Metric::Dot => -distance,
+        Metric::L2 => 1.0 / (1.0 + distance),
+    }
+}
+
+/// What BM25 indexes of a chunk: its text and the fields that name it.
+fn searchable(metadata: &Metadata) -> String {
+    ["path", "symbol", "key_path", "section", "source_id", "text"]
+        .iter()
+        .filter_map(|k| metadata.get(*k))
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub struct Collection {
+    index: VectorIndex,
+    bm25: Bm25,
+}
+
+impl Collection {
+    pub fn new(index: VectorIndex) -> Self {
+        let mut bm25 = Bm25::new();
+        for (id, metadata) in index.entries() {
+            bm25.insert(id, &searchable(metadata));
+        }
+        Self { index, bm25 }
+    }
+
+    /// Opens an index saved by ingest.
+    pub fn open(dir: &Path) -> Result<Self, IndexError> {
+        Ok(Self::new(VectorIndex::open(dir)?))
+    }
+
+    pub fn index(&self) -> &VectorIndex {
+        &self.index
+    }
+
+    /// Both searches fused, best first.
+    pub fn search(&self, search: &Search<'_>) -> Result<Vec<Candidate>, IndexError> {
+        let mut found: HashMap<String, Candidate> = HashMap::new();
+
+        if let Some(vector) = search.vector {
+            let metric = self.index.config().metric;
+            let hits = self.index.search(vector, search.depth, search.filter)?;
+            let kept = hits
+                .into_iter()
+                .map(|h| (similarity(metric, h.distance), h))
+                .filter(|(s, _)| search.min_similarity.is_none_or(|min| *s >= min));
+            for (rank, (sim, hit)) in kept.enumerate() {
+                let c = found
+                    .entry(hit.id.clone())
+                    .or_insert_with(|| Candidate::new(hit.id, hit.metadata));
+                c.similarity = Some(sim);
+                c.vector_rank = Some(rank + 1);
+                c.fused += 1.0 / (RRF_K + (rank + 1) as f32);
+            }
+        }
+
+        let accept = |id: &str| {
+            self.index
+                .metadata(id)
+                .is_some_and(|m| search.filter.is_none_or(|f| f.matches(m)))
+        };
+        for (rank, (id, score)) in self
+            .bm25
+            .search(search.text, search.depth, accept)
+            .into_iter()
+            .enumerate()
+        {
+            let Some(metadata) = self.index.metadata(&id) else {
+                continue;
+            };
+            let c = found
+                .entry(id.clone())
+                .or_insert_with(|| Candidate::new(id, metadata.clone()));
+            c.bm25 = Some(score);
+            c.bm25_rank = Some(rank + 1);
+            c.fused += 1.0 / (RRF_K + (rank + 1) as f32);
+        }
+
+        let mut fused: Vec<Candidate> = found.into_values().collect();
+        fused.sort_by(|a, b| b.fused.total_cmp(&a.fused).then_with(|| a.id.cmp(&b.id)));
+        Ok(fused)
+    }
+}
+
+/// Searches named collections for [`QueryRequest`]s.
+pub struct Retriever {
+    collections: BTreeMap<String, Collection>,
+    default_collection: String,
+    embedder: Arc<dyn Embedder>,
+    reranker: Option<Arc<dyn Reranker>>,
+}
+
+impl Retriever {
+    pub fn new(embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            collections: BTreeMap::new(),
+            default_collection: DEFAULT_COLLECTION.to_string(),
+            embedder,
+            reranker: None,
+        }
+    }
+
+    pub fn with_collection(mut self, name: impl Into<String>, collection: Collection) -> Self {
+        self.collections.insert(name.into(), collection);
+        self
+    }
+
+    /// Searched when a request names none; [`DEFAULT_COLLECTION`] unless set.
+    pub fn with_default_collection(mut self, name: impl Into<String>) -> Self {
+        self.default_collection = name.into();
+        self
+    }
+
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    pub fn collections(&self) -> impl Iterator<Item = (&str, &Collection)> {
+        self.collections.iter().map(|(n, c)| (n.as_str(), c))
+    }
+
+    pub fn default_collection(&self) -> &str {
+        &self.default_collection
+    }
+
+    /// The top `k` contexts for `req` from the collection it names.
+    pub async fn retrieve(&self, req: &QueryRequest) -> Result<Vec<ContextResult>, QueryError> {
+        if req.q.trim().is_empty() {
+            return Err(QueryError::Invalid("q must not be empty".into()));
+        }
+        if !(1..=MAX_K).contains(&req.k) {
+            return Err(QueryError::Invalid(format!(
+                "k must be between 1 and {MAX_K}"
+            )));
+        }
+        let name = req
+            .collection
+            .as_deref()
+            .unwrap_or(&self.default_collection);
+        let collection = self
+            .collections
+            .get(name)
+            .ok_or_else(|| QueryError::UnknownCollection(name.to_string()))?;
+
+        // Keyword search still answers while the embedder is down.
+        let vector = {
+            let _timer = metrics::DURATION
+                .with_label_values(&["embedding"])
+                .start_timer();
+            match self.embedder.embed(std::slice::from_ref(&req.q)).await {
+                Ok(mut v) => v.pop(),
+                Err(e) => {
+                    tracing::warn!(error = %e, "embedding failed; searching keywords only");
+                    None
+                }
+            }
+        };
+        let filter = req
+            .path_prefix
+            .as_ref()
+            .map(|p| Filter::new().prefix("path", p));
+        let lists = if vector.is_some() { 2.0 } else { 1.0 };
+        let mut candidates = {
+            let _timer = metrics::DURATION
+                .with_label_values(&["search"])
+                .start_timer();
+            collection.search(&Search {
+                text: &req.q,
+                vector: vector.as_deref(),
+                filter: filter.as_ref(),
+                min_similarity: req.min_score,
+                depth: (req.k * 5).max(50),
+            })?
+        };
+
+        if let Some(reranker) = &self.reranker {
+            candidates.truncate((req.k * 3).max(20));
+            let texts: Vec<String> = candidates
+                .iter()
+                .map(|c| c.metadata.get("text").cloned().unwrap_or_default())
+                .collect();
+            let _timer = metrics::DURATION
+                .with_label_values(&["rerank"])
+                .start_timer();
+            match reranker.rerank(&req.q, &texts).await {
+                Ok(scores) if scores.len() == candidates.len() => {
+                    for (c, s) in candidates.iter_mut().zip(scores) {
+                        c.rerank = Some(s);
+                    }
+                    candidates.sort_by(|a, b| {
+                        b.rerank
+                            .unwrap_or(f32::MIN)
+                            .total_cmp(&a.rerank.unwrap_or(f32::MIN))
+                    });
+                }
+                Ok(scores) => tracing::warn!(
+                    got = scores.len(),
+                    want = candidates.len(),
+                    "reranker returned the wrong number of scores; keeping fused order"
+                ),
+                Err(e) => tracing::warn!(error = %e, "rerank failed; keeping fused order"),
+            }
+        }
+
+        candidates.truncate(req.k);
+        let best = lists / (RRF_K + 1.0);
+        Ok(candidates.into_iter().map(|c| context(c, best)).collect())
+    }
+}
+
+/// Index metadata stored as numbers in `api.py`'s payloads.
+const NUMERIC: &[&str] = &["total_chunks", "start_line", "end_line", "page"];
+
+fn context(c: Candidate, best: f32) -> ContextResult {
+    let mut metadata = Map::new();
+    let mut path = c.id.clone();
+    let mut chunk = 0;
+    let mut text = String::new();
+    for (key, value) in c.metadata {
+        match key.as_str() {
+            "path" => path = value,
+            "chunk" => chunk = value.parse().unwrap_or(0),
+            "text" => text = value,
+            k if NUMERIC.contains(&k) => {
+                let v = value
+                    .parse::<u64>()
+                    .map_or(Value::String(value), Value::from);
+                metadata.insert(key, v);
+            }
+            _ => {
+                metadata.insert(key, Value::String(value));
+            }
+        }
+    }
+    let mut retrieval = Map::new();
+    retrieval.insert("fused".into(), Value::from(c.fused));
+    let optional = [
+        ("similarity", c.similarity.map(Value::from)),
+        ("vector_rank", c.vector_rank.map(Value::from)),
+        ("bm25", c.bm25.map(Value::from)),
+        ("bm25_rank", c.bm25_rank.map(Value::from)),
+        ("rerank", c.rerank.map(Value::from)),
+    ];
+    for (key, value) in optional {
+        if let Some(v) = value {
+            retrieval.insert(key.into(), v);
+        }
+    }
+    metadata.insert("retrieval".into(), Value::Object(retrieval));
+    ContextResult {
+        path,
+        chunk,
+        score: c.rerank.unwrap_or(c.fused / best),
+        text,
+        metadata,
+    }
+}
@@ -0,0 +1,120 @@
+//! `recon-retriever`: the recon RAG API over indexes built by
+//! `recon-ingest`.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use clap::Parser;
+use embed::batch::Batching;
+use embed::cache::{Cache, Cached};
+use embed::Embedder;
+use retriever::api::Api;
+use retriever::{Collection, Retriever};
+
+#[derive(Parser)]
+#[command(
+    name = "recon-retriever",
+    about = "Hybrid keyword and vector search over recon indexes"
+)]
+struct Args {
+    #[arg(long, env = "RETRIEVER_LISTEN", default_value = "0.0.0.0:7000")]
+    listen: SocketAddr,
+    /// `NAME=DIR` of an index to serve; repeatable.
+    #[arg(
+        long = "collection",
+        value_parser = parse_mapping,
+        default_value = "sovereignty-arch=recon-index"
+    )]
+    collections: Vec<(String, PathBuf)>,
+    /// Collection searched when a query names none.
+    #[arg(long, env = "COLLECTION", default_value = retriever::DEFAULT_COLLECTION)]
+    default_collection: String,
+    #[arg(long, env = "EMBED_URL", default_value = embed::http::DEFAULT_URL)]
+    embed_url: String,
+    /// Model behind `EMBED_URL`; cached vectors are kept per model.
+    #[arg(long, env = "EMBED_MODEL", default_value = embed::http::DEFAULT_MODEL)]
+    embed_model: String,
+    /// Sentence-transformers checkpoint to embed with in process instead
+    /// of calling `EMBED_URL`, in builds with the `local` feature; its
+    /// directory name keys the cache.
+    #[arg(long, env = "EMBED_MODEL_DIR")]
+    embed_model_dir: Option<PathBuf>,
+    /// Embedding cache shared with `recon-ingest`.
+    #[arg(long, env = "EMBED_CACHE", default_value = "recon-embed-cache")]
+    embed_cache: PathBuf,
+    #[arg(long)]
+    no_embed_cache: bool,
+    /// Embedding requests in flight; concurrent queries beyond it share
+    /// requests.
+    #[arg(long, env = "EMBED_CONCURRENCY", default_value_t = embed::batch::DEFAULT_CONCURRENCY)]
+    embed_concurrency: usize,
+    #[arg(long, env = "LLM_URL", default_value = llm::llamacpp::DEFAULT_URL)]
+    llm_url: String,
+    /// Cross-encoder `/rerank` endpoint; fused order is kept without one.
+    #[arg(long, env = "RERANK_URL")]
+    rerank_url: Option<String>,
+    #[arg(long, env = "MAX_CONTEXT_LENGTH", default_value_t = retriever::answer::MAX_CONTEXT_LENGTH)]
+    max_context_length: usize,
+    /// Share of an answer's sentences its cited contexts must support.
+    #[arg(long, env = "MIN_SUPPORT", default_value_t = retriever::answer::DEFAULT_MIN_SUPPORT)]
+    min_support: f32,
+}
+
+fn parse_mapping(s: &str) -> Result<(String, PathBuf), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), PathBuf::from(v)))
+        .ok_or_else(|| format!("expected NAME=DIR, got {s:?}"))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+
+    let (backend, model) = embed::backend(
+        &args.embed_url,
+        &args.embed_model,
+        args.embed_model_dir.as_deref(),
+    )?;
+    let batching = Batching::new(backend).with_concurrency(args.embed_concurrency);
+    let embedder: Arc<dyn Embedder> = if args.no_embed_cache {
+        Arc::new(batching)
+    } else {
+        let cache = Cache::open(&args.embed_cache, &model)
+            .with_context(|| format!("opening {}", args.embed_cache.display()))?;
+        Arc::new(Cached::new(Arc::new(batching), cache))
+    };
+    let mut retriever = Retriever::new(embedder).with_default_collection(&args.default_collection);
+    for (name, dir) in &args.collections {
+        let collection =
+            Collection::open(dir).with_context(|| format!("opening {}", dir.display()))?;
+        tracing::info!(collection = %name, vectors = collection.index().len(), "collection loaded");
+        retriever = retriever.with_collection(name, collection);
+    }
+    if let Some(url) = &args.rerank_url {
+        retriever = retriever.with_reranker(Arc::new(retriever::rerank::Http::new(url)));
+    }
+
+    let llm_url = args.llm_url.trim_end_matches('/');
+    // `api.py` derives the embedder's health URL the same way.
+    let embed_health = format!("{}/health", args.embed_url.replace("/embed", ""));
+    let app = Api::new(Arc::new(retriever))
+        .with_llm(Arc::new(llm::llamacpp::LlamaCpp::new(llm_url)))
+        .with_max_context(args.max_context_length)
+        .with_min_support(args.min_support)
+        .with_health_urls(Some(embed_health), Some(format!("{llm_url}/health")))
+        .router();
+
+    let listener = tokio::net::TcpListener::bind(args.listen).await?;
+    tracing::info!(listen = %args.listen, "retriever listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
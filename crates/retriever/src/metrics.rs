@@ -0,0 +1,60 @@
+//! Prometheus metrics, registered in the process-wide default registry
+//! under the names `recon/retriever/api.py` exported.
+
+use std::sync::LazyLock;
+
+use prometheus::{
+    register_gauge, register_histogram_vec, register_int_counter_vec, Encoder, Gauge, HistogramVec,
+    IntCounterVec, TextEncoder,
+};
+
+/// `rag_queries_total{collection, status}`, `status` being `success` or
+/// `error`.
+pub static QUERIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rag_queries_total",
+        "Total RAG queries",
+        &["collection", "status"]
+    )
+    .expect("metric registered twice")
+});
+
+/// `rag_query_duration_seconds{operation}`: `total`, `embedding`,
+/// `search`, `rerank` or `llm`.
+pub static DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "rag_query_duration_seconds",
+        "Query processing time",
+        &["operation"]
+    )
+    .expect("metric registered twice")
+});
+
+/// `rag_answers_total{outcome}`, `outcome` being `grounded` or
+/// `abstained`.
+pub static ANSWERS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rag_answers_total",
+        "LLM answers by support check outcome",
+        &["outcome"]
+    )
+    .expect("metric registered twice")
+});
+
+/// `rag_context_relevance_score`: mean score of the last query's contexts.
+pub static RELEVANCE: LazyLock<Gauge> = LazyLock::new(|| {
+    register_gauge!(
+        "rag_context_relevance_score",
+        "Average context relevance score"
+    )
+    .expect("metric registered twice")
+});
+
+/// Everything in the default registry, in the text exposition format.
+pub fn render() -> String {
+    let mut buf = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buf)
+        .expect("text encoding cannot fail");
+    String::from_utf8(buf).expect("text encoding is UTF-8")
+}
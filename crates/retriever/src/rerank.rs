@@ -0,0 +1,116 @@
+//! Second-stage reranking of fused candidates, e.g. by a cross-encoder.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RerankError {
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("reranker returned HTTP {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error("malformed rerank response: {0}")]
+    Malformed(String),
+}
+
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    /// A relevance score per text, in the order given; higher is better.
+    async fn rerank(&self, query: &str, texts: &[String]) -> Result<Vec<f32>, RerankError>;
+}
+
+/// A cross-encoder served by Hugging Face text-embeddings-inference:
+/// `POST /rerank {"query", "texts"}` answered by `[{"index", "score"}]`.
+pub struct Http {
+    http: reqwest::Client,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct Ranked {
+    index: usize,
+    score: f32,
+}
+
+impl Http {
+    /// `url` is the full endpoint, e.g. `http://localhost:8082/rerank`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Reranker for Http {
+    async fn rerank(&self, query: &str, texts: &[String]) -> Result<Vec<f32>, RerankError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let res = self
+            .http
+            .post(&self.url)
+            .json(&json!({ "query": query, "texts": texts, "truncate": true }))
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            let status = res.status().as_u16();
+            let mut body = res.text().await.unwrap_or_default();
+            body.truncate(body.floor_char_boundary(512));
+            return Err(RerankError::Status { status, body });
+        }
+        let ranked: Vec<Ranked> = res
+            .json()
+            .await
+            .map_err(|e| RerankError::Malformed(e.to_string()))?;
+        let mut scores = vec![None; texts.len()];
+        for r in ranked {
+            let slot = scores
+                .get_mut(r.index)
+                .ok_or_else(|| RerankError::Malformed(format!("index {} out of range", r.index)))?;
+            *slot = Some(r.score);
+        }
+        scores
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| s.ok_or_else(|| RerankError::Malformed(format!("no score for text {i}"))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+
+    use super::*;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn maps_ranked_indexes_back_to_text_order() {
+        let app = Router::new().route(
+            "/rerank",
+            post(|Json(body): Json<Value>| async move {
+                assert_eq!(body["query"], "dao");
+                // Best first, as the server sorts them.
+                Json(json!([{"index": 1, "score": 0.9}, {"index": 0, "score": 0.2}]))
+            }),
+        );
+        let url = serve(app).await;
+        let texts = vec!["unrelated".to_string(), "dao statute".to_string()];
+        let scores = Http::new(format!("{url}/rerank"))
+            .rerank("dao", &texts)
+            .await
+            .unwrap();
+        assert_eq!(scores, [0.2, 0.9]);
+    }
+}
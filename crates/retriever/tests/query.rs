@@ -0,0 +1,193 @@
+//! `POST /query` over a small collection, as `api.py` clients call it.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use embed::{EmbedError, Embedder};
+use retriever::api::Api;
+use retriever::rerank::{RerankError, Reranker};
+use retriever::{Collection, QueryResponse, Retriever};
+use serde_json::{json, Value};
+use vectors::{Config, Metadata, VectorIndex};
+
+/// Embeds a text as its vowel counts, or fails when told to.
+struct Vowels {
+    down: bool,
+}
+
+fn vowels(text: &str) -> Vec<f32> {
+    "aeiou"
+        .chars()
+        .map(|c| text.matches(c).count() as f32 + 1.0)
+        .collect()
+}
+
+#[async_trait]
+impl Embedder for Vowels {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if self.down {
+            return Err(EmbedError::Status {
+                status: 503,
+                body: "down".into(),
+            });
+        }
+        Ok(texts.iter().map(|t| vowels(t)).collect())
+    }
+}
+
+/// Prefers shorter texts.
+struct Shortest;
+
+#[async_trait]
+impl Reranker for Shortest {
+    async fn rerank(&self, _query: &str, texts: &[String]) -> Result<Vec<f32>, RerankError> {
+        Ok(texts.iter().map(|t| -(t.len() as f32)).collect())
+    }
+}
+
+const CHUNKS: &[(&str, &str)] = &[
+    (
+        "legal/sf0068.md",
+        "Wyoming SF0068 amends W.S. 17-31-101 for decentralized autonomous organizations.",
+    ),
+    (
+        "docs/architecture.md",
+        "The sovereignty architecture routes events through the gateway to Discord.",
+    ),
+    (
+        "docs/models.md",
+        "Model routing sends reasoning-heavy questions to O3 and quick ones elsewhere.",
+    ),
+    (
+        "src/gateway.rs",
+        "Signature verification rejects unsigned webhook deliveries early on.",
+    ),
+];
+
+fn collection() -> Collection {
+    let mut index = VectorIndex::new(Config::new(5));
+    for (path, text) in CHUNKS {
+        let metadata = Metadata::from([
+            ("path".to_string(), path.to_string()),
+            ("chunk".to_string(), "0".to_string()),
+            ("total_chunks".to_string(), "1".to_string()),
+            ("extension".to_string(), ".md".to_string()),
+            ("text".to_string(), text.to_string()),
+        ]);
+        index
+            .upsert(format!("{path}#0"), &vowels(text), metadata)
+            .unwrap();
+    }
+    Collection::new(index)
+}
+
+async fn serve(retriever: Retriever, llm: Option<Arc<dyn llm::Provider>>) -> String {
+    let mut api = Api::new(Arc::new(retriever));
+    if let Some(llm) = llm {
+        api = api.with_llm(llm);
+    }
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = api.router();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+async fn query(url: &str, body: Value) -> (u16, Value) {
+    let res = reqwest::Client::new()
+        .post(format!("{url}/query"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    (res.status().as_u16(), res.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn exact_identifiers_rank_first_and_survive_min_score() {
+    let retriever = Retriever::new(Arc::new(Vowels { down: false }))
+        .with_collection("sovereignty-arch", collection());
+    let url = serve(retriever, None).await;
+
+    let (status, body) = query(&url, json!({"q": "W.S. 17-31-101", "include_llm": false})).await;
+    assert_eq!(status, 200);
+    let res: QueryResponse = serde_json::from_value(body).unwrap();
+    assert_eq!(res.collection, "sovereignty-arch");
+    assert_eq!(res.total_contexts, CHUNKS.len());
+    assert_eq!(res.contexts[0].path, "legal/sf0068.md");
+    assert_eq!(res.contexts[0].metadata["total_chunks"], 1);
+    assert_eq!(res.contexts[0].metadata["retrieval"]["bm25_rank"], 1);
+    assert!(res.answer.is_none());
+
+    // A similarity floor no embedding meets leaves only keyword matches.
+    let (_, body) = query(
+        &url,
+        json!({"q": "O3", "min_score": 0.9999, "include_llm": false}),
+    )
+    .await;
+    let res: QueryResponse = serde_json::from_value(body).unwrap();
+    let paths: Vec<&str> = res.contexts.iter().map(|c| c.path.as_str()).collect();
+    assert_eq!(paths, ["docs/models.md"]);
+
+    let (_, body) = query(
+        &url,
+        json!({"q": "routing", "path_prefix": "docs/", "k": 1, "min_score": null}),
+    )
+    .await;
+    let res: QueryResponse = serde_json::from_value(body).unwrap();
+    assert_eq!(res.contexts.len(), 1);
+    assert!(res.contexts[0].path.starts_with("docs/"));
+
+    let (status, body) = query(&url, json!({"q": "x", "k": 0})).await;
+    assert_eq!(status, 422);
+    assert!(body["detail"].as_str().unwrap().contains("k must be"));
+    let (status, _) = query(&url, json!({"q": "x", "collection": "missing"})).await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn reranks_answers_and_falls_back_to_keywords() {
+    let retriever = Retriever::new(Arc::new(Vowels { down: true }))
+        .with_collection("sovereignty-arch", collection())
+        .with_reranker(Arc::new(Shortest));
+    let llm = Arc::new(
+        llm::replay::Replay::new()
+            .respond(
+                "Question: gateway",
+                "Signature verification rejects unsigned webhook deliveries [1].",
+            )
+            .respond(
+                "Question: discord",
+                "The gateway forwards events to Discord [1]. It retries failed posts five times.",
+            ),
+    );
+    let url = serve(retriever, Some(llm.clone())).await;
+
+    // The embedder is down: keyword matches only, reordered by the reranker.
+    let (status, body) = query(&url, json!({"q": "gateway"})).await;
+    assert_eq!(status, 200);
+    let res: QueryResponse = serde_json::from_value(body).unwrap();
+    let paths: Vec<&str> = res.contexts.iter().map(|c| c.path.as_str()).collect();
+    assert_eq!(paths, ["src/gateway.rs", "docs/architecture.md"]);
+    assert!(res.contexts[0].metadata["retrieval"]["rerank"].is_number());
+    assert_eq!(
+        res.answer.as_deref(),
+        Some("Signature verification rejects unsigned webhook deliveries [1].")
+    );
+    let support = res.support.unwrap();
+    assert!(!support.abstained);
+    assert_eq!(support.sources[0].path, "src/gateway.rs");
+    assert_eq!(support.sentences[0].cites, [1]);
+    let prompt = &llm.calls()[0].messages[0].content;
+    assert!(prompt.contains("[1] src/gateway.rs (chunk 0, score: "));
+
+    // Half the answer cites nothing: abstain, and say why.
+    let (_, body) = query(&url, json!({"q": "discord"})).await;
+    assert_eq!(body["answer"], retriever::answer::ABSTENTION);
+    assert_eq!(body["support"]["abstained"], true);
+    assert_eq!(body["support"]["hallucination_score"], 0.5);
+    assert_eq!(body["support"]["sentences"][1]["supported"], false);
+
+    let (_, body) = query(&url, json!({"q": "gateway", "include_llm": false})).await;
+    assert!(body.get("support").is_none());
+}
@@ -0,0 +1,91 @@
+//! Secrets from environment variables.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::{Backend, Error, Lease, Secret, SecretRef, DEFAULT_FIELD};
+
+/// Variables the TypeScript services and `docker-compose.yml` already use.
+const COMPAT_ALIASES: &[(&str, &str)] = &[
+    ("kv/discord/bot_token", "DISCORD_TOKEN"),
+    ("kv/events/hmac_key", "EVENTS_HMAC_KEY"),
+    ("kv/git/webhook_secret", "GITHUB_WEBHOOK_SECRET"),
+    ("kv/openai/api_key", "OPENAI_API_KEY"),
+    ("kv/github/pat", "GITHUB_TOKEN"),
+];
+
+/// Reads each reference from a variable derived from its path
+/// (`vault://kv/ctrl/api_token` → `CTRL_API_TOKEN`, a `#field` suffix adds
+/// `_FIELD`). Known references also fall back to their legacy names, e.g.
+/// `DISCORD_TOKEN`.
+pub struct EnvBackend {
+    aliases: HashMap<String, String>,
+}
+
+impl Default for EnvBackend {
+    fn default() -> Self {
+        let aliases = COMPAT_ALIASES
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Self { aliases }
+    }
+}
+
+impl EnvBackend {
+    /// Reads `r` from `var` as a last resort.
+    pub fn with_alias(mut self, r: &SecretRef, var: impl Into<String>) -> Self {
+        self.aliases.insert(r.key(), var.into());
+        self
+    }
+
+    /// Derived variable name for `r`.
+    pub fn var_name(r: &SecretRef) -> String {
+        let mut name = r.path.clone();
+        if r.field() != DEFAULT_FIELD {
+            name = format!("{name}_{}", r.field());
+        }
+        name.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Backend for EnvBackend {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn read(&self, r: &SecretRef) -> Result<Lease, Error> {
+        let derived = Self::var_name(r);
+        let alias = (r.field() == DEFAULT_FIELD)
+            .then(|| self.aliases.get(&r.key()))
+            .flatten();
+        std::iter::once(&derived)
+            .chain(alias)
+            .find_map(|var| std::env::var(var).ok())
+            .map(|v| Lease::new(Secret::new(v)))
+            .ok_or_else(|| Error::NotFound(r.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_variable_names() {
+        let r: SecretRef = "vault://kv/ctrl/api_token".parse().unwrap();
+        assert_eq!(EnvBackend::var_name(&r), "CTRL_API_TOKEN");
+        let r: SecretRef = "vault://kv/pgvector/conn#dsn".parse().unwrap();
+        assert_eq!(EnvBackend::var_name(&r), "PGVECTOR_CONN_DSN");
+    }
+}
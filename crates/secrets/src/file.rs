@@ -0,0 +1,164 @@
+//! Secrets from a sealed local file.
+//!
+//! The plaintext is a JSON object keyed by `<mount>/<path>`; a value is either
+//! a string (read as the `value` field) or an object of fields:
+//!
+//! ```json
+//! { "kv/discord/bot_token": "…", "kv/pgvector/conn": { "dsn": "…" } }
+//! ```
+//!
+//! On disk it is `SKSEC1` ‖ 12-byte nonce ‖ ChaCha20-Poly1305 ciphertext.
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::{Backend, Error, Lease, Secret, SecretRef, DEFAULT_FIELD};
+
+const MAGIC: &[u8] = b"SKSEC1";
+const NONCE_LEN: usize = 12;
+
+/// 256-bit sealing key.
+#[derive(Clone)]
+pub struct FileKey(Zeroizing<[u8; 32]>);
+
+impl FileKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(Zeroizing::new(bytes))
+    }
+
+    /// Standard base64 of exactly 32 bytes (`openssl rand -base64 32`).
+    pub fn from_base64(s: &str) -> Result<Self, Error> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(s.trim())
+            .map_err(|e| Error::File(format!("key is not base64: {e}")))?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| Error::File("key must be 32 bytes".to_string()))?;
+        Ok(Self::from_bytes(bytes))
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(self.0.as_slice()))
+    }
+}
+
+/// Encrypts `plaintext` into the on-disk format.
+pub fn seal(key: &FileKey, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ct = key
+        .cipher()
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| Error::File("encryption failed".to_string()))?;
+    Ok([MAGIC, nonce.as_slice(), &ct].concat())
+}
+
+/// Decrypts the on-disk format.
+pub fn open(key: &FileKey, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let body = sealed
+        .strip_prefix(MAGIC)
+        .filter(|b| b.len() > NONCE_LEN)
+        .ok_or_else(|| Error::File("not a sealed secrets file".to_string()))?;
+    let (nonce, ct) = body.split_at(NONCE_LEN);
+    key.cipher()
+        .decrypt(Nonce::from_slice(nonce), ct)
+        .map(Zeroizing::new)
+        .map_err(|_| Error::File("wrong key or corrupted file".to_string()))
+}
+
+pub struct FileBackend {
+    path: PathBuf,
+    key: FileKey,
+}
+
+impl FileBackend {
+    pub fn new(path: impl Into<PathBuf>, key: FileKey) -> Self {
+        Self {
+            path: path.into(),
+            key,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[async_trait]
+impl Backend for FileBackend {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    // Re-read on every miss so edits to the file are picked up on refresh.
+    async fn read(&self, r: &SecretRef) -> Result<Lease, Error> {
+        let sealed = tokio::fs::read(&self.path)
+            .await
+            .map_err(|e| Error::File(format!("{}: {e}", self.path.display())))?;
+        let plain = open(&self.key, &sealed)?;
+        let doc: Value = serde_json::from_slice(&plain)
+            .map_err(|e| Error::File(format!("invalid JSON: {e}")))?;
+        let value = match (&doc[r.key()], r.field()) {
+            (Value::Null, _) => return Err(Error::NotFound(r.clone())),
+            (Value::String(s), DEFAULT_FIELD) => s.clone(),
+            (Value::Object(fields), field) => match fields.get(field) {
+                Some(Value::String(s)) => s.clone(),
+                _ => return Err(Error::MissingField(r.clone(), field.to_string())),
+            },
+            (_, field) => return Err(Error::MissingField(r.clone(), field.to_string())),
+        };
+        Ok(Lease::new(Secret::new(value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn round_trips_and_reads_fields() {
+        let key = FileKey::from_bytes([7; 32]);
+        let plain = br#"{"kv/discord/bot_token":"tok","kv/pgvector/conn":{"dsn":"postgres://"}}"#;
+        let sealed = seal(&key, plain).unwrap();
+        assert!(open(&FileKey::from_bytes([8; 32]), &sealed).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.enc");
+        std::fs::write(&path, sealed).unwrap();
+        let backend = FileBackend::new(&path, key);
+        let read = |s: &str| {
+            let r: SecretRef = s.parse().unwrap();
+            let backend = &backend;
+            async move { backend.read(&r).await }
+        };
+        assert_eq!(
+            read("vault://kv/discord/bot_token")
+                .await
+                .unwrap()
+                .secret
+                .expose(),
+            "tok"
+        );
+        assert_eq!(
+            read("vault://kv/pgvector/conn#dsn")
+                .await
+                .unwrap()
+                .secret
+                .expose(),
+            "postgres://"
+        );
+        assert!(matches!(
+            read("vault://kv/pgvector/conn").await,
+            Err(Error::MissingField(..))
+        ));
+        assert!(matches!(
+            read("vault://kv/nope").await,
+            Err(Error::NotFound(_))
+        ));
+    }
+}
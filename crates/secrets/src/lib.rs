@@ -0,0 +1,368 @@
+//! Resolution of `vault://` secret references.
+//!
+//! `discovery.yml` names secrets as `vault://<mount>/<path>[#field]`
+//! (`token_secret_ref`, `webhook_secret_ref`, ...). A [`Resolver`] turns those
+//! into values through one [`Backend`]:
+//!
+//! * [`env::EnvBackend`] for local development and compose files,
+//! * [`file::FileBackend`] for a ChaCha20-Poly1305 sealed JSON file,
+//! * [`vault::VaultBackend`] for a Vault-compatible KV v2 HTTP API.
+//!
+//! Resolved values are cached, refreshed before they expire, and registered
+//! with a [`redact::Redactor`] so they never reach the logs.
+
+pub mod env;
+pub mod file;
+pub mod redact;
+pub mod vault;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use zeroize::Zeroizing;
+
+use crate::redact::Redactor;
+
+const SCHEME: &str = "vault://";
+
+/// Field read when a reference has no `#field` suffix.
+pub const DEFAULT_FIELD: &str = "value";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid secret ref {0:?}: expected vault://<mount>/<path>[#field]")]
+    InvalidRef(String),
+    #[error("{0} not found")]
+    NotFound(SecretRef),
+    #[error("{0}: field {1:?} missing")]
+    MissingField(SecretRef, String),
+    #[error("{backend} backend: {message}")]
+    Backend {
+        backend: &'static str,
+        message: String,
+    },
+    #[error("secrets file: {0}")]
+    File(String),
+    #[error("vault request failed: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+/// A parsed `vault://<mount>/<path>[#field]` reference.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SecretRef {
+    pub mount: String,
+    pub path: String,
+    pub field: Option<String>,
+}
+
+impl SecretRef {
+    /// Field to read, defaulting to [`DEFAULT_FIELD`].
+    pub fn field(&self) -> &str {
+        self.field.as_deref().unwrap_or(DEFAULT_FIELD)
+    }
+
+    /// `<mount>/<path>`, the key used by the file backend.
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.mount, self.path)
+    }
+}
+
+impl FromStr for SecretRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidRef(s.to_string());
+        let rest = s.strip_prefix(SCHEME).ok_or_else(invalid)?;
+        let (rest, field) = match rest.split_once('#') {
+            Some((rest, field)) if !field.is_empty() => (rest, Some(field.to_string())),
+            Some(_) => return Err(invalid()),
+            None => (rest, None),
+        };
+        let (mount, path) = rest.split_once('/').ok_or_else(invalid)?;
+        if mount.is_empty() || path.is_empty() || path.split('/').any(str::is_empty) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            mount: mount.to_string(),
+            path: path.to_string(),
+            field,
+        })
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{SCHEME}{}/{}", self.mount, self.path)?;
+        if let Some(field) = &self.field {
+            write!(f, "#{field}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A resolved value. `Debug` never prints it and the memory is wiped on drop.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(Zeroizing::new(value.into()))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether `presented` equals the secret, compared without an early
+    /// exit so timing does not reveal it; for checking bearer tokens.
+    pub fn matches(&self, presented: &str) -> bool {
+        let secret = self.0.as_bytes();
+        secret.len() == presented.len()
+            && secret
+                .iter()
+                .zip(presented.bytes())
+                .fold(0, |acc, (x, y)| acc | (x ^ y))
+                == 0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+/// A value read from a backend and how long it may be cached.
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub secret: Secret,
+    /// Backend-provided lifetime; `None` falls back to the resolver default.
+    pub ttl: Option<Duration>,
+    /// KV version, when the backend tracks one.
+    pub version: Option<u64>,
+}
+
+impl Lease {
+    pub fn new(secret: Secret) -> Self {
+        Self {
+            secret,
+            ttl: None,
+            version: None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait Backend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn read(&self, r: &SecretRef) -> Result<Lease, Error>;
+
+    /// Extends whatever credential the backend itself holds and returns its
+    /// remaining lifetime. Backends without credentials do nothing.
+    async fn renew(&self) -> Result<Option<Duration>, Error> {
+        Ok(None)
+    }
+}
+
+struct Cached {
+    lease: Lease,
+    expires: Instant,
+}
+
+/// Caching front for a [`Backend`].
+pub struct Resolver {
+    backend: Arc<dyn Backend>,
+    cache: Mutex<HashMap<SecretRef, Cached>>,
+    default_ttl: Duration,
+    redactor: Redactor,
+}
+
+impl Resolver {
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Self {
+            backend,
+            cache: Mutex::new(HashMap::new()),
+            default_ttl: Duration::from_secs(300),
+            redactor: Redactor::default(),
+        }
+    }
+
+    /// Cache lifetime for values whose backend reports no TTL.
+    pub fn with_default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Shares a redactor, e.g. one seeded with `security.redaction.patterns`.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Redactor that knows every value this resolver has handed out.
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    /// Parses `reference` and resolves it.
+    pub async fn resolve_str(&self, reference: &str) -> Result<Secret, Error> {
+        self.resolve(&reference.parse()?).await
+    }
+
+    pub async fn resolve(&self, r: &SecretRef) -> Result<Secret, Error> {
+        Ok(self.lease(r).await?.secret)
+    }
+
+    /// Like [`Resolver::resolve`] but keeps the version and TTL.
+    pub async fn lease(&self, r: &SecretRef) -> Result<Lease, Error> {
+        if let Some(hit) = self.cached(r) {
+            return Ok(hit);
+        }
+        self.fetch(r).await
+    }
+
+    fn cached(&self, r: &SecretRef) -> Option<Lease> {
+        let cache = self.cache.lock().expect("secret cache poisoned");
+        cache
+            .get(r)
+            .filter(|c| c.expires > Instant::now())
+            .map(|c| c.lease.clone())
+    }
+
+    async fn fetch(&self, r: &SecretRef) -> Result<Lease, Error> {
+        let lease = self.backend.read(r).await?;
+        self.redactor.add_literal(lease.secret.expose());
+        let ttl = lease.ttl.unwrap_or(self.default_ttl);
+        self.cache.lock().expect("secret cache poisoned").insert(
+            r.clone(),
+            Cached {
+                lease: lease.clone(),
+                expires: Instant::now() + ttl,
+            },
+        );
+        tracing::debug!(secret = %r, backend = self.backend.name(), ?ttl, "resolved secret");
+        Ok(lease)
+    }
+
+    /// Re-reads cached entries that expire within `margin`. Failures keep the
+    /// old value so a backend blip does not take dependents down.
+    pub async fn refresh(&self, margin: Duration) {
+        let due: Vec<SecretRef> = {
+            let cache = self.cache.lock().expect("secret cache poisoned");
+            let cutoff = Instant::now() + margin;
+            cache
+                .iter()
+                .filter(|(_, c)| c.expires <= cutoff)
+                .map(|(r, _)| r.clone())
+                .collect()
+        };
+        for r in due {
+            if let Err(e) = self.fetch(&r).await {
+                tracing::warn!(secret = %r, error = %self.redactor.redact(&e.to_string()), "refresh failed; keeping cached value");
+            }
+        }
+    }
+
+    /// Background loop: renews the backend credential and refreshes cached
+    /// values every `interval`.
+    pub fn spawn_renewal(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let this = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(interval);
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                match this.backend.renew().await {
+                    Ok(Some(ttl)) => tracing::debug!(?ttl, "renewed backend credential"),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!(error = %e, "credential renewal failed"),
+                }
+                this.refresh(interval * 2).await;
+            }
+        })
+    }
+}
+
+/// Picks a backend from the environment:
+///
+/// * `SECRETS_BACKEND=env` (default): see [`env::EnvBackend`].
+/// * `SECRETS_BACKEND=file`: `SECRETS_FILE` sealed with `SECRETS_FILE_KEY`.
+/// * `SECRETS_BACKEND=vault`: `VAULT_ADDR`, `VAULT_TOKEN`, `VAULT_NAMESPACE`.
+pub fn backend_from_env() -> Result<Arc<dyn Backend>, Error> {
+    let var = |name: &'static str| {
+        std::env::var(name).map_err(|_| Error::Backend {
+            backend: "config",
+            message: format!("{name} is not set"),
+        })
+    };
+    let kind = std::env::var("SECRETS_BACKEND").unwrap_or_else(|_| "env".to_string());
+    Ok(match kind.as_str() {
+        "env" => Arc::new(env::EnvBackend::default()),
+        "file" => {
+            let key = file::FileKey::from_base64(&var("SECRETS_FILE_KEY")?)?;
+            Arc::new(file::FileBackend::new(var("SECRETS_FILE")?, key))
+        }
+        "vault" => {
+            let mut backend =
+                vault::VaultBackend::new(var("VAULT_ADDR")?, Secret::new(var("VAULT_TOKEN")?));
+            if let Ok(ns) = std::env::var("VAULT_NAMESPACE") {
+                backend = backend.with_namespace(ns);
+            }
+            Arc::new(backend)
+        }
+        other => {
+            return Err(Error::Backend {
+                backend: "config",
+                message: format!("unknown SECRETS_BACKEND {other:?} (env|file|vault)"),
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_only_the_exact_secret() {
+        let secret = Secret::new("s3cret");
+        assert!(secret.matches("s3cret"));
+        assert!(!secret.matches("s3creT"));
+        assert!(!secret.matches("s3cret-longer"));
+        assert!(!secret.matches(""));
+    }
+
+    #[test]
+    fn parses_discovery_refs() {
+        let r: SecretRef = "vault://kv/discord/bot_token".parse().unwrap();
+        assert_eq!(r.mount, "kv");
+        assert_eq!(r.path, "discord/bot_token");
+        assert_eq!(r.field(), "value");
+        let r: SecretRef = "vault://kv/pgvector/conn#dsn".parse().unwrap();
+        assert_eq!(r.field(), "dsn");
+        assert_eq!(r.to_string(), "vault://kv/pgvector/conn#dsn");
+        for bad in [
+            "kv/x",
+            "vault://kv",
+            "vault://kv/",
+            "vault://kv//x",
+            "vault://kv/x#",
+        ] {
+            assert!(bad.parse::<SecretRef>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn secret_debug_is_redacted() {
+        assert_eq!(format!("{:?}", Secret::new("hunter2")), "Secret(***)");
+    }
+}
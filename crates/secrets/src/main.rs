@@ -0,0 +1,198 @@
+//! `secretd`: resolves `vault://` references for services that cannot link
+//! the library (the TypeScript bot and gateway, refinory's Python API).
+
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::{Parser, Subcommand};
+use secrets::redact::Redactor;
+use secrets::{file, Error, Resolver, Secret};
+use serde::Deserialize;
+
+#[derive(Parser)]
+#[command(name = "secretd", about = "vault:// secret resolver sidecar")]
+struct Args {
+    /// discovery.yml whose `security.redaction.patterns` are applied to logs.
+    #[arg(long, global = true, env = "DISCOVERY_CONFIG_PATH")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve `GET /v1/resolve?ref=vault://...` on a local port.
+    Serve {
+        #[arg(long, env = "SECRETD_LISTEN", default_value = "127.0.0.1:7373")]
+        listen: SocketAddr,
+        /// Bearer token clients must present; unset allows any local caller.
+        #[arg(long, env = "SECRETD_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+        /// Seconds between credential renewal and cache refresh passes.
+        #[arg(long, default_value_t = 60)]
+        renew_every: u64,
+    },
+    /// Print one resolved secret to stdout.
+    Get { reference: String },
+    /// Seal a plaintext JSON document from stdin for the file backend.
+    Seal {
+        /// Output path for the sealed file.
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Replace every `vault://` environment value and exec a command.
+    Exec {
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let redactor = match &args.config {
+        Some(path) => {
+            let cfg = discovery::Discovery::load(path)?;
+            Redactor::with_patterns(cfg.security.redaction.patterns.iter().map(String::as_str))?
+        }
+        None => Redactor::default(),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(redactor.stderr_writer())
+        .init();
+
+    match args.command {
+        Command::Seal { out } => {
+            let key = file::FileKey::from_base64(
+                &std::env::var("SECRETS_FILE_KEY").context("SECRETS_FILE_KEY")?,
+            )?;
+            let mut plain = zeroize::Zeroizing::new(Vec::new());
+            std::io::stdin().read_to_end(&mut plain)?;
+            serde_json::from_slice::<serde_json::Value>(&plain).context("stdin is not JSON")?;
+            std::fs::write(&out, file::seal(&key, &plain)?)?;
+            return Ok(());
+        }
+        command => {
+            let resolver =
+                Arc::new(Resolver::new(secrets::backend_from_env()?).with_redactor(redactor));
+            match command {
+                Command::Serve {
+                    listen,
+                    token,
+                    renew_every,
+                } => serve(resolver, listen, token.map(Secret::new), renew_every).await,
+                Command::Get { reference } => {
+                    println!("{}", resolver.resolve_str(&reference).await?.expose());
+                    Ok(())
+                }
+                Command::Exec { command } => exec(&resolver, &command).await,
+                Command::Seal { .. } => unreachable!(),
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct App {
+    resolver: Arc<Resolver>,
+    token: Option<Secret>,
+}
+
+#[derive(Deserialize)]
+struct ResolveQuery {
+    #[serde(rename = "ref")]
+    reference: String,
+}
+
+async fn serve(
+    resolver: Arc<Resolver>,
+    listen: SocketAddr,
+    token: Option<Secret>,
+    renew_every: u64,
+) -> anyhow::Result<()> {
+    if token.is_none() && !listen.ip().is_loopback() {
+        anyhow::bail!("refusing to listen on {listen} without SECRETD_TOKEN");
+    }
+    resolver.spawn_renewal(Duration::from_secs(renew_every));
+    let app = Router::new()
+        .route("/health", get(|| async { "ok" }))
+        .route("/v1/resolve", get(resolve))
+        .with_state(App { resolver, token });
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    tracing::info!(%listen, "secretd listening");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+async fn resolve(
+    State(app): State<App>,
+    headers: HeaderMap,
+    Query(q): Query<ResolveQuery>,
+) -> Response {
+    if let Some(token) = &app.token {
+        let presented = headers
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !presented.is_some_and(|t| token.matches(t)) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    let reference = match q.reference.parse::<secrets::SecretRef>() {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match app.resolver.lease(&reference).await {
+        Ok(lease) => Json(serde_json::json!({
+            "ref": q.reference,
+            "value": lease.secret.expose(),
+            "version": lease.version,
+        }))
+        .into_response(),
+        Err(e @ (Error::NotFound(_) | Error::MissingField(..))) => {
+            (StatusCode::NOT_FOUND, e.to_string()).into_response()
+        }
+        Err(e) => {
+            tracing::error!(secret = %q.reference, error = %e, "resolve failed");
+            (StatusCode::BAD_GATEWAY, "backend error").into_response()
+        }
+    }
+}
+
+async fn exec(resolver: &Resolver, command: &[String]) -> anyhow::Result<()> {
+    let mut cmd = std::process::Command::new(&command[0]);
+    cmd.args(&command[1..]);
+    for (name, value) in std::env::vars() {
+        if value.starts_with("vault://") {
+            let secret = resolver
+                .resolve_str(&value)
+                .await
+                .with_context(|| format!("resolving ${name}"))?;
+            cmd.env(name, secret.expose());
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        Err(cmd.exec()).with_context(|| format!("exec {}", command[0]))
+    }
+    #[cfg(not(unix))]
+    {
+        let status = cmd.status()?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
@@ -0,0 +1,129 @@
+//! Masking of secret material in log output.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::{Arc, RwLock};
+
+use regex::Regex;
+use zeroize::Zeroizing;
+
+pub const MASK: &str = "[REDACTED]";
+
+/// Values shorter than this are not masked literally; they would match too
+/// much unrelated text.
+const MIN_LITERAL_LEN: usize = 4;
+
+#[derive(Default)]
+struct Rules {
+    patterns: Vec<Regex>,
+    literals: Vec<Zeroizing<String>>,
+}
+
+/// Masks resolved secret values and `security.redaction.patterns` matches.
+/// Cheap to clone; clones share their rules.
+#[derive(Clone, Default)]
+pub struct Redactor(Arc<RwLock<Rules>>);
+
+impl Redactor {
+    /// Compiles `patterns`; invalid ones are returned as errors.
+    pub fn with_patterns<'a>(
+        patterns: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self, regex::Error> {
+        let patterns = patterns
+            .into_iter()
+            .map(Regex::new)
+            .collect::<Result<_, _>>()?;
+        Ok(Self(Arc::new(RwLock::new(Rules {
+            patterns,
+            literals: Vec::new(),
+        }))))
+    }
+
+    pub fn add_literal(&self, value: &str) {
+        if value.len() < MIN_LITERAL_LEN {
+            return;
+        }
+        let mut rules = self.0.write().expect("redactor poisoned");
+        if !rules.literals.iter().any(|l| l.as_str() == value) {
+            rules.literals.push(Zeroizing::new(value.to_string()));
+        }
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let rules = self.0.read().expect("redactor poisoned");
+        let mut out = Cow::Borrowed(text);
+        for lit in &rules.literals {
+            if out.contains(lit.as_str()) {
+                out = Cow::Owned(out.replace(lit.as_str(), MASK));
+            }
+        }
+        for re in &rules.patterns {
+            if re.is_match(&out) {
+                out = Cow::Owned(re.replace_all(&out, MASK).into_owned());
+            }
+        }
+        out
+    }
+
+    /// A `tracing_subscriber` writer factory that redacts before writing to
+    /// stderr.
+    pub fn stderr_writer(&self) -> RedactingMakeWriter {
+        RedactingMakeWriter(self.clone())
+    }
+}
+
+pub struct RedactingMakeWriter(Redactor);
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for RedactingMakeWriter {
+    type Writer = RedactingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            redactor: self.0.clone(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// Buffers one formatted event and writes it redacted on drop.
+pub struct RedactingWriter {
+    redactor: Redactor,
+    buf: Vec<u8>,
+}
+
+impl Write for RedactingWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RedactingWriter {
+    fn drop(&mut self) {
+        let text = String::from_utf8_lossy(&self.buf);
+        let _ = io::stderr().write_all(self.redactor.redact(&text).as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_literals_and_discovery_patterns() {
+        let r =
+            Redactor::with_patterns([r"(?i)api[_-]?key\s*[:=]\s*\S+", r"Bearer\s+[A-Za-z0-9._-]+"])
+                .unwrap();
+        r.add_literal("s3cr3t-token");
+        r.add_literal("ab");
+        assert_eq!(
+            r.redact("token=s3cr3t-token api_key: xyz ab Authorization: Bearer abc.def"),
+            "token=[REDACTED] [REDACTED] ab Authorization: [REDACTED]"
+        );
+        assert!(matches!(r.redact("nothing here"), Cow::Borrowed(_)));
+    }
+}
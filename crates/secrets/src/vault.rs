@@ -0,0 +1,129 @@
+//! Vault (or OpenBao) KV v2 over HTTP.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{Backend, Error, Lease, Secret, SecretRef};
+
+pub struct VaultBackend {
+    http: reqwest::Client,
+    addr: String,
+    token: Secret,
+    namespace: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ReadResponse {
+    #[serde(default)]
+    lease_duration: u64,
+    data: KvData,
+}
+
+#[derive(Deserialize)]
+struct KvData {
+    data: Option<serde_json::Map<String, Value>>,
+    metadata: Option<KvMetadata>,
+}
+
+#[derive(Deserialize)]
+struct KvMetadata {
+    version: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RenewResponse {
+    auth: RenewAuth,
+}
+
+#[derive(Deserialize)]
+struct RenewAuth {
+    lease_duration: u64,
+}
+
+impl VaultBackend {
+    /// `addr` is the server root, e.g. `https://vault.internal:8200`.
+    pub fn new(addr: impl Into<String>, token: Secret) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            addr: addr.into().trim_end_matches('/').to_string(),
+            token,
+            namespace: None,
+        }
+    }
+
+    /// Sends `X-Vault-Namespace` (Vault Enterprise / HCP).
+    pub fn with_namespace(mut self, ns: impl Into<String>) -> Self {
+        self.namespace = Some(ns.into());
+        self
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let mut req = self
+            .http
+            .request(method, format!("{}/v1/{path}", self.addr))
+            .header("X-Vault-Token", self.token.expose());
+        if let Some(ns) = &self.namespace {
+            req = req.header("X-Vault-Namespace", ns);
+        }
+        req
+    }
+}
+
+fn backend_err(message: impl Into<String>) -> Error {
+    Error::Backend {
+        backend: "vault",
+        message: message.into(),
+    }
+}
+
+#[async_trait]
+impl Backend for VaultBackend {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn read(&self, r: &SecretRef) -> Result<Lease, Error> {
+        let res = self
+            .request(
+                reqwest::Method::GET,
+                &format!("{}/data/{}", r.mount, r.path),
+            )
+            .send()
+            .await?;
+        match res.status() {
+            StatusCode::NOT_FOUND => return Err(Error::NotFound(r.clone())),
+            s if !s.is_success() => return Err(backend_err(format!("GET {r}: HTTP {s}"))),
+            _ => {}
+        }
+        let body: ReadResponse = res.json().await?;
+        // A deleted-but-versioned secret comes back with `data: null`.
+        let fields = body.data.data.ok_or_else(|| Error::NotFound(r.clone()))?;
+        let value = match fields.get(r.field()) {
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => return Err(Error::MissingField(r.clone(), r.field().to_string())),
+        };
+        Ok(Lease {
+            secret: Secret::new(value),
+            ttl: (body.lease_duration > 0).then(|| Duration::from_secs(body.lease_duration)),
+            version: body.data.metadata.and_then(|m| m.version),
+        })
+    }
+
+    async fn renew(&self) -> Result<Option<Duration>, Error> {
+        let res = self
+            .request(reqwest::Method::POST, "auth/token/renew-self")
+            .json(&serde_json::json!({}))
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(backend_err(format!("token renew: HTTP {}", res.status())));
+        }
+        let body: RenewResponse = res.json().await?;
+        Ok(Some(Duration::from_secs(body.auth.lease_duration)))
+    }
+}
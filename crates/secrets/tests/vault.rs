@@ -0,0 +1,130 @@
+//! KV v2 backend against a local mock of the Vault HTTP API.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use secrets::vault::VaultBackend;
+use secrets::{Error, Resolver, Secret};
+use serde_json::json;
+
+#[derive(Default)]
+struct Mock {
+    reads: AtomicUsize,
+    renewals: AtomicUsize,
+}
+
+async fn read(
+    State(m): State<Arc<Mock>>,
+    headers: HeaderMap,
+    Path((mount, path)): Path<(String, String)>,
+) -> impl IntoResponse {
+    if headers.get("X-Vault-Token").map(|v| v.as_bytes()) != Some(b"root") {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    m.reads.fetch_add(1, Ordering::SeqCst);
+    match (mount.as_str(), path.as_str()) {
+        ("kv", "discord/bot_token") => Json(json!({
+            "lease_duration": 0,
+            "data": { "data": { "value": "bot-token-123" }, "metadata": { "version": 3 } }
+        }))
+        .into_response(),
+        ("kv", "pgvector/conn") => Json(json!({
+            "lease_duration": 1,
+            "data": { "data": { "dsn": "postgres://u:p@db/recon" }, "metadata": { "version": 1 } }
+        }))
+        .into_response(),
+        _ => (StatusCode::NOT_FOUND, Json(json!({ "errors": [] }))).into_response(),
+    }
+}
+
+async fn renew(State(m): State<Arc<Mock>>) -> impl IntoResponse {
+    m.renewals.fetch_add(1, Ordering::SeqCst);
+    Json(json!({ "auth": { "lease_duration": 3600, "renewable": true } }))
+}
+
+async fn start() -> (String, Arc<Mock>) {
+    let mock = Arc::new(Mock::default());
+    let app = Router::new()
+        .route("/v1/{mount}/data/{*path}", get(read))
+        .route("/v1/auth/token/renew-self", post(renew))
+        .with_state(mock.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{addr}"), mock)
+}
+
+#[tokio::test]
+async fn resolves_caches_and_redacts() {
+    let (addr, mock) = start().await;
+    let resolver = Resolver::new(Arc::new(VaultBackend::new(addr, Secret::new("root"))));
+
+    let lease = resolver
+        .lease(&"vault://kv/discord/bot_token".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(lease.secret.expose(), "bot-token-123");
+    assert_eq!(lease.version, Some(3));
+    resolver
+        .resolve_str("vault://kv/discord/bot_token")
+        .await
+        .unwrap();
+    assert_eq!(
+        mock.reads.load(Ordering::SeqCst),
+        1,
+        "second read is cached"
+    );
+
+    assert_eq!(
+        resolver.redactor().redact("posting with bot-token-123"),
+        "posting with [REDACTED]"
+    );
+
+    let dsn = resolver
+        .resolve_str("vault://kv/pgvector/conn#dsn")
+        .await
+        .unwrap();
+    assert_eq!(dsn.expose(), "postgres://u:p@db/recon");
+    assert!(matches!(
+        resolver.resolve_str("vault://kv/missing").await,
+        Err(Error::NotFound(_))
+    ));
+    assert!(matches!(
+        resolver
+            .resolve_str("vault://kv/discord/bot_token#user")
+            .await,
+        Err(Error::MissingField(..))
+    ));
+}
+
+#[tokio::test]
+async fn renewal_loop_renews_token_and_refreshes_expiring_values() {
+    let (addr, mock) = start().await;
+    let resolver = Arc::new(
+        Resolver::new(Arc::new(VaultBackend::new(addr, Secret::new("root"))))
+            .with_default_ttl(Duration::from_secs(3600)),
+    );
+    resolver
+        .resolve_str("vault://kv/pgvector/conn#dsn")
+        .await
+        .unwrap();
+    resolver
+        .resolve_str("vault://kv/discord/bot_token")
+        .await
+        .unwrap();
+
+    let task = resolver.spawn_renewal(Duration::from_millis(100));
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    task.abort();
+
+    assert!(mock.renewals.load(Ordering::SeqCst) >= 2);
+    // Only the 1s-lease value is due for refresh; the other lives for an hour.
+    let reads = mock.reads.load(Ordering::SeqCst);
+    assert!(reads >= 3, "reads = {reads}");
+}
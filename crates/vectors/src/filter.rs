@@ -0,0 +1,80 @@
+//! Metadata filters: every clause must hold.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Metadata;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Clause {
+    Eq {
+        key: String,
+        value: String,
+    },
+    In {
+        key: String,
+        values: Vec<String>,
+    },
+    /// e.g. a `path` under a directory.
+    Prefix {
+        key: String,
+        prefix: String,
+    },
+}
+
+impl Clause {
+    fn matches(&self, metadata: &Metadata) -> bool {
+        match self {
+            Clause::Eq { key, value } => metadata.get(key) == Some(value),
+            Clause::In { key, values } => metadata.get(key).is_some_and(|v| values.contains(v)),
+            Clause::Prefix { key, prefix } => {
+                metadata.get(key).is_some_and(|v| v.starts_with(prefix))
+            }
+        }
+    }
+}
+
+/// Matches everything until clauses are added.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Filter {
+    pub clauses: Vec<Clause>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn eq(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.clauses.push(Clause::Eq {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn one_of<I, S>(mut self, key: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.clauses.push(Clause::In {
+            key: key.into(),
+            values: values.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    pub fn prefix(mut self, key: impl Into<String>, prefix: impl Into<String>) -> Self {
+        self.clauses.push(Clause::Prefix {
+            key: key.into(),
+            prefix: prefix.into(),
+        });
+        self
+    }
+
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.clauses.iter().all(|c| c.matches(metadata))
+    }
+}
@@ -0,0 +1,260 @@
+//! Hierarchical navigable small-world graph (Malkov & Yashunin, 2016).
+//!
+//! Node ids are index slots. Tombstoned slots stay in the graph as
+//! waypoints; searches only keep slots the caller accepts.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+use crate::store::Vectors;
+use crate::{HnswParams, Metric};
+
+/// A slot and its distance from the query, ordered by distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Near(pub f32, pub u32);
+
+impl Eq for Near {}
+
+impl PartialOrd for Near {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Near {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+pub(crate) struct Graph {
+    params: HnswParams,
+    /// `links[slot][level]`.
+    links: Vec<Vec<Vec<u32>>>,
+    entry: Option<u32>,
+    max_level: usize,
+    /// xorshift state for level assignment, persisted so reopened indexes
+    /// keep drawing the same sequence.
+    rng: u64,
+}
+
+impl Graph {
+    pub fn new(params: HnswParams) -> Self {
+        Self {
+            params,
+            links: Vec::new(),
+            entry: None,
+            max_level: 0,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    fn random_level(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        // Uniform in (0, 1].
+        let u = ((self.rng >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let ml = 1.0 / (self.params.m.max(2) as f64).ln();
+        (-u.ln() * ml).floor() as usize
+    }
+
+    fn max_links(&self, level: usize) -> usize {
+        if level == 0 {
+            self.params.m * 2
+        } else {
+            self.params.m
+        }
+    }
+
+    /// Links `slot`, which must be the next slot, into the graph.
+    pub fn insert(&mut self, slot: u32, vectors: &Vectors, metric: Metric) {
+        debug_assert_eq!(slot as usize, self.links.len());
+        let level = self.random_level();
+        self.links.push(vec![Vec::new(); level + 1]);
+        let Some(entry) = self.entry else {
+            self.entry = Some(slot);
+            self.max_level = level;
+            return;
+        };
+        let q = vectors.get(slot as usize);
+        let dist = |s: u32| metric.distance(q, vectors.get(s as usize));
+        let mut ep = Near(dist(entry), entry);
+        for l in (level + 1..=self.max_level).rev() {
+            ep = self.search_layer(q, ep, 1, l, vectors, metric, |_| true)[0];
+        }
+        for l in (0..=level.min(self.max_level)).rev() {
+            let found = self.search_layer(
+                q,
+                ep,
+                self.params.ef_construction,
+                l,
+                vectors,
+                metric,
+                |_| true,
+            );
+            let chosen: Vec<u32> = found.iter().take(self.max_links(l)).map(|n| n.1).collect();
+            for &n in &chosen {
+                self.link(n, slot, l, vectors, metric);
+            }
+            self.links[slot as usize][l] = chosen;
+            ep = found[0];
+        }
+        if level > self.max_level {
+            self.entry = Some(slot);
+            self.max_level = level;
+        }
+    }
+
+    /// Adds `to` to `from`'s links at `level`, keeping only the closest
+    /// when there are too many.
+    fn link(&mut self, from: u32, to: u32, level: usize, vectors: &Vectors, metric: Metric) {
+        let max = self.max_links(level);
+        let links = &mut self.links[from as usize][level];
+        links.push(to);
+        if links.len() > max {
+            let v = vectors.get(from as usize);
+            let mut by_distance: Vec<Near> = links
+                .iter()
+                .map(|&s| Near(metric.distance(v, vectors.get(s as usize)), s))
+                .collect();
+            by_distance.sort();
+            *links = by_distance.into_iter().take(max).map(|n| n.1).collect();
+        }
+    }
+
+    /// Beam search within one level, closest first. Only accepted slots
+    /// are returned, but every slot is walked through.
+    #[allow(clippy::too_many_arguments)]
+    fn search_layer(
+        &self,
+        q: &[f32],
+        ep: Near,
+        ef: usize,
+        level: usize,
+        vectors: &Vectors,
+        metric: Metric,
+        accept: impl Fn(u32) -> bool,
+    ) -> Vec<Near> {
+        let mut visited = HashSet::from([ep.1]);
+        let mut candidates = BinaryHeap::from([Reverse(ep)]);
+        let mut results = BinaryHeap::new();
+        if accept(ep.1) {
+            results.push(ep);
+        }
+        while let Some(Reverse(c)) = candidates.pop() {
+            if results.len() >= ef && results.peek().is_some_and(|w: &Near| c.0 > w.0) {
+                break;
+            }
+            for &n in self.links[c.1 as usize].get(level).into_iter().flatten() {
+                if !visited.insert(n) {
+                    continue;
+                }
+                let d = metric.distance(q, vectors.get(n as usize));
+                if results.len() < ef || results.peek().is_some_and(|w: &Near| d < w.0) {
+                    candidates.push(Reverse(Near(d, n)));
+                    if accept(n) {
+                        results.push(Near(d, n));
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+        results.into_sorted_vec()
+    }
+
+    pub fn search(
+        &self,
+        q: &[f32],
+        k: usize,
+        vectors: &Vectors,
+        metric: Metric,
+        accept: impl Fn(u32) -> bool,
+    ) -> Vec<Near> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        let mut ep = Near(metric.distance(q, vectors.get(entry as usize)), entry);
+        for l in (1..=self.max_level).rev() {
+            ep = self.search_layer(q, ep, 1, l, vectors, metric, |_| true)[0];
+        }
+        let ef = self.params.ef_search.max(k);
+        let mut found = self.search_layer(q, ep, ef, 0, vectors, metric, accept);
+        found.truncate(k);
+        found
+    }
+
+    /// Little-endian: node count, entry (`u32::MAX` for none), max level,
+    /// rng state, then per node its level count and per level the link
+    /// count and links.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let put = |out: &mut Vec<u8>, v: u32| out.extend_from_slice(&v.to_le_bytes());
+        put(&mut out, self.links.len() as u32);
+        put(&mut out, self.entry.unwrap_or(u32::MAX));
+        put(&mut out, self.max_level as u32);
+        out.extend_from_slice(&self.rng.to_le_bytes());
+        for levels in &self.links {
+            put(&mut out, levels.len() as u32);
+            for links in levels {
+                put(&mut out, links.len() as u32);
+                for &l in links {
+                    put(&mut out, l);
+                }
+            }
+        }
+        out
+    }
+
+    /// `None` if `bytes` is not a graph over exactly `nodes` slots.
+    pub fn decode(bytes: &[u8], params: HnswParams, nodes: usize) -> Option<Self> {
+        let mut r = Reader { bytes, at: 0 };
+        let count = r.u32()? as usize;
+        let entry = r.u32()?;
+        let max_level = r.u32()? as usize;
+        let rng = u64::from_le_bytes(r.take(8)?.try_into().ok()?);
+        if count != nodes || (entry != u32::MAX && entry as usize >= count) {
+            return None;
+        }
+        let mut links = Vec::with_capacity(count);
+        for _ in 0..count {
+            let levels = r.u32()? as usize;
+            let mut node = Vec::with_capacity(levels.min(64));
+            for _ in 0..levels {
+                let n = r.u32()? as usize;
+                let ids = (0..n).map(|_| r.u32()).collect::<Option<Vec<u32>>>()?;
+                if ids.iter().any(|&i| i as usize >= count) {
+                    return None;
+                }
+                node.push(ids);
+            }
+            links.push(node);
+        }
+        Some(Self {
+            params,
+            links,
+            entry: (entry != u32::MAX).then_some(entry),
+            max_level,
+            rng,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let chunk = self.bytes.get(self.at..self.at + n)?;
+        self.at += n;
+        Some(chunk)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
@@ -0,0 +1,455 @@
+//! An embedded vector index, so semantic search needs neither pgvector
+//! nor Qdrant.
+//!
+//! [`VectorIndex`] holds vectors of one configured dimension, each under a
+//! string id with string [`Metadata`] (`request_id`, `expert`, `path`, …).
+//! [`Mode::Flat`] compares the query with every vector; [`Mode::Hnsw`]
+//! walks a hierarchical navigable small-world graph and trades a little
+//! recall for speed on large collections. Either way a [`Filter`] limits
+//! results to matching metadata; when few vectors match, the search is
+//! exact over just those.
+//!
+//! Distances follow pgvector's operators: cosine distance (`<=>`),
+//! Euclidean distance (`<->`) and negative inner product (`<#>`); lower is
+//! closer. [`VectorIndex::save`] writes the index to a directory and
+//! [`VectorIndex::open`] maps the vectors back in with mmap.
+//!
+//! Removing or replacing a vector leaves a tombstone that searches skip;
+//! [`VectorIndex::compact`] rebuilds without them.
+
+pub mod filter;
+mod hnsw;
+mod store;
+
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+pub use filter::Filter;
+use hnsw::{Graph, Near};
+use store::Vectors;
+
+pub type Metadata = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// `1 - cos(a, b)`. Vectors are normalized when added.
+    #[default]
+    Cosine,
+    /// `|a - b|`.
+    L2,
+    /// `-(a · b)`.
+    Dot,
+}
+
+impl Metric {
+    fn distance(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => 1.0 - dot(a, b),
+            Metric::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+            Metric::Dot => -dot(a, b),
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HnswParams {
+    /// Links per node above layer 0; layer 0 keeps twice as many.
+    pub m: usize,
+    /// Beam width while inserting.
+    pub ef_construction: usize,
+    /// Beam width while searching; raised to `k` when smaller.
+    pub ef_search: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Mode {
+    #[default]
+    Flat,
+    Hnsw(HnswParams),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub dimension: usize,
+    #[serde(default)]
+    pub metric: Metric,
+    #[serde(default)]
+    pub mode: Mode,
+}
+
+impl Config {
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension,
+            metric: Metric::default(),
+            mode: Mode::default(),
+        }
+    }
+
+    pub fn with_metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn with_hnsw(mut self, params: HnswParams) -> Self {
+        self.mode = Mode::Hnsw(params);
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IndexError {
+    #[error("vector has {got} dimensions, index has {expected}")]
+    Dimension { expected: usize, got: usize },
+    #[error("vector has a NaN or infinite component")]
+    NotFinite,
+    #[error("cannot normalize a zero vector for cosine distance")]
+    Zero,
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("{path}: {message}")]
+    Corrupt { path: String, message: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub id: String,
+    pub distance: f32,
+    pub metadata: Metadata,
+}
+
+/// With a filter matching at most this many vectors, HNSW mode searches
+/// them exhaustively instead of walking the graph.
+const EXACT_BELOW: usize = 2048;
+
+pub struct VectorIndex {
+    config: Config,
+    vectors: Vectors,
+    /// Per slot; slots are never reused.
+    ids: Vec<String>,
+    metadata: Vec<Metadata>,
+    deleted: Vec<bool>,
+    slots: HashMap<String, u32>,
+    graph: Option<Graph>,
+}
+
+impl VectorIndex {
+    pub fn new(config: Config) -> Self {
+        Self {
+            vectors: Vectors::new(config.dimension),
+            ids: Vec::new(),
+            metadata: Vec::new(),
+            deleted: Vec::new(),
+            slots: HashMap::new(),
+            graph: match config.mode {
+                Mode::Flat => None,
+                Mode::Hnsw(params) => Some(Graph::new(params)),
+            },
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Live vectors.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Tombstoned slots, reclaimed by [`VectorIndex::compact`].
+    pub fn tombstones(&self) -> usize {
+        self.ids.len() - self.slots.len()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.slots.contains_key(id)
+    }
+
+    pub fn metadata(&self, id: &str) -> Option<&Metadata> {
+        self.slots.get(id).map(|&s| &self.metadata[s as usize])
+    }
+
+    /// Ids and metadata of the live vectors, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Metadata)> {
+        (0..self.ids.len())
+            .filter(|&s| !self.deleted[s])
+            .map(|s| (self.ids[s].as_str(), &self.metadata[s]))
+    }
+
+    /// Checks `vector` and normalizes it for cosine distance.
+    fn prepare(&self, vector: &[f32]) -> Result<Vec<f32>, IndexError> {
+        if vector.len() != self.config.dimension {
+            return Err(IndexError::Dimension {
+                expected: self.config.dimension,
+                got: vector.len(),
+            });
+        }
+        if !vector.iter().all(|x| x.is_finite()) {
+            return Err(IndexError::NotFinite);
+        }
+        let mut v = vector.to_vec();
+        if self.config.metric == Metric::Cosine {
+            let norm = dot(&v, &v).sqrt();
+            if norm == 0.0 {
+                return Err(IndexError::Zero);
+            }
+            v.iter_mut().for_each(|x| *x /= norm);
+        }
+        Ok(v)
+    }
+
+    /// Adds `vector` under `id`, replacing any vector already there.
+    pub fn upsert(
+        &mut self,
+        id: impl Into<String>,
+        vector: &[f32],
+        metadata: Metadata,
+    ) -> Result<(), IndexError> {
+        let id = id.into();
+        let v = self.prepare(vector)?;
+        self.remove(&id);
+        let slot = self.ids.len() as u32;
+        self.vectors.push(&v);
+        self.ids.push(id.clone());
+        self.metadata.push(metadata);
+        self.deleted.push(false);
+        self.slots.insert(id, slot);
+        if let Some(graph) = &mut self.graph {
+            graph.insert(slot, &self.vectors, self.config.metric);
+        }
+        Ok(())
+    }
+
+    /// Returns whether `id` was present.
+    pub fn remove(&mut self, id: &str) -> bool {
+        match self.slots.remove(id) {
+            Some(slot) => {
+                self.deleted[slot as usize] = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The `k` nearest live vectors matching `filter`, closest first.
+    pub fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<Hit>, IndexError> {
+        let q = self.prepare(query)?;
+        if k == 0 || self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let accept = |slot: u32| {
+            !self.deleted[slot as usize]
+                && filter.is_none_or(|f| f.matches(&self.metadata[slot as usize]))
+        };
+        let near = match &self.graph {
+            Some(graph) if filter.is_none_or(|_| self.count(accept) > EXACT_BELOW) => {
+                graph.search(&q, k, &self.vectors, self.config.metric, accept)
+            }
+            _ => self.exact(&q, k, accept),
+        };
+        Ok(near
+            .into_iter()
+            .map(|Near(distance, slot)| Hit {
+                id: self.ids[slot as usize].clone(),
+                distance,
+                metadata: self.metadata[slot as usize].clone(),
+            })
+            .collect())
+    }
+
+    fn count(&self, accept: impl Fn(u32) -> bool) -> usize {
+        (0..self.ids.len() as u32).filter(|&s| accept(s)).count()
+    }
+
+    fn exact(&self, q: &[f32], k: usize, accept: impl Fn(u32) -> bool) -> Vec<Near> {
+        let mut best = BinaryHeap::with_capacity(k + 1);
+        for slot in (0..self.ids.len() as u32).filter(|&s| accept(s)) {
+            let d = self
+                .config
+                .metric
+                .distance(q, self.vectors.get(slot as usize));
+            best.push(Near(d, slot));
+            if best.len() > k {
+                best.pop();
+            }
+        }
+        best.into_sorted_vec()
+    }
+
+    /// Rebuilds the index without tombstones.
+    pub fn compact(&mut self) {
+        let mut fresh = VectorIndex::new(self.config);
+        for slot in 0..self.ids.len() {
+            if self.deleted[slot] {
+                continue;
+            }
+            let id = self.ids[slot].clone();
+            fresh.vectors.push(self.vectors.get(slot));
+            fresh.slots.insert(id.clone(), fresh.ids.len() as u32);
+            fresh.ids.push(id);
+            fresh
+                .metadata
+                .push(std::mem::take(&mut self.metadata[slot]));
+            fresh.deleted.push(false);
+        }
+        fresh.rebuild_graph();
+        *self = fresh;
+    }
+
+    fn rebuild_graph(&mut self) {
+        if let Mode::Hnsw(params) = self.config.mode {
+            let mut graph = Graph::new(params);
+            for slot in 0..self.ids.len() as u32 {
+                graph.insert(slot, &self.vectors, self.config.metric);
+            }
+            self.graph = Some(graph);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random vectors.
+    pub(crate) fn random_vectors(n: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut state = seed | 1;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        };
+        (0..n).map(|_| (0..dim).map(|_| next()).collect()).collect()
+    }
+
+    fn meta(pairs: &[(&str, &str)]) -> Metadata {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn flat_search_is_exact_and_honours_filters_and_replacements() {
+        let mut index = VectorIndex::new(Config::new(2).with_metric(Metric::L2));
+        index
+            .upsert("a", &[0.0, 0.0], meta(&[("expert", "backend")]))
+            .unwrap();
+        index
+            .upsert("b", &[1.0, 0.0], meta(&[("expert", "security")]))
+            .unwrap();
+        index
+            .upsert("c", &[5.0, 5.0], meta(&[("expert", "security")]))
+            .unwrap();
+
+        let ids = |hits: Vec<Hit>| hits.into_iter().map(|h| h.id).collect::<Vec<_>>();
+        assert_eq!(ids(index.search(&[0.9, 0.0], 2, None).unwrap()), ["b", "a"]);
+        let security = Filter::new().eq("expert", "security");
+        assert_eq!(
+            ids(index.search(&[0.0, 0.0], 5, Some(&security)).unwrap()),
+            ["b", "c"]
+        );
+
+        index
+            .upsert("b", &[9.0, 9.0], meta(&[("expert", "security")]))
+            .unwrap();
+        assert_eq!(ids(index.search(&[0.9, 0.0], 2, None).unwrap()), ["a", "c"]);
+        assert_eq!((index.len(), index.tombstones()), (3, 1));
+        index.compact();
+        assert_eq!((index.len(), index.tombstones()), (3, 0));
+        assert_eq!(index.search(&[9.0, 9.0], 1, None).unwrap()[0].distance, 0.0);
+
+        assert!(matches!(
+            index.search(&[1.0], 1, None),
+            Err(IndexError::Dimension {
+                expected: 2,
+                got: 1
+            })
+        ));
+        assert!(matches!(
+            index.upsert("d", &[f32::NAN, 0.0], Metadata::new()),
+            Err(IndexError::NotFinite)
+        ));
+    }
+
+    #[test]
+    fn hnsw_recall_matches_flat_search() {
+        let data = random_vectors(3000, 24, 7);
+        let queries = random_vectors(50, 24, 99);
+        let mut flat = VectorIndex::new(Config::new(24));
+        let mut hnsw = VectorIndex::new(Config::new(24).with_hnsw(HnswParams::default()));
+        for (i, v) in data.iter().enumerate() {
+            let m = meta(&[("request_id", if i % 2 == 0 { "even" } else { "odd" })]);
+            flat.upsert(i.to_string(), v, m.clone()).unwrap();
+            hnsw.upsert(i.to_string(), v, m).unwrap();
+        }
+
+        let recall = |filter: Option<&Filter>| {
+            let mut found = 0;
+            for q in &queries {
+                let truth: Vec<String> = flat
+                    .search(q, 10, filter)
+                    .unwrap()
+                    .into_iter()
+                    .map(|h| h.id)
+                    .collect();
+                found += hnsw
+                    .search(q, 10, filter)
+                    .unwrap()
+                    .iter()
+                    .filter(|h| truth.contains(&h.id))
+                    .count();
+            }
+            found as f64 / (queries.len() * 10) as f64
+        };
+        assert!(recall(None) >= 0.95, "recall {}", recall(None));
+        // Half the vectors match, so the graph is walked with the filter.
+        let even = Filter::new().eq("request_id", "even");
+        assert!(
+            recall(Some(&even)) >= 0.95,
+            "recall {}",
+            recall(Some(&even))
+        );
+        let hits = hnsw.search(&queries[0], 10, Some(&even)).unwrap();
+        assert!(hits.iter().all(|h| h.metadata["request_id"] == "even"));
+    }
+}
@@ -0,0 +1,377 @@
+//! Vector storage and the on-disk layout.
+//!
+//! A saved index is a directory of three files:
+//!
+//! - `vectors.<generation>.f32`: every slot's vector, little-endian, back
+//!   to back;
+//! - `hnsw.<generation>.bin`: the graph, in HNSW mode ([`Graph::encode`]);
+//! - `index.json`: the generation, config, ids, metadata and tombstones.
+//!
+//! Each save writes a new generation's files, then renames a synced
+//! `index.json` over the old one; only that rename makes the save take
+//! effect, and the previous generation's files are deleted after it. A
+//! crash before it leaves the previous save whole, and stray files of the
+//! unfinished generation are overwritten or deleted by the next save.
+//! Indexes saved before generations existed use `vectors.f32` and
+//! `hnsw.bin` until they are saved again.
+//!
+//! [`VectorIndex::open`] maps the vectors file instead of reading it and
+//! rebuilds the graph if its file does not fit the slots `index.json`
+//! lists. Vectors added after opening live in memory until the next save.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+
+use crate::hnsw::Graph;
+use crate::{Config, IndexError, Metadata, Mode, VectorIndex};
+
+#[cfg(target_endian = "big")]
+compile_error!("vectors.f32 is little-endian and is mapped without conversion");
+
+const FORMAT_VERSION: u32 = 1;
+
+pub(crate) struct Vectors {
+    dim: usize,
+    mapped: Option<Mmap>,
+    /// Vectors in `mapped`.
+    mapped_len: usize,
+    tail: Vec<f32>,
+}
+
+impl Vectors {
+    pub fn new(dim: usize) -> Self {
+        Self {
+            dim,
+            mapped: None,
+            mapped_len: 0,
+            tail: Vec::new(),
+        }
+    }
+
+    fn mapped(&self) -> &[f32] {
+        match &self.mapped {
+            Some(map) => {
+                let bytes = &map[..self.mapped_len * self.dim * 4];
+                // SAFETY: `map_file` checked the mapping is 4-byte aligned
+                // and long enough, any bit pattern is a valid f32, and the
+                // file is little-endian like the target.
+                unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast::<f32>(), bytes.len() / 4) }
+            }
+            None => &[],
+        }
+    }
+
+    pub fn get(&self, slot: usize) -> &[f32] {
+        let d = self.dim;
+        if slot < self.mapped_len {
+            &self.mapped()[slot * d..(slot + 1) * d]
+        } else {
+            let i = slot - self.mapped_len;
+            &self.tail[i * d..(i + 1) * d]
+        }
+    }
+
+    pub fn push(&mut self, v: &[f32]) {
+        self.tail.extend_from_slice(v);
+    }
+
+    /// Maps the first `count` vectors of `path`.
+    fn map_file(path: &Path, dim: usize, count: usize) -> Result<Self, IndexError> {
+        let mut vectors = Self::new(dim);
+        if count == 0 || dim == 0 {
+            return Ok(vectors);
+        }
+        let file = File::open(path).map_err(io(path))?;
+        // SAFETY: a generation's vectors file is complete before
+        // `index.json` names it and is never written again, so this
+        // mapping is never modified; other writers to the directory are
+        // not supported.
+        let map = unsafe { Mmap::map(&file) }.map_err(io(path))?;
+        let corrupt = |message: String| IndexError::Corrupt {
+            path: path.display().to_string(),
+            message,
+        };
+        if map.len() < count * dim * 4 {
+            return Err(corrupt(format!(
+                "{} bytes is too short for {count} vectors of {dim} dimensions",
+                map.len()
+            )));
+        }
+        if map.as_ptr().align_offset(std::mem::align_of::<f32>()) != 0 {
+            return Err(corrupt("mapping is not aligned for f32".to_string()));
+        }
+        vectors.mapped = Some(map);
+        vectors.mapped_len = count;
+        Ok(vectors)
+    }
+}
+
+fn io(path: &Path) -> impl FnOnce(std::io::Error) -> IndexError + '_ {
+    move |source| IndexError::Io {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    /// Names the vector and graph files; `None` for the unnumbered files
+    /// of indexes saved before generations existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generation: Option<u64>,
+    config: Config,
+    ids: Vec<String>,
+    metadata: Vec<Metadata>,
+    /// Tombstoned slots.
+    deleted: Vec<u32>,
+}
+
+fn vectors_file(generation: Option<u64>) -> String {
+    match generation {
+        Some(g) => format!("vectors.{g}.f32"),
+        None => "vectors.f32".to_string(),
+    }
+}
+
+fn graph_file(generation: Option<u64>) -> String {
+    match generation {
+        Some(g) => format!("hnsw.{g}.bin"),
+        None => "hnsw.bin".to_string(),
+    }
+}
+
+/// Deletes vector and graph files in `dir` of generations other than
+/// `keep`. Failures are only logged: a stray file costs disk space, not
+/// correctness.
+fn remove_stale(dir: &Path, keep: u64) {
+    let current = [vectors_file(Some(keep)), graph_file(Some(keep))];
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else { continue };
+        let ours = ["vectors.", "hnsw."].iter().any(|p| name.starts_with(p))
+            && [".f32", ".bin", ".tmp"].iter().any(|s| name.ends_with(s));
+        if ours && !current.iter().any(|c| c == name) {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                tracing::warn!(file = %entry.path().display(), error = %e, "cannot remove stale index file");
+            }
+        }
+    }
+}
+
+/// Writes `body` next to `path`, syncs it and renames it over `path`.
+fn replace(path: &Path, body: &[u8]) -> Result<(), IndexError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = Path::new(&tmp);
+    std::fs::write(tmp, body).map_err(io(tmp))?;
+    File::open(tmp)
+        .and_then(|f| f.sync_all())
+        .map_err(io(tmp))?;
+    std::fs::rename(tmp, path).map_err(io(path))
+}
+
+impl VectorIndex {
+    /// Writes the index to `dir`, creating it if needed. Afterwards the
+    /// vectors are served from the saved file.
+    pub fn save(&mut self, dir: impl AsRef<Path>) -> Result<(), IndexError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir).map_err(io(dir))?;
+        let previous = std::fs::read(dir.join("index.json"))
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Header>(&json).ok())
+            .and_then(|h| h.generation);
+        let generation = previous.map_or(1, |g| g + 1);
+        self.write_generation(dir, generation)?;
+
+        let count = self.ids.len();
+        let header = Header {
+            version: FORMAT_VERSION,
+            generation: Some(generation),
+            config: self.config,
+            ids: self.ids.clone(),
+            metadata: self.metadata.clone(),
+            deleted: (0..count as u32)
+                .filter(|&s| self.deleted[s as usize])
+                .collect(),
+        };
+        let json = serde_json::to_vec(&header).expect("index header serializes");
+        replace(&dir.join("index.json"), &json)?;
+        remove_stale(dir, generation);
+
+        let vectors_path = dir.join(vectors_file(Some(generation)));
+        self.vectors = Vectors::map_file(&vectors_path, self.config.dimension, count)?;
+        Ok(())
+    }
+
+    /// Writes the vector and graph files of `generation`, which nothing
+    /// refers to until `index.json` does.
+    fn write_generation(&self, dir: &Path, generation: u64) -> Result<(), IndexError> {
+        let count = self.ids.len();
+        let mut bytes = Vec::with_capacity(count * self.config.dimension * 4);
+        for slot in 0..count {
+            for x in self.vectors.get(slot) {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+        replace(&dir.join(vectors_file(Some(generation))), &bytes)?;
+        if let Some(graph) = &self.graph {
+            replace(&dir.join(graph_file(Some(generation))), &graph.encode())?;
+        }
+        Ok(())
+    }
+
+    /// Opens an index written by [`VectorIndex::save`].
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, IndexError> {
+        let dir = dir.as_ref();
+        let header_path = dir.join("index.json");
+        let json = std::fs::read(&header_path).map_err(io(&header_path))?;
+        let corrupt = |message: String| IndexError::Corrupt {
+            path: header_path.display().to_string(),
+            message,
+        };
+        let header: Header = serde_json::from_slice(&json).map_err(|e| corrupt(e.to_string()))?;
+        if header.version != FORMAT_VERSION {
+            return Err(corrupt(format!("unsupported version {}", header.version)));
+        }
+        let count = header.ids.len();
+        if header.metadata.len() != count || header.deleted.iter().any(|&s| s as usize >= count) {
+            return Err(corrupt("ids, metadata and tombstones disagree".to_string()));
+        }
+
+        let config = header.config;
+        let vectors = Vectors::map_file(
+            &dir.join(vectors_file(header.generation)),
+            config.dimension,
+            count,
+        )?;
+        let mut deleted = vec![false; count];
+        for s in header.deleted {
+            deleted[s as usize] = true;
+        }
+        let slots: HashMap<String, u32> = header
+            .ids
+            .iter()
+            .enumerate()
+            .filter(|(s, _)| !deleted[*s])
+            .map(|(s, id)| (id.clone(), s as u32))
+            .collect();
+        let mut index = VectorIndex {
+            config,
+            vectors,
+            ids: header.ids,
+            metadata: header.metadata,
+            deleted,
+            slots,
+            graph: None,
+        };
+        if let Mode::Hnsw(params) = config.mode {
+            let graph = std::fs::read(dir.join(graph_file(header.generation)))
+                .ok()
+                .and_then(|bytes| Graph::decode(&bytes, params, count));
+            match graph {
+                Some(graph) => index.graph = Some(graph),
+                None => {
+                    tracing::warn!(dir = %dir.display(), "graph file missing or stale, rebuilding");
+                    index.rebuild_graph();
+                }
+            }
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::random_vectors;
+    use crate::{Filter, HnswParams};
+
+    #[test]
+    fn saved_indexes_reopen_mapped_and_keep_growing() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = random_vectors(300, 8, 3);
+        let config = Config::new(8).with_hnsw(HnswParams::default());
+        let mut index = VectorIndex::new(config);
+        for (i, v) in data.iter().enumerate() {
+            let meta = Metadata::from([("expert".to_string(), format!("e{}", i % 3))]);
+            index.upsert(format!("v{i}"), v, meta).unwrap();
+        }
+        index.remove("v0");
+        let before = index.search(&data[0], 5, None).unwrap();
+        index.save(dir.path()).unwrap();
+
+        let mut reopened = VectorIndex::open(dir.path()).unwrap();
+        assert!(reopened.vectors.mapped.is_some());
+        assert_eq!(reopened.len(), 299);
+        assert!(!reopened.contains("v0"));
+        assert_eq!(reopened.search(&data[0], 5, None).unwrap(), before);
+
+        // New vectors join the mapped ones and survive the next save.
+        reopened
+            .upsert(
+                "new",
+                &data[0],
+                Metadata::from([("expert".into(), "e9".into())]),
+            )
+            .unwrap();
+        reopened.save(dir.path()).unwrap();
+        let again = VectorIndex::open(dir.path()).unwrap();
+        let hits = again
+            .search(&data[0], 1, Some(&Filter::new().eq("expert", "e9")))
+            .unwrap();
+        assert_eq!(
+            (hits[0].id.as_str(), hits[0].distance.abs() < 1e-6),
+            ("new", true)
+        );
+
+        // A graph that no longer fits is rebuilt rather than trusted.
+        std::fs::write(dir.path().join("hnsw.2.bin"), b"stale").unwrap();
+        let rebuilt = VectorIndex::open(dir.path()).unwrap();
+        assert_eq!(rebuilt.search(&data[5], 1, None).unwrap()[0].id, "v5");
+    }
+
+    #[test]
+    fn a_save_cut_short_leaves_the_previous_one_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = random_vectors(50, 4, 7);
+        let config = Config::new(4).with_hnsw(HnswParams::default());
+        let mut index = VectorIndex::new(config);
+        for (i, v) in data.iter().enumerate() {
+            index.upsert(format!("v{i}"), v, Metadata::new()).unwrap();
+        }
+        index.save(dir.path()).unwrap();
+
+        // Compaction moves every vector after v0 down a slot; the process
+        // dies after writing the new files but before `index.json`.
+        index.remove("v0");
+        index.compact();
+        index.write_generation(dir.path(), 2).unwrap();
+        let reopened = VectorIndex::open(dir.path()).unwrap();
+        assert_eq!(reopened.len(), 50);
+        for i in [0, 1, 49] {
+            let hit = &reopened.search(&data[i], 1, None).unwrap()[0];
+            assert_eq!(hit.id, format!("v{i}"));
+            assert!(hit.distance.abs() < 1e-6);
+        }
+
+        // The next save replaces the stray generation and removes the old.
+        index.save(dir.path()).unwrap();
+        let mut files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["hnsw.2.bin", "index.json", "vectors.2.f32"]);
+        let reopened = VectorIndex::open(dir.path()).unwrap();
+        assert_eq!(reopened.len(), 49);
+        assert_eq!(reopened.search(&data[1], 1, None).unwrap()[0].id, "v1");
+    }
+}